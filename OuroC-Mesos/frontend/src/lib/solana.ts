@@ -10,6 +10,8 @@ import {
   Connection,
   SystemProgram,
   SYSVAR_INSTRUCTIONS_PUBKEY,
+  ComputeBudgetProgram,
+  type TransactionInstruction,
 } from '@solana/web3.js';
 import {
   getAssociatedTokenAddress,
@@ -26,6 +28,20 @@ const idl = idlJson as Idl;
 // USDC Mint Address on Solana Devnet
 export const USDC_MINT_DEVNET = new PublicKey('4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU');
 
+// Compute budget for process_trigger transactions (keep in sync with the program's constants.rs)
+export const PROCESS_TRIGGER_COMPUTE_UNIT_LIMIT = 300_000;
+export const DEFAULT_COMPUTE_UNIT_PRICE_MICROLAMPORTS = 1_000;
+
+/**
+ * ComputeBudget instructions that must come first in a process_trigger transaction
+ */
+export function processTriggerComputeBudgetInstructions(): TransactionInstruction[] {
+  return [
+    ComputeBudgetProgram.setComputeUnitLimit({ units: PROCESS_TRIGGER_COMPUTE_UNIT_LIMIT }),
+    ComputeBudgetProgram.setComputeUnitPrice({ microLamports: DEFAULT_COMPUTE_UNIT_PRICE_MICROLAMPORTS }),
+  ];
+}
+
 // OuroC-Prima Program ID (deployed on devnet)
 export const PROGRAM_ID = new PublicKey('CFEtrptTe5eFXpZtB3hr1VMGuWF9oXguTnUFUaeVgeyT');
 
@@ -456,8 +472,8 @@ export async function processOneTimePayment(
       })
       .instruction();
 
-    // Combine Ed25519 verification + process_trigger into one transaction
-    const tx = new Transaction().add(ed25519Ix, processTriggerIx);
+    // Compute budget, then Ed25519 verification + process_trigger in one transaction
+    const tx = new Transaction().add(...processTriggerComputeBudgetInstructions(), ed25519Ix, processTriggerIx);
     const sig = await walletAdapter.sendTransaction(tx, connection);
     await connection.confirmTransaction(sig, 'confirmed');
 
@@ -1,3 +1,64 @@
+// Regenerates the TypeScript bindings for the frontend from the committed Candid interface
+// (kept up to date by the test_candid_interface_is_up_to_date test).
+//
+// Needs `didc` on PATH. Local builds without it skip generation; CI sets
+// OUROC_REQUIRE_TS_BINDINGS=1 so a missing tool fails the build instead.
+
+use std::path::Path;
+use std::process::Command;
+
+const CANDID_FILE: &str = "ouroc_timer_rust.did";
+const BINDINGS_FILE: &str = "src/generated/ourc_canister.d.ts";
+
+// Candid types that get a branded alias, so values checked on the frontend
+// (e.g. by CreateSubscriptionRequestBuilder) can't be mixed up with raw objects
+const BRANDED_TYPES: &[&str] = &[
+    "CreateSubscriptionRequest",
+    "Subscription",
+    "SubscriptionStatus",
+    "LicenseValidationResult",
+    "CanisterHealth",
+    "FeeConfig",
+];
+
 fn main() {
     println!("cargo:rerun-if-changed=src/");
-}
\ No newline at end of file
+    println!("cargo:rerun-if-changed={}", CANDID_FILE);
+    println!("cargo:rerun-if-env-changed=OUROC_REQUIRE_TS_BINDINGS");
+
+    let required = std::env::var_os("OUROC_REQUIRE_TS_BINDINGS").is_some();
+    match generate_bindings() {
+        Ok(()) => {}
+        Err(e) if required => panic!("TypeScript binding generation failed: {}", e),
+        Err(e) => println!("cargo:warning=Skipping TypeScript bindings: {}", e),
+    }
+}
+
+fn generate_bindings() -> Result<(), String> {
+    if !Path::new(CANDID_FILE).exists() {
+        return Err(format!("{} not found", CANDID_FILE));
+    }
+
+    let output = Command::new("didc")
+        .args(["bind", CANDID_FILE, "--target", "ts"])
+        .output()
+        .map_err(|e| format!("could not run didc: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("didc bind failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    let mut bindings = String::from("// Generated by build.rs from ouroc_timer_rust.did - do not edit\n\n");
+    bindings.push_str(&String::from_utf8_lossy(&output.stdout));
+    bindings.push_str("\ndeclare const brand: unique symbol;\n");
+    bindings.push_str("export type Branded<T, B extends string> = T & { readonly [brand]: B };\n");
+    for name in BRANDED_TYPES {
+        bindings.push_str(&format!("export type {0}Brand = Branded<{0}, '{0}'>;\n", name));
+    }
+
+    // Only write on change, otherwise rerun-if-changed=src/ would rebuild every time
+    if std::fs::read_to_string(BINDINGS_FILE).ok().as_deref() == Some(bindings.as_str()) {
+        return Ok(());
+    }
+    std::fs::create_dir_all("src/generated").map_err(|e| e.to_string())?;
+    std::fs::write(BINDINGS_FILE, bindings).map_err(|e| e.to_string())
+}
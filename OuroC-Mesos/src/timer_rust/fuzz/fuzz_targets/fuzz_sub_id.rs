@@ -0,0 +1,35 @@
+// Fuzz target for subscription ID validation
+// Run with: cargo fuzz run fuzz_sub_id
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/types.rs"]
+mod types;
+#[allow(dead_code)]
+#[path = "../../src/utils.rs"]
+mod utils;
+
+use utils::is_valid_subscription_id;
+
+// Fragments that must never make it into an accepted ID (path traversal, HTML, SQL)
+const REJECTED_PATTERNS: &[&str] = &["../", "<script", "SELECT ", "DROP "];
+
+fuzz_target!(|data: Vec<u8>| {
+    let id = String::from_utf8_lossy(&data);
+    let valid = is_valid_subscription_id(&id);
+
+    // Pure check - the same input always gets the same answer
+    assert_eq!(valid, is_valid_subscription_id(&id));
+
+    if id.bytes().any(|b| b < 32) {
+        assert!(!valid, "accepted ID with a control character: {:?}", id);
+    }
+    for pattern in REJECTED_PATTERNS {
+        if id.contains(pattern) {
+            assert!(!valid, "accepted ID containing {:?}: {:?}", pattern, id);
+        }
+    }
+});
@@ -0,0 +1,46 @@
+// Anchor discriminators for talking to the ouroc_prima program
+//
+// Anchor prefixes instruction data, accounts and events with the first 8 bytes of
+// sha256("<namespace>:<name>"), where the namespace is "global" for instructions,
+// "account" for account types (CamelCase name) and "event" for events
+
+use sha2::{Digest, Sha256};
+
+/// Namespace Anchor uses for instruction discriminators
+pub const INSTRUCTION_NAMESPACE: &str = "global";
+
+/// Entry point for ICP-triggered payments and notifications (opcode argument selects which)
+pub const PROCESS_TRIGGER: &str = "process_trigger";
+
+/// First 8 bytes of sha256("<namespace>:<method_name>")
+pub fn compute_anchor_discriminator(namespace: &str, method_name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("{}:{}", namespace, method_name).as_bytes());
+    hash[..8].try_into().expect("sha256 output is 32 bytes")
+}
+
+/// Discriminator for a program instruction (snake_case name as in lib.rs)
+pub fn instruction_discriminator(method_name: &str) -> [u8; 8] {
+    compute_anchor_discriminator(INSTRUCTION_NAMESPACE, method_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruction_discriminators_match_idl() {
+        // Reference values from frontend/src/idl/ouroc_prima.json
+        assert_eq!(instruction_discriminator(PROCESS_TRIGGER), [212, 102, 71, 228, 39, 36, 10, 65]);
+        assert_eq!(instruction_discriminator("process_payment"), [189, 81, 30, 198, 139, 186, 115, 23]);
+        assert_eq!(instruction_discriminator("send_notification"), [78, 183, 81, 89, 170, 147, 185, 61]);
+    }
+
+    #[test]
+    fn test_other_namespaces() {
+        assert_eq!(compute_anchor_discriminator("event", "PaymentProcessed"), [22, 109, 191, 213, 83, 63, 120, 219]);
+        assert_ne!(
+            compute_anchor_discriminator("account", "Subscription"),
+            compute_anchor_discriminator(INSTRUCTION_NAMESPACE, "Subscription")
+        );
+    }
+}
@@ -7,6 +7,23 @@ use crate::subscription_manager::{get_subscription_count, list_subscriptions};
 use crate::timer::{get_active_timer_count, get_notification_timer_count};
 use ic_cdk::api::{time, canister_balance};
 use candid::{CandidType, Deserialize};
+use std::collections::VecDeque;
+
+// Hourly samples of the cumulative failed payment count, one week deep
+const FAILURE_HISTORY_CAPACITY: usize = 168;
+const MIN_BASELINE_SAMPLES: usize = 3;
+// Hourly cycle balance snapshots, one week deep
+const CYCLE_USAGE_LOG_CAPACITY: usize = 168;
+const NANOS_PER_HOUR: u64 = 3600 * 1_000_000_000;
+const CONNECTIVITY_HISTORY_CAPACITY: usize = 24;
+
+thread_local! {
+    static FAILURE_HISTORY: std::cell::RefCell<VecDeque<(Timestamp, u32)>> = const { std::cell::RefCell::new(VecDeque::new()) };
+    static CYCLE_USAGE_LOG: std::cell::RefCell<VecDeque<(Timestamp, u64)>> = const { std::cell::RefCell::new(VecDeque::new()) };
+    // Total successful payments at each cycle snapshot, kept in step with CYCLE_USAGE_LOG
+    static PAYMENTS_AT_SNAPSHOT: std::cell::RefCell<VecDeque<u64>> = const { std::cell::RefCell::new(VecDeque::new()) };
+    static CONNECTIVITY_HISTORY: std::cell::RefCell<VecDeque<ConnectivityRecord>> = const { std::cell::RefCell::new(VecDeque::new()) };
+}
 
 pub async fn perform_health_check() -> CanisterHealth {
     ic_cdk::println!("🏥 Performing health check...");
@@ -52,10 +69,115 @@ pub async fn perform_health_check() -> CanisterHealth {
 
     increment_health_check_counter();
 
+    let failure_rate_pct = calculate_failure_rate_pct(failed_payments);
+    maybe_send_health_alert(cycle_balance, failure_rate_pct, now).await;
+
     ic_cdk::println!("✅ Health check completed | Status: {:?} | Uptime: {}s", status, uptime_seconds);
     health
 }
 
+/// Failed triggers as a share of all trigger attempts
+fn calculate_failure_rate_pct(failed_payments: u32) -> f64 {
+    let successful: u64 = list_subscriptions().iter().map(|s| s.trigger_count).sum();
+    let attempts = successful + failed_payments as u64;
+    if attempts == 0 {
+        return 0.0;
+    }
+    failed_payments as f64 * 100.0 / attempts as f64
+}
+
+fn breached_thresholds(thresholds: &AlertThresholds, cycle_balance: u64, failure_rate_pct: f64) -> Vec<String> {
+    let mut reasons = Vec::new();
+    let min_cycle_balance = thresholds.min_cycle_balance_tl.saturating_mul(1_000_000_000_000);
+    if cycle_balance < min_cycle_balance {
+        reasons.push(format!("Cycle balance {} below {}T", cycle_balance, thresholds.min_cycle_balance_tl));
+    }
+    if failure_rate_pct > thresholds.max_failure_rate_pct {
+        reasons.push(format!("Payment failure rate {:.2}% above {:.2}%", failure_rate_pct, thresholds.max_failure_rate_pct));
+    }
+    reasons
+}
+
+/// POST a signed HealthAlert when a threshold is breached, at most once per ALERT_COOLDOWN_NANOS.
+/// The body is signed with HMAC-SHA256 (hex, X-OuroC-Signature header). Every replica sends the
+/// outcall, so receivers should dedupe on the alert timestamp.
+async fn maybe_send_health_alert(cycle_balance: u64, failure_rate_pct: f64, now: Timestamp) {
+    let config = get_alert_config_internal();
+    let (Some(url), Some(secret)) = (config.alert_webhook_url, config.webhook_signing_secret) else {
+        return;
+    };
+    if config.last_alert_time.is_some_and(|last| now.saturating_sub(last) < ALERT_COOLDOWN_NANOS) {
+        return;
+    }
+    let reasons = breached_thresholds(&config.alert_thresholds, cycle_balance, failure_rate_pct);
+    if reasons.is_empty() {
+        return;
+    }
+
+    let alert = HealthAlert {
+        canister_id: ic_cdk::api::id().to_string(),
+        reasons,
+        cycle_balance,
+        failure_rate_pct,
+        timestamp: now,
+    };
+    let body = match serde_json::to_vec(&alert) {
+        Ok(body) => body,
+        Err(e) => {
+            ic_cdk::println!("❌ Failed to encode health alert: {}", e);
+            return;
+        }
+    };
+
+    // Claim the slot before awaiting so concurrent health checks don't send duplicates
+    record_alert_sent(now);
+    match post_signed_webhook(&url, &body, &sign_webhook_payload(secret.as_bytes(), &body)).await {
+        Ok(()) => ic_cdk::println!("🚨 Health alert sent: {}", alert.reasons.join("; ")),
+        Err(e) => ic_cdk::println!("❌ Failed to send health alert: {}", e),
+    }
+}
+
+pub(crate) fn sign_webhook_payload(secret: &[u8], body: &[u8]) -> String {
+    use hmac::{Hmac, Mac};
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub(crate) async fn post_signed_webhook(url: &str, body: &[u8], signature: &str) -> Result<(), String> {
+    use ic_cdk::api::management_canister::http_request::{
+        http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, TransformContext, TransformFunc,
+    };
+
+    let request = CanisterHttpRequestArgument {
+        url: url.to_string(),
+        method: HttpMethod::POST,
+        body: Some(body.to_vec()),
+        max_response_bytes: Some(2_000),
+        transform: Some(TransformContext {
+            function: TransformFunc(candid::Func {
+                principal: ic_cdk::api::id(),
+                method: "transform_http_response".to_string(),
+            }),
+            context: vec![],
+        }),
+        headers: vec![
+            HttpHeader { name: "Content-Type".to_string(), value: "application/json".to_string() },
+            HttpHeader { name: "X-OuroC-Signature".to_string(), value: signature.to_string() },
+        ],
+    };
+
+    let (response,) = http_request(request, 25_000_000_000)
+        .await
+        .map_err(|(code, msg)| format!("HTTP outcall failed: {:?} - {}", code, msg))?;
+    let status: u32 = response.status.0.try_into().unwrap_or(500);
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(format!("Webhook responded with status {}", status))
+    }
+}
+
 pub async fn get_system_metrics() -> SystemMetrics {
     ic_cdk::println!("📊 Gathering system metrics...");
 
@@ -111,6 +233,10 @@ pub async fn get_detailed_health_report() -> DetailedHealthReport {
 
     let (current_fee_address, proposed_fee_address, fee_address_proposal_time) = get_fee_governance_status();
 
+    // Queries can't make outcalls, so report what the last connectivity checks recorded
+    let connectivity = CONNECTIVITY_HISTORY.with(|h| h.borrow().iter().cloned().collect::<Vec<_>>());
+    let last_check = connectivity.last();
+
     let report = DetailedHealthReport {
         health,
         metrics,
@@ -134,6 +260,11 @@ pub async fn get_detailed_health_report() -> DetailedHealthReport {
         auto_refill_enabled: is_auto_refill_enabled(),
         cycle_threshold: get_cycle_threshold(),
         health_check_counter: get_health_check_counter(),
+        anomaly_alerts: detect_failure_anomalies(),
+        cycle_projection: get_cycle_analytics(),
+        solana_rpc_healthy: last_check.map(|r| r.healthy).unwrap_or(false),
+        last_solana_latency_ms: last_check.map(|r| r.latency_ms),
+        p99_latency_ms: calculate_p99_latency_ms(&connectivity),
         generated_at: time(),
     };
 
@@ -150,9 +281,209 @@ pub struct DetailedHealthReport {
     pub auto_refill_enabled: bool,
     pub cycle_threshold: u64,
     pub health_check_counter: u64,
+    pub anomaly_alerts: Vec<AnomalyAlert>,
+    pub cycle_projection: CycleAnalytics,
+    pub solana_rpc_healthy: bool,
+    pub last_solana_latency_ms: Option<u64>,
+    pub p99_latency_ms: Option<u64>,
     pub generated_at: Timestamp,
 }
 
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ConnectivityStatus {
+    pub healthy: bool,
+    pub latency_ms: u64,
+    pub rpc_endpoint: String,
+    pub error: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ConnectivityRecord {
+    pub timestamp: Timestamp,
+    pub healthy: bool,
+    pub latency_ms: u64,
+}
+
+/// Time a getHealth round trip to the configured Solana RPC endpoint and keep the result
+/// in the 24-entry connectivity history
+pub async fn check_solana_connectivity() -> ConnectivityStatus {
+    let (_network, _key_name, rpc_endpoint) = get_network_config();
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getHealth"
+    }).to_string();
+
+    let started_at = time();
+    let result = crate::solana::make_http_request(&rpc_endpoint, "POST", request_body.as_bytes()).await;
+    let finished_at = time();
+    let latency_ms = finished_at.saturating_sub(started_at) / 1_000_000;
+
+    let error = match result {
+        Ok(response) if crate::solana::is_healthy_response(&response.body) => None,
+        Ok(_) => Some("RPC endpoint reported unhealthy".to_string()),
+        Err(e) => Some(e),
+    };
+    let healthy = error.is_none();
+    if let Some(e) = &error {
+        ic_cdk::println!("⚠️ Solana connectivity check against {} failed: {}", rpc_endpoint, e);
+    }
+
+    CONNECTIVITY_HISTORY.with(|h| {
+        let mut history = h.borrow_mut();
+        if history.len() >= CONNECTIVITY_HISTORY_CAPACITY {
+            history.pop_front();
+        }
+        history.push_back(ConnectivityRecord { timestamp: finished_at, healthy, latency_ms });
+    });
+
+    ConnectivityStatus { healthy, latency_ms, rpc_endpoint, error }
+}
+
+/// Nearest-rank 99th percentile of the recorded latencies
+fn calculate_p99_latency_ms(history: &[ConnectivityRecord]) -> Option<u64> {
+    if history.is_empty() {
+        return None;
+    }
+    let mut latencies: Vec<u64> = history.iter().map(|r| r.latency_ms).collect();
+    latencies.sort_unstable();
+    let rank = (latencies.len() * 99).div_ceil(100);
+    Some(latencies[rank - 1])
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct CycleAnalytics {
+    pub burn_rate_cycles_per_hour: u64,
+    pub avg_cost_per_payment_cycles: u64,
+    pub projected_empty_in_seconds: Option<u64>, // None while nothing is being burned
+}
+
+/// Snapshot the cycle balance; called from the heartbeat timer each interval
+pub fn record_cycle_sample(now: Timestamp) {
+    let payments: u64 = list_subscriptions().iter().map(|s| s.success_payment_count.unwrap_or(0) as u64).sum();
+    CYCLE_USAGE_LOG.with(|c| {
+        let mut log = c.borrow_mut();
+        PAYMENTS_AT_SNAPSHOT.with(|p| {
+            let mut payments_log = p.borrow_mut();
+            if log.len() >= CYCLE_USAGE_LOG_CAPACITY {
+                log.pop_front();
+                payments_log.pop_front();
+            }
+            log.push_back((now, canister_balance()));
+            payments_log.push_back(payments);
+        });
+    });
+}
+
+pub fn get_cycle_analytics() -> CycleAnalytics {
+    let snapshots: Vec<(Timestamp, u64)> = CYCLE_USAGE_LOG.with(|c| c.borrow().iter().copied().collect());
+    let payments: Vec<u64> = PAYMENTS_AT_SNAPSHOT.with(|p| p.borrow().iter().copied().collect());
+    let payments_in_window = match (payments.first(), payments.last()) {
+        (Some(first), Some(last)) => last.saturating_sub(*first),
+        _ => 0,
+    };
+    calculate_cycle_analytics(&snapshots, payments_in_window, canister_balance())
+}
+
+fn calculate_cycle_analytics(snapshots: &[(Timestamp, u64)], payments_in_window: u64, current_balance: u64) -> CycleAnalytics {
+    // Only count drops between snapshots so a top-up doesn't read as negative burn
+    let burned: u128 = snapshots.windows(2)
+        .map(|pair| pair[0].1.saturating_sub(pair[1].1) as u128)
+        .sum();
+    let elapsed_nanos = match (snapshots.first(), snapshots.last()) {
+        (Some(first), Some(last)) => last.0.saturating_sub(first.0),
+        _ => 0,
+    };
+
+    let burn_rate_cycles_per_hour = if elapsed_nanos > 0 {
+        (burned * NANOS_PER_HOUR as u128 / elapsed_nanos as u128) as u64
+    } else {
+        0
+    };
+    let avg_cost_per_payment_cycles = if payments_in_window > 0 {
+        (burned / payments_in_window as u128) as u64
+    } else {
+        0
+    };
+    let projected_empty_in_seconds = (burn_rate_cycles_per_hour > 0)
+        .then(|| (current_balance as u128 * 3600 / burn_rate_cycles_per_hour as u128) as u64);
+
+    CycleAnalytics {
+        burn_rate_cycles_per_hour,
+        avg_cost_per_payment_cycles,
+        projected_empty_in_seconds,
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum AnomalySeverity {
+    Warning,  // Above mean + 2 standard deviations
+    Critical, // Above mean + 3 standard deviations
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct AnomalyAlert {
+    pub severity: AnomalySeverity,
+    pub description: String,
+    pub current_rate: f64, // Failures in the latest sampling interval
+    pub threshold: f64,
+}
+
+/// Sample the failed payment counter; called from the heartbeat timer each interval
+pub fn record_failure_sample(now: Timestamp) {
+    FAILURE_HISTORY.with(|h| {
+        let mut history = h.borrow_mut();
+        if history.len() >= FAILURE_HISTORY_CAPACITY {
+            history.pop_front();
+        }
+        history.push_back((now, get_failed_payment_count()));
+    });
+}
+
+/// Compare the latest interval's failures against the rolling mean and standard deviation
+/// of the earlier intervals in the history
+pub fn detect_failure_anomalies() -> Vec<AnomalyAlert> {
+    let history: Vec<(Timestamp, u32)> = FAILURE_HISTORY.with(|h| h.borrow().iter().copied().collect());
+    find_failure_anomalies(&history)
+}
+
+fn find_failure_anomalies(history: &[(Timestamp, u32)]) -> Vec<AnomalyAlert> {
+    // The counter only grows, so per-interval failures are the differences between samples
+    let rates: Vec<f64> = history.windows(2)
+        .map(|pair| pair[1].1.saturating_sub(pair[0].1) as f64)
+        .collect();
+    let Some((&current_rate, baseline)) = rates.split_last() else {
+        return Vec::new();
+    };
+    if baseline.len() < MIN_BASELINE_SAMPLES {
+        return Vec::new();
+    }
+
+    let mean = baseline.iter().sum::<f64>() / baseline.len() as f64;
+    let variance = baseline.iter().map(|rate| (rate - mean).powi(2)).sum::<f64>() / baseline.len() as f64;
+    let stddev = variance.sqrt();
+
+    let threshold = mean + 2.0 * stddev;
+    if current_rate <= threshold {
+        return Vec::new();
+    }
+
+    let severity = if current_rate > mean + 3.0 * stddev {
+        AnomalySeverity::Critical
+    } else {
+        AnomalySeverity::Warning
+    };
+    vec![AnomalyAlert {
+        severity,
+        description: format!(
+            "Payment failures spiked to {} in the last interval (7-day mean {:.2}, stddev {:.2})",
+            current_rate, mean, stddev
+        ),
+        current_rate,
+        threshold,
+    }]
+}
+
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct SubscriptionStats {
     pub total: usize,
@@ -290,6 +621,7 @@ pub async fn get_subscription_health_metrics() -> SubscriptionHealthMetrics {
     let now = time();
 
     let mut total_triggers = 0u64;
+    let mut success_payment_count = 0u64;
     let mut total_failures = 0u32;
     let mut subscriptions_with_failures = 0usize;
     let mut oldest_subscription = now;
@@ -297,6 +629,7 @@ pub async fn get_subscription_health_metrics() -> SubscriptionHealthMetrics {
 
     for subscription in &subscriptions {
         total_triggers += subscription.trigger_count;
+        success_payment_count += subscription.success_payment_count.unwrap_or(0) as u64;
         total_failures += subscription.failed_payment_count;
 
         if subscription.failed_payment_count > 0 {
@@ -311,9 +644,9 @@ pub async fn get_subscription_health_metrics() -> SubscriptionHealthMetrics {
         }
     }
 
-    let success_rate = if total_triggers > 0 {
-        let successful_triggers = total_triggers - total_failures as u64;
-        (successful_triggers as f64 / total_triggers as f64) * 100.0
+    let attempts = success_payment_count + total_failures as u64;
+    let success_rate = if attempts > 0 {
+        (success_payment_count as f64 / attempts as f64) * 100.0
     } else {
         100.0
     };
@@ -325,6 +658,7 @@ pub async fn get_subscription_health_metrics() -> SubscriptionHealthMetrics {
             .count(),
         subscriptions_with_failures,
         total_triggers,
+        success_payment_count,
         total_failures,
         success_rate,
         oldest_subscription_age_seconds: calculate_uptime_seconds(oldest_subscription),
@@ -342,9 +676,176 @@ pub struct SubscriptionHealthMetrics {
     pub active_subscriptions: usize,
     pub subscriptions_with_failures: usize,
     pub total_triggers: u64,
+    pub success_payment_count: u64,
     pub total_failures: u32,
     pub success_rate: f64,
     pub oldest_subscription_age_seconds: u64,
     pub newest_subscription_age_seconds: u64,
     pub calculated_at: Timestamp,
-}
\ No newline at end of file
+}
+
+/// Prometheus text exposition (text/plain; version=0.0.4) for scraping through the HTTP gateway
+pub fn get_prometheus_metrics() -> Vec<u8> {
+    let subscriptions = list_subscriptions();
+    let count_status = |status: SubscriptionStatus| subscriptions.iter().filter(|s| s.status == status).count() as u64;
+
+    let metrics = [
+        PrometheusMetric {
+            name: "ourc_subscriptions_active",
+            help: "Number of active subscriptions",
+            metric_type: "gauge",
+            value: count_status(SubscriptionStatus::Active),
+        },
+        PrometheusMetric {
+            name: "ourc_subscriptions_paused",
+            help: "Number of paused subscriptions",
+            metric_type: "gauge",
+            value: count_status(SubscriptionStatus::Paused),
+        },
+        PrometheusMetric {
+            name: "ourc_subscriptions_cancelled",
+            help: "Number of cancelled subscriptions not yet archived or cleaned up",
+            metric_type: "gauge",
+            value: count_status(SubscriptionStatus::Cancelled),
+        },
+        PrometheusMetric {
+            name: "ourc_cycle_balance",
+            help: "Canister cycle balance",
+            metric_type: "gauge",
+            value: canister_balance(),
+        },
+        PrometheusMetric {
+            name: "ourc_failed_payment_count_total",
+            help: "Failed payment triggers since the canister started",
+            metric_type: "counter",
+            value: get_failed_payment_count() as u64,
+        },
+        PrometheusMetric {
+            name: "ourc_last_heartbeat_timestamp",
+            help: "Unix time in seconds of the last successful liveness heartbeat",
+            metric_type: "gauge",
+            value: get_heartbeat_state().last_heartbeat_at / 1_000_000_000,
+        },
+    ];
+
+    render_prometheus_metrics(&metrics).into_bytes()
+}
+
+struct PrometheusMetric {
+    name: &'static str,
+    help: &'static str,
+    metric_type: &'static str,
+    value: u64,
+}
+
+fn render_prometheus_metrics(metrics: &[PrometheusMetric]) -> String {
+    let mut output = String::new();
+    for metric in metrics {
+        output.push_str(&format!("# HELP {} {}\n", metric.name, metric.help));
+        output.push_str(&format!("# TYPE {} {}\n", metric.name, metric.metric_type));
+        output.push_str(&format!("{} {}\n", metric.name, metric.value));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus_output_is_parseable() {
+        let metrics = [
+            PrometheusMetric { name: "ourc_subscriptions_active", help: "Active", metric_type: "gauge", value: 3 },
+            PrometheusMetric { name: "ourc_failed_payment_count_total", help: "Failed", metric_type: "counter", value: 0 },
+        ];
+        let output = render_prometheus_metrics(&metrics);
+        assert!(output.ends_with('\n'));
+
+        // Every sample must be preceded by its HELP and TYPE lines and carry a numeric value
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), metrics.len() * 3);
+        for (chunk, metric) in lines.chunks(3).zip(metrics.iter()) {
+            assert!(chunk[0].starts_with(&format!("# HELP {} ", metric.name)));
+            let type_line: Vec<&str> = chunk[1].split(' ').collect();
+            assert_eq!(type_line[..3], ["#", "TYPE", metric.name]);
+            assert!(["counter", "gauge"].contains(&type_line[3]));
+
+            let (name, value) = chunk[2].split_once(' ').unwrap();
+            assert_eq!(name, metric.name);
+            assert!(name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+            assert_eq!(value.parse::<f64>().unwrap(), metric.value as f64);
+        }
+    }
+
+    #[test]
+    fn test_failure_spike_detection() {
+        let samples = |counts: &[u32]| -> Vec<(Timestamp, u32)> {
+            counts.iter().enumerate().map(|(hour, count)| (hour as u64, *count)).collect()
+        };
+
+        // Too little history for a baseline
+        assert!(find_failure_anomalies(&samples(&[0, 1, 2])).is_empty());
+
+        // Steady one failure per hour
+        assert!(find_failure_anomalies(&samples(&[0, 1, 2, 3, 4, 5])).is_empty());
+
+        // Baseline rates 1, 2, 1, 2 (mean 1.5, stddev 0.5): 10 is far above mean + 3 stddev
+        let alerts = find_failure_anomalies(&samples(&[0, 1, 3, 4, 6, 16]));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].severity, AnomalySeverity::Critical);
+        assert_eq!(alerts[0].current_rate, 10.0);
+        assert_eq!(alerts[0].threshold, 2.5);
+
+        // 2.8 failures sit between the two thresholds
+        let alerts = find_failure_anomalies(&[(0, 0), (1, 10), (2, 30), (3, 40), (4, 60), (5, 88)]);
+        assert_eq!(alerts[0].severity, AnomalySeverity::Warning);
+    }
+
+    #[test]
+    fn test_webhook_signature_matches_rfc4231() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign_webhook_payload(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_breached_thresholds() {
+        let thresholds = AlertThresholds { min_cycle_balance_tl: 2, max_failure_rate_pct: 10.0 };
+        assert!(breached_thresholds(&thresholds, 3_000_000_000_000, 5.0).is_empty());
+        assert_eq!(breached_thresholds(&thresholds, 1_000_000_000_000, 5.0).len(), 1);
+        assert_eq!(breached_thresholds(&thresholds, 1_000_000_000_000, 10.5).len(), 2);
+    }
+
+    #[test]
+    fn test_cycle_analytics() {
+        const HOUR: u64 = 3600 * 1_000_000_000;
+
+        assert_eq!(calculate_cycle_analytics(&[], 0, 1_000), CycleAnalytics {
+            burn_rate_cycles_per_hour: 0,
+            avg_cost_per_payment_cycles: 0,
+            projected_empty_in_seconds: None,
+        });
+
+        // 300 burned over 3 hours with a top-up in between that must not offset the burn
+        let snapshots = [(0, 1_000), (HOUR, 900), (2 * HOUR, 5_000), (3 * HOUR, 4_800)];
+        let analytics = calculate_cycle_analytics(&snapshots, 6, 4_800);
+        assert_eq!(analytics.burn_rate_cycles_per_hour, 100);
+        assert_eq!(analytics.avg_cost_per_payment_cycles, 50);
+        assert_eq!(analytics.projected_empty_in_seconds, Some(48 * 3600));
+    }
+
+    #[test]
+    fn test_p99_latency() {
+        let record = |latency_ms| ConnectivityRecord { timestamp: 0, healthy: true, latency_ms };
+
+        assert_eq!(calculate_p99_latency_ms(&[]), None);
+        assert_eq!(calculate_p99_latency_ms(&[record(120)]), Some(120));
+
+        // With 24 samples the nearest rank is the slowest one
+        let mut history: Vec<ConnectivityRecord> = (1..=23).map(|i| record(i * 10)).collect();
+        history.insert(5, record(2_000));
+        assert_eq!(calculate_p99_latency_ms(&history), Some(2_000));
+    }
+}
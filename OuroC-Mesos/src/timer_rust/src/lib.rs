@@ -13,9 +13,11 @@ mod sol_rpc;      // NEW: SOL RPC client wrapper
 mod solana_rpc;   // NEW: Solana integration using SOL RPC canister
 mod solana_client;
 mod nonce_manager; // NEW: Durable nonce management
+mod transaction_wire; // Legacy transaction wire format
 mod utils;
 mod health;
 mod threshold_ed25519;
+mod anchor_utils;
 
 // Import types for use in public API
 use types::*;
@@ -28,9 +30,10 @@ use ic_cdk::{
     init,
     post_upgrade,
     pre_upgrade,
-    storage::{stable_restore, stable_save},
+    storage::stable_restore,
 };
 
+use candid::Principal;
 use ic_cdk::api::management_canister::http_request::{HttpResponse, TransformArgs};
 
 // =============================================================================
@@ -42,6 +45,10 @@ fn init() {
     ic_cdk::println!("🚀 Ouro-C Timer Canister (Rust) initializing...");
     state::init();
     timer::start_blockhash_refresh_timer();
+    timer::start_heartbeat_timer();
+    timer::start_cleanup_timer();
+    timer::start_event_index_timer();
+    timer::start_metrics_snapshot_timer();
     ic_cdk::println!("✅ Ouro-C Timer Canister (Rust) initialized successfully");
 }
 
@@ -50,7 +57,6 @@ fn pre_upgrade() {
     ic_cdk::println!("💾 Saving state before upgrade...");
 
     // Collect all state data
-    let subscriptions = subscription_manager::get_all_subscriptions();
     let _encrypted_metadata = state::get_all_encrypted_metadata();
     let admin_list = authorization::get_admin_list();
     let read_only_users = authorization::get_read_only_users_list();
@@ -68,14 +74,31 @@ fn pre_upgrade() {
         trigger_fee_lamports: 5000,
         gas_reserve_lamports: 5000,
         cycle_refill_ratio: 0.3,
+        confirmation_timeout_seconds: types::DEFAULT_CONFIRMATION_TIMEOUT_SECONDS,
+        compute_unit_price_microlamports: types::DEFAULT_COMPUTE_UNIT_PRICE_MICROLAMPORTS,
+        compute_unit_limit: types::DEFAULT_COMPUTE_UNIT_LIMIT,
     });
     let canister_start_time = state::get_canister_start_time();
     let failed_payment_count = state::get_failed_payment_count();
     let health_check_counter = state::get_health_check_counter();
+    let timer_next_exec = timer::get_timer_next_exec();
+    let jitter_enabled = state::is_jitter_enabled();
+    let heartbeat = state::get_heartbeat_state();
+    let archived_subscriptions = subscription_manager::get_all_archived_subscriptions();
+    let use_sol_rpc_canister = state::use_sol_rpc_canister();
+    let rpc_endpoints = state::list_rpc_endpoints();
+    let subscription_order = subscription_manager::get_subscription_order();
+    let state_log = state::get_all_state_events();
+    let subscription_owners = subscription_manager::get_all_subscription_owners();
+    let cleanup_schedule = state::get_cleanup_schedule();
+    let alert_config = state::get_alert_config_internal();
+    let dead_letter_queue = subscription_manager::get_all_dead_letters();
+    let webhook_secrets = subscription_manager::get_all_webhook_secrets();
+    let expired_index = subscription_manager::get_expired_index();
+    let metrics_history = subscription_manager::get_all_metrics_history();
 
     // Create state structure for stable storage
-    let canister_state = state::create_canister_state(
-        subscriptions,
+    let canister_state = state::create_canister_state(state::CanisterStateSnapshot {
         admin_list,
         read_only_users,
         network_env,
@@ -92,11 +115,27 @@ fn pre_upgrade() {
         canister_start_time,
         failed_payment_count,
         health_check_counter,
-    );
+        timer_next_exec,
+        jitter_enabled,
+        heartbeat,
+        archived_subscriptions,
+        use_sol_rpc_canister,
+        rpc_endpoints,
+        subscription_order,
+        state_log,
+        subscription_owners,
+        cleanup_schedule,
+        alert_config,
+        dead_letter_queue,
+        webhook_secrets,
+        expired_index,
+        metrics_history,
+    });
 
-    match stable_save((&canister_state,)) {
+    // Subscriptions are already in their stable map
+    match state::save_canister_state(&canister_state) {
         Ok(_) => ic_cdk::println!("✅ State saved successfully"),
-        Err(e) => ic_cdk::println!("❌ Failed to save state: {:?}", e),
+        Err(e) => ic_cdk::println!("❌ Failed to save state: {}", e),
     }
 }
 
@@ -104,20 +143,46 @@ fn pre_upgrade() {
 fn post_upgrade() {
     ic_cdk::println!("🔄 Restoring state after upgrade...");
 
-    match stable_restore::<(state::CanisterState,)>() {
-        Ok((canister_state,)) => {
+    // Read a pre-memory-manager blob before the stable structures claim the memory
+    let restored = if state::has_legacy_stable_state() {
+        ic_cdk::println!("📦 Migrating legacy stable state into stable structures");
+        stable_restore::<(state::CanisterState,)>().map(|(canister_state,)| canister_state)
+    } else {
+        state::load_canister_state()
+    };
+
+    match restored {
+        Ok(canister_state) => {
             // Restore all state
             state::restore_canister_state(canister_state.clone());
 
             // Restore timers
-            let (active_timers, notification_timers) = timer::get_all_timers();
-            timer::restore_timers(active_timers, notification_timers);
+            match canister_state.timer_next_exec.clone() {
+                Some(next_exec) => timer::restore_timers(next_exec),
+                None => {
+                    // State from before timers were persisted: reschedule active subscriptions
+                    for subscription in subscription_manager::list_subscriptions() {
+                        if subscription.status == types::SubscriptionStatus::Active {
+                            timer::schedule_subscription_timer(&subscription);
+                            timer::schedule_notification_timer(&subscription);
+                        }
+                    }
+                }
+            }
 
             // Start blockhash refresh timer
             timer::start_blockhash_refresh_timer();
 
+            // Restart liveness heartbeat
+            timer::start_heartbeat_timer();
+
+            // Restart daily subscription cleanup, event indexing and metrics snapshots
+            timer::start_cleanup_timer();
+            timer::start_event_index_timer();
+            timer::start_metrics_snapshot_timer();
+
             ic_cdk::println!("✅ State restored successfully. {} subscriptions loaded",
-                              canister_state.subscriptions.len());
+                              subscription_manager::get_subscription_count());
         }
         Err(e) => {
             ic_cdk::println!("❌ Failed to restore state: {:?}. Starting fresh.", e);
@@ -158,9 +223,14 @@ async fn create_subscription(req: CreateSubscriptionRequest) -> Result<Subscript
     subscription_manager::create_subscription(req).await
 }
 
+#[update]
+async fn batch_create_subscriptions(reqs: Vec<CreateSubscriptionRequest>) -> Vec<Result<SubscriptionId, String>> {
+    subscription_manager::batch_create_subscriptions(reqs).await
+}
+
 #[query]
-fn get_subscription(id: SubscriptionId) -> Option<Subscription> {
-    subscription_manager::get_subscription(id)
+fn get_subscription(id: SubscriptionId) -> Option<SubscriptionWithStats> {
+    subscription_manager::get_subscription_with_stats(id)
 }
 
 #[query]
@@ -168,6 +238,41 @@ fn list_subscriptions() -> Vec<Subscription> {
     subscription_manager::list_subscriptions()
 }
 
+#[query]
+fn get_subscription_statistics() -> SubscriptionStats {
+    subscription_manager::get_subscription_statistics()
+}
+
+#[update]
+async fn simulate_payment_with_swap(id: SubscriptionId) -> Result<SwapSimulation, String> {
+    subscription_manager::simulate_payment_with_swap(id).await
+}
+
+#[query]
+fn get_subscription_revenue_forecast(merchant_address: String, days: u32) -> RevenueForecast {
+    subscription_manager::get_subscription_revenue_forecast(merchant_address, days)
+}
+
+#[query]
+fn get_metrics_history(from_day: u32, to_day: u32) -> Vec<DailyMetricsSnapshot> {
+    subscription_manager::get_metrics_history(from_day, to_day)
+}
+
+#[query]
+fn get_upcoming_payments(subscriber_address: String, days_ahead: u32) -> Vec<UpcomingPayment> {
+    subscription_manager::get_upcoming_payments(subscriber_address, days_ahead)
+}
+
+#[query]
+fn filter_subscriptions(filter: SubscriptionFilter) -> Vec<Subscription> {
+    subscription_manager::filter_subscriptions(filter)
+}
+
+#[query]
+fn list_subscriptions_paginated(page: u32, page_size: u32) -> Result<PaginatedSubscriptions, String> {
+    subscription_manager::list_subscriptions_paginated(page, page_size)
+}
+
 #[update]
 async fn update_subscription_addresses(
     id: SubscriptionId,
@@ -182,16 +287,61 @@ async fn pause_subscription(id: SubscriptionId) -> Result<(), String> {
     subscription_manager::pause_subscription(id).await
 }
 
+#[update]
+fn add_subscription_owner(id: SubscriptionId, principal: Principal) -> Result<(), String> {
+    subscription_manager::add_subscription_owner(id, principal)
+}
+
+#[update]
+fn remove_subscription_owner(id: SubscriptionId, principal: Principal) -> Result<(), String> {
+    subscription_manager::remove_subscription_owner(id, principal)
+}
+
+#[query]
+fn get_subscription_owners(id: SubscriptionId) -> Vec<Principal> {
+    subscription_manager::get_subscription_owners(id)
+}
+
 #[update]
 async fn resume_subscription(id: SubscriptionId) -> Result<(), String> {
     subscription_manager::resume_subscription(id)
 }
 
+#[update]
+fn archive_subscription(id: SubscriptionId) -> Result<(), String> {
+    subscription_manager::archive_subscription(id)
+}
+
+#[query]
+fn list_archived_subscriptions(page: u32, page_size: u32) -> Result<Vec<Subscription>, String> {
+    subscription_manager::list_archived_subscriptions(page, page_size)
+}
+
+#[update]
+fn restore_archived_subscription(id: SubscriptionId) -> Result<(), String> {
+    subscription_manager::restore_archived_subscription(id)
+}
+
+#[update]
+fn snooze_subscription(id: SubscriptionId, periods: u8) -> Result<(), String> {
+    subscription_manager::snooze_subscription(id, periods)
+}
+
 #[update]
 async fn cancel_subscription(id: SubscriptionId) -> Result<(), String> {
     subscription_manager::cancel_subscription(id).await
 }
 
+#[update]
+async fn verify_payment_confirmed(id: SubscriptionId, expected_tx: String) -> Result<VerificationResult, String> {
+    subscription_manager::verify_payment_confirmed(id, expected_tx).await
+}
+
+#[update]
+async fn bulk_cancel_by_merchant(merchant_address: String) -> Result<BulkCancelResult, String> {
+    subscription_manager::bulk_cancel_by_merchant(merchant_address).await
+}
+
 #[update]
 fn cleanup_old_subscriptions(older_than_seconds: u64) -> candid::Nat {
     let count = subscription_manager::cleanup_old_subscriptions(older_than_seconds);
@@ -314,6 +464,11 @@ async fn update_fee_config(new_config: FeeConfig) -> Result<(), String> {
     state::update_fee_config(new_config)
 }
 
+#[update]
+fn update_compute_budget(compute_unit_limit: u32, compute_unit_price_microlamports: u64) -> Result<(), String> {
+    state::update_compute_budget(compute_unit_limit, compute_unit_price_microlamports)
+}
+
 #[query]
 async fn get_fee_config() -> Result<FeeConfig, String> {
     state::get_fee_config()
@@ -358,6 +513,41 @@ async fn enable_auto_refill(enabled: bool) {
     state::enable_auto_refill(enabled)
 }
 
+#[update]
+fn set_timer_jitter_enabled(enabled: bool) -> Result<(), String> {
+    state::set_jitter_enabled(enabled)
+}
+
+#[query]
+fn is_timer_jitter_enabled() -> bool {
+    state::is_jitter_enabled()
+}
+
+#[update]
+fn set_use_sol_rpc_canister(enabled: bool) -> Result<(), String> {
+    state::set_use_sol_rpc_canister(enabled)
+}
+
+#[query]
+fn is_using_sol_rpc_canister() -> bool {
+    state::use_sol_rpc_canister()
+}
+
+#[update]
+fn add_rpc_endpoint(url: String) -> Result<(), String> {
+    state::add_rpc_endpoint(url)
+}
+
+#[update]
+fn remove_rpc_endpoint(url: String) -> Result<(), String> {
+    state::remove_rpc_endpoint(url)
+}
+
+#[query]
+fn list_rpc_endpoints() -> Vec<String> {
+    state::list_rpc_endpoints()
+}
+
 #[query]
 async fn monitor_cycles() -> Result<bool, String> {
     // Mock implementation
@@ -399,6 +589,47 @@ async fn check_wallet_health() -> health::WalletHealthReport {
     health::check_wallet_health().await
 }
 
+#[query]
+fn get_heartbeat_status() -> HeartbeatStatus {
+    state::get_heartbeat_status()
+}
+
+#[query]
+fn get_active_trigger_count() -> u32 {
+    subscription_manager::get_active_trigger_count()
+}
+
+#[update]
+fn set_alert_webhook(
+    alert_webhook_url: Option<String>,
+    alert_thresholds: AlertThresholds,
+    webhook_signing_secret: Option<String>,
+) -> Result<(), String> {
+    state::set_alert_webhook(alert_webhook_url, alert_thresholds, webhook_signing_secret)
+}
+
+#[query]
+fn get_alert_config() -> Result<(Option<String>, AlertThresholds, Option<Timestamp>), String> {
+    state::get_alert_config()
+}
+
+#[query]
+fn get_cleanup_schedule() -> CleanupSchedule {
+    state::get_cleanup_schedule()
+}
+
+#[update]
+fn set_cleanup_age_seconds(cleanup_age_seconds: u64) -> Result<(), String> {
+    state::set_cleanup_age_seconds(cleanup_age_seconds)
+}
+
+#[update]
+fn set_heartbeat_config(contract_address: Option<String>, interval_seconds: u64) -> Result<(), String> {
+    state::set_heartbeat_config(contract_address, interval_seconds)?;
+    timer::start_heartbeat_timer();
+    Ok(())
+}
+
 #[query]
 async fn perform_emergency_health_check() -> health::EmergencyHealthReport {
     health::perform_emergency_health_check().await
@@ -409,6 +640,21 @@ async fn get_subscription_health_metrics() -> health::SubscriptionHealthMetrics
     health::get_subscription_health_metrics().await
 }
 
+#[update]
+async fn check_solana_connectivity() -> health::ConnectivityStatus {
+    health::check_solana_connectivity().await
+}
+
+#[query]
+fn get_cycle_analytics() -> health::CycleAnalytics {
+    health::get_cycle_analytics()
+}
+
+#[query]
+fn get_prometheus_metrics() -> Vec<u8> {
+    health::get_prometheus_metrics()
+}
+
 #[query]
 fn ping() -> (String, Timestamp, String) {
     ("ok".to_string(), time(), "1.0.0".to_string())
@@ -562,6 +808,51 @@ async fn delete_encrypted_metadata(subscription_id: String) -> Result<(), String
     state::delete_encrypted_metadata(subscription_id)
 }
 
+#[query]
+fn get_trace_log(trace_id: String) -> Result<Vec<String>, String> {
+    state::get_trace_log(trace_id)
+}
+
+#[query]
+fn get_state_log(after: Option<Timestamp>, limit: u32) -> Result<Vec<StateEvent>, String> {
+    state::get_state_log(after, limit)
+}
+
+#[update]
+fn set_subscription_webhook(id: SubscriptionId, url: Option<String>, secret: [u8; 32]) -> Result<(), String> {
+    subscription_manager::set_subscription_webhook(id, url, secret)
+}
+
+#[query]
+fn get_webhook_delivery_log(limit: u32) -> Result<Vec<WebhookDelivery>, String> {
+    subscription_manager::get_webhook_delivery_log(limit)
+}
+
+#[query]
+fn get_dead_letter_queue(limit: u32) -> Result<Vec<FailedPaymentRecord>, String> {
+    subscription_manager::get_dead_letter_queue(limit)
+}
+
+#[update]
+fn retry_from_dlq(id: SubscriptionId) -> Result<(), String> {
+    subscription_manager::retry_from_dlq(id)
+}
+
+#[update]
+fn clear_dlq() -> Result<(), String> {
+    subscription_manager::clear_dlq()
+}
+
+#[update]
+fn delete_subscriber_data(subscriber_address: String) -> Result<u64, String> {
+    subscription_manager::delete_subscriber_data(subscriber_address)
+}
+
+#[query]
+fn get_deletion_log() -> Result<Vec<DeletionRecord>, String> {
+    state::get_deletion_log()
+}
+
 #[query]
 async fn list_encrypted_metadata() -> Result<Vec<String>, String> {
     state::list_encrypted_metadata()
@@ -668,32 +959,14 @@ async fn generate_payment_signature(
 /// This combines subscription creation with signature generation
 #[update]
 async fn create_subscription_with_signature(
-    subscription_id: String,
-    solana_contract_address: String,
-    payment_token_mint: String,
-    amount: u64,
-    subscriber_address: String,
-    merchant_address: String,
-    interval_seconds: i64,
-    start_time: Option<u64>,
-    api_key: String,
+    req: CreateSubscriptionRequest,
 ) -> Result<(String, Vec<u8>, i64), String> {
     // First validate the license
-    license::validate_api_key(&api_key).await
+    license::validate_api_key(&req.api_key).await
         .map_err(|e| format!("License validation failed: {}", e))?;
 
-    // Create the subscription request struct
-    let req = CreateSubscriptionRequest {
-        subscription_id: subscription_id.clone(),
-        solana_contract_address,
-        payment_token_mint,
-        amount,
-        subscriber_address,
-        merchant_address,
-        interval_seconds: interval_seconds as u64,
-        start_time,
-        api_key,
-    };
+    let subscription_id = req.subscription_id.clone();
+    let amount = req.amount;
 
     // Create the subscription
     let sub_result = subscription_manager::create_subscription(req).await?;
@@ -729,4 +1002,21 @@ fn transform_http_response(raw: TransformArgs) -> HttpResponse {
 // =============================================================================
 
 // Export all the public functions for Candid interface generation
-export_candid!();
\ No newline at end of file
+export_candid!();
+#[cfg(test)]
+mod tests {
+    // The committed .did is what build.rs turns into TypeScript bindings, so it has to track
+    // the Rust interface. Run with UPDATE_CANDID=1 to rewrite it after changing an endpoint.
+    #[test]
+    fn test_candid_interface_is_up_to_date() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/ouroc_timer_rust.did");
+        let generated = super::__export_service();
+
+        if std::env::var_os("UPDATE_CANDID").is_some() {
+            std::fs::write(path, &generated).unwrap();
+            return;
+        }
+        let committed = std::fs::read_to_string(path).unwrap_or_default();
+        assert!(committed == generated, "ouroc_timer_rust.did is out of date; rerun with UPDATE_CANDID=1");
+    }
+}
@@ -29,6 +29,7 @@ mod solana_client;
 mod authorization;
 mod cycle_management;
 mod threshold_ed25519;
+mod transaction_wire;
 
 // Re-export commonly used types and functions
 pub use solana_client::*;
@@ -102,6 +103,7 @@ pub struct Subscription {
     pub failed_payment_count: u32,
     pub last_failure_time: Option<Timestamp>,
     pub last_error: Option<String>,
+    pub reminder_days_before_payment: u32,
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -126,6 +128,7 @@ pub struct CreateSubscriptionRequest {
     pub interval_seconds: u64,
     pub start_time: Option<Timestamp>,
     pub api_key: String,
+    pub reminder_days_before_payment: Option<u32>,
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -607,6 +610,7 @@ async fn create_subscription(req: CreateSubscriptionRequest) -> Result<Subscript
         failed_payment_count: 0,
         last_failure_time: None,
         last_error: None,
+        reminder_days_before_payment: req.reminder_days_before_payment.unwrap_or(1),
     };
 
     // Store subscription
@@ -744,7 +748,8 @@ fn schedule_subscription_timer(subscription: &Subscription) {
 }
 
 fn schedule_notification_timer(subscription: &Subscription) {
-    let notification_time = subscription.next_execution - (24 * 60 * 60 * 1_000_000_000); // 24 hours before
+    let reminder_nanos = subscription.reminder_days_before_payment as u64 * 24 * 60 * 60 * 1_000_000_000;
+    let notification_time = subscription.next_execution.saturating_sub(reminder_nanos);
     let now = time();
 
     if notification_time > now {
@@ -915,27 +920,28 @@ async fn send_solana_opcode(
     let timestamp_bytes = time().to_le_bytes();
     instruction_data.extend_from_slice(&timestamp_bytes);
 
-    // Build accounts (simplified)
+    // Build accounts (simplified); invalid addresses are rejected when serializing
+    let decode_address = |address: &str| bs58::decode(address).into_vec().unwrap_or_default();
     let accounts = vec![
         SolanaAccountMeta {
-            pubkey: contract_address.as_bytes().to_vec(),
+            pubkey: decode_address(contract_address),
             is_signer: false,
             is_writable: true,
         },
         SolanaAccountMeta {
-            pubkey: subscriber_address.as_bytes().to_vec(),
+            pubkey: decode_address(subscriber_address),
             is_signer: false,
             is_writable: false,
         },
         SolanaAccountMeta {
-            pubkey: merchant_address.as_bytes().to_vec(),
+            pubkey: decode_address(merchant_address),
             is_signer: false,
             is_writable: false,
         },
     ];
 
     let instruction = SolanaInstruction {
-        program_id: contract_address.as_bytes().to_vec(),
+        program_id: decode_address(contract_address),
         accounts,
         data: instruction_data,
     };
@@ -946,8 +952,11 @@ async fn send_solana_opcode(
         fee_payer: keypair.public_key.clone(),
     };
 
-    // Serialize transaction (simplified)
-    let serialized_tx = serialize_transaction(&transaction);
+    // Serialize transaction (signature slots are filled in after signing)
+    let serialized_tx = match serialize_transaction(&transaction) {
+        Ok(tx) => tx,
+        Err(e) => return Err(format!("Failed to serialize transaction: {}", e)),
+    };
 
     // Sign transaction
     let signature = match sign_with_main_key(serialized_tx).await {
@@ -965,11 +974,39 @@ async fn send_solana_opcode(
     }
 }
 
-// Simplified transaction serialization
-fn serialize_transaction(_transaction: &SolanaTransaction) -> Vec<u8> {
-    // This is a simplified implementation
-    // In production, implement proper Solana transaction serialization
-    b"mock_serialized_transaction".to_vec()
+// Serialize to the Solana legacy transaction wire format with zeroed signature slots
+fn serialize_transaction(transaction: &SolanaTransaction) -> std::result::Result<Vec<u8>, String> {
+    fn to_key(bytes: &[u8]) -> std::result::Result<[u8; 32], String> {
+        bytes.try_into().map_err(|_| format!("Public key must be 32 bytes, got {}", bytes.len()))
+    }
+
+    let instructions = transaction.instructions.iter()
+        .map(|ix| {
+            Ok(transaction_wire::WireInstruction {
+                program_id: to_key(&ix.program_id)?,
+                accounts: ix.accounts.iter()
+                    .map(|meta| Ok(transaction_wire::WireAccountMeta {
+                        pubkey: to_key(&meta.pubkey)?,
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    }))
+                    .collect::<std::result::Result<Vec<_>, String>>()?,
+                data: ix.data.clone(),
+            })
+        })
+        .collect::<std::result::Result<Vec<_>, String>>()?;
+
+    let blockhash = bs58::decode(&transaction.recent_blockhash)
+        .into_vec()
+        .map_err(|e| format!("Invalid blockhash: {}", e))?;
+
+    let message = transaction_wire::compile_message(
+        to_key(&transaction.fee_payer)?,
+        &instructions,
+        to_key(&blockhash)?,
+    )?;
+    let signatures = vec![[0u8; 64]; message[0] as usize];
+    transaction_wire::serialize_transaction(&signatures, &message)
 }
 
 // The get_main_keypair function is now provided by the threshold_ed25519 module
@@ -20,7 +20,7 @@ pub async fn validate_api_key(api_key: &str) -> Result<LicenseValidationResult,
             .cloned()
     }) {
         ic_cdk::println!("✅ Using cached license validation");
-        return Ok(cached_result);
+        return Ok(with_quota_usage(cached_result, api_key));
     }
 
     // Mock validation - in production, this would call an external license service
@@ -31,7 +31,23 @@ pub async fn validate_api_key(api_key: &str) -> Result<LicenseValidationResult,
         cache.borrow_mut().insert(api_key.to_string(), result.clone());
     });
 
-    Ok(result)
+    Ok(with_quota_usage(result, api_key))
+}
+
+/// Quota usage changes with every subscription, so it is filled in fresh rather than cached
+fn with_quota_usage(mut result: LicenseValidationResult, api_key: &str) -> LicenseValidationResult {
+    result.subscription_quota_used = crate::subscription_manager::count_subscriptions_for_api_key(api_key);
+    result.subscription_quota_limit = result.tier.as_ref().and_then(get_tier_limits);
+    result
+}
+
+/// Maximum subscriptions per tier; None means unlimited
+pub fn get_tier_limits(tier: &LicenseTier) -> Option<u32> {
+    match tier {
+        LicenseTier::Community => Some(10),
+        LicenseTier::Beta => Some(100),
+        LicenseTier::Enterprise => None,
+    }
 }
 
 pub async fn consume_license_usage(api_key: &str) -> Result<(), String> {
@@ -104,6 +120,8 @@ async fn mock_license_validation(api_key: &str) -> Result<LicenseValidationResul
             tier: None,
             rate_limit_remaining: 0,
             expires_at,
+            subscription_quota_used: 0,
+            subscription_quota_limit: None,
             message: "API key is required".to_string(),
         });
     }
@@ -119,6 +137,8 @@ async fn mock_license_validation(api_key: &str) -> Result<LicenseValidationResul
                 tier: Some(LicenseTier::Community),
                 rate_limit_remaining,
                 expires_at,
+                subscription_quota_used: 0,
+                subscription_quota_limit: None,
                 message: "Community license valid".to_string(),
             })
         }
@@ -129,6 +149,8 @@ async fn mock_license_validation(api_key: &str) -> Result<LicenseValidationResul
                 tier: Some(LicenseTier::Beta),
                 rate_limit_remaining,
                 expires_at,
+                subscription_quota_used: 0,
+                subscription_quota_limit: None,
                 message: "Beta license valid".to_string(),
             })
         }
@@ -139,6 +161,8 @@ async fn mock_license_validation(api_key: &str) -> Result<LicenseValidationResul
                 tier: Some(LicenseTier::Enterprise),
                 rate_limit_remaining,
                 expires_at,
+                subscription_quota_used: 0,
+                subscription_quota_limit: None,
                 message: "Enterprise license valid".to_string(),
             })
         }
@@ -149,6 +173,8 @@ async fn mock_license_validation(api_key: &str) -> Result<LicenseValidationResul
                 tier: None,
                 rate_limit_remaining: 0,
                 expires_at,
+                subscription_quota_used: 0,
+                subscription_quota_limit: None,
                 message: "Invalid API key format".to_string(),
             })
         }
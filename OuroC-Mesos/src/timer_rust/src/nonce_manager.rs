@@ -9,9 +9,25 @@ use solana_instruction::{AccountMeta, Instruction};
 use solana_message::Message;
 use solana_pubkey::Pubkey;
 use std::str::FromStr;
+use sha2::{Digest, Sha256};
+use crate::transaction_wire::{compile_message, WireAccountMeta, WireInstruction};
 
-// System program ID (hardcoded for compatibility)
-pub const SYSTEM_PROGRAM_ID: Pubkey = Pubkey::new_from_array([1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1]);
+// System program ID ("11111111111111111111111111111111" is 32 zero bytes)
+pub const SYSTEM_PROGRAM_ID: Pubkey = Pubkey::new_from_array([0; 32]);
+
+// Sysvars the system program's nonce instructions read
+const RECENT_BLOCKHASHES_SYSVAR: &str = "SysvarRecentB1ockHashes11111111111111111111";
+const RENT_SYSVAR: &str = "SysvarRent111111111111111111111111111111111";
+
+// SystemInstruction discriminants (u32 little-endian)
+const SYSTEM_IX_CREATE_ACCOUNT_WITH_SEED: u32 = 3;
+const SYSTEM_IX_ADVANCE_NONCE_ACCOUNT: u32 = 4;
+const SYSTEM_IX_INITIALIZE_NONCE_ACCOUNT: u32 = 6;
+
+// Nonce account size and its rent-exempt minimum
+const NONCE_ACCOUNT_LENGTH: u64 = 80;
+const NONCE_ACCOUNT_RENT_EXEMPT_LAMPORTS: u64 = 1_447_680;
+const NONCE_ACCOUNT_SEED: &str = "ouroc-nonce";
 
 /// Configuration for nonce account
 pub struct NonceConfig {
@@ -51,12 +67,17 @@ impl NonceConfig {
 
     /// Get the current nonce value from the blockchain
     pub async fn get_current_nonce(&self) -> Result<Hash, String> {
+        ic_cdk::println!("🔍 Fetching nonce from account: {}", self.nonce_account);
+
+        if !crate::state::use_sol_rpc_canister() {
+            let nonce = NonceManager::fetch_nonce_value(&self.nonce_account).await?;
+            return Ok(Hash::new_from_array(nonce));
+        }
+
         let client = create_sol_rpc_client();
         let nonce_pubkey = Pubkey::from_str(&self.nonce_account)
             .map_err(|e| format!("Invalid nonce account address: {}", e))?;
 
-        ic_cdk::println!("🔍 Fetching nonce from account: {}", self.nonce_account);
-
         let account_info = client
             .get_account_info(nonce_pubkey)
             .send()
@@ -90,15 +111,7 @@ impl NonceConfig {
     pub fn create_advance_nonce_instruction(&self) -> Instruction {
         let nonce_pubkey = Pubkey::from_str(&self.nonce_account).unwrap();
         let authority_pubkey = Pubkey::from_str(&self.authority).unwrap();
-
-        Instruction {
-            program_id: SYSTEM_PROGRAM_ID,
-            accounts: vec![
-                AccountMeta::new(nonce_pubkey, false),
-                AccountMeta::new_readonly(authority_pubkey, true),
-            ],
-            data: vec![2, 0, 0, 0], // Advance nonce instruction
-        }
+        advance_nonce_instruction(&nonce_pubkey, &authority_pubkey)
     }
 
     /// Create instruction to initialize nonce account (for one-time setup)
@@ -106,18 +119,155 @@ impl NonceConfig {
         let nonce_pubkey = Pubkey::from_str(&self.nonce_account).unwrap();
         let authority_pubkey = Pubkey::from_str(&self.authority).unwrap();
 
+        let mut data = SYSTEM_IX_INITIALIZE_NONCE_ACCOUNT.to_le_bytes().to_vec();
+        data.extend_from_slice(authority_pubkey.as_ref());
+
         Instruction {
             program_id: SYSTEM_PROGRAM_ID,
             accounts: vec![
                 AccountMeta::new(nonce_pubkey, false),
-                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
-                AccountMeta::new_readonly(authority_pubkey, true),
+                AccountMeta::new_readonly(Pubkey::from_str(RECENT_BLOCKHASHES_SYSVAR).unwrap(), false),
+                AccountMeta::new_readonly(Pubkey::from_str(RENT_SYSVAR).unwrap(), false),
             ],
-            data: vec![0, 0, 0, 0], // Initialize nonce instruction
+            data,
         }
     }
 }
 
+/// AdvanceNonceAccount: [nonce (writable), RecentBlockhashes sysvar, authority (signer)]
+fn advance_nonce_instruction(nonce_account: &Pubkey, authority: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: SYSTEM_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*nonce_account, false),
+            AccountMeta::new_readonly(Pubkey::from_str(RECENT_BLOCKHASHES_SYSVAR).unwrap(), false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: SYSTEM_IX_ADVANCE_NONCE_ACCOUNT.to_le_bytes().to_vec(),
+    }
+}
+
+pub(crate) fn to_wire_instruction(instruction: Instruction) -> WireInstruction {
+    WireInstruction {
+        program_id: instruction.program_id.to_bytes(),
+        accounts: instruction.accounts.iter()
+            .map(|meta| WireAccountMeta {
+                pubkey: meta.pubkey.to_bytes(),
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            })
+            .collect(),
+        data: instruction.data,
+    }
+}
+
+/// Durable nonce management over Solana RPC HTTPS outcalls
+/// Transactions built via `build_and_send_transaction` use the nonce in place of a recent
+/// blockhash, which the IC's replicas can't agree on reliably
+pub struct NonceManager;
+
+impl NonceManager {
+    /// Address of the nonce account owned by `authority` (created with a seed so the canister
+    /// can create it without holding a second keypair)
+    pub fn nonce_account_address(authority: &Pubkey) -> Pubkey {
+        let mut hasher = Sha256::new();
+        hasher.update(authority.as_ref());
+        hasher.update(NONCE_ACCOUNT_SEED.as_bytes());
+        hasher.update(SYSTEM_PROGRAM_ID.as_ref());
+        Pubkey::new_from_array(hasher.finalize().into())
+    }
+
+    /// Create and fund a nonce account with `authority` as payer and nonce authority
+    /// Returns the nonce account address
+    pub async fn initialize_nonce_account(authority: &str, rpc: &str) -> Result<String, String> {
+        let authority_pubkey = Pubkey::from_str(authority)
+            .map_err(|e| format!("Invalid authority address: {}", e))?;
+        let nonce_pubkey = Self::nonce_account_address(&authority_pubkey);
+
+        ic_cdk::println!("🔧 Creating nonce account {} for authority {}", nonce_pubkey, authority);
+
+        let config = NonceConfig {
+            authority: authority.to_string(),
+            nonce_account: nonce_pubkey.to_string(),
+        };
+        let instructions = [
+            to_wire_instruction(create_nonce_account_with_seed_instruction(&authority_pubkey, &nonce_pubkey)),
+            to_wire_instruction(config.create_initialize_nonce_instruction()),
+        ];
+
+        // A brand-new nonce can't sign for itself, so setup uses a recent blockhash
+        let blockhash = crate::solana::latest_blockhash(rpc).await?;
+        let message = compile_message(
+            authority_pubkey.to_bytes(),
+            &instructions,
+            crate::transaction_wire::decode_base58_32(&blockhash)?,
+        )?;
+
+        let signed_transaction = crate::solana::sign_transaction_with_ecdsa(&message).await?;
+        let tx_signature = crate::solana::submit_transaction(rpc, &signed_transaction).await?;
+
+        ic_cdk::println!("✅ Nonce account {} created | tx: {}", nonce_pubkey, tx_signature);
+        Ok(nonce_pubkey.to_string())
+    }
+
+    /// Read the current durable nonce via getAccountInfo
+    pub async fn fetch_nonce_value(nonce_account: &str) -> Result<[u8; 32], String> {
+        let data = crate::solana::get_account_data_via_https(nonce_account)
+            .await?
+            .ok_or("Nonce account not found. Please create it first.")?;
+
+        parse_nonce_value(&data)
+    }
+
+    /// AdvanceNonceAccount instruction to place first in a durable-nonce transaction
+    pub fn advance_nonce_ix(nonce_account: &str, authority_pubkey: Vec<u8>) -> Result<WireInstruction, String> {
+        let nonce_pubkey = Pubkey::from_str(nonce_account)
+            .map_err(|e| format!("Invalid nonce account address: {}", e))?;
+        let authority: [u8; 32] = authority_pubkey.as_slice().try_into()
+            .map_err(|_| format!("Authority public key must be 32 bytes, got {}", authority_pubkey.len()))?;
+
+        Ok(to_wire_instruction(advance_nonce_instruction(&nonce_pubkey, &Pubkey::new_from_array(authority))))
+    }
+}
+
+/// CreateAccountWithSeed for a rent-exempt, system-owned nonce account
+fn create_nonce_account_with_seed_instruction(authority: &Pubkey, nonce_account: &Pubkey) -> Instruction {
+    let mut data = SYSTEM_IX_CREATE_ACCOUNT_WITH_SEED.to_le_bytes().to_vec();
+    data.extend_from_slice(authority.as_ref()); // base
+    data.extend_from_slice(&(NONCE_ACCOUNT_SEED.len() as u64).to_le_bytes());
+    data.extend_from_slice(NONCE_ACCOUNT_SEED.as_bytes());
+    data.extend_from_slice(&NONCE_ACCOUNT_RENT_EXEMPT_LAMPORTS.to_le_bytes());
+    data.extend_from_slice(&NONCE_ACCOUNT_LENGTH.to_le_bytes());
+    data.extend_from_slice(SYSTEM_PROGRAM_ID.as_ref()); // owner
+
+    Instruction {
+        program_id: SYSTEM_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(*nonce_account, false),
+        ],
+        data,
+    }
+}
+
+/// Extract the durable nonce from nonce account data
+/// Layout: version u32, state u32 (1 = initialized), authority (32), durable nonce (32), fee calculator (8)
+pub fn parse_nonce_value(data: &[u8]) -> Result<[u8; 32], String> {
+    const STATE_OFFSET: usize = 4;
+    const NONCE_OFFSET: usize = 4 + 4 + 32;
+    const STATE_INITIALIZED: u32 = 1;
+
+    if data.len() < NONCE_OFFSET + 32 {
+        return Err(format!("Nonce account data too short: {} bytes", data.len()));
+    }
+    let state = u32::from_le_bytes(data[STATE_OFFSET..STATE_OFFSET + 4].try_into().unwrap());
+    if state != STATE_INITIALIZED {
+        return Err("Nonce account is not initialized".to_string());
+    }
+
+    Ok(data[NONCE_OFFSET..NONCE_OFFSET + 32].try_into().unwrap())
+}
+
 /// Create a transaction using durable nonce instead of blockhash
 pub fn create_nonce_transaction(
     instructions: Vec<Instruction>,
@@ -125,15 +275,7 @@ pub fn create_nonce_transaction(
     nonce: &Hash,
     nonce_account: &Pubkey,
 ) -> Message {
-    // Manual advance nonce instruction
-    let nonce_instruction = Instruction {
-        program_id: SYSTEM_PROGRAM_ID,
-        accounts: vec![
-            AccountMeta::new(*nonce_account, false),
-            AccountMeta::new_readonly(*payer, true),
-        ],
-        data: vec![2, 0, 0, 0], // Advance nonce instruction
-    };
+    let nonce_instruction = advance_nonce_instruction(nonce_account, payer);
 
     let mut all_instructions = vec![nonce_instruction];
     all_instructions.extend(instructions);
@@ -157,4 +299,41 @@ mod tests {
         // Should produce a deterministic result
         assert!(nonce_account != Pubkey::default());
     }
+
+    #[test]
+    fn test_system_program_id() {
+        assert_eq!(SYSTEM_PROGRAM_ID, Pubkey::from_str("11111111111111111111111111111111").unwrap());
+    }
+
+    #[test]
+    fn test_advance_nonce_ix() {
+        let nonce_account = "A8CgmkD62QatJCEDh8pcN123SyXbQmjKwfvz3qJYPg2Z";
+        let authority = [7u8; 32];
+        let ix = NonceManager::advance_nonce_ix(nonce_account, authority.to_vec()).unwrap();
+
+        assert_eq!(ix.program_id, [0u8; 32]);
+        assert_eq!(ix.data, vec![4, 0, 0, 0]);
+        assert_eq!(ix.accounts.len(), 3);
+        assert!(ix.accounts[0].is_writable && !ix.accounts[0].is_signer);
+        assert_eq!(ix.accounts[1].pubkey, Pubkey::from_str(RECENT_BLOCKHASHES_SYSVAR).unwrap().to_bytes());
+        assert_eq!(ix.accounts[2].pubkey, authority);
+        assert!(ix.accounts[2].is_signer);
+
+        assert!(NonceManager::advance_nonce_ix(nonce_account, vec![1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_parse_nonce_value() {
+        let mut data = vec![0u8; 80];
+        data[0..4].copy_from_slice(&1u32.to_le_bytes()); // current version
+        data[4..8].copy_from_slice(&1u32.to_le_bytes()); // initialized
+        data[8..40].copy_from_slice(&[5u8; 32]); // authority
+        data[40..72].copy_from_slice(&[9u8; 32]); // durable nonce
+
+        assert_eq!(parse_nonce_value(&data).unwrap(), [9u8; 32]);
+
+        data[4..8].copy_from_slice(&0u32.to_le_bytes());
+        assert!(parse_nonce_value(&data).is_err());
+        assert!(parse_nonce_value(&data[..40]).is_err());
+    }
 }
\ No newline at end of file
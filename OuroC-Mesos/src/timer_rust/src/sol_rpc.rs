@@ -1,12 +1,17 @@
 // SOL RPC Client wrapper for interacting with IC's SOL RPC canister
 // This module provides a clean interface to the mainnet SOL RPC canister: tghme-zyaaa-aaaar-qarca-cai
+// `SolRpcClient` is the transport used when `use_sol_rpc_canister` is on; HTTPS outcalls are the fallback
 
 use crate::state::get_network_config;
-use sol_rpc_client::{IcRuntime, SolRpcClient};
+use base64::Engine;
+use sol_rpc_client::{IcRuntime, SolRpcClient as SolRpcCanisterClient};
 use sol_rpc_types::{
-    CommitmentLevel,
+    CommitmentLevel, MultiRpcResult, SendTransactionEncoding, SendTransactionParams,
 };
 use candid::Principal;
+use solana_pubkey::Pubkey;
+use std::fmt::Debug;
+use std::str::FromStr;
 
 /// The IC mainnet SOL RPC canister ID
 const SOL_RPC_CANISTER_ID: &str = "tghme-zyaaa-aaaar-qarca-cai";
@@ -14,7 +19,11 @@ const SOL_RPC_CANISTER_ID: &str = "tghme-zyaaa-aaaar-qarca-cai";
 /// Create a SOL RPC client configured for Solana Devnet
 /// This client makes inter-canister calls to the IC's SOL RPC canister
 /// Uses single provider (DrpcDevnet) for transaction submissions to avoid consensus issues
-pub fn create_sol_rpc_client() -> SolRpcClient<IcRuntime> {
+pub fn create_sol_rpc_client() -> SolRpcCanisterClient<IcRuntime> {
+    create_sol_rpc_client_for(get_sol_rpc_canister_id())
+}
+
+fn create_sol_rpc_client_for(sol_rpc_principal: Principal) -> SolRpcCanisterClient<IcRuntime> {
     let (_network_env, _key_name, _rpc_endpoint) = get_network_config();
 
     // Use single provider (DrpcDevnet) to avoid consensus issues with transaction submissions
@@ -25,15 +34,11 @@ pub fn create_sol_rpc_client() -> SolRpcClient<IcRuntime> {
         RpcSource::Supported(SupportedRpcProviderId::DrpcDevnet)
     ]);
 
-    // Parse the SOL RPC canister ID
-    let sol_rpc_principal = Principal::from_text(SOL_RPC_CANISTER_ID)
-        .expect("Invalid SOL RPC canister ID");
-
-    ic_cdk::println!("🔗 Creating SOL RPC client for canister: {}", SOL_RPC_CANISTER_ID);
+    ic_cdk::println!("🔗 Creating SOL RPC client for canister: {}", sol_rpc_principal);
     ic_cdk::println!("   Network: Solana Devnet");
     ic_cdk::println!("   Provider: DrpcDevnet (single provider for transaction consensus)");
 
-    SolRpcClient::builder(IcRuntime, sol_rpc_principal)
+    SolRpcCanisterClient::builder(IcRuntime, sol_rpc_principal)
         .with_rpc_sources(rpc_sources)
         .with_default_commitment_level(CommitmentLevel::Finalized)
         .build()
@@ -44,3 +49,98 @@ pub fn get_sol_rpc_canister_id() -> Principal {
     Principal::from_text(SOL_RPC_CANISTER_ID)
         .expect("Invalid SOL RPC canister ID")
 }
+
+/// Production Solana RPC access through the SOL RPC canister (inter-canister Candid calls),
+/// used instead of HTTPS outcalls when `use_sol_rpc_canister` is enabled
+pub struct SolRpcClient {
+    pub rpc_canister: Principal,
+}
+
+impl Default for SolRpcClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SolRpcClient {
+    pub fn new() -> Self {
+        Self { rpc_canister: get_sol_rpc_canister_id() }
+    }
+
+    fn client(&self) -> SolRpcCanisterClient<IcRuntime> {
+        create_sol_rpc_client_for(self.rpc_canister)
+    }
+
+    /// Lamport balance of `address`
+    pub async fn get_balance(&self, address: &str) -> Result<u64, String> {
+        let pubkey = parse_pubkey(address)?;
+        let result = self.client().get_balance(pubkey).send().await;
+        expect_consistent(result, "getBalance")
+    }
+
+    /// Raw data of `address` (None if the account doesn't exist)
+    pub async fn get_account_data(&self, address: &str) -> Result<Option<Vec<u8>>, String> {
+        let pubkey = parse_pubkey(address)?;
+        let result = self.client().get_account_info(pubkey).send().await;
+
+        match expect_consistent(result, "getAccountInfo")? {
+            Some(account) => account.data.decode()
+                .map(Some)
+                .ok_or_else(|| format!("Undecodable account data for {}", address)),
+            None => Ok(None),
+        }
+    }
+
+    /// Recent blockhash (base58), estimated from the latest finalized block
+    pub async fn get_latest_blockhash(&self) -> Result<String, String> {
+        self.client()
+            .estimate_recent_blockhash()
+            .send()
+            .await
+            .map(|hash| hash.to_string())
+            .map_err(|errors| format!("Failed to estimate recent blockhash: {:?}", errors))
+    }
+
+    /// Submit a signed, wire-serialized transaction and return its signature
+    /// Inconsistent provider responses count as sent if any provider accepted the transaction
+    pub async fn send_transaction(&self, tx_bytes: Vec<u8>) -> Result<String, String> {
+        let params = SendTransactionParams::from_encoded_transaction(
+            base64::engine::general_purpose::STANDARD.encode(&tx_bytes),
+            SendTransactionEncoding::Base64,
+        );
+
+        match self.client().send_transaction(params).send().await {
+            MultiRpcResult::Consistent(result) => result
+                .map(|signature| signature.to_string())
+                .map_err(|e| format!("Transaction failed: {:?}", e)),
+            MultiRpcResult::Inconsistent(results) => results
+                .iter()
+                .find_map(|(_source, result)| result.as_ref().ok().map(|signature| signature.to_string()))
+                .ok_or_else(|| format!("All RPC providers failed. Results: {:?}", results)),
+        }
+    }
+
+    /// Token account balance as (raw amount, decimals)
+    pub async fn get_token_account_balance(&self, account: &str) -> Result<(u64, u8), String> {
+        let pubkey = parse_pubkey(account)?;
+        let result = self.client().get_token_account_balance(pubkey).send().await;
+        let balance = expect_consistent(result, "getTokenAccountBalance")?;
+
+        let amount = balance.amount.parse::<u64>()
+            .map_err(|e| format!("Invalid token amount {}: {}", balance.amount, e))?;
+        Ok((amount, balance.decimals))
+    }
+}
+
+fn parse_pubkey(address: &str) -> Result<Pubkey, String> {
+    Pubkey::from_str(address).map_err(|e| format!("Invalid Solana address {}: {}", address, e))
+}
+
+fn expect_consistent<T: Debug>(result: MultiRpcResult<T>, method: &str) -> Result<T, String> {
+    match result {
+        MultiRpcResult::Consistent(result) => result.map_err(|e| format!("RPC error in {}: {:?}", method, e)),
+        MultiRpcResult::Inconsistent(results) => {
+            Err(format!("Inconsistent {} responses from RPC providers: {:?}", method, results))
+        }
+    }
+}
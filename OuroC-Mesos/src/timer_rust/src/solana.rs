@@ -2,6 +2,7 @@
 
 use crate::types::*;
 use crate::state::{get_network_config, get_main_wallet_address};
+use crate::sol_rpc::SolRpcClient;
 use ic_cdk::api::time;
 use candid::{CandidType, Deserialize, Principal};
 use sha2::{Sha256, Digest};
@@ -89,32 +90,44 @@ pub async fn send_solana_opcode(
     subscription_id: &str,
     subscriber_address: &str,
     merchant_address: &str,
-    opcode: u8, // 0 = Payment, 1 = Notification
+    opcode: u8, // 0 = Payment, 1 = Notification, 255 = Heartbeat
+    trace_id: Option<&str>,
 ) -> Result<String, String> {
     ic_cdk::println!("🔗 Sending Solana opcode {} to contract: {} for subscription: {}",
                       opcode, contract_address, subscription_id);
 
-    let (_network, _key_name, rpc_endpoint) = get_network_config();
+    let rpc_endpoint = get_healthy_rpc().await?;
     let main_wallet = get_main_wallet_address();
 
-    // Build transaction instruction data
-    let mut instruction_data = vec![opcode]; // Opcode as first byte
+    // Build transaction instruction data, routed to process_trigger by its Anchor discriminator
+    let mut instruction_data = crate::anchor_utils::instruction_discriminator(crate::anchor_utils::PROCESS_TRIGGER).to_vec();
+    instruction_data.push(opcode);
 
     // Add subscription ID as bytes (32 bytes max for Solana account addressing)
     let sub_id_bytes = subscription_id.as_bytes();
     let sub_id_len = sub_id_bytes.len().min(32);
     instruction_data.extend_from_slice(&sub_id_bytes[..sub_id_len]);
 
-    // Pad to 33 bytes total (1 byte opcode + 32 bytes subscription ID)
-    while instruction_data.len() < 33 {
+    // Pad to 41 bytes total (8 byte discriminator + 1 byte opcode + 32 bytes subscription ID)
+    while instruction_data.len() < 41 {
         instruction_data.push(0);
     }
 
+    // Optional 16-byte trace tag after the fixed header
+    if let Some(trace_id) = trace_id {
+        instruction_data.extend_from_slice(&crate::utils::trace_tag(trace_id));
+    }
+
     ic_cdk::println!("📝 Transaction details:");
     ic_cdk::println!("  Contract: {}", contract_address);
     ic_cdk::println!("  Subscriber: {}", subscriber_address);
     ic_cdk::println!("  Merchant: {}", merchant_address);
-    ic_cdk::println!("  Opcode: {} ({})", opcode, if opcode == 0 { "Payment" } else { "Notification" });
+    let opcode_name = match opcode {
+        0 => "Payment",
+        crate::types::HEARTBEAT_OPCODE => "Heartbeat",
+        _ => "Notification",
+    };
+    ic_cdk::println!("  Opcode: {} ({})", opcode, opcode_name);
     ic_cdk::println!("  From wallet: {}", main_wallet);
     ic_cdk::println!("  Instruction data: {} bytes", instruction_data.len());
 
@@ -144,13 +157,15 @@ pub async fn send_solana_opcode(
 }
 
 // Build and send a Solana transaction using HTTP outcalls
+// Uses the canister's durable nonce instead of a recent blockhash (avoids IC consensus issues)
 async fn build_and_send_transaction(
     rpc_endpoint: &str,
     program_id: &str,
     accounts: &[&str],
     instruction_data: &[u8],
 ) -> Result<String, String> {
-    use crate::state::get_cached_blockhash;
+    use crate::nonce_manager::{NonceConfig, NonceManager};
+    use crate::transaction_wire::{compile_message, decode_base58_32, WireAccountMeta, WireInstruction};
 
     ic_cdk::println!("🔨 Building Solana transaction...");
     ic_cdk::println!("  RPC: {}", rpc_endpoint);
@@ -158,17 +173,34 @@ async fn build_and_send_transaction(
     ic_cdk::println!("  Accounts: {}", accounts.len());
     ic_cdk::println!("  Data: {} bytes", instruction_data.len());
 
-    // Step 1: Use cached blockhash (avoids IC consensus issues)
-    let blockhash = get_cached_blockhash()
-        .ok_or("No cached blockhash available. Blockhash cache needs refresh.")?;
-    ic_cdk::println!("✅ Using cached blockhash: {}", blockhash);
-
-    // Step 2: Build transaction message
-    let transaction_message = build_transaction_message(
-        program_id,
-        accounts,
-        instruction_data,
-        &blockhash,
+    // Step 1: Fetch the durable nonce that stands in for the recent blockhash
+    let nonce_config = NonceConfig::from_main_wallet()?;
+    let nonce = nonce_config.get_current_nonce().await?.to_bytes();
+    ic_cdk::println!("✅ Using durable nonce from {}", nonce_config.nonce_account);
+
+    // Step 2: Build transaction message (advance nonce first, then compute budget, then our instruction)
+    // The first account is the payer/signer; the program itself is passed read-only
+    let payer = decode_base58_32(accounts.first().ok_or("Transaction needs a payer account")?)?;
+    let main_instruction = WireInstruction {
+        program_id: decode_base58_32(program_id)?,
+        accounts: accounts.iter().enumerate()
+            .map(|(i, account)| Ok(WireAccountMeta {
+                pubkey: decode_base58_32(account)?,
+                is_signer: i == 0,
+                is_writable: *account != program_id,
+            }))
+            .collect::<Result<Vec<_>, String>>()?,
+        data: instruction_data.to_vec(),
+    };
+    let advance_nonce = NonceManager::advance_nonce_ix(&nonce_config.nonce_account, payer.to_vec())?;
+    let (compute_unit_limit, compute_unit_price) = crate::state::get_compute_budget();
+    let [set_compute_unit_limit, set_compute_unit_price] =
+        crate::solana_rpc::compute_budget_instructions(compute_unit_limit, compute_unit_price)
+            .map(crate::nonce_manager::to_wire_instruction);
+    let transaction_message = compile_message(
+        payer,
+        &[advance_nonce, set_compute_unit_limit, set_compute_unit_price, main_instruction],
+        nonce,
     )?;
     ic_cdk::println!("✅ Built transaction message");
 
@@ -177,7 +209,7 @@ async fn build_and_send_transaction(
     ic_cdk::println!("✅ Signed transaction with tECDSA");
 
     // Step 4: Send transaction to Solana RPC
-    let tx_signature = send_transaction_to_rpc(rpc_endpoint, &signed_transaction).await?;
+    let tx_signature = submit_transaction(rpc_endpoint, &signed_transaction).await?;
     ic_cdk::println!("✅ Transaction sent | signature: {}", tx_signature);
 
     Ok(tx_signature)
@@ -263,64 +295,8 @@ pub async fn refresh_blockhash_cache() -> Result<(), String> {
     Ok(())
 }
 
-/// Build a Solana transaction message (serialized for signing)
-fn build_transaction_message(
-    program_id: &str,
-    accounts: &[&str],
-    instruction_data: &[u8],
-    blockhash: &str,
-) -> Result<Vec<u8>, String> {
-    // Simplified transaction message building
-    // In production, you'd use a proper Solana transaction library
-
-    let mut message = Vec::new();
-
-    // Add header (num required signatures, num readonly signed, num readonly unsigned)
-    message.push(1); // 1 signer (ICP canister wallet)
-    message.push(0); // 0 readonly signed
-    message.push(accounts.len() as u8 - 1); // Others are readonly unsigned
-
-    // Add account keys (compact array encoding)
-    message.push(accounts.len() as u8);
-    for account in accounts {
-        // Decode base58 address to 32 bytes
-        let decoded = bs58::decode(account)
-            .into_vec()
-            .map_err(|e| format!("Invalid account address {}: {}", account, e))?;
-        if decoded.len() != 32 {
-            return Err(format!("Account {} is not 32 bytes", account));
-        }
-        message.extend_from_slice(&decoded);
-    }
-
-    // Add recent blockhash
-    let blockhash_bytes = bs58::decode(blockhash)
-        .into_vec()
-        .map_err(|e| format!("Invalid blockhash: {}", e))?;
-    message.extend_from_slice(&blockhash_bytes);
-
-    // Add instructions (compact array with 1 instruction)
-    message.push(1); // Number of instructions
-
-    // Program ID index
-    let program_idx = accounts.iter().position(|&a| a == program_id)
-        .ok_or("Program ID not in accounts")? as u8;
-    message.push(program_idx);
-
-    // Accounts indices for this instruction
-    let account_indices: Vec<u8> = (0..accounts.len() as u8).collect();
-    message.push(account_indices.len() as u8);
-    message.extend_from_slice(&account_indices);
-
-    // Instruction data
-    message.push(instruction_data.len() as u8);
-    message.extend_from_slice(instruction_data);
-
-    Ok(message)
-}
-
 /// Sign transaction message using IC Schnorr Ed25519
-async fn sign_transaction_with_ecdsa(message: &[u8]) -> Result<Vec<u8>, String> {
+pub(crate) async fn sign_transaction_with_ecdsa(message: &[u8]) -> Result<Vec<u8>, String> {
     let (_, key_name, _) = get_network_config();
     let canister_id = ic_cdk::api::id();
 
@@ -365,18 +341,9 @@ async fn sign_transaction_with_ecdsa(message: &[u8]) -> Result<Vec<u8>, String>
             ic_cdk::println!("✅ Message signed with Schnorr Ed25519 ({} bytes)", response.signature.len());
 
             // Combine signature with message for Solana transaction format
-            let mut signed_tx = Vec::new();
-
-            // Add signature count (compact array)
-            signed_tx.push(1);
-
-            // Add signature (64 bytes for Ed25519)
-            signed_tx.extend_from_slice(&response.signature);
-
-            // Add message
-            signed_tx.extend_from_slice(message);
-
-            Ok(signed_tx)
+            let signature: [u8; 64] = response.signature.as_slice().try_into()
+                .map_err(|_| format!("Invalid signature length: {}", response.signature.len()))?;
+            crate::transaction_wire::serialize_transaction(&[signature], message)
         }
         Err((code, msg)) => {
             Err(format!("Failed to sign transaction: {:?} - {}", code, msg))
@@ -390,6 +357,24 @@ struct SignWithSchnorrResponse {
 }
 
 /// Send signed transaction to Solana RPC
+/// Submit a signed transaction through the configured transport
+pub(crate) async fn submit_transaction(rpc_url: &str, signed_transaction: &[u8]) -> Result<String, String> {
+    if crate::state::use_sol_rpc_canister() {
+        SolRpcClient::new().send_transaction(signed_transaction.to_vec()).await
+    } else {
+        send_transaction_to_rpc(rpc_url, signed_transaction).await
+    }
+}
+
+/// Recent blockhash through the configured transport
+pub(crate) async fn latest_blockhash(rpc_url: &str) -> Result<String, String> {
+    if crate::state::use_sol_rpc_canister() {
+        SolRpcClient::new().get_latest_blockhash().await
+    } else {
+        get_recent_blockhash(rpc_url).await
+    }
+}
+
 async fn send_transaction_to_rpc(rpc_url: &str, signed_transaction: &[u8]) -> Result<String, String> {
     // Encode transaction as base64
     let tx_base64 = general_purpose::STANDARD.encode(signed_transaction);
@@ -432,7 +417,7 @@ async fn send_transaction_to_rpc(rpc_url: &str, signed_transaction: &[u8]) -> Re
 }
 
 /// Make HTTP request to Solana RPC using IC HTTP outcalls
-async fn make_http_request(
+pub(crate) async fn make_http_request(
     url: &str,
     method: &str,
     body: &[u8],
@@ -494,7 +479,7 @@ fn generate_mock_transaction_hash(program_id: &str, data: &[u8]) -> String {
 pub async fn verify_solana_transaction(tx_hash: &str) -> Result<bool, String> {
     ic_cdk::println!("🔍 Verifying Solana transaction: {}", tx_hash);
 
-    let (_network, _key_name, rpc_endpoint) = get_network_config();
+    let rpc_endpoint = get_healthy_rpc().await?;
 
     let request_body = serde_json::json!({
         "jsonrpc": "2.0",
@@ -541,7 +526,11 @@ pub async fn verify_solana_transaction(tx_hash: &str) -> Result<bool, String> {
 pub async fn get_solana_balance(address: &str) -> Result<u64, String> {
     ic_cdk::println!("💰 Getting Solana balance for: {}", address);
 
-    let (_network, _key_name, rpc_endpoint) = get_network_config();
+    if crate::state::use_sol_rpc_canister() {
+        return SolRpcClient::new().get_balance(address).await;
+    }
+
+    let rpc_endpoint = get_healthy_rpc().await?;
 
     let request_body = serde_json::json!({
         "jsonrpc": "2.0",
@@ -573,6 +562,409 @@ pub async fn get_solana_balance(address: &str) -> Result<u64, String> {
     Ok(balance)
 }
 
+/// First configured RPC endpoint that answers getHealth with "ok", cached for 30 seconds
+/// Endpoints are tried in order: the network's default endpoint, then those added by admins
+pub async fn get_healthy_rpc() -> Result<String, String> {
+    let now = time();
+    if let Some(endpoint) = crate::state::get_cached_healthy_rpc(now) {
+        return Ok(endpoint);
+    }
+
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getHealth"
+    }).to_string();
+
+    for endpoint in crate::state::get_rpc_candidates() {
+        match make_http_request(&endpoint, "POST", request_body.as_bytes()).await {
+            Ok(response) if is_healthy_response(&response.body) => {
+                crate::state::record_healthy_rpc(endpoint.clone(), time());
+                return Ok(endpoint);
+            }
+            Ok(_) => ic_cdk::println!("⚠️ RPC endpoint {} reported unhealthy", endpoint),
+            Err(e) => ic_cdk::println!("⚠️ RPC endpoint {} unreachable: {}", endpoint, e),
+        }
+    }
+
+    Err("No healthy RPC".to_string())
+}
+
+pub fn is_healthy_response(body: &[u8]) -> bool {
+    serde_json::from_slice::<serde_json::Value>(body)
+        .map(|json| json["result"] == "ok")
+        .unwrap_or(false)
+}
+
+/// Raw account data through the configured transport (None if the account doesn't exist)
+pub async fn get_account_data(address: &str) -> Result<Option<Vec<u8>>, String> {
+    if crate::state::use_sol_rpc_canister() {
+        SolRpcClient::new().get_account_data(address).await
+    } else {
+        get_account_data_via_https(address).await
+    }
+}
+
+pub(crate) async fn get_account_data_via_https(address: &str) -> Result<Option<Vec<u8>>, String> {
+    let rpc_endpoint = get_healthy_rpc().await?;
+
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getAccountInfo",
+        "params": [
+            address,
+            {
+                "encoding": "base64",
+                "commitment": "finalized"
+            }
+        ]
+    }).to_string();
+
+    let response = make_http_request(&rpc_endpoint, "POST", request_body.as_bytes()).await?;
+
+    let json: serde_json::Value = serde_json::from_slice(&response.body)
+        .map_err(|e| format!("Failed to parse account info response: {}", e))?;
+    if let Some(error) = json.get("error") {
+        return Err(format!("Solana RPC error: {}", error));
+    }
+
+    match json["result"]["value"]["data"][0].as_str() {
+        Some(data_base64) => general_purpose::STANDARD
+            .decode(data_base64)
+            .map(Some)
+            .map_err(|e| format!("Invalid account data for {}: {}", address, e)),
+        None => Ok(None),
+    }
+}
+
+/// Successful transaction signatures touching `address`, newest first, stopping at `until` (exclusive)
+pub(crate) async fn get_signatures_for_address(address: &str, until: Option<&str>, limit: u32) -> Result<Vec<String>, String> {
+    let rpc_endpoint = get_healthy_rpc().await?;
+
+    let mut config = serde_json::json!({ "limit": limit, "commitment": "finalized" });
+    if let Some(until) = until {
+        config["until"] = serde_json::Value::from(until);
+    }
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getSignaturesForAddress",
+        "params": [address, config]
+    }).to_string();
+
+    let response = make_http_request(&rpc_endpoint, "POST", request_body.as_bytes()).await?;
+    let json: serde_json::Value = serde_json::from_slice(&response.body)
+        .map_err(|e| format!("Failed to parse signatures response: {}", e))?;
+    if let Some(error) = json.get("error") {
+        return Err(format!("Solana RPC error: {}", error));
+    }
+
+    Ok(json["result"]
+        .as_array()
+        .ok_or("Missing result in signatures response")?
+        .iter()
+        .filter(|entry| entry["err"].is_null())
+        .filter_map(|entry| entry["signature"].as_str().map(str::to_string))
+        .collect())
+}
+
+/// meta.logMessages of a confirmed transaction
+pub(crate) async fn get_transaction_logs(signature: &str) -> Result<Vec<String>, String> {
+    let rpc_endpoint = get_healthy_rpc().await?;
+
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getTransaction",
+        "params": [signature, { "encoding": "json", "commitment": "finalized", "maxSupportedTransactionVersion": 0 }]
+    }).to_string();
+
+    let response = make_http_request(&rpc_endpoint, "POST", request_body.as_bytes()).await?;
+    let json: serde_json::Value = serde_json::from_slice(&response.body)
+        .map_err(|e| format!("Failed to parse transaction {}: {}", signature, e))?;
+    if let Some(error) = json.get("error") {
+        return Err(format!("Solana RPC error: {}", error));
+    }
+    // A failed transaction still logs the events emitted before it reverted
+    let tx_error = &json["result"]["meta"]["err"];
+    if !tx_error.is_null() {
+        return Err(format!("Transaction {} failed: {}", signature, tx_error));
+    }
+
+    Ok(json["result"]["meta"]["logMessages"]
+        .as_array()
+        .map(|logs| logs.iter().filter_map(|line| line.as_str().map(str::to_string)).collect())
+        .unwrap_or_default())
+}
+
+/// Check the subscriber's token account holds at least `required` micro-units before triggering a payment
+/// so an underfunded subscription fails here instead of in a fee-paying Solana transaction.
+/// If the balance can't be fetched the check is skipped and the Solana program stays the source of truth.
+pub async fn check_subscriber_usdc_balance(subscriber_token_account: &str, required: u64) -> Result<(), String> {
+    if crate::state::use_sol_rpc_canister() {
+        return match SolRpcClient::new().get_token_account_balance(subscriber_token_account).await {
+            Ok((balance, _decimals)) => ensure_sufficient_balance(balance, required),
+            Err(e) => {
+                ic_cdk::println!("⚠️ Skipping balance check for {}: {}", subscriber_token_account, e);
+                Ok(())
+            }
+        };
+    }
+
+    let rpc_endpoint = match get_healthy_rpc().await {
+        Ok(rpc_endpoint) => rpc_endpoint,
+        Err(e) => {
+            ic_cdk::println!("⚠️ Skipping balance check for {}: {}", subscriber_token_account, e);
+            return Ok(());
+        }
+    };
+
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getTokenAccountBalance",
+        "params": [
+            subscriber_token_account,
+            {
+                "commitment": "confirmed"
+            }
+        ]
+    }).to_string();
+
+    let response = match make_http_request(&rpc_endpoint, "POST", request_body.as_bytes()).await {
+        Ok(response) => response,
+        Err(e) => {
+            ic_cdk::println!("⚠️ Skipping balance check for {}: {}", subscriber_token_account, e);
+            return Ok(());
+        }
+    };
+
+    match parse_token_account_balance(&response.body) {
+        Ok(balance) => ensure_sufficient_balance(balance, required),
+        Err(e) => {
+            ic_cdk::println!("⚠️ Skipping balance check for {}: {}", subscriber_token_account, e);
+            Ok(())
+        }
+    }
+}
+
+/// Check `account_address` (a token account for `token_mint`) holds at least `required` raw units
+/// Err means the balance couldn't be fetched, not that it's too low
+pub async fn check_token_account_balance(account_address: &str, token_mint: &str, required: u64) -> Result<bool, String> {
+    let balance = get_token_account_balance(account_address).await?;
+
+    ic_cdk::println!("🪙 Token account {} ({}) holds {} | required {}",
+                      account_address, token_mint, balance, required);
+    Ok(balance >= required)
+}
+
+/// Raw balance of a token account via getTokenAccountBalance; a missing account reads as zero
+pub async fn get_token_account_balance(account_address: &str) -> Result<u64, String> {
+    let rpc_endpoint = get_healthy_rpc().await?;
+
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getTokenAccountBalance",
+        "params": [
+            account_address,
+            {
+                "commitment": "confirmed"
+            }
+        ]
+    }).to_string();
+
+    let response = make_http_request(&rpc_endpoint, "POST", request_body.as_bytes()).await?;
+    parse_token_account_balance(&response.body)
+}
+
+/// The parts of a Jupiter V6 quote the swap simulation uses
+#[derive(Clone, Debug, PartialEq)]
+pub struct JupiterQuote {
+    pub out_amount: u64,
+    pub min_out_amount: u64, // otherAmountThreshold: output after the requested slippage
+    pub price_impact_bps: u16,
+}
+
+/// Ask the Jupiter V6 quote API for an ExactIn swap of `amount` raw units
+pub async fn get_jupiter_quote(input_mint: &str, output_mint: &str, amount: u64, slippage_bps: u16) -> Result<JupiterQuote, String> {
+    let url = format!(
+        "{}?inputMint={}&outputMint={}&amount={}&slippageBps={}&swapMode=ExactIn",
+        JUPITER_QUOTE_API_URL, input_mint, output_mint, amount, slippage_bps
+    );
+    let response = make_http_request(&url, "GET", &[]).await
+        .map_err(|e| format!("Jupiter quote failed: {}", e))?;
+    parse_jupiter_quote(&response.body)
+}
+
+/// Parse a Jupiter V6 /quote response; amounts are decimal strings, priceImpactPct a fraction ("0.01" = 1%)
+pub fn parse_jupiter_quote(body: &[u8]) -> Result<JupiterQuote, String> {
+    let json: serde_json::Value = serde_json::from_slice(body)
+        .map_err(|e| format!("Failed to parse Jupiter quote: {}", e))?;
+    if let Some(error) = json["error"].as_str() {
+        return Err(format!("Jupiter quote error: {}", error));
+    }
+
+    let amount = |field: &str| -> Result<u64, String> {
+        json[field]
+            .as_str()
+            .ok_or(format!("Missing {} in Jupiter quote", field))?
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid {} in Jupiter quote: {}", field, e))
+    };
+    let price_impact = json["priceImpactPct"]
+        .as_str()
+        .ok_or("Missing priceImpactPct in Jupiter quote")?
+        .parse::<f64>()
+        .map_err(|e| format!("Invalid priceImpactPct in Jupiter quote: {}", e))?;
+
+    Ok(JupiterQuote {
+        out_amount: amount("outAmount")?,
+        min_out_amount: amount("otherAmountThreshold")?,
+        price_impact_bps: (price_impact.abs() * 10_000.0).round().min(u16::MAX as f64) as u16,
+    })
+}
+
+/// Parse a getTokenAccountBalance response into raw token units.
+/// An RPC error (e.g. the token account doesn't exist) counts as a zero balance.
+pub fn parse_token_account_balance(body: &[u8]) -> Result<u64, String> {
+    let json: serde_json::Value = serde_json::from_slice(body)
+        .map_err(|e| format!("Failed to parse token balance response: {}", e))?;
+
+    if !json["error"].is_null() {
+        return Ok(0);
+    }
+
+    json["result"]["value"]["amount"]
+        .as_str()
+        .ok_or("Missing token amount in response")?
+        .parse::<u64>()
+        .map_err(|e| format!("Invalid token amount in response: {}", e))
+}
+
+pub fn ensure_sufficient_balance(balance: u64, required: u64) -> Result<(), String> {
+    if balance < required {
+        return Err(format!("Insufficient subscriber balance: {} available, {} required", balance, required));
+    }
+    Ok(())
+}
+
+/// Poll getSignatureStatuses every 2 seconds until `sig` reaches `commitment`
+/// Returns Ok(false) if the timeout (capped at 30 seconds) expires first and
+/// Err if the transaction failed on-chain
+pub async fn confirm_transaction(sig: &str, timeout_nanos: u64, commitment: &str) -> Result<bool, String> {
+    let rpc_endpoint = match get_healthy_rpc().await {
+        Ok(rpc_endpoint) => rpc_endpoint,
+        Err(e) => {
+            ic_cdk::println!("⚠️ Can't confirm {}: {}", sig, e);
+            return Ok(false);
+        }
+    };
+    let deadline = time().saturating_add(timeout_nanos.min(MAX_CONFIRMATION_TIMEOUT_NANOS));
+
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getSignatureStatuses",
+        "params": [[sig], { "searchTransactionHistory": false }]
+    }).to_string();
+
+    loop {
+        match make_http_request(&rpc_endpoint, "POST", request_body.as_bytes()).await {
+            Ok(response) => {
+                if let Some(status) = parse_signature_status(&response.body)? {
+                    if commitment_reached(&status, commitment) {
+                        ic_cdk::println!("✅ Transaction {} reached {}", sig, status);
+                        return Ok(true);
+                    }
+                }
+            }
+            Err(e) => ic_cdk::println!("⚠️ Signature status check failed for {}: {}", sig, e),
+        }
+
+        if time().saturating_add(CONFIRMATION_POLL_INTERVAL_NANOS) > deadline {
+            ic_cdk::println!("⏳ Transaction {} not {} before timeout", sig, commitment);
+            return Ok(false);
+        }
+        sleep(CONFIRMATION_POLL_INTERVAL_NANOS).await;
+    }
+}
+
+/// confirmationStatus of the first entry in a getSignatureStatuses response
+/// (None while the transaction is unknown to the cluster, Err if it failed)
+pub fn parse_signature_status(body: &[u8]) -> Result<Option<String>, String> {
+    let json: serde_json::Value = serde_json::from_slice(body)
+        .map_err(|e| format!("Failed to parse signature status response: {}", e))?;
+
+    if let Some(error) = json.get("error") {
+        return Err(format!("Solana RPC error: {}", error));
+    }
+
+    let status = &json["result"]["value"][0];
+    if status.is_null() {
+        return Ok(None);
+    }
+    if !status["err"].is_null() {
+        return Err(format!("Transaction failed: {}", status["err"]));
+    }
+
+    Ok(status["confirmationStatus"].as_str().map(|s| s.to_string()))
+}
+
+/// processed < confirmed < finalized
+pub fn commitment_reached(status: &str, commitment: &str) -> bool {
+    let rank = |level: &str| match level {
+        "processed" => 0,
+        "confirmed" => 1,
+        "finalized" => 2,
+        _ => 3,
+    };
+    rank(status) <= 2 && rank(status) >= rank(commitment)
+}
+
+/// Resolve after `delay_nanos` using a one-shot timer (the IC has no blocking sleep)
+pub(crate) async fn sleep(delay_nanos: u64) {
+    use std::cell::RefCell;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll, Waker};
+
+    struct Sleep {
+        state: Rc<RefCell<(bool, Option<Waker>)>>,
+    }
+
+    impl Future for Sleep {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let mut state = self.state.borrow_mut();
+            if state.0 {
+                Poll::Ready(())
+            } else {
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    let state = Rc::new(RefCell::new((false, None::<Waker>)));
+    let timer_state = state.clone();
+    ic_cdk_timers::set_timer(std::time::Duration::from_nanos(delay_nanos), move || {
+        let waker = {
+            let mut state = timer_state.borrow_mut();
+            state.0 = true;
+            state.1.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+
+    Sleep { state }.await
+}
+
 pub async fn send_solana_transaction(
     from_address: &str,
     to_address: &str,
@@ -582,7 +974,7 @@ pub async fn send_solana_transaction(
     ic_cdk::println!("💸 Sending Solana transaction: {} -> {} ({} lamports)",
                       from_address, to_address, amount_lamports);
 
-    let (_network, _key_name, rpc_endpoint) = get_network_config();
+    let rpc_endpoint = get_healthy_rpc().await?;
 
     // Build instruction data for SOL transfer
     let data = instruction_data.unwrap_or_else(|| {
@@ -698,4 +1090,67 @@ pub enum TransactionStatus {
     Confirmed,
     Finalized,
     Failed,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_underfunded_subscriber_is_rejected_before_broadcast() {
+        let body = br#"{"jsonrpc":"2.0","result":{"context":{"slot":1},"value":{"amount":"9999999","decimals":6,"uiAmount":9.999999,"uiAmountString":"9.999999"}},"id":1}"#;
+        let balance = parse_token_account_balance(body).unwrap();
+
+        assert_eq!(balance, 9_999_999);
+        assert!(ensure_sufficient_balance(balance, 10_000_000).is_err());
+        assert!(ensure_sufficient_balance(balance, 9_999_999).is_ok());
+    }
+
+    #[test]
+    fn test_parse_signature_status() {
+        let confirmed = br#"{"jsonrpc":"2.0","result":{"context":{"slot":82},"value":[{"slot":72,"confirmations":10,"err":null,"status":{"Ok":null},"confirmationStatus":"confirmed"}]},"id":1}"#;
+        assert_eq!(parse_signature_status(confirmed).unwrap(), Some("confirmed".to_string()));
+
+        let unknown = br#"{"jsonrpc":"2.0","result":{"context":{"slot":82},"value":[null]},"id":1}"#;
+        assert_eq!(parse_signature_status(unknown).unwrap(), None);
+
+        let failed = br#"{"jsonrpc":"2.0","result":{"context":{"slot":82},"value":[{"slot":72,"confirmations":null,"err":{"InstructionError":[1,{"Custom":6020}]},"confirmationStatus":"finalized"}]},"id":1}"#;
+        assert!(parse_signature_status(failed).is_err());
+    }
+
+    #[test]
+    fn test_is_healthy_response() {
+        assert!(is_healthy_response(br#"{"jsonrpc":"2.0","result":"ok","id":1}"#));
+        assert!(!is_healthy_response(br#"{"jsonrpc":"2.0","error":{"code":-32005,"message":"Node is behind by 42 slots","data":{"numSlotsBehind":42}},"id":1}"#));
+        assert!(!is_healthy_response(b"<html>502 Bad Gateway</html>"));
+    }
+
+    #[test]
+    fn test_commitment_reached() {
+        assert!(commitment_reached("confirmed", "confirmed"));
+        assert!(commitment_reached("finalized", "confirmed"));
+        assert!(!commitment_reached("processed", "confirmed"));
+        assert!(!commitment_reached("confirmed", "finalized"));
+        assert!(!commitment_reached("unknown", "processed"));
+    }
+
+    #[test]
+    fn test_parse_jupiter_quote() {
+        let body = br#"{"inputMint":"Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB","inAmount":"10000000","outputMint":"EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v","outAmount":"9994000","otherAmountThreshold":"9894060","swapMode":"ExactIn","slippageBps":100,"priceImpactPct":"0.0006","routePlan":[]}"#;
+        assert_eq!(parse_jupiter_quote(body).unwrap(), JupiterQuote {
+            out_amount: 9_994_000,
+            min_out_amount: 9_894_060,
+            price_impact_bps: 6,
+        });
+
+        assert!(parse_jupiter_quote(br#"{"error":"Could not find any route","errorCode":"COULD_NOT_FIND_ANY_ROUTE"}"#).is_err());
+        assert!(parse_jupiter_quote(br#"{"outAmount":9994000}"#).is_err());
+    }
+
+    #[test]
+    fn test_missing_token_account_counts_as_empty() {
+        let body = br#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid param: could not find account"},"id":1}"#;
+        assert_eq!(parse_token_account_balance(body).unwrap(), 0);
+        assert!(parse_token_account_balance(b"not json").is_err());
+    }
+}
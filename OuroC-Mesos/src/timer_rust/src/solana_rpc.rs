@@ -2,17 +2,15 @@
 // This replaces the old HTTP outcall approach with proper consensus handling
 // Uses durable nonces to eliminate blockhash timing issues
 
-use crate::sol_rpc::create_sol_rpc_client;
 use crate::state::get_main_wallet_address;
-use crate::nonce_manager::NonceConfig;
+use crate::types::IndexResult;
+use crate::nonce_manager::{NonceConfig, NonceManager};
 use solana_instruction::{AccountMeta, Instruction};
 use solana_message::Message;
 use solana_pubkey::Pubkey;
 use solana_signature::Signature;
 use solana_transaction::Transaction;
-use sol_rpc_types::{SendTransactionParams, SendTransactionEncoding};
 use std::str::FromStr;
-use base64::Engine;
 
 // Get program addresses when needed to avoid const issues
 fn get_system_program_id() -> Pubkey {
@@ -27,6 +25,97 @@ fn get_instructions_sysvar_id() -> Pubkey {
     Pubkey::from_str("Sysvar1nstructions1111111111111111111111111").unwrap()
 }
 
+fn get_ed25519_program_id() -> Pubkey {
+    Pubkey::from_str("Ed25519SigVerify111111111111111111111111111").unwrap()
+}
+
+fn get_compute_budget_program_id() -> Pubkey {
+    Pubkey::from_str("ComputeBudget111111111111111111111111111111").unwrap()
+}
+
+/// SetComputeUnitLimit and SetComputeUnitPrice, placed ahead of the transaction's own
+/// instructions (after AdvanceNonceAccount, which must stay first)
+pub fn compute_budget_instructions(compute_unit_limit: u32, compute_unit_price_microlamports: u64) -> [Instruction; 2] {
+    let mut limit_data = vec![2]; // SetComputeUnitLimit
+    limit_data.extend_from_slice(&compute_unit_limit.to_le_bytes());
+    let mut price_data = vec![3]; // SetComputeUnitPrice
+    price_data.extend_from_slice(&compute_unit_price_microlamports.to_le_bytes());
+
+    [
+        Instruction { program_id: get_compute_budget_program_id(), accounts: vec![], data: limit_data },
+        Instruction { program_id: get_compute_budget_program_id(), accounts: vec![], data: price_data },
+    ]
+}
+
+fn get_associated_token_program_id() -> Pubkey {
+    Pubkey::from_str("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL").unwrap()
+}
+
+/// Derive the associated token account holding `mint` for `wallet_address`
+pub fn derive_associated_token_account(wallet_address: &str, mint: &str) -> Result<String, String> {
+    let wallet = Pubkey::from_str(wallet_address)
+        .map_err(|e| format!("Invalid wallet address: {}", e))?;
+    let mint = Pubkey::from_str(mint)
+        .map_err(|e| format!("Invalid mint address: {}", e))?;
+
+    let (token_account, _bump) = Pubkey::find_program_address(
+        &[wallet.as_ref(), get_token_program_id().as_ref(), mint.as_ref()],
+        &get_associated_token_program_id(),
+    );
+    Ok(token_account.to_string())
+}
+
+/// Build an Ed25519Program precompile instruction for a single signature
+/// The contract checks this instruction (placed right before its own) instead of verifying in software
+/// Layout: [num_signatures, padding] + 7 u16 offsets + pubkey (16) + signature (48) + message (112)
+pub fn create_ed25519_verify_instruction(
+    public_key: &[u8; 32],
+    signature: &[u8],
+    message: &[u8],
+) -> Result<Instruction, String> {
+    const PUBKEY_OFFSET: u16 = 16;
+    const SIGNATURE_OFFSET: u16 = PUBKEY_OFFSET + 32;
+    const MESSAGE_OFFSET: u16 = SIGNATURE_OFFSET + 64;
+    const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+    if signature.len() != 64 {
+        return Err(format!("Invalid signature length: expected 64 bytes, got {}", signature.len()));
+    }
+    let message_size = u16::try_from(message.len())
+        .map_err(|_| format!("Message too long for Ed25519 instruction: {} bytes", message.len()))?;
+
+    let mut data = Vec::with_capacity(MESSAGE_OFFSET as usize + message.len());
+    data.push(1); // num_signatures
+    data.push(0); // padding
+    for offset in [
+        SIGNATURE_OFFSET,
+        CURRENT_INSTRUCTION,
+        PUBKEY_OFFSET,
+        CURRENT_INSTRUCTION,
+        MESSAGE_OFFSET,
+        message_size,
+        CURRENT_INSTRUCTION,
+    ] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(public_key);
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+
+    Ok(Instruction {
+        program_id: get_ed25519_program_id(),
+        accounts: vec![],
+        data,
+    })
+}
+
+/// Optional extras for an opcode transaction
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpcodeOptions<'a> {
+    pub memo: Option<&'a str>, // Extra memo instruction shown in the subscriber's wallet history
+    pub trace_id: Option<&'a str>,
+}
+
 /// Send a Solana opcode using the SOL RPC canister with durable nonces
 /// This eliminates blockhash timing issues and provides reliable transaction sending
 /// Updated to match contract's process_trigger function signature
@@ -37,7 +126,9 @@ pub async fn send_solana_opcode_via_rpc(
     merchant_address: &str,
     amount: u64, // USDC amount in micro-units (6 decimals)
     opcode: u8, // 0 = Payment, 1 = Notification
+    options: OpcodeOptions<'_>,
 ) -> Result<String, String> {
+    let OpcodeOptions { memo, trace_id } = options;
     ic_cdk::println!("🔗 Sending Solana opcode {} via SOL RPC canister (using durable nonces)", opcode);
     ic_cdk::println!("  Contract: {}", contract_address);
     ic_cdk::println!("  Subscription: {}", subscription_id);
@@ -53,9 +144,6 @@ pub async fn send_solana_opcode_via_rpc(
         ic_cdk::println!("   Actual:   {}", contract_address);
     }
 
-    // Create SOL RPC client
-    let client = create_sol_rpc_client();
-
     // Initialize nonce configuration
     let nonce_config = NonceConfig::from_main_wallet()
         .map_err(|e| format!("Failed to initialize nonce config: {}", e))?;
@@ -82,25 +170,27 @@ pub async fn send_solana_opcode_via_rpc(
     ic_cdk::println!("⏰ Current timestamp: {}", timestamp);
     ic_cdk::println!("💰 Payment amount: {} USDC", amount as f64 / 1_000_000.0);
 
-    // Create the actual message that the contract will verify
     // The contract verifies: subscription_id + timestamp + amount
-    let mut message_to_sign = Vec::new();
-    message_to_sign.extend_from_slice(subscription_id.as_bytes());
-    message_to_sign.extend_from_slice(&timestamp.to_le_bytes());
-    message_to_sign.extend_from_slice(&amount.to_le_bytes());
-
-    ic_cdk::println!("📝 Message to sign: {} bytes", message_to_sign.len());
+    ic_cdk::println!("📝 Message to sign:");
     ic_cdk::println!("   Subscription ID: {}", subscription_id);
     ic_cdk::println!("   Timestamp: {}", timestamp);
     ic_cdk::println!("   Amount: {} USDC", amount as f64 / 1_000_000.0);
 
+    // Re-read the subscription for the version the contract expects. Another instance that read
+    // the same state sends the same version, and only the first of those transactions lands.
+    // Read-only: syncing here would re-arm the timers of the trigger that is running.
+    let on_chain = fetch_onchain_state(subscription_id).await?;
+    if on_chain.status != crate::types::SubscriptionStatus::Active {
+        return Err(format!("Subscription {} is {:?} on Solana, not triggering", subscription_id, on_chain.status));
+    }
+
     // Sign the payment message using IC's threshold Ed25519
     ic_cdk::println!("🔏 Signing payment message with IC threshold Ed25519...");
 
     // Use the proper threshold Ed25519 signing from threshold_ed25519 module
     // This creates the message format: subscription_id + timestamp + amount
     // and signs it directly using IC's management canister
-    let (payment_signature_vec, _) = crate::threshold_ed25519::create_payment_authorization(
+    let (payment_signature_vec, signed_timestamp) = crate::threshold_ed25519::create_payment_authorization(
         "test_key_1", // Use test key for devnet
         subscription_id,
         amount,
@@ -108,28 +198,47 @@ pub async fn send_solana_opcode_via_rpc(
     .map_err(|e| format!("Failed to sign payment message: {}", e))?;
 
     ic_cdk::println!("✅ Payment message signed successfully");
+
+    // The signature covers the timestamp taken at signing time; use it everywhere so the
+    // contract's expected message matches the Ed25519 instruction
+    let timestamp = signed_timestamp;
+    let mut signed_message = Vec::new();
+    signed_message.extend_from_slice(subscription_id.as_bytes());
+    signed_message.extend_from_slice(&timestamp.to_le_bytes());
+    signed_message.extend_from_slice(&amount.to_le_bytes());
     ic_cdk::println!("🔑 Generated {} byte signature for payment verification", payment_signature_vec.len());
 
     // Build instruction data matching contract's process_trigger signature:
     // opcode: u8, icp_signature: Option<[u8; 64]>, timestamp: i64
-    let mut instruction_data = Vec::new();
+    // Anchor routes on the 8-byte instruction discriminator in front of the arguments
+    let mut instruction_data = crate::anchor_utils::instruction_discriminator(crate::anchor_utils::PROCESS_TRIGGER).to_vec();
 
     // 1. Opcode (1 byte)
     instruction_data.push(opcode);
 
-    // 2. ICP Signature (64 bytes) - use the payment signature
+    // 2. ICP Signature (1-byte Some tag + 64 bytes) - use the payment signature
     if payment_signature_vec.len() != 64 {
         return Err(format!("Invalid signature length: expected 64 bytes, got {}", payment_signature_vec.len()));
     }
+    instruction_data.push(1);
     instruction_data.extend_from_slice(&payment_signature_vec);
 
     // 3. Timestamp (8 bytes, little-endian)
     instruction_data.extend_from_slice(&timestamp.to_le_bytes());
 
-    ic_cdk::println!("📝 Instruction data: {} bytes (opcode + signature + timestamp)", instruction_data.len());
+    // 4. Expected subscription version (8 bytes, little-endian)
+    instruction_data.extend_from_slice(&on_chain.version.to_le_bytes());
+
+    // 5. Optional trace tag (16 bytes) - trailing, so the contract's argument decoding ignores it
+    if let Some(trace_id) = trace_id {
+        instruction_data.extend_from_slice(&crate::utils::trace_tag(trace_id));
+    }
+
+    ic_cdk::println!("📝 Instruction data: {} bytes (discriminator + opcode + signature + timestamp + version)", instruction_data.len());
     ic_cdk::println!("   Opcode: {}", opcode);
     ic_cdk::println!("   Signature: 64 bytes (payment message signature)");
     ic_cdk::println!("   Timestamp: {}", timestamp);
+    ic_cdk::println!("   Expected version: {}", on_chain.version);
 
     // Get current durable nonce (this is fast and reliable)
     ic_cdk::println!("🔄 Fetching current durable nonce...");
@@ -150,7 +259,7 @@ pub async fn send_solana_opcode_via_rpc(
     ic_cdk::println!("🔍 Derived config PDA: {}", config_pda);
 
     // USDC Mint on Solana Devnet
-    let usdc_mint_pubkey = Pubkey::from_str("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+    let usdc_mint_pubkey = Pubkey::from_str(crate::types::USDC_MINT_ADDRESS)
         .map_err(|e| format!("Invalid USDC mint address: {}", e))?;
 
     // Memo program address
@@ -180,13 +289,33 @@ pub async fn send_solana_opcode_via_rpc(
 
     // Create advance nonce instruction (required for nonce transactions)
     let advance_nonce_instruction = nonce_config.create_advance_nonce_instruction();
+    let (compute_unit_limit, compute_unit_price) = crate::state::get_compute_budget();
+    let [set_compute_unit_limit, set_compute_unit_price] =
+        compute_budget_instructions(compute_unit_limit, compute_unit_price);
+
+    // Ed25519 precompile instruction must come immediately before the contract instruction
+    let ed25519_instruction = create_ed25519_verify_instruction(
+        &payer_pubkey.to_bytes(),
+        &payment_signature_vec,
+        &signed_message,
+    )?;
 
-    ic_cdk::println!("✅ Created Solana instructions: main + nonce advance");
+    ic_cdk::println!("✅ Created Solana instructions: nonce advance + compute budget + Ed25519 verify + main");
 
     // Build transaction message using nonce instead of blockhash
     let nonce_pubkey = Pubkey::from_str(&nonce_config.nonce_account).unwrap();
+    let mut instructions =
+        vec![advance_nonce_instruction, set_compute_unit_limit, set_compute_unit_price, ed25519_instruction, main_instruction];
+    // The contract writes its own notification memo; a caller-supplied one rides alongside it
+    if let Some(memo) = memo {
+        instructions.push(Instruction {
+            program_id: memo_program,
+            accounts: vec![],
+            data: memo.as_bytes().to_vec(),
+        });
+    }
     let message = Message::new_with_blockhash(
-        &[advance_nonce_instruction, main_instruction],
+        &instructions,
         Some(&payer_pubkey),
         &current_nonce,
     );
@@ -227,57 +356,18 @@ pub async fn send_solana_opcode_via_rpc(
     let serialized_transaction = bincode::serialize(&transaction)
         .map_err(|e| format!("Failed to serialize transaction: {}", e))?;
 
-    let encoded_transaction = base64::engine::general_purpose::STANDARD.encode(&serialized_transaction);
-
-    // Send transaction using SOL RPC canister
-    ic_cdk::println!("📤 Sending transaction via SOL RPC canister (nonce-based)...");
-
-    let send_result = client
-        .send_transaction(SendTransactionParams::from_encoded_transaction(
-            encoded_transaction,
-            SendTransactionEncoding::Base64,
-        ))
-        .send()
-        .await;
-
-    let tx_signature = match send_result {
-        sol_rpc_types::MultiRpcResult::Consistent(result) => {
-            match result {
-                Ok(signature) => {
-                    ic_cdk::println!("✅ Transaction sent successfully!");
-                    signature.to_string()
-                }
-                Err(e) => {
-                    let error_msg = format!("Transaction failed: {:?}", e);
-                    ic_cdk::println!("❌ {}", error_msg);
-                    return Err(error_msg);
-                }
-            }
-        }
-        sol_rpc_types::MultiRpcResult::Inconsistent(results) => {
-            // Handle inconsistent results gracefully per IC team recommendation
-            ic_cdk::println!("⚠️  Inconsistent responses from RPC providers, checking for success...");
-
-            // Check if any provider succeeded
-            for (source, result) in &results {
-                if let Ok(signature) = result {
-                    ic_cdk::println!("✅ Transaction succeeded via provider: {:?}", source);
-                    return Ok(signature.to_string());  // At least one succeeded
-                }
-            }
-
-            // If none succeeded, return error
-            let error_msg = format!("All RPC providers failed. Results: {:?}", results);
-            ic_cdk::println!("❌ {}", error_msg);
-            return Err(error_msg);
-        }
-    };
+    // Send transaction (SOL RPC canister, or HTTPS outcall when disabled)
+    ic_cdk::println!("📤 Sending transaction (nonce-based)...");
+    let (_network, _key_name, rpc_endpoint) = crate::state::get_network_config();
+    let tx_signature = crate::solana::submit_transaction(&rpc_endpoint, &serialized_transaction).await
+        .inspect_err(|e| ic_cdk::println!("❌ {}", e))?;
 
     ic_cdk::println!("🎉 Transaction signature: {}", tx_signature);
     Ok(tx_signature)
 }
 
 /// Initialize nonce account (one-time setup function)
+/// Returns the configured nonce account if it's live, otherwise creates one
 #[ic_cdk::update]
 pub async fn initialize_nonce_account() -> Result<String, String> {
     ic_cdk::println!("🔍 Checking nonce account setup...");
@@ -298,15 +388,12 @@ pub async fn initialize_nonce_account() -> Result<String, String> {
         Err(e) => {
             ic_cdk::println!("⚠️  Nonce account check failed: {}", e);
 
-            // Since we encountered consensus failures with programmatic creation,
-            // we'll use the manually created nonce account
-            let manually_created_nonce = "A8CgmkD62QatJCEDh8pcN123SyXbQmjKwfvz3qJYPg2Z";
+            // Creating the account spends the main wallet's lamports
+            crate::authorization::require_admin()?;
 
-            ic_cdk::println!("ℹ️  Using manually created nonce account: {}", manually_created_nonce);
-            ic_cdk::println!("🔗 To verify: solana nonce-account {}", manually_created_nonce);
-
-            // Return the address of the manually created account
-            Ok(manually_created_nonce.to_string())
+            // Create it over HTTPS outcalls (the SOL RPC canister path hit consensus failures)
+            let (_network, _key_name, rpc_endpoint) = crate::state::get_network_config();
+            NonceManager::initialize_nonce_account(&nonce_config.authority, &rpc_endpoint).await
         }
     }
 }
@@ -321,6 +408,226 @@ pub async fn get_current_nonce() -> Result<String, String> {
     Ok(nonce.to_string())
 }
 
+/// Fields of the Solana `Subscription` account the canister mirrors
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnChainSubscriptionState {
+    pub status: crate::types::SubscriptionStatus,
+    pub payments_made: u64,
+    pub version: u64, // Expected version for the next trigger
+    pub next_payment_time: i64, // Unix seconds
+}
+
+/// Anchor account discriminator: first 8 bytes of sha256("account:<Name>")
+fn account_discriminator(name: &str) -> [u8; 8] {
+    crate::anchor_utils::compute_anchor_discriminator("account", name)
+}
+
+/// Decode the Borsh-serialized Solana `Subscription` account
+/// `id` and `merchant_name` are length-prefixed strings, so later fields are read in order
+/// rather than at fixed offsets
+pub fn parse_onchain_subscription(data: &[u8]) -> Result<OnChainSubscriptionState, String> {
+    use crate::types::SubscriptionStatus;
+
+    if data.len() < 8 || data[..8] != account_discriminator("Subscription") {
+        return Err("Account is not an OuroC subscription".to_string());
+    }
+
+    let mut offset = 8;
+    let mut take = |len: usize| -> Result<&[u8], String> {
+        let bytes = data.get(offset..offset + len).ok_or("Subscription account data truncated")?;
+        offset += len;
+        Ok(bytes)
+    };
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap()) as usize;
+
+    let id_len = read_u32(take(4)?);
+    take(id_len)?; // id
+    take(32 + 32)?; // subscriber, merchant
+    let name_len = read_u32(take(4)?);
+    take(name_len)?; // merchant_name
+    take(8 + 8)?; // amount, interval_seconds
+    let next_payment_time = i64::from_le_bytes(take(8)?.try_into().unwrap());
+    let status = match take(1)?[0] {
+        0 => SubscriptionStatus::Active,
+        1 => SubscriptionStatus::Paused,
+        2 => SubscriptionStatus::Cancelled,
+        3 => SubscriptionStatus::Expired,
+        other => return Err(format!("Unknown subscription status {}", other)),
+    };
+    take(8)?; // created_at
+    if take(1)?[0] == 1 {
+        take(8)?; // last_payment_time
+    }
+    let payments_made = u64::from_le_bytes(take(8)?.try_into().unwrap());
+    take(8 + 64 + 4 + 32 + 8 + 8)?; // total_paid .. grace_period_seconds
+    for _ in 0..3 {
+        if take(1)?[0] == 1 {
+            take(8)?; // trial_end_time, max_payments, remaining_payments
+        }
+    }
+    take(1)?; // use_token_2022
+    for _ in 0..3 {
+        if take(1)?[0] == 1 {
+            take(8)?; // last_notification_time, paused_at, expires_at
+        }
+    }
+    take(1)?; // paused_by
+    if take(1)?[0] == 1 {
+        take(1)?; // authorization_mode_override
+    }
+    take(8 + 8 + 8 + 2)?; // spending_cap_per_period .. slippage_bps
+    if take(1)?[0] == 1 {
+        take(8)?; // slippage_updated_at
+    }
+    take(32 + 1 + 8)?; // integrity_hash, disputed, dispute_payment_number
+    let version = u64::from_le_bytes(take(8)?.try_into().unwrap());
+
+    Ok(OnChainSubscriptionState { status, payments_made, version, next_payment_time })
+}
+
+/// Re-read a subscription's Solana account and update the canister's copy where they differ
+/// Returns the fields that changed
+#[ic_cdk::update]
+pub async fn sync_subscription_state(id: crate::types::SubscriptionId) -> Result<Vec<String>, String> {
+    crate::authorization::require_read_access()?;
+    refresh_onchain_state(&id).await.map(|(changed, _)| changed)
+}
+
+/// sync_subscription_state without the access check, also returning what was read
+pub async fn refresh_onchain_state(id: &str) -> Result<(Vec<String>, OnChainSubscriptionState), String> {
+    let id = id.to_string();
+    let on_chain = fetch_onchain_state(&id).await?;
+
+    let changed = crate::subscription_manager::apply_onchain_state(
+        &id,
+        on_chain.status.clone(),
+        on_chain.payments_made,
+        (on_chain.next_payment_time.max(0) as u64).saturating_mul(1_000_000_000),
+    )?;
+
+    if changed.is_empty() {
+        ic_cdk::println!("✅ Subscription {} already matches Solana", id);
+    } else {
+        ic_cdk::println!("🔄 StateSynced {}: {}", id, changed.join(", "));
+    }
+    Ok((changed, on_chain))
+}
+
+/// Read a subscription's Solana account without touching the canister's copy or its timers
+/// Trigger paths call this for the version they must send
+pub async fn fetch_onchain_state(id: &str) -> Result<OnChainSubscriptionState, String> {
+    let subscription = crate::subscription_manager::get_subscription(id.to_string())
+        .ok_or("Subscription not found".to_string())?;
+    let program_id = Pubkey::from_str(&subscription.solana_contract_address)
+        .map_err(|e| format!("Invalid contract address: {}", e))?;
+    let (subscription_pda, _bump) = Pubkey::find_program_address(
+        &[b"subscription".as_slice(), id.as_bytes()],
+        &program_id,
+    );
+
+    let data = crate::solana::get_account_data(&subscription_pda.to_string())
+        .await?
+        .ok_or_else(|| format!("Subscription account {} not found on Solana", subscription_pda))?;
+    parse_onchain_subscription(&data)
+}
+
+/// Fields of the Solana program's PaymentProcessed event that the canister tracks
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaymentProcessedEvent {
+    pub subscription_id: String,
+    pub payment_number: u64,
+    pub amount: u64,
+    pub timestamp: i64, // Unix seconds
+}
+
+/// Anchor event discriminator: first 8 bytes of sha256("event:<Name>")
+fn event_discriminator(name: &str) -> [u8; 8] {
+    crate::anchor_utils::compute_anchor_discriminator("event", name)
+}
+
+/// Decode the PaymentProcessed events Anchor's emit! wrote to "Program data: <base64>" log lines
+pub fn parse_payment_events(logs: &[String]) -> Vec<PaymentProcessedEvent> {
+    use base64::Engine;
+
+    let discriminator = event_discriminator("PaymentProcessed");
+    logs.iter()
+        .filter_map(|line| line.strip_prefix("Program data: "))
+        .filter_map(|data| base64::engine::general_purpose::STANDARD.decode(data).ok())
+        .filter(|data| data.len() >= 8 && data[..8] == discriminator)
+        .filter_map(|data| {
+            // Borsh: subscription_id (u32 length + bytes), payment_number, amount, merchant_amount,
+            // fee_amount, tier (u8 index + u16 bps), timestamp
+            let id_len = u32::from_le_bytes(data.get(8..12)?.try_into().ok()?) as usize;
+            let subscription_id = String::from_utf8(data.get(12..12 + id_len)?.to_vec()).ok()?;
+            let fields = 12 + id_len;
+            let payment_number = u64::from_le_bytes(data.get(fields..fields + 8)?.try_into().ok()?);
+            let amount = u64::from_le_bytes(data.get(fields + 8..fields + 16)?.try_into().ok()?);
+            let timestamp = i64::from_le_bytes(data.get(fields + 35..fields + 43)?.try_into().ok()?);
+            Some(PaymentProcessedEvent { subscription_id, payment_number, amount, timestamp })
+        })
+        .collect()
+}
+
+/// Read recent transactions of every contract address in use and catch each subscription's
+/// trigger_count up to the PaymentProcessed events found. Without `from_signature` each contract
+/// resumes after the newest signature the previous run indexed. `limit` is per contract address.
+pub async fn run_program_event_index(from_signature: Option<String>, limit: u32) -> IndexResult {
+    use crate::types::MAX_PAGE_SIZE;
+
+    let limit = limit.clamp(1, MAX_PAGE_SIZE as u32);
+    let mut contract_addresses: Vec<String> = crate::subscription_manager::list_subscriptions()
+        .into_iter()
+        .map(|sub| sub.solana_contract_address)
+        .collect();
+    contract_addresses.sort();
+    contract_addresses.dedup();
+
+    let mut result = IndexResult::default();
+    for contract_address in contract_addresses {
+        let until = from_signature.clone().or_else(|| crate::state::get_last_indexed_signature(&contract_address));
+        let signatures = match crate::solana::get_signatures_for_address(&contract_address, until.as_deref(), limit).await {
+            Ok(signatures) => signatures,
+            Err(e) => {
+                result.errors.push(format!("{}: {}", contract_address, e));
+                continue;
+            }
+        };
+
+        let mut updated = std::collections::HashSet::new();
+        for signature in &signatures {
+            let logs = match crate::solana::get_transaction_logs(signature).await {
+                Ok(logs) => logs,
+                Err(e) => {
+                    result.errors.push(format!("{}: {}", signature, e));
+                    continue;
+                }
+            };
+            for event in parse_payment_events(&logs) {
+                result.events_processed += 1;
+                // Events for subscriptions this canister doesn't track aren't errors
+                if let Ok(true) = crate::subscription_manager::apply_indexed_payment(&event.subscription_id, event.payment_number) {
+                    updated.insert(event.subscription_id);
+                }
+            }
+        }
+        result.subscriptions_updated += updated.len() as u32;
+
+        if let Some(newest) = signatures.first() {
+            crate::state::set_last_indexed_signature(&contract_address, newest.clone());
+        }
+    }
+
+    ic_cdk::println!("📚 Indexed {} payment events, {} subscriptions updated, {} errors",
+                     result.events_processed, result.subscriptions_updated, result.errors.len());
+    result
+}
+
+#[ic_cdk::update]
+pub async fn index_program_events(from_signature: Option<String>, limit: u32) -> Result<IndexResult, String> {
+    crate::authorization::require_admin()?;
+    Ok(run_program_event_index(from_signature, limit).await)
+}
+
 /// Debug function to list all subscriptions and their contract addresses
 #[ic_cdk::update]
 pub async fn debug_list_subscriptions() -> String {
@@ -346,3 +653,99 @@ pub async fn debug_list_subscriptions() -> String {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SubscriptionStatus;
+
+    fn borsh_string(value: &str) -> Vec<u8> {
+        let mut out = (value.len() as u32).to_le_bytes().to_vec();
+        out.extend_from_slice(value.as_bytes());
+        out
+    }
+
+    #[test]
+    fn test_compute_budget_instruction_encoding() {
+        let [limit, price] = compute_budget_instructions(300_000, 25_000);
+
+        assert_eq!(limit.program_id.to_string(), "ComputeBudget111111111111111111111111111111");
+        assert!(limit.accounts.is_empty());
+        assert_eq!(limit.data, vec![2, 0xe0, 0x93, 0x04, 0x00]);
+
+        assert_eq!(price.program_id, limit.program_id);
+        assert!(price.accounts.is_empty());
+        assert_eq!(price.data, vec![3, 0xa8, 0x61, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_parse_onchain_subscription() {
+        let mut data = account_discriminator("Subscription").to_vec();
+        data.extend(borsh_string("sub_12345"));
+        data.extend_from_slice(&[1u8; 32]); // subscriber
+        data.extend_from_slice(&[2u8; 32]); // merchant
+        data.extend(borsh_string("Acme"));
+        data.extend_from_slice(&10_000_000u64.to_le_bytes()); // amount
+        data.extend_from_slice(&2_592_000i64.to_le_bytes()); // interval_seconds
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes()); // next_payment_time
+        data.push(1); // Paused
+        data.extend_from_slice(&1_690_000_000i64.to_le_bytes()); // created_at
+        data.push(1); // last_payment_time: Some
+        data.extend_from_slice(&1_697_000_000i64.to_le_bytes());
+        data.extend_from_slice(&7u64.to_le_bytes()); // payments_made
+        data.extend_from_slice(&[0u8; 8 + 64 + 4 + 32 + 8 + 8]); // total_paid .. grace_period_seconds
+        data.push(1); // trial_end_time: Some
+        data.extend_from_slice(&1_691_000_000i64.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0]); // max_payments, remaining_payments, use_token_2022
+        data.extend_from_slice(&[0, 1]); // last_notification_time, paused_at: Some
+        data.extend_from_slice(&1_699_000_000i64.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 1, 3]); // expires_at, paused_by, authorization_mode_override: Some(Hybrid)
+        data.extend_from_slice(&[0u8; 8 + 8 + 8 + 2 + 1 + 32 + 1 + 8]); // spending_cap_per_period .. dispute_payment_number
+        data.extend_from_slice(&9u64.to_le_bytes()); // version
+
+        assert_eq!(parse_onchain_subscription(&data).unwrap(), OnChainSubscriptionState {
+            status: SubscriptionStatus::Paused,
+            payments_made: 7,
+            version: 9,
+            next_payment_time: 1_700_000_000,
+        });
+
+        assert!(parse_onchain_subscription(&data[..data.len() - 1]).is_err());
+        data[0] ^= 0xff;
+        assert!(parse_onchain_subscription(&data).is_err());
+    }
+
+    #[test]
+    fn test_parse_payment_events() {
+        use base64::Engine;
+
+        // Matches the discriminator in the program's IDL
+        assert_eq!(event_discriminator("PaymentProcessed"), [22, 109, 191, 213, 83, 63, 120, 219]);
+
+        let mut data = event_discriminator("PaymentProcessed").to_vec();
+        data.extend(borsh_string("sub_12345"));
+        data.extend_from_slice(&7u64.to_le_bytes()); // payment_number
+        data.extend_from_slice(&10_000_000u64.to_le_bytes()); // amount
+        data.extend_from_slice(&[0u8; 8 * 2 + 3]); // merchant_amount, fee_amount, tier
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes()); // timestamp
+        let mut other_event = event_discriminator("SubscriptionCancelled").to_vec();
+        other_event.extend(borsh_string("sub_99999"));
+
+        let encode = |bytes: &[u8]| format!("Program data: {}", base64::engine::general_purpose::STANDARD.encode(bytes));
+        let logs = vec![
+            "Program CFEtrptTe5eFXpZtB3hr1VMGuWF9oXguTnUFUaeVgeyT invoke [1]".to_string(),
+            "Program log: Instruction: ProcessTrigger".to_string(),
+            encode(&other_event),
+            encode(&data),
+            encode(&data[..data.len() - 1]), // Truncated
+            "Program data: not-base64!".to_string(),
+        ];
+
+        assert_eq!(parse_payment_events(&logs), vec![PaymentProcessedEvent {
+            subscription_id: "sub_12345".to_string(),
+            payment_number: 7,
+            amount: 10_000_000,
+            timestamp: 1_700_000_000,
+        }]);
+    }
+}
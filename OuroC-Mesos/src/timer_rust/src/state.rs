@@ -1,16 +1,49 @@
 // State management module
 
 use crate::types::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use ic_cdk::api::{time, canister_balance, caller};
 use candid::{CandidType, Deserialize};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{DefaultMemoryImpl, StableCell, StableLog};
+
+pub type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+// Stable memory partitions
+pub const SUBSCRIPTIONS_MEMORY_ID: MemoryId = MemoryId::new(0);
+const CANISTER_STATE_MEMORY_ID: MemoryId = MemoryId::new(1);
+const DELETION_LOG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(2);
+const DELETION_LOG_DATA_MEMORY_ID: MemoryId = MemoryId::new(3);
 
 // Thread-local state
 thread_local! {
+    // Stable memory - subscriptions live here directly; the rest of CanisterState is
+    // Candid-encoded into a cell in pre_upgrade
+    static MEMORY_MANAGER: std::cell::RefCell<MemoryManager<DefaultMemoryImpl>> =
+        std::cell::RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+    static CANISTER_STATE_CELL: std::cell::RefCell<StableCell<Vec<u8>, Memory>> = std::cell::RefCell::new(
+        StableCell::init(get_memory(CANISTER_STATE_MEMORY_ID), Vec::new())
+            .expect("Failed to initialize canister state cell")
+    );
+    // Append-only audit log of subscriber data deletions; entries are never removed
+    static DELETION_LOG: std::cell::RefCell<StableLog<DeletionRecord, Memory, Memory>> = std::cell::RefCell::new(
+        StableLog::init(get_memory(DELETION_LOG_INDEX_MEMORY_ID), get_memory(DELETION_LOG_DATA_MEMORY_ID))
+            .expect("Failed to initialize deletion log")
+    );
+
+    // State change audit trail, oldest entries dropped past MAX_STATE_LOG_ENTRIES
+    static STATE_LOG: std::cell::RefCell<VecDeque<StateEvent>> = const { std::cell::RefCell::new(VecDeque::new()) };
+    // Log lines captured per trigger trace ID; oldest traces dropped past MAX_TRACES
+    static TRACE_LOGS: std::cell::RefCell<HashMap<String, Vec<String>>> = std::cell::RefCell::new(HashMap::new());
+    static TRACE_ORDER: std::cell::RefCell<VecDeque<String>> = const { std::cell::RefCell::new(VecDeque::new()) };
+
     // Network configuration
     static NETWORK_ENV: std::cell::RefCell<NetworkEnvironment> = std::cell::RefCell::new(NetworkEnvironment::Devnet);
     static ED25519_KEY_NAME: std::cell::RefCell<String> = std::cell::RefCell::new("test_key_1".to_string());
     static SOLANA_RPC_ENDPOINT: std::cell::RefCell<String> = std::cell::RefCell::new("https://api.devnet.solana.com".to_string());
+    static RPC_ENDPOINTS: std::cell::RefCell<Vec<String>> = const { std::cell::RefCell::new(Vec::new()) };
+    static LAST_HEALTHY_RPC: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+    static LAST_HEALTHY_RPC_AT: std::cell::RefCell<Timestamp> = const { std::cell::RefCell::new(0) };
 
     // Wallet addresses
     static MAIN_WALLET_ADDRESS: std::cell::RefCell<String> = std::cell::RefCell::new(String::new());
@@ -22,12 +55,17 @@ thread_local! {
     static IS_INITIALIZED: std::cell::RefCell<bool> = std::cell::RefCell::new(false);
     static AUTO_CYCLE_REFILL: std::cell::RefCell<bool> = std::cell::RefCell::new(true);
     static CYCLE_THRESHOLD: std::cell::RefCell<u64> = std::cell::RefCell::new(5_000_000_000_000);
+    static JITTER_ENABLED: std::cell::RefCell<bool> = const { std::cell::RefCell::new(true) };
+    static USE_SOL_RPC_CANISTER: std::cell::RefCell<bool> = const { std::cell::RefCell::new(true) };
 
     // Fee configuration
     static FEE_CONFIG: std::cell::RefCell<FeeConfig> = std::cell::RefCell::new(FeeConfig {
         trigger_fee_lamports: 5000,
         gas_reserve_lamports: 5000,
         cycle_refill_ratio: 0.3,
+        confirmation_timeout_seconds: DEFAULT_CONFIRMATION_TIMEOUT_SECONDS,
+        compute_unit_price_microlamports: DEFAULT_COMPUTE_UNIT_PRICE_MICROLAMPORTS,
+        compute_unit_limit: DEFAULT_COMPUTE_UNIT_LIMIT,
     });
 
     // Health monitoring
@@ -35,15 +73,67 @@ thread_local! {
     static FAILED_PAYMENT_COUNT: std::cell::RefCell<u32> = std::cell::RefCell::new(0);
     static HEALTH_CHECK_COUNTER: std::cell::RefCell<u64> = std::cell::RefCell::new(0);
 
+    // Liveness heartbeat
+    static HEARTBEAT: std::cell::RefCell<HeartbeatState> = const { std::cell::RefCell::new(HeartbeatState {
+        contract_address: None,
+        heartbeat_interval_seconds: DEFAULT_HEARTBEAT_INTERVAL_SECONDS,
+        last_heartbeat_at: 0,
+        heartbeat_success_count: 0,
+        consecutive_failures: 0,
+    }) };
+
+    // Critical health alert webhook
+    static ALERT_CONFIG: std::cell::RefCell<AlertConfig> = std::cell::RefCell::new(AlertConfig::default());
+
+    // Automatic cleanup of cancelled/expired subscriptions
+    // Newest transaction signature indexed per contract address, so the daily index only reads new ones
+    static LAST_INDEXED_SIGNATURES: std::cell::RefCell<HashMap<String, String>> = std::cell::RefCell::new(HashMap::new());
+    static CLEANUP_SCHEDULE: std::cell::RefCell<CleanupSchedule> = const { std::cell::RefCell::new(CleanupSchedule {
+        cleanup_age_seconds: DEFAULT_CLEANUP_AGE_SECONDS,
+        last_cleanup_time: 0,
+        next_cleanup_time: 0,
+    }) };
+
     // Solana blockhash cache (to avoid consensus issues)
     static CACHED_BLOCKHASH: std::cell::RefCell<Option<String>> = std::cell::RefCell::new(None);
     static BLOCKHASH_FETCHED_AT: std::cell::RefCell<Timestamp> = std::cell::RefCell::new(0);
 }
 
+pub fn get_memory(id: MemoryId) -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(id))
+}
+
+/// True if stable memory still holds a `stable_save` blob from before the memory manager
+/// (the manager's header starts with "MGR"). Must be checked before anything touches stable structures.
+pub fn has_legacy_stable_state() -> bool {
+    if ic_cdk::api::stable::stable_size() == 0 {
+        return false;
+    }
+    let mut magic = [0u8; 3];
+    ic_cdk::api::stable::stable_read(0, &mut magic);
+    &magic != b"MGR"
+}
+
+pub fn save_canister_state(state: &CanisterState) -> Result<(), String> {
+    let bytes = candid::encode_one(state).map_err(|e| format!("Failed to encode canister state: {}", e))?;
+    CANISTER_STATE_CELL.with(|c| c.borrow_mut().set(bytes))
+        .map(|_| ())
+        .map_err(|e| format!("Failed to write canister state: {:?}", e))
+}
+
+pub fn load_canister_state() -> Result<CanisterState, String> {
+    let bytes = CANISTER_STATE_CELL.with(|c| c.borrow().get().clone());
+    if bytes.is_empty() {
+        return Err("No saved canister state".to_string());
+    }
+    candid::decode_one(&bytes).map_err(|e| format!("Failed to decode canister state: {}", e))
+}
+
 // State structure for stable storage
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct CanisterState {
-    pub subscriptions: HashMap<String, Subscription>,
+    // Only set in state saved before subscriptions moved to the stable map; migrated on upgrade
+    pub subscriptions: Option<HashMap<String, Subscription>>,
     pub admin_list: Vec<String>,
     pub read_only_users: Vec<String>,
     pub network_env: NetworkEnvironment,
@@ -60,6 +150,85 @@ pub struct CanisterState {
     pub canister_start_time: Timestamp,
     pub failed_payment_count: u32,
     pub health_check_counter: u64,
+    // Optional so state saved before timers were persisted still decodes
+    pub timer_next_exec: Option<HashMap<String, (u64, u64)>>,
+    pub jitter_enabled: Option<bool>,
+    pub heartbeat: Option<HeartbeatState>,
+    pub archived_subscriptions: Option<HashMap<String, Subscription>>,
+    pub use_sol_rpc_canister: Option<bool>,
+    pub rpc_endpoints: Option<Vec<String>>,
+    pub subscription_order: Option<Vec<String>>,
+    pub state_log: Option<Vec<StateEvent>>,
+    pub subscription_owners: Option<HashMap<String, Vec<candid::Principal>>>,
+    pub cleanup_schedule: Option<CleanupSchedule>,
+    pub alert_config: Option<AlertConfig>,
+    pub dead_letter_queue: Option<Vec<FailedPaymentRecord>>,
+    pub webhook_secrets: Option<HashMap<String, [u8; 32]>>,
+    pub expired_index: Option<Vec<(u64, Vec<String>)>>,
+    pub metrics_history: Option<Vec<DailyMetricsSnapshot>>,
+}
+
+// State change audit log
+pub fn log_state_event(action: &str, subscription_id: Option<&str>, detail: impl Into<String>) {
+    let event = StateEvent {
+        timestamp: time(),
+        actor: caller(),
+        action: action.to_string(),
+        subscription_id: subscription_id.map(str::to_string),
+        detail: detail.into(),
+    };
+    STATE_LOG.with(|l| {
+        let mut log = l.borrow_mut();
+        if log.len() >= MAX_STATE_LOG_ENTRIES {
+            log.pop_front();
+        }
+        log.push_back(event);
+    });
+}
+
+/// Events after `after` (exclusive), oldest first, at most `limit` (capped at MAX_PAGE_SIZE)
+pub fn get_state_log(after: Option<Timestamp>, limit: u32) -> Result<Vec<StateEvent>, String> {
+    crate::authorization::require_read_access()?;
+    let limit = (limit as usize).min(MAX_PAGE_SIZE);
+    Ok(STATE_LOG.with(|l| {
+        l.borrow()
+            .iter()
+            .filter(|event| after.is_none_or(|after| event.timestamp > after))
+            .take(limit)
+            .cloned()
+            .collect()
+    }))
+}
+
+pub fn get_all_state_events() -> Vec<StateEvent> {
+    STATE_LOG.with(|l| l.borrow().iter().cloned().collect())
+}
+
+// Per-trigger trace logs
+pub fn record_trace_line(trace_id: &str, line: String) {
+    TRACE_LOGS.with(|t| {
+        let mut traces = t.borrow_mut();
+        if !traces.contains_key(trace_id) {
+            TRACE_ORDER.with(|o| {
+                let mut order = o.borrow_mut();
+                if order.len() >= MAX_TRACES {
+                    if let Some(oldest) = order.pop_front() {
+                        traces.remove(&oldest);
+                    }
+                }
+                order.push_back(trace_id.to_string());
+            });
+        }
+        let lines = traces.entry(trace_id.to_string()).or_default();
+        if lines.len() < MAX_TRACE_LINES {
+            lines.push(line);
+        }
+    });
+}
+
+pub fn get_trace_log(trace_id: String) -> Result<Vec<String>, String> {
+    crate::authorization::require_read_access()?;
+    Ok(TRACE_LOGS.with(|t| t.borrow().get(&trace_id).cloned().unwrap_or_default()))
 }
 
 // Network configuration functions
@@ -90,6 +259,7 @@ pub fn set_network(network: NetworkEnvironment) -> Result<(), String> {
         }
     };
 
+    log_state_event("set_network", None, format!("{:?}", network));
     ic_cdk::println!("Network set to {:?} with endpoint: {}", network, endpoint);
     Ok(())
 }
@@ -102,6 +272,73 @@ pub fn get_network_config() -> (NetworkEnvironment, String, String) {
     )
 }
 
+// Fallback RPC endpoints
+pub fn add_rpc_endpoint(url: String) -> Result<(), String> {
+    crate::authorization::require_admin()?;
+    if !url.starts_with("https://") {
+        return Err("RPC endpoint must be an https:// URL".to_string());
+    }
+    RPC_ENDPOINTS.with(|e| {
+        let mut endpoints = e.borrow_mut();
+        if endpoints.contains(&url) {
+            return Err("RPC endpoint already added".to_string());
+        }
+        if endpoints.len() >= MAX_RPC_ENDPOINTS {
+            return Err(format!("At most {} RPC endpoints can be configured", MAX_RPC_ENDPOINTS));
+        }
+        endpoints.push(url.clone());
+        Ok(())
+    })?;
+    log_state_event("add_rpc_endpoint", None, url.clone());
+    ic_cdk::println!("RPC endpoint added: {}", url);
+    Ok(())
+}
+
+pub fn remove_rpc_endpoint(url: String) -> Result<(), String> {
+    crate::authorization::require_admin()?;
+    RPC_ENDPOINTS.with(|e| {
+        let mut endpoints = e.borrow_mut();
+        let index = endpoints.iter().position(|endpoint| *endpoint == url)
+            .ok_or("RPC endpoint not found".to_string())?;
+        endpoints.remove(index);
+        Ok::<_, String>(())
+    })?;
+    if LAST_HEALTHY_RPC.with(|h| h.borrow().as_ref() == Some(&url)) {
+        LAST_HEALTHY_RPC.with(|h| *h.borrow_mut() = None);
+    }
+    log_state_event("remove_rpc_endpoint", None, url.clone());
+    ic_cdk::println!("RPC endpoint removed: {}", url);
+    Ok(())
+}
+
+pub fn list_rpc_endpoints() -> Vec<String> {
+    RPC_ENDPOINTS.with(|e| e.borrow().clone())
+}
+
+/// Network default endpoint first, then the configured fallbacks
+pub fn get_rpc_candidates() -> Vec<String> {
+    let mut candidates = vec![SOLANA_RPC_ENDPOINT.with(|e| e.borrow().clone())];
+    for endpoint in list_rpc_endpoints() {
+        if !candidates.contains(&endpoint) {
+            candidates.push(endpoint);
+        }
+    }
+    candidates
+}
+
+pub fn get_cached_healthy_rpc(now: Timestamp) -> Option<String> {
+    let checked_at = LAST_HEALTHY_RPC_AT.with(|t| *t.borrow());
+    if now.saturating_sub(checked_at) >= HEALTHY_RPC_CACHE_NANOS {
+        return None;
+    }
+    LAST_HEALTHY_RPC.with(|h| h.borrow().clone())
+}
+
+pub fn record_healthy_rpc(url: String, now: Timestamp) {
+    LAST_HEALTHY_RPC.with(|h| *h.borrow_mut() = Some(url));
+    LAST_HEALTHY_RPC_AT.with(|t| *t.borrow_mut() = now);
+}
+
 // Canister initialization
 pub async fn initialize_canister() -> Result<(String, String), String> {
     ic_cdk::println!("🔧 Initializing canister with Threshold Ed25519 wallets...");
@@ -114,6 +351,7 @@ pub async fn initialize_canister() -> Result<(String, String), String> {
 
     let fee_address = CURRENT_FEE_ADDRESS.with(|f| f.borrow().clone());
 
+    log_state_event("initialize_canister", None, format!("main wallet {}", main_address));
     ic_cdk::println!("✅ Canister initialized with main wallet: {} | Fee wallet managed by Solana contract", main_address);
     Ok((main_address, fee_address))
 }
@@ -126,6 +364,7 @@ pub fn is_initialized() -> bool {
 pub fn update_fee_config(new_config: FeeConfig) -> Result<(), String> {
     crate::authorization::require_admin()?;
     FEE_CONFIG.with(|f| *f.borrow_mut() = new_config.clone());
+    log_state_event("update_fee_config", None, format!("{:?}", new_config));
     ic_cdk::println!("Fee configuration updated");
     Ok(())
 }
@@ -135,6 +374,34 @@ pub fn get_fee_config() -> Result<FeeConfig, String> {
     Ok(FEE_CONFIG.with(|f| f.borrow().clone()))
 }
 
+pub fn get_confirmation_timeout_seconds() -> u64 {
+    FEE_CONFIG.with(|f| f.borrow().confirmation_timeout_seconds)
+}
+
+pub fn update_compute_budget(compute_unit_limit: u32, compute_unit_price_microlamports: u64) -> Result<(), String> {
+    crate::authorization::require_admin()?;
+    if compute_unit_limit == 0 || compute_unit_limit > MAX_COMPUTE_UNIT_LIMIT {
+        return Err(format!("Compute unit limit must be between 1 and {}", MAX_COMPUTE_UNIT_LIMIT));
+    }
+    FEE_CONFIG.with(|f| {
+        let mut config = f.borrow_mut();
+        config.compute_unit_limit = compute_unit_limit;
+        config.compute_unit_price_microlamports = compute_unit_price_microlamports;
+    });
+    log_state_event("update_compute_budget", None, format!("{} units at {} microlamports", compute_unit_limit, compute_unit_price_microlamports));
+    ic_cdk::println!("Compute budget updated: {} units at {} microlamports",
+                      compute_unit_limit, compute_unit_price_microlamports);
+    Ok(())
+}
+
+/// (compute_unit_limit, compute_unit_price_microlamports)
+pub fn get_compute_budget() -> (u32, u64) {
+    FEE_CONFIG.with(|f| {
+        let config = f.borrow();
+        (config.compute_unit_limit, config.compute_unit_price_microlamports)
+    })
+}
+
 // Cycle management
 pub fn get_cycle_balance() -> u64 {
     canister_balance()
@@ -143,12 +410,14 @@ pub fn get_cycle_balance() -> u64 {
 pub fn set_cycle_threshold(new_threshold: u64) {
     crate::authorization::require_admin().ok();
     CYCLE_THRESHOLD.with(|t| *t.borrow_mut() = new_threshold);
+    log_state_event("set_cycle_threshold", None, new_threshold.to_string());
     ic_cdk::println!("Cycle threshold updated to: {}", new_threshold);
 }
 
 pub fn enable_auto_refill(enabled: bool) {
     crate::authorization::require_admin().ok();
     AUTO_CYCLE_REFILL.with(|a| *a.borrow_mut() = enabled);
+    log_state_event("enable_auto_refill", None, enabled.to_string());
     ic_cdk::println!("Auto-refill {}", if enabled { "enabled" } else { "disabled" });
 }
 
@@ -160,6 +429,32 @@ pub fn is_auto_refill_enabled() -> bool {
     AUTO_CYCLE_REFILL.with(|a| *a.borrow())
 }
 
+// Timer jitter
+pub fn set_jitter_enabled(enabled: bool) -> Result<(), String> {
+    crate::authorization::require_admin()?;
+    JITTER_ENABLED.with(|j| *j.borrow_mut() = enabled);
+    log_state_event("set_jitter_enabled", None, enabled.to_string());
+    ic_cdk::println!("Timer jitter {}", if enabled { "enabled" } else { "disabled" });
+    Ok(())
+}
+
+pub fn is_jitter_enabled() -> bool {
+    JITTER_ENABLED.with(|j| *j.borrow())
+}
+
+// Solana RPC transport (SOL RPC canister, or HTTPS outcalls when disabled)
+pub fn set_use_sol_rpc_canister(enabled: bool) -> Result<(), String> {
+    crate::authorization::require_admin()?;
+    USE_SOL_RPC_CANISTER.with(|u| *u.borrow_mut() = enabled);
+    log_state_event("set_use_sol_rpc_canister", None, enabled.to_string());
+    ic_cdk::println!("Solana RPC via {}", if enabled { "SOL RPC canister" } else { "HTTPS outcalls" });
+    Ok(())
+}
+
+pub fn use_sol_rpc_canister() -> bool {
+    USE_SOL_RPC_CANISTER.with(|u| *u.borrow())
+}
+
 // Wallet functions
 pub fn get_main_wallet_address() -> String {
     MAIN_WALLET_ADDRESS.with(|w| w.borrow().clone())
@@ -170,6 +465,7 @@ pub fn get_current_fee_address() -> String {
 }
 
 pub fn set_main_wallet_address(address: String) {
+    log_state_event("set_main_wallet_address", None, address.clone());
     MAIN_WALLET_ADDRESS.with(|w| *w.borrow_mut() = address);
 }
 
@@ -184,6 +480,7 @@ pub fn propose_fee_address_change(new_address: String) -> Result<(), String> {
     PROPOSED_FEE_ADDRESS.with(|p| *p.borrow_mut() = Some(new_address.clone()));
     FEE_ADDRESS_PROPOSAL_TIME.with(|t| *t.borrow_mut() = Some(time()));
 
+    log_state_event("propose_fee_address_change", None, new_address.clone());
     ic_cdk::println!("Proposed fee address change to: {}", new_address);
     Ok(())
 }
@@ -197,6 +494,7 @@ pub fn execute_fee_address_change() -> Result<(), String> {
         PROPOSED_FEE_ADDRESS.with(|p| *p.borrow_mut() = None);
         FEE_ADDRESS_PROPOSAL_TIME.with(|t| *t.borrow_mut() = None);
 
+        log_state_event("execute_fee_address_change", None, new_address.clone());
         ic_cdk::println!("Fee address changed to: {}", new_address);
         Ok(())
     } else {
@@ -210,6 +508,7 @@ pub fn cancel_fee_address_proposal() -> Result<(), String> {
     PROPOSED_FEE_ADDRESS.with(|p| *p.borrow_mut() = None);
     FEE_ADDRESS_PROPOSAL_TIME.with(|t| *t.borrow_mut() = None);
 
+    log_state_event("cancel_fee_address_proposal", None, "");
     ic_cdk::println!("Fee address proposal cancelled");
     Ok(())
 }
@@ -243,28 +542,215 @@ pub fn increment_health_check_counter() {
     HEALTH_CHECK_COUNTER.with(|c| *c.borrow_mut() += 1);
 }
 
+// Liveness heartbeat
+pub fn set_heartbeat_config(contract_address: Option<String>, interval_seconds: u64) -> Result<(), String> {
+    crate::authorization::require_admin()?;
+
+    if let Some(address) = &contract_address {
+        if !crate::utils::is_valid_solana_address(address) {
+            return Err("Invalid Solana address format".to_string());
+        }
+    }
+    if interval_seconds < MIN_INTERVAL_SECONDS {
+        return Err(format!("Heartbeat interval must be at least {} seconds", MIN_INTERVAL_SECONDS));
+    }
+
+    HEARTBEAT.with(|h| {
+        let mut heartbeat = h.borrow_mut();
+        heartbeat.contract_address = contract_address;
+        heartbeat.heartbeat_interval_seconds = interval_seconds;
+    });
+    log_state_event("set_heartbeat_config", None, format!("every {} seconds", interval_seconds));
+    ic_cdk::println!("Heartbeat configured: every {} seconds", interval_seconds);
+    Ok(())
+}
+
+pub fn get_heartbeat_state() -> HeartbeatState {
+    HEARTBEAT.with(|h| h.borrow().clone())
+}
+
+pub fn record_heartbeat_success(now: Timestamp) {
+    HEARTBEAT.with(|h| {
+        let mut heartbeat = h.borrow_mut();
+        heartbeat.last_heartbeat_at = now;
+        heartbeat.heartbeat_success_count += 1;
+        heartbeat.consecutive_failures = 0;
+    });
+}
+
+pub fn record_heartbeat_failure() {
+    HEARTBEAT.with(|h| {
+        let mut heartbeat = h.borrow_mut();
+        heartbeat.consecutive_failures = heartbeat.consecutive_failures.saturating_add(1);
+    });
+}
+
+pub fn get_heartbeat_status() -> HeartbeatStatus {
+    HEARTBEAT.with(|h| {
+        let heartbeat = h.borrow();
+        HeartbeatStatus {
+            last_ok: heartbeat.last_heartbeat_at,
+            consecutive_failures: heartbeat.consecutive_failures,
+        }
+    })
+}
+
+// Health alert webhook
+pub fn set_alert_webhook(
+    alert_webhook_url: Option<String>,
+    alert_thresholds: AlertThresholds,
+    webhook_signing_secret: Option<String>,
+) -> Result<(), String> {
+    crate::authorization::require_admin()?;
+    if let Some(url) = &alert_webhook_url {
+        if !url.starts_with("https://") {
+            return Err("Alert webhook must be an https:// URL".to_string());
+        }
+        if webhook_signing_secret.as_ref().is_none_or(|secret| secret.is_empty()) {
+            return Err("A webhook signing secret is required".to_string());
+        }
+    }
+    if !(0.0..=100.0).contains(&alert_thresholds.max_failure_rate_pct) {
+        return Err("Failure rate threshold must be between 0 and 100".to_string());
+    }
+
+    ALERT_CONFIG.with(|a| {
+        let mut config = a.borrow_mut();
+        config.alert_webhook_url = alert_webhook_url.clone();
+        config.alert_thresholds = alert_thresholds;
+        config.webhook_signing_secret = webhook_signing_secret;
+    });
+    log_state_event("set_alert_webhook", None, alert_webhook_url.unwrap_or_default());
+    ic_cdk::println!("Alert webhook configuration updated");
+    Ok(())
+}
+
+/// (alert_webhook_url, alert_thresholds, last_alert_time) - the signing secret stays private
+pub fn get_alert_config() -> Result<(Option<String>, AlertThresholds, Option<Timestamp>), String> {
+    crate::authorization::require_admin()?;
+    Ok(ALERT_CONFIG.with(|a| {
+        let config = a.borrow();
+        (config.alert_webhook_url.clone(), config.alert_thresholds.clone(), config.last_alert_time)
+    }))
+}
+
+pub fn get_alert_config_internal() -> AlertConfig {
+    ALERT_CONFIG.with(|a| a.borrow().clone())
+}
+
+pub fn record_alert_sent(now: Timestamp) {
+    ALERT_CONFIG.with(|a| a.borrow_mut().last_alert_time = Some(now));
+}
+
+// Automatic cleanup
+pub fn set_cleanup_age_seconds(cleanup_age_seconds: u64) -> Result<(), String> {
+    crate::authorization::require_admin()?;
+    if cleanup_age_seconds == 0 {
+        return Err("Cleanup age must be greater than 0".to_string());
+    }
+    CLEANUP_SCHEDULE.with(|c| c.borrow_mut().cleanup_age_seconds = cleanup_age_seconds);
+    log_state_event("set_cleanup_age_seconds", None, cleanup_age_seconds.to_string());
+    ic_cdk::println!("Cleanup age set to {} seconds", cleanup_age_seconds);
+    Ok(())
+}
+
+pub fn get_cleanup_schedule() -> CleanupSchedule {
+    CLEANUP_SCHEDULE.with(|c| c.borrow().clone())
+}
+
+pub fn set_next_cleanup_time(next_cleanup_time: Timestamp) {
+    CLEANUP_SCHEDULE.with(|c| c.borrow_mut().next_cleanup_time = next_cleanup_time);
+}
+
+pub fn record_cleanup_run(now: Timestamp) {
+    CLEANUP_SCHEDULE.with(|c| {
+        let mut schedule = c.borrow_mut();
+        schedule.last_cleanup_time = now;
+        schedule.next_cleanup_time = now + CLEANUP_INTERVAL_SECONDS * 1_000_000_000;
+    });
+}
+
+pub fn get_last_indexed_signature(contract_address: &str) -> Option<String> {
+    LAST_INDEXED_SIGNATURES.with(|l| l.borrow().get(contract_address).cloned())
+}
+
+pub fn set_last_indexed_signature(contract_address: &str, signature: String) {
+    LAST_INDEXED_SIGNATURES.with(|l| l.borrow_mut().insert(contract_address.to_string(), signature));
+}
+
+// Live values gathered in pre_upgrade, packed into CanisterState for stable storage
+pub struct CanisterStateSnapshot {
+    pub admin_list: Vec<String>,
+    pub read_only_users: Vec<String>,
+    pub network_env: NetworkEnvironment,
+    pub ed25519_key_name: String,
+    pub solana_rpc_endpoint: String,
+    pub main_wallet_address: String,
+    pub current_fee_address: String,
+    pub proposed_fee_address: Option<String>,
+    pub fee_address_proposal_time: Option<Timestamp>,
+    pub is_initialized: bool,
+    pub auto_cycle_refill: bool,
+    pub cycle_threshold: u64,
+    pub fee_config: FeeConfig,
+    pub canister_start_time: Timestamp,
+    pub failed_payment_count: u32,
+    pub health_check_counter: u64,
+    pub timer_next_exec: HashMap<String, (u64, u64)>,
+    pub jitter_enabled: bool,
+    pub heartbeat: HeartbeatState,
+    pub archived_subscriptions: HashMap<String, Subscription>,
+    pub use_sol_rpc_canister: bool,
+    pub rpc_endpoints: Vec<String>,
+    pub subscription_order: Vec<String>,
+    pub state_log: Vec<StateEvent>,
+    pub subscription_owners: HashMap<String, Vec<candid::Principal>>,
+    pub cleanup_schedule: CleanupSchedule,
+    pub alert_config: AlertConfig,
+    pub dead_letter_queue: Vec<FailedPaymentRecord>,
+    pub webhook_secrets: HashMap<String, [u8; 32]>,
+    pub expired_index: Vec<(u64, Vec<String>)>,
+    pub metrics_history: Vec<DailyMetricsSnapshot>,
+}
+
 // For stable storage
-pub fn create_canister_state(
-    subscriptions: HashMap<String, Subscription>,
-    admin_list: Vec<String>,
-    read_only_users: Vec<String>,
-    network_env: NetworkEnvironment,
-    ed25519_key_name: String,
-    solana_rpc_endpoint: String,
-    main_wallet_address: String,
-    current_fee_address: String,
-    proposed_fee_address: Option<String>,
-    fee_address_proposal_time: Option<Timestamp>,
-    is_initialized: bool,
-    auto_cycle_refill: bool,
-    cycle_threshold: u64,
-    fee_config: FeeConfig,
-    canister_start_time: Timestamp,
-    failed_payment_count: u32,
-    health_check_counter: u64,
-) -> CanisterState {
+pub fn create_canister_state(snapshot: CanisterStateSnapshot) -> CanisterState {
+    let CanisterStateSnapshot {
+        admin_list,
+        read_only_users,
+        network_env,
+        ed25519_key_name,
+        solana_rpc_endpoint,
+        main_wallet_address,
+        current_fee_address,
+        proposed_fee_address,
+        fee_address_proposal_time,
+        is_initialized,
+        auto_cycle_refill,
+        cycle_threshold,
+        fee_config,
+        canister_start_time,
+        failed_payment_count,
+        health_check_counter,
+        timer_next_exec,
+        jitter_enabled,
+        heartbeat,
+        archived_subscriptions,
+        use_sol_rpc_canister,
+        rpc_endpoints,
+        subscription_order,
+        state_log,
+        subscription_owners,
+        cleanup_schedule,
+        alert_config,
+        dead_letter_queue,
+        webhook_secrets,
+        expired_index,
+        metrics_history,
+    } = snapshot;
+
     CanisterState {
-        subscriptions,
+        subscriptions: None,
         admin_list,
         read_only_users,
         network_env,
@@ -281,11 +767,36 @@ pub fn create_canister_state(
         canister_start_time,
         failed_payment_count,
         health_check_counter,
+        timer_next_exec: Some(timer_next_exec),
+        jitter_enabled: Some(jitter_enabled),
+        heartbeat: Some(heartbeat),
+        archived_subscriptions: Some(archived_subscriptions),
+        use_sol_rpc_canister: Some(use_sol_rpc_canister),
+        rpc_endpoints: Some(rpc_endpoints),
+        subscription_order: Some(subscription_order),
+        state_log: Some(state_log),
+        subscription_owners: Some(subscription_owners),
+        cleanup_schedule: Some(cleanup_schedule),
+        alert_config: Some(alert_config),
+        dead_letter_queue: Some(dead_letter_queue),
+        webhook_secrets: Some(webhook_secrets),
+        expired_index: Some(expired_index),
+        metrics_history: Some(metrics_history),
     }
 }
 
 pub fn restore_canister_state(state: CanisterState) {
-    crate::subscription_manager::restore_subscriptions(state.subscriptions);
+    if let Some(subscriptions) = state.subscriptions {
+        crate::subscription_manager::migrate_legacy_subscriptions(subscriptions);
+    }
+    crate::subscription_manager::restore_subscription_order(state.subscription_order);
+    crate::subscription_manager::rebuild_merchant_index();
+    crate::subscription_manager::restore_subscription_owners(state.subscription_owners.unwrap_or_default());
+    crate::subscription_manager::restore_archived_subscriptions(state.archived_subscriptions.unwrap_or_default());
+    crate::subscription_manager::restore_dead_letter_queue(state.dead_letter_queue.unwrap_or_default());
+    crate::subscription_manager::restore_webhook_secrets(state.webhook_secrets.unwrap_or_default());
+    crate::subscription_manager::restore_expired_index(state.expired_index);
+    crate::subscription_manager::restore_metrics_history(state.metrics_history.unwrap_or_default());
     crate::authorization::restore_admins(state.admin_list, state.read_only_users);
 
     NETWORK_ENV.with(|n| *n.borrow_mut() = state.network_env);
@@ -302,6 +813,19 @@ pub fn restore_canister_state(state: CanisterState) {
     CANISTER_START_TIME.with(|t| *t.borrow_mut() = state.canister_start_time);
     FAILED_PAYMENT_COUNT.with(|f| *f.borrow_mut() = state.failed_payment_count);
     HEALTH_CHECK_COUNTER.with(|h| *h.borrow_mut() = state.health_check_counter);
+    JITTER_ENABLED.with(|j| *j.borrow_mut() = state.jitter_enabled.unwrap_or(true));
+    USE_SOL_RPC_CANISTER.with(|u| *u.borrow_mut() = state.use_sol_rpc_canister.unwrap_or(true));
+    RPC_ENDPOINTS.with(|e| *e.borrow_mut() = state.rpc_endpoints.unwrap_or_default());
+    STATE_LOG.with(|l| *l.borrow_mut() = state.state_log.unwrap_or_default().into());
+    if let Some(heartbeat) = state.heartbeat {
+        HEARTBEAT.with(|h| *h.borrow_mut() = heartbeat);
+    }
+    if let Some(alert_config) = state.alert_config {
+        ALERT_CONFIG.with(|a| *a.borrow_mut() = alert_config);
+    }
+    if let Some(cleanup_schedule) = state.cleanup_schedule {
+        CLEANUP_SCHEDULE.with(|c| *c.borrow_mut() = cleanup_schedule);
+    }
 }
 
 // Initialize state
@@ -349,6 +873,7 @@ pub fn store_encrypted_metadata(
     };
 
     ENCRYPTED_METADATA.with(|m| m.borrow_mut().insert(subscription_id.clone(), metadata));
+    log_state_event("store_encrypted_metadata", Some(&subscription_id), "");
     ic_cdk::println!("Encrypted metadata stored for subscription: {}", subscription_id);
 
     Ok(())
@@ -369,6 +894,7 @@ pub fn delete_encrypted_metadata(subscription_id: String) -> Result<(), String>
         }
 
         if m.borrow_mut().remove(&subscription_id).is_some() {
+            log_state_event("delete_encrypted_metadata", Some(&subscription_id), "");
             ic_cdk::println!("Encrypted metadata deleted for subscription: {}", subscription_id);
             Ok(())
         } else {
@@ -390,6 +916,23 @@ pub fn list_encrypted_metadata() -> Result<Vec<String>, String> {
     Ok(metadata_list)
 }
 
+/// Remove metadata without the encryptor check, for admin data erasure
+pub fn erase_encrypted_metadata(subscription_id: &str) -> bool {
+    ENCRYPTED_METADATA.with(|m| m.borrow_mut().remove(subscription_id).is_some())
+}
+
+pub fn append_deletion_record(record: &DeletionRecord) -> Result<(), String> {
+    DELETION_LOG
+        .with(|log| log.borrow().append(record))
+        .map(|_| ())
+        .map_err(|e| format!("Failed to write deletion record: {:?}", e))
+}
+
+pub fn get_deletion_log() -> Result<Vec<DeletionRecord>, String> {
+    crate::authorization::require_admin()?;
+    Ok(DELETION_LOG.with(|log| log.borrow().iter().collect()))
+}
+
 pub fn get_all_encrypted_metadata() -> HashMap<String, crate::types::EncryptedMetadata> {
     ENCRYPTED_METADATA.with(|m| m.borrow().clone())
 }
@@ -4,19 +4,91 @@ use crate::types::*;
 use ic_cdk_timers::{set_timer, clear_timer};
 use std::time::Duration;
 use std::collections::HashMap;
+use sha2::{Digest, Sha256};
 
 thread_local! {
     static ACTIVE_TIMERS: std::cell::RefCell<HashMap<String, TimerInfo>> = std::cell::RefCell::new(HashMap::new());
     static NOTIFICATION_TIMERS: std::cell::RefCell<HashMap<String, TimerInfo>> = std::cell::RefCell::new(HashMap::new());
+    static HEARTBEAT_TIMER: std::cell::RefCell<Option<ic_cdk_timers::TimerId>> = const { std::cell::RefCell::new(None) };
+    static CLEANUP_TIMER: std::cell::RefCell<Option<ic_cdk_timers::TimerId>> = const { std::cell::RefCell::new(None) };
+    static EVENT_INDEX_TIMER: std::cell::RefCell<Option<ic_cdk_timers::TimerId>> = const { std::cell::RefCell::new(None) };
+    static METRICS_SNAPSHOT_TIMER: std::cell::RefCell<Option<ic_cdk_timers::TimerId>> = const { std::cell::RefCell::new(None) };
+    // Absolute (payment_exec, notification_exec) nanos per subscription; 0 means no timer.
+    // TimerIds do not survive an upgrade, so this is what gets persisted and re-armed.
+    static TIMER_NEXT_EXEC: std::cell::RefCell<HashMap<SubscriptionId, (u64, u64)>> = std::cell::RefCell::new(HashMap::new());
+}
+
+fn record_payment_exec(subscription_id: &str, payment_exec: u64) {
+    TIMER_NEXT_EXEC.with(|t| {
+        t.borrow_mut().entry(subscription_id.to_string()).or_insert((0, 0)).0 = payment_exec;
+    });
+}
+
+fn record_notification_exec(subscription_id: &str, notification_exec: u64) {
+    TIMER_NEXT_EXEC.with(|t| {
+        t.borrow_mut().entry(subscription_id.to_string()).or_insert((0, 0)).1 = notification_exec;
+    });
+}
+
+fn clear_next_exec(subscription_id: &str, notification: bool) {
+    TIMER_NEXT_EXEC.with(|t| {
+        let mut timers = t.borrow_mut();
+        if let Some(entry) = timers.get_mut(subscription_id) {
+            if notification {
+                entry.1 = 0;
+            } else {
+                entry.0 = 0;
+            }
+            if *entry == (0, 0) {
+                timers.remove(subscription_id);
+            }
+        }
+    });
+}
+
+/// Store a newly armed timer, clearing the one it replaces so the old timer can't fire as well
+fn replace_timer(timers: &'static std::thread::LocalKey<std::cell::RefCell<HashMap<String, TimerInfo>>>, timer_info: TimerInfo) {
+    let previous = timers.with(|t| t.borrow_mut().insert(timer_info.subscription_id.clone(), timer_info));
+    if let Some(previous) = previous {
+        clear_timer(previous.timer_id);
+    }
+}
+
+/// Re-arm a payment timer after a fixed delay without moving the subscription's billing time
+pub fn schedule_subscription_retry(subscription_id: &str, delay_nanos: u64) {
+    let execution_time = ic_cdk::api::time() + delay_nanos;
+    let timer_id = arm_payment_timer(subscription_id.to_string(), delay_nanos);
+
+    let timer_info = TimerInfo {
+        subscription_id: subscription_id.to_string(),
+        timer_id,
+    };
+    replace_timer(&ACTIVE_TIMERS, timer_info);
+    record_payment_exec(subscription_id, execution_time);
+}
+
+/// Deterministic per-subscription offset so timers created in the same second don't all fire at once.
+/// Jitter only delays when the trigger fires: the next billing time is always derived from
+/// `next_execution`, never from the moment the timer ran, so billing accuracy is unaffected.
+pub fn compute_jitter_nanos(subscription_id: &str) -> u64 {
+    let hash = Sha256::digest(subscription_id.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash[0..8]);
+    u64::from_le_bytes(bytes) % MAX_JITTER_NANOS
 }
 
 pub fn schedule_subscription_timer(subscription: &Subscription) {
     let now = ic_cdk::api::time();
+    let jitter_nanos = if crate::state::is_jitter_enabled() {
+        compute_jitter_nanos(&subscription.id)
+    } else {
+        0
+    };
     let delay_nanos = if subscription.next_execution > now {
         subscription.next_execution - now
     } else {
         0
-    };
+    } + jitter_nanos;
 
     let delay_seconds = delay_nanos / 1_000_000_000;
     let subscription_id = subscription.id.clone();
@@ -24,21 +96,15 @@ pub fn schedule_subscription_timer(subscription: &Subscription) {
     ic_cdk::println!("⏰ Scheduling timer for subscription {} in {} seconds",
                       subscription.id, delay_seconds);
 
-    let timer_id = set_timer(Duration::from_nanos(delay_nanos), move || {
-        let id = subscription_id.clone();
-        ic_cdk::spawn(async move {
-            crate::subscription_manager::trigger_subscription(id).await;
-        });
-    });
+    let timer_id = arm_payment_timer(subscription_id, delay_nanos);
 
     // Store timer info
     let timer_info = TimerInfo {
         subscription_id: subscription.id.clone(),
         timer_id,
-        execution_time: subscription.next_execution,
-        is_notification: false,
     };
-    ACTIVE_TIMERS.with(|t| t.borrow_mut().insert(subscription.id.clone(), timer_info));
+    replace_timer(&ACTIVE_TIMERS, timer_info);
+    record_payment_exec(&subscription.id, now + delay_nanos);
 }
 
 pub fn schedule_notification_timer(subscription: &Subscription) {
@@ -51,34 +117,35 @@ pub fn schedule_notification_timer(subscription: &Subscription) {
         return;
     }
 
-    let notification_time = subscription.next_execution - (24 * 60 * 60 * 1_000_000_000); // 24 hours before
+    let notification_time = calculate_notification_time(
+        subscription.next_execution,
+        subscription.reminder_days_before_payment,
+    );
     let now = ic_cdk::api::time();
 
     if notification_time > now {
         let delay_nanos = notification_time - now;
-        let subscription_id = subscription.id.clone();
-
-        let timer_id = set_timer(Duration::from_nanos(delay_nanos), move || {
-            let id = subscription_id.clone();
-            ic_cdk::spawn(async move {
-                crate::subscription_manager::trigger_notification(id).await;
-            });
-        });
+        let timer_id = arm_notification_timer(subscription.id.clone(), delay_nanos);
 
         // Store notification timer info
         let timer_info = TimerInfo {
             subscription_id: subscription.id.clone(),
             timer_id,
-            execution_time: notification_time,
-            is_notification: true,
         };
-        NOTIFICATION_TIMERS.with(|t| t.borrow_mut().insert(subscription.id.clone(), timer_info));
+        replace_timer(&NOTIFICATION_TIMERS, timer_info);
+        record_notification_exec(&subscription.id, notification_time);
 
         ic_cdk::println!("🔔 Scheduled notification for subscription: {} (interval {} seconds > 1 day)",
                           subscription.id, subscription.interval_seconds);
     }
 }
 
+/// Merchant-configured reminder lead time before the payment
+pub fn calculate_notification_time(next_execution: Timestamp, reminder_days_before_payment: u32) -> Timestamp {
+    const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+    next_execution.saturating_sub(reminder_days_before_payment as u64 * NANOS_PER_DAY)
+}
+
 pub fn cancel_timer(subscription_id: &str) {
     ACTIVE_TIMERS.with(|timers| {
         if let Some(timer_info) = timers.borrow_mut().remove(subscription_id) {
@@ -88,6 +155,7 @@ pub fn cancel_timer(subscription_id: &str) {
             ic_cdk::println!("✅ Timer {} cancelled successfully", subscription_id);
         }
     });
+    clear_next_exec(subscription_id, false);
 }
 
 pub fn cancel_notification_timer(subscription_id: &str) {
@@ -99,6 +167,25 @@ pub fn cancel_notification_timer(subscription_id: &str) {
             ic_cdk::println!("✅ Notification timer {} cancelled successfully", subscription_id);
         }
     });
+    clear_next_exec(subscription_id, true);
+}
+
+fn arm_payment_timer(subscription_id: SubscriptionId, delay_nanos: u64) -> ic_cdk_timers::TimerId {
+    set_timer(Duration::from_nanos(delay_nanos), move || {
+        let id = subscription_id.clone();
+        ic_cdk::spawn(async move {
+            crate::subscription_manager::trigger_subscription(id).await;
+        });
+    })
+}
+
+fn arm_notification_timer(subscription_id: SubscriptionId, delay_nanos: u64) -> ic_cdk_timers::TimerId {
+    set_timer(Duration::from_nanos(delay_nanos), move || {
+        let id = subscription_id.clone();
+        ic_cdk::spawn(async move {
+            crate::subscription_manager::trigger_notification(id).await;
+        });
+    })
 }
 
 pub fn get_active_timer_count() -> usize {
@@ -109,16 +196,178 @@ pub fn get_notification_timer_count() -> usize {
     NOTIFICATION_TIMERS.with(|t| t.borrow().len())
 }
 
-pub fn get_all_timers() -> (HashMap<String, TimerInfo>, HashMap<String, TimerInfo>) {
-    (
-        ACTIVE_TIMERS.with(|t| t.borrow().clone()),
-        NOTIFICATION_TIMERS.with(|t| t.borrow().clone()),
-    )
+pub fn get_timer_next_exec() -> HashMap<SubscriptionId, (u64, u64)> {
+    TIMER_NEXT_EXEC.with(|t| t.borrow().clone())
 }
 
-pub fn restore_timers(active: HashMap<String, TimerInfo>, notification: HashMap<String, TimerInfo>) {
-    ACTIVE_TIMERS.with(|t| *t.borrow_mut() = active);
-    NOTIFICATION_TIMERS.with(|t| *t.borrow_mut() = notification);
+/// A timer to re-arm after an upgrade, with its delay from now
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestoredTimer {
+    pub subscription_id: SubscriptionId,
+    pub execution_time: u64,
+    pub delay_nanos: u64,
+    pub is_notification: bool,
+}
+
+/// Work out which timers to re-arm from the persisted next-execution map.
+/// Overdue timers get a zero delay so they fire right after the upgrade instead of being dropped.
+pub fn plan_restored_timers(next_exec: &HashMap<SubscriptionId, (u64, u64)>, now: u64) -> Vec<RestoredTimer> {
+    let mut restored = Vec::new();
+    for (subscription_id, (payment_exec, notification_exec)) in next_exec {
+        for (execution_time, is_notification) in [(*payment_exec, false), (*notification_exec, true)] {
+            if execution_time == 0 {
+                continue;
+            }
+            restored.push(RestoredTimer {
+                subscription_id: subscription_id.clone(),
+                execution_time,
+                delay_nanos: execution_time.saturating_sub(now),
+                is_notification,
+            });
+        }
+    }
+    restored
+}
+
+/// Re-arm subscription timers after an upgrade.
+/// Timers set with ic_cdk_timers are lost on upgrade, so every timer recorded before the
+/// upgrade is set again from its absolute execution time.
+pub fn restore_timers(next_exec: HashMap<SubscriptionId, (u64, u64)>) {
+    let now = ic_cdk::api::time();
+    let restored = plan_restored_timers(&next_exec, now);
+
+    ACTIVE_TIMERS.with(|t| t.borrow_mut().clear());
+    NOTIFICATION_TIMERS.with(|t| t.borrow_mut().clear());
+
+    for timer in &restored {
+        let timer_id = if timer.is_notification {
+            arm_notification_timer(timer.subscription_id.clone(), timer.delay_nanos)
+        } else {
+            arm_payment_timer(timer.subscription_id.clone(), timer.delay_nanos)
+        };
+        let timer_info = TimerInfo {
+            subscription_id: timer.subscription_id.clone(),
+            timer_id,
+        };
+        let timers = if timer.is_notification { &NOTIFICATION_TIMERS } else { &ACTIVE_TIMERS };
+        timers.with(|t| t.borrow_mut().insert(timer.subscription_id.clone(), timer_info));
+    }
+    TIMER_NEXT_EXEC.with(|t| *t.borrow_mut() = next_exec);
+
+    ic_cdk::println!("⏰ Restored {} timers after upgrade", restored.len());
+}
+
+// ============================================================================
+// Liveness Heartbeat Timer
+// ============================================================================
+
+/// Start (or restart) the recurring heartbeat that proves liveness on Solana between subscription cycles
+pub fn start_heartbeat_timer() {
+    let interval_seconds = crate::state::get_heartbeat_state().heartbeat_interval_seconds;
+
+    HEARTBEAT_TIMER.with(|t| {
+        if let Some(timer_id) = t.borrow_mut().take() {
+            clear_timer(timer_id);
+        }
+    });
+
+    let timer_id = ic_cdk_timers::set_timer_interval(Duration::from_secs(interval_seconds), || {
+        crate::health::record_failure_sample(ic_cdk::api::time());
+        crate::health::record_cycle_sample(ic_cdk::api::time());
+        ic_cdk::spawn(async {
+            crate::health::check_solana_connectivity().await;
+        });
+        ic_cdk::spawn(send_heartbeat());
+    });
+    HEARTBEAT_TIMER.with(|t| *t.borrow_mut() = Some(timer_id));
+
+    ic_cdk::println!("💓 Heartbeat timer started (every {} seconds)", interval_seconds);
+}
+
+async fn send_heartbeat() {
+    let contract_address = match crate::state::get_heartbeat_state().contract_address {
+        Some(address) => address,
+        None => {
+            ic_cdk::println!("⏭️ Skipping heartbeat: no contract address configured");
+            return;
+        }
+    };
+    let main_wallet = crate::state::get_main_wallet_address();
+
+    match crate::solana::send_solana_opcode(
+        &contract_address,
+        "heartbeat",
+        &main_wallet,
+        &main_wallet,
+        HEARTBEAT_OPCODE,
+        None,
+    ).await {
+        Ok(tx_hash) => {
+            crate::state::record_heartbeat_success(ic_cdk::api::time());
+            ic_cdk::println!("💓 Heartbeat sent | tx: {}", tx_hash);
+        }
+        Err(e) => {
+            crate::state::record_heartbeat_failure();
+            ic_cdk::println!("❌ Heartbeat failed: {}", e);
+        }
+    }
+}
+
+// ============================================================================
+// Maintenance Timers
+// ============================================================================
+
+/// Remove cancelled and expired subscriptions past the configured age once a day
+pub fn start_cleanup_timer() {
+    CLEANUP_TIMER.with(|t| {
+        if let Some(timer_id) = t.borrow_mut().take() {
+            clear_timer(timer_id);
+        }
+    });
+
+    let timer_id = ic_cdk_timers::set_timer_interval(Duration::from_secs(CLEANUP_INTERVAL_SECONDS), || {
+        let cleanup_age_seconds = crate::state::get_cleanup_schedule().cleanup_age_seconds;
+        crate::subscription_manager::cleanup_old_subscriptions(cleanup_age_seconds);
+        crate::state::record_cleanup_run(ic_cdk::api::time());
+    });
+    CLEANUP_TIMER.with(|t| *t.borrow_mut() = Some(timer_id));
+    crate::state::set_next_cleanup_time(ic_cdk::api::time() + CLEANUP_INTERVAL_SECONDS * 1_000_000_000);
+
+    ic_cdk::println!("🧹 Cleanup timer started (every {} seconds)", CLEANUP_INTERVAL_SECONDS);
+}
+
+/// Reconcile trigger counts with the Solana program's PaymentProcessed events once a day
+pub fn start_event_index_timer() {
+    EVENT_INDEX_TIMER.with(|t| {
+        if let Some(timer_id) = t.borrow_mut().take() {
+            clear_timer(timer_id);
+        }
+    });
+
+    let timer_id = ic_cdk_timers::set_timer_interval(Duration::from_secs(EVENT_INDEX_INTERVAL_SECONDS), || {
+        ic_cdk::spawn(async {
+            crate::solana_rpc::run_program_event_index(None, MAX_PAGE_SIZE as u32).await;
+        });
+    });
+    EVENT_INDEX_TIMER.with(|t| *t.borrow_mut() = Some(timer_id));
+
+    ic_cdk::println!("📚 Event index timer started (every {} seconds)", EVENT_INDEX_INTERVAL_SECONDS);
+}
+
+/// Record a daily metrics snapshot for get_metrics_history
+pub fn start_metrics_snapshot_timer() {
+    METRICS_SNAPSHOT_TIMER.with(|t| {
+        if let Some(timer_id) = t.borrow_mut().take() {
+            clear_timer(timer_id);
+        }
+    });
+
+    let timer_id = ic_cdk_timers::set_timer_interval(Duration::from_secs(METRICS_SNAPSHOT_INTERVAL_SECONDS), || {
+        crate::subscription_manager::take_metrics_snapshot();
+    });
+    METRICS_SNAPSHOT_TIMER.with(|t| *t.borrow_mut() = Some(timer_id));
+
+    ic_cdk::println!("📈 Metrics snapshot timer started (every {} seconds)", METRICS_SNAPSHOT_INTERVAL_SECONDS);
 }
 
 // ============================================================================
@@ -130,4 +379,61 @@ pub fn restore_timers(active: HashMap<String, TimerInfo>, notification: HashMap<
 pub fn start_blockhash_refresh_timer() {
     ic_cdk::println!("✅ Blockhash refresh timer disabled - using durable nonces instead");
     // No longer needed since we use durable nonces for all Solana transactions
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_jitter_nanos() {
+        let jitter = compute_jitter_nanos("sub_12345");
+        assert!(jitter < MAX_JITTER_NANOS);
+        assert_eq!(jitter, compute_jitter_nanos("sub_12345"));
+        assert_ne!(jitter, compute_jitter_nanos("sub_12346"));
+    }
+
+    #[test]
+    fn test_seven_day_reminder_fires_seven_days_before_payment() {
+        const DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+        let next_execution = 100 * DAY;
+
+        assert_eq!(calculate_notification_time(next_execution, 7), 93 * DAY);
+        assert_eq!(calculate_notification_time(next_execution, 1), 99 * DAY);
+        assert_eq!(calculate_notification_time(3 * DAY, 7), 0);
+    }
+
+    #[test]
+    fn test_upgrade_restores_every_timer() {
+        const SECOND: u64 = 1_000_000_000;
+        let mut next_exec: HashMap<SubscriptionId, (u64, u64)> = HashMap::new();
+        next_exec.insert("sub_due_later".to_string(), (100 * SECOND, 50 * SECOND));
+        next_exec.insert("sub_overdue".to_string(), (5 * SECOND, 0));
+        next_exec.insert("sub_no_notification".to_string(), (200 * SECOND, 0));
+
+        // Simulate pre_upgrade save and post_upgrade restore through candid
+        let saved = candid::encode_one(&next_exec).unwrap();
+        let loaded: HashMap<SubscriptionId, (u64, u64)> = candid::decode_one(&saved).unwrap();
+
+        let now = 10 * SECOND;
+        let restored = plan_restored_timers(&loaded, now);
+
+        assert_eq!(restored.len(), 4);
+        for (subscription_id, (payment_exec, _)) in &next_exec {
+            let payment = restored
+                .iter()
+                .find(|t| &t.subscription_id == subscription_id && !t.is_notification)
+                .expect("payment timer dropped on upgrade");
+            assert_eq!(payment.delay_nanos, payment_exec.saturating_sub(now));
+        }
+
+        let overdue = restored.iter().find(|t| t.subscription_id == "sub_overdue").unwrap();
+        assert_eq!(overdue.delay_nanos, 0);
+
+        let notification = restored
+            .iter()
+            .find(|t| t.subscription_id == "sub_due_later" && t.is_notification)
+            .unwrap();
+        assert_eq!(notification.delay_nanos, 40 * SECOND);
+    }
+}
@@ -0,0 +1,240 @@
+// Solana legacy transaction wire format
+//
+// Layout: compact-u16 signature count, 64-byte signatures, then the message:
+// header (num_required_signatures, num_readonly_signed, num_readonly_unsigned),
+// compact-u16 account key count + 32-byte keys, 32-byte recent blockhash,
+// compact-u16 instruction count + per instruction
+// (program_id_index, compact-u16 account indices, compact-u16 data)
+
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug)]
+pub struct WireAccountMeta {
+    pub pubkey: [u8; 32],
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct WireInstruction {
+    pub program_id: [u8; 32],
+    pub accounts: Vec<WireAccountMeta>,
+    pub data: Vec<u8>,
+}
+
+/// Decode a base58 address or hash into its 32 raw bytes
+pub fn decode_base58_32(value: &str) -> Result<[u8; 32], String> {
+    let bytes = bs58::decode(value)
+        .into_vec()
+        .map_err(|e| format!("Invalid base58 value {}: {}", value, e))?;
+    bytes.as_slice().try_into().map_err(|_| format!("{} is not 32 bytes", value))
+}
+
+/// Solana's "shortvec" length prefix: 7 bits per byte, high bit set while more bytes follow
+pub fn encode_compact_u16(value: u16, out: &mut Vec<u8>) {
+    let mut remaining = value;
+    loop {
+        let mut byte = (remaining & 0x7f) as u8;
+        remaining >>= 7;
+        if remaining == 0 {
+            out.push(byte);
+            return;
+        }
+        byte |= 0x80;
+        out.push(byte);
+    }
+}
+
+fn encode_len(len: usize, out: &mut Vec<u8>) -> Result<(), String> {
+    let len = u16::try_from(len).map_err(|_| format!("Length {} exceeds compact-u16 range", len))?;
+    encode_compact_u16(len, out);
+    Ok(())
+}
+
+/// Compile and serialize a legacy message
+/// Account keys are ordered like the Solana SDK: fee payer, writable signers, readonly signers,
+/// writable non-signers, readonly non-signers (each group sorted by key bytes)
+pub fn compile_message(
+    fee_payer: [u8; 32],
+    instructions: &[WireInstruction],
+    recent_blockhash: [u8; 32],
+) -> Result<Vec<u8>, String> {
+    // (is_signer, is_writable) per key
+    let mut key_meta: BTreeMap<[u8; 32], (bool, bool)> = BTreeMap::new();
+    for ix in instructions {
+        key_meta.entry(ix.program_id).or_default();
+        for account in &ix.accounts {
+            let meta = key_meta.entry(account.pubkey).or_default();
+            meta.0 |= account.is_signer;
+            meta.1 |= account.is_writable;
+        }
+    }
+    key_meta.remove(&fee_payer);
+
+    let group = |signer: bool, writable: bool| -> Vec<[u8; 32]> {
+        key_meta
+            .iter()
+            .filter(|(_, meta)| **meta == (signer, writable))
+            .map(|(key, _)| *key)
+            .collect()
+    };
+    let writable_signers = group(true, true);
+    let readonly_signers = group(true, false);
+    let writable_non_signers = group(false, true);
+    let readonly_non_signers = group(false, false);
+
+    let mut account_keys = vec![fee_payer];
+    account_keys.extend(writable_signers.iter());
+    account_keys.extend(readonly_signers.iter());
+    account_keys.extend(writable_non_signers.iter());
+    account_keys.extend(readonly_non_signers.iter());
+
+    let to_u8 = |n: usize| u8::try_from(n).map_err(|_| "Too many accounts for a legacy message".to_string());
+    let index_of = |key: &[u8; 32]| -> Result<u8, String> {
+        to_u8(account_keys.iter().position(|k| k == key).ok_or("Account key missing from message")?)
+    };
+
+    let mut message = Vec::new();
+    message.push(to_u8(1 + writable_signers.len() + readonly_signers.len())?);
+    message.push(to_u8(readonly_signers.len())?);
+    message.push(to_u8(readonly_non_signers.len())?);
+
+    encode_len(account_keys.len(), &mut message)?;
+    for key in &account_keys {
+        message.extend_from_slice(key);
+    }
+
+    message.extend_from_slice(&recent_blockhash);
+
+    encode_len(instructions.len(), &mut message)?;
+    for ix in instructions {
+        message.push(index_of(&ix.program_id)?);
+        encode_len(ix.accounts.len(), &mut message)?;
+        for account in &ix.accounts {
+            message.push(index_of(&account.pubkey)?);
+        }
+        encode_len(ix.data.len(), &mut message)?;
+        message.extend_from_slice(&ix.data);
+    }
+
+    Ok(message)
+}
+
+/// Prefix a compiled message with its signatures. The signature slot count must match the
+/// message header; pass zeroed signatures for a transaction that is still to be signed.
+pub fn serialize_transaction(signatures: &[[u8; 64]], message: &[u8]) -> Result<Vec<u8>, String> {
+    let required = *message.first().ok_or("Empty message")? as usize;
+    if signatures.len() != required {
+        return Err(format!("Message requires {} signatures, got {}", required, signatures.len()));
+    }
+
+    let mut transaction = Vec::with_capacity(3 + signatures.len() * 64 + message.len());
+    encode_len(signatures.len(), &mut transaction)?;
+    for signature in signatures {
+        transaction.extend_from_slice(signature);
+    }
+    transaction.extend_from_slice(message);
+    Ok(transaction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_instruction::{AccountMeta, Instruction};
+    use solana_message::Message;
+    use solana_pubkey::Pubkey;
+    use solana_transaction::Transaction;
+
+    #[test]
+    fn test_encode_compact_u16() {
+        let encode = |value: u16| {
+            let mut out = Vec::new();
+            encode_compact_u16(value, &mut out);
+            out
+        };
+        assert_eq!(encode(0), vec![0x00]);
+        assert_eq!(encode(0x7f), vec![0x7f]);
+        assert_eq!(encode(0x80), vec![0x80, 0x01]);
+        assert_eq!(encode(0x3fff), vec![0xff, 0x7f]);
+        assert_eq!(encode(0x4000), vec![0x80, 0x80, 0x01]);
+        assert_eq!(encode(u16::MAX), vec![0xff, 0xff, 0x03]);
+    }
+
+    #[test]
+    fn test_single_transfer_bytes() {
+        // System transfer of 1 lamport from [1; 32] to [2; 32]
+        let payer = [1u8; 32];
+        let recipient = [2u8; 32];
+        let system_program = [0u8; 32];
+        let blockhash = [9u8; 32];
+        let mut data = vec![2, 0, 0, 0];
+        data.extend_from_slice(&1u64.to_le_bytes());
+
+        let ix = WireInstruction {
+            program_id: system_program,
+            accounts: vec![
+                WireAccountMeta { pubkey: payer, is_signer: true, is_writable: true },
+                WireAccountMeta { pubkey: recipient, is_signer: false, is_writable: true },
+            ],
+            data: data.clone(),
+        };
+        let message = compile_message(payer, &[ix], blockhash).unwrap();
+        let transaction = serialize_transaction(&[[0u8; 64]], &message).unwrap();
+
+        let mut expected = vec![1];
+        expected.extend_from_slice(&[0u8; 64]);
+        expected.extend_from_slice(&[1, 0, 1]); // header
+        expected.push(3);
+        expected.extend_from_slice(&payer);
+        expected.extend_from_slice(&recipient);
+        expected.extend_from_slice(&system_program);
+        expected.extend_from_slice(&blockhash);
+        expected.extend_from_slice(&[1, 2, 2, 0, 1, 12]);
+        expected.extend_from_slice(&data);
+
+        assert_eq!(transaction, expected);
+    }
+
+    #[test]
+    fn test_matches_solana_sdk_serialization() {
+        let payer = Pubkey::new_from_array([7u8; 32]);
+        let program = Pubkey::new_from_array([200u8; 32]);
+        let readonly_signer = Pubkey::new_from_array([3u8; 32]);
+        let writable = [Pubkey::new_from_array([50u8; 32]), Pubkey::new_from_array([40u8; 32])];
+        let readonly = Pubkey::new_from_array([100u8; 32]);
+        let blockhash = solana_hash::Hash::new_from_array([42u8; 32]);
+        let data: Vec<u8> = (0..150u8).collect(); // Needs a two-byte length prefix
+
+        let sdk_ix = Instruction {
+            program_id: program,
+            accounts: vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(writable[0], false),
+                AccountMeta::new_readonly(readonly, false),
+                AccountMeta::new_readonly(readonly_signer, true),
+                AccountMeta::new(writable[1], false),
+            ],
+            data: data.clone(),
+        };
+        let sdk_message = Message::new_with_blockhash(std::slice::from_ref(&sdk_ix), Some(&payer), &blockhash);
+        let sdk_bytes = bincode::serialize(&Transaction::new_unsigned(sdk_message)).unwrap();
+
+        let wire_ix = WireInstruction {
+            program_id: program.to_bytes(),
+            accounts: sdk_ix
+                .accounts
+                .iter()
+                .map(|meta| WireAccountMeta {
+                    pubkey: meta.pubkey.to_bytes(),
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data,
+        };
+        let message = compile_message(payer.to_bytes(), &[wire_ix], blockhash.to_bytes()).unwrap();
+        let transaction = serialize_transaction(&[[0u8; 64]; 2], &message).unwrap();
+
+        assert_eq!(transaction, sdk_bytes);
+    }
+}
@@ -1,7 +1,9 @@
 // Types module for Ouro-C Timer Canister
 
 use candid::{CandidType, Deserialize};
+use ic_stable_structures::storable::{Bound, Storable};
 use serde::{Serialize as SerdeSerialize};
+use std::borrow::Cow;
 
 pub type SubscriptionId = String;
 pub type SolanaAddress = String;
@@ -13,13 +15,83 @@ pub const MAX_AMOUNT_USDC: u64 = 1_000_000_000_000; // 1M USDC (6 decimals)
 pub const MIN_INTERVAL_SECONDS: u64 = 10; // 10 seconds minimum (for demo purposes)
 pub const MAX_INTERVAL_SECONDS: u64 = 31536000; // 1 year maximum
 pub const MAX_TOTAL_SUBSCRIPTIONS: usize = 10000;
+pub const MAX_PAGE_SIZE: usize = 100;
+pub const MAX_BATCH_CREATE_SIZE: usize = 100;
+pub const MAX_BULK_CANCEL_SIZE: usize = 200; // Cancellations (each with a Solana notification) per bulk_cancel_by_merchant call
+pub const MAX_STATE_LOG_ENTRIES: usize = 10_000;
+pub const MAX_TRACES: usize = 200; // Most recent trigger traces kept for get_trace_log
+pub const MAX_TRACE_LINES: usize = 50;
+pub const MAX_DEAD_LETTER_ENTRIES: usize = 1000;
+pub const MAX_WEBHOOK_DELIVERY_LOG: usize = 1000;
+pub const WEBHOOK_RETRY_DELAYS_SECONDS: [u64; 3] = [5, 30, 120];
+pub const EVENT_INDEX_INTERVAL_SECONDS: u64 = 86400; // Re-index Solana payment events daily
+pub const MAX_RPC_ENDPOINTS: usize = 5;
+pub const HEALTHY_RPC_CACHE_NANOS: u64 = 30 * 1_000_000_000; // Re-check endpoint health every 30 seconds
+pub const STATS_CACHE_NANOS: u64 = 60 * 1_000_000_000;
+pub const METRICS_SNAPSHOT_INTERVAL_SECONDS: u64 = 86400; // Daily metrics snapshot for get_metrics_history
+pub const MAX_METRICS_HISTORY: usize = 365; // One year of daily snapshots
+pub const MAX_FORECAST_DAYS: u32 = 365;
+pub const MAX_UPCOMING_PAYMENTS: usize = 50;
 pub const SUBSCRIPTION_ID_MAX_LENGTH: usize = 64;
 pub const SUBSCRIPTION_ID_MIN_LENGTH: usize = 4;
+pub const DEFAULT_REMINDER_DAYS: u32 = 1; // Notify 24 hours before payment unless the merchant configured otherwise
+pub const MAX_REMINDER_DAYS: u32 = 30; // Matches the Solana contract's MAX_REMINDER_DAYS
+pub const MAX_SNOOZE_PERIODS: u8 = 12; // Matches the Solana contract's MAX_SNOOZE_PERIODS
+pub const USDC_MINT_ADDRESS: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
 
 // Failure handling constants
 pub const MAX_CONSECUTIVE_FAILURES: u32 = 10;
 pub const EXPONENTIAL_BACKOFF_BASE: u64 = 2;
 pub const MAX_BACKOFF_MULTIPLIER: u64 = 16;
+pub const IMMEDIATE_RETRY_DELAY_NANOS: u64 = 5 * 1_000_000_000; // 5 seconds
+pub const INSUFFICIENT_TOKEN_BALANCE_ERROR: &str = "Insufficient token balance"; // Prefix of swap-payment balance failures
+pub const MERCHANT_OFFBOARDED_MEMO: &str = "Subscription cancelled: the merchant has closed their account";
+pub const RENEW_APPROVAL_MEMO: &str = "Action required: renew your subscription approval"; // Sent when the one-year delegation runs out
+
+// Transaction confirmation constants
+pub const DEFAULT_CONFIRMATION_TIMEOUT_SECONDS: u64 = 30;
+pub const MAX_CONFIRMATION_TIMEOUT_NANOS: u64 = 30 * 1_000_000_000; // Stay well inside the IC's call limits
+pub const CONFIRMATION_POLL_INTERVAL_NANOS: u64 = 2 * 1_000_000_000;
+pub const CONFIRMATION_COMMITMENT: &str = "confirmed";
+
+// Swap payment simulation constants
+pub const JUPITER_QUOTE_API_URL: &str = "https://quote-api.jup.ag/v6/quote";
+pub const DEFAULT_SWAP_SLIPPAGE_BPS: u16 = 100; // Matches the Solana contract's DEFAULT_SLIPPAGE_BPS
+pub const PLATFORM_FEE_BPS: u64 = 200; // Fee the Solana contract's initialize sets
+
+// Compute budget constants
+pub const DEFAULT_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000; // Solana's per-transaction maximum
+pub const DEFAULT_COMPUTE_UNIT_PRICE_MICROLAMPORTS: u64 = 1_000;
+
+// Timer scheduling constants
+pub const MAX_JITTER_NANOS: u64 = 60_000_000_000; // Spread trigger firing over up to 60 seconds
+pub const DEFAULT_HEARTBEAT_INTERVAL_SECONDS: u64 = 3600; // 1 hour
+pub const HEARTBEAT_OPCODE: u8 = 255;
+pub const ALERT_COOLDOWN_NANOS: u64 = 60 * 60 * 1_000_000_000; // At most one webhook alert per hour
+pub const CLEANUP_INTERVAL_SECONDS: u64 = 24 * 60 * 60; // Daily cleanup of cancelled/expired subscriptions
+pub const DEFAULT_CLEANUP_AGE_SECONDS: u64 = 30 * 24 * 60 * 60; // Remove them 30 days past their last execution
+
+// Solana program error codes (Anchor custom errors start at 6000)
+pub const SOLANA_ERROR_SUBSCRIPTION_EXPIRED: u32 = 6044;
+pub const SOLANA_ERROR_INVALID_SIGNATURE: u32 = 6008;
+pub const SOLANA_ERROR_SIGNATURE_EXPIRED: u32 = 6009;
+pub const SOLANA_ERROR_TIMESTAMP_EXPIRED: u32 = 6010;
+pub const SOLANA_ERROR_UNAUTHORIZED_CANISTER: u32 = 6011;
+pub const SOLANA_ERROR_UNAUTHORIZED_ACCESS: u32 = 6012;
+pub const SOLANA_ERROR_AUTHORIZATION_FAILED: u32 = 6015;
+pub const SOLANA_ERROR_DELEGATE_NOT_SET: u32 = 6020;
+pub const SOLANA_ERROR_INSUFFICIENT_DELEGATION: u32 = 6021;
+pub const SOLANA_ERROR_SIGNATURE_REPLAYED: u32 = 6066;
+pub const SOLANA_ERROR_SUBSCRIBER_NOT_APPROVED: u32 = 6069;
+
+/// How a failed payment trigger should be retried
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ErrorCategory {
+    ImmediateRetry,     // Stale signature/timestamp - retry at once with a fresh signature
+    ExponentialBackoff, // Transient or resource errors - back off
+    PermanentFailure,   // Authorization revoked or rejected - pause until someone intervenes
+}
 
 // License tiers for IP protection
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, SerdeSerialize)]
@@ -35,6 +107,7 @@ pub enum SubscriptionStatus {
     Paused,
     Cancelled,
     Expired,
+    Archived,
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, SerdeSerialize)]
@@ -61,6 +134,114 @@ pub struct Subscription {
     pub failed_payment_count: u32,
     pub last_failure_time: Option<Timestamp>,
     pub last_error: Option<String>,
+    pub cumulative_drift_nanos: i64, // Sum of trigger lateness (negative = early), for diagnostics
+    pub reminder_days_before_payment: u32, // Mirrors the Solana subscription account
+    pub snoozed_until: Option<Timestamp>, // Billing skipped until this time by snooze_subscription
+    pub last_triggered_tx: Option<TransactionHash>, // Signature of the last confirmed payment trigger
+    pub success_payment_count: Option<u32>, // Successful triggers; None for subscriptions stored before it was tracked
+    pub last_trace_id: Option<String>, // Trace ID of the latest trigger, for get_trace_log
+    pub api_key_hash: Option<String>, // SHA-256 of the API key it was created with; None for older subscriptions
+    pub webhook_url: Option<String>, // Payment events are POSTed here; the signing secret is kept out of this struct
+}
+
+impl Subscription {
+    /// Successful triggers as a percentage of successes plus current failures; 100 before any attempt
+    pub fn payment_success_rate(&self) -> f64 {
+        let successes = self.success_payment_count.unwrap_or(0) as f64;
+        let attempts = successes + self.failed_payment_count as f64;
+        if attempts == 0.0 {
+            return 100.0;
+        }
+        successes * 100.0 / attempts
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SubscriptionWithStats {
+    pub subscription: Subscription,
+    pub payment_success_rate: f64,
+}
+
+// Stored Candid-encoded in the stable subscription map. New fields must be Option<_>
+// so subscriptions written by an older version still decode.
+impl Storable for Subscription {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(candid::encode_one(self).expect("Failed to encode subscription"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("Failed to decode subscription")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PaginatedSubscriptions {
+    pub items: Vec<Subscription>,
+    pub total: u64,
+    pub has_more: bool,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct SubscriptionFilter {
+    pub status: Option<SubscriptionStatus>,
+    pub merchant_address: Option<String>,
+    pub subscriber_address: Option<String>,
+    pub created_after: Option<Timestamp>,
+    pub amount_min: Option<u64>,
+    pub amount_max: Option<u64>,
+    pub limit: Option<u32>, // Defaults to (and is capped at) MAX_PAGE_SIZE
+}
+
+impl SubscriptionFilter {
+    pub fn matches(&self, subscription: &Subscription) -> bool {
+        self.status.as_ref().is_none_or(|status| &subscription.status == status)
+            && self.merchant_address.as_ref().is_none_or(|addr| &subscription.merchant_address == addr)
+            && self.subscriber_address.as_ref().is_none_or(|addr| &subscription.subscriber_address == addr)
+            && self.created_after.is_none_or(|after| subscription.created_at > after)
+            && self.amount_min.is_none_or(|min| subscription.amount >= min)
+            && self.amount_max.is_none_or(|max| subscription.amount <= max)
+    }
+}
+
+/// One entry in the state change audit log
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct StateEvent {
+    pub timestamp: Timestamp,
+    pub actor: candid::Principal,
+    pub action: String,
+    pub subscription_id: Option<SubscriptionId>,
+    pub detail: String,
+}
+
+/// A subscription that was auto-paused because its payments kept failing
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct FailedPaymentRecord {
+    pub subscription_id: SubscriptionId,
+    pub last_attempt: Timestamp,
+    pub amount: u64,
+    pub error_reason: String,
+    pub retry_count: u32,
+}
+
+/// Audit entry for a right-to-erasure deletion; holds only a hash of the subscriber address
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct DeletionRecord {
+    pub data_hash: String, // Hex SHA-256 of the subscriber address
+    pub deleted_at: Timestamp,
+}
+
+impl Storable for DeletionRecord {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(candid::encode_one(self).expect("Failed to encode deletion record"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("Failed to decode deletion record")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -74,6 +255,8 @@ pub struct CreateSubscriptionRequest {
     pub interval_seconds: u64,
     pub start_time: Option<Timestamp>,
     pub api_key: String,
+    pub trial_end_time: Option<Timestamp>, // Free trial end - first execution happens here
+    pub reminder_days_before_payment: Option<u32>, // Same value the Solana subscription was created with
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -92,6 +275,9 @@ pub struct FeeConfig {
     pub trigger_fee_lamports: u64,
     pub gas_reserve_lamports: u64,
     pub cycle_refill_ratio: f64,
+    pub confirmation_timeout_seconds: u64, // How long a trigger waits for its transaction to confirm
+    pub compute_unit_price_microlamports: u64, // Priority fee per compute unit
+    pub compute_unit_limit: u32,
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -130,6 +316,38 @@ pub struct LicenseValidationResult {
     pub rate_limit_remaining: usize,
     pub expires_at: Timestamp,
     pub message: String,
+    pub subscription_quota_used: u32, // Live subscriptions created with this API key
+    pub subscription_quota_limit: Option<u32>, // None = unlimited
+}
+
+/// JSON body POSTed to a subscription's webhook after each successful payment
+#[derive(CandidType, Deserialize, SerdeSerialize, Clone, Debug)]
+pub struct PaymentWebhookEvent {
+    pub subscription_id: SubscriptionId,
+    pub payment_number: u64,
+    pub amount: u64,
+    pub fee: u64, // Trigger fee in lamports
+    pub timestamp: Timestamp,
+    pub tx_hash: TransactionHash,
+}
+
+/// Summary of one index_program_events run
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct IndexResult {
+    pub events_processed: u32,
+    pub subscriptions_updated: u32,
+    pub errors: Vec<String>,
+}
+
+/// Outcome of delivering one PaymentWebhookEvent, including retries
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct WebhookDelivery {
+    pub subscription_id: SubscriptionId,
+    pub payment_number: u64,
+    pub attempts: u32,
+    pub delivered: bool,
+    pub last_error: Option<String>,
+    pub timestamp: Timestamp, // Time of the last attempt
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -142,6 +360,129 @@ pub struct CycleReport {
     pub total_refilled: u64,
 }
 
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HeartbeatStatus {
+    pub last_ok: Timestamp,
+    pub consecutive_failures: u32,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HeartbeatState {
+    pub contract_address: Option<SolanaAddress>,
+    pub heartbeat_interval_seconds: u64,
+    pub last_heartbeat_at: Timestamp,
+    pub heartbeat_success_count: u64,
+    pub consecutive_failures: u32,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SubscriptionStats {
+    pub total_active: u64,
+    pub total_paused: u64,
+    pub total_cancelled: u64,
+    pub total_expired: u64,
+    pub total_revenue_usdc: u128, // Sum of amount * successful triggers, in micro-units
+    pub avg_payment_amount: u64,
+    pub total_failed_payments: u64,
+    pub oldest_subscription_created_at: Option<Timestamp>,
+    pub payment_success_rate_pct: f64, // Successful triggers over all attempts; 0 before any attempt
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct SwapSimulation {
+    pub input_token: String, // Mint the subscriber pays in
+    pub input_amount: u64,
+    pub expected_usdc_output: u64, // Jupiter's quoted output, micro-USDC
+    pub expected_merchant_amount: u64,
+    pub expected_fee: u64, // PLATFORM_FEE_BPS of the output; the contract's fee tiers and min/max can change it
+    pub effective_slippage_bps: u16, // Quoted price impact
+    pub will_succeed: bool,
+    pub failure_reason: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RevenueForecast {
+    pub projected_payments: u32,
+    pub projected_usdc: u64, // Micro-units
+    pub at_risk_subscriptions: Vec<SubscriptionId>, // Active but with failed payments since the last success
+    pub confidence_pct: f64, // Share of projected_usdc not coming from at-risk subscriptions; 100 when nothing is projected
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct VerificationResult {
+    pub confirmed: bool,
+    pub payment_number: u64,
+    pub amount: u64, // Micro-units
+    pub timestamp: i64, // Unix seconds, from the PaymentProcessed event
+    pub error: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BulkCancelResult {
+    pub cancelled: u32,
+    pub notification_failed: Vec<SubscriptionId>, // Cancelled, but the subscriber wasn't notified on Solana
+    pub errors: Vec<(SubscriptionId, String)>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct UpcomingPayment {
+    pub subscription_id: SubscriptionId,
+    pub merchant_address: SolanaAddress,
+    pub amount_usdc: u64, // Micro-units
+    pub payment_token_mint: String,
+    pub scheduled_at: Timestamp,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct DailyMetricsSnapshot {
+    pub date_days_since_epoch: u32,
+    pub active_count: u64,
+    pub total_volume_usdc: u128, // Cumulative, same basis as SubscriptionStats::total_revenue_usdc
+    pub new_subscriptions: u32, // Counts since the previous snapshot
+    pub cancelled: u32,
+    pub failed_payments: u32,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct AlertThresholds {
+    pub min_cycle_balance_tl: u64, // Trillions of cycles
+    pub max_failure_rate_pct: f64,
+}
+
+impl Default for AlertThresholds {
+    fn default() -> Self {
+        AlertThresholds {
+            min_cycle_balance_tl: 1,
+            max_failure_rate_pct: 20.0,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct AlertConfig {
+    pub alert_webhook_url: Option<String>,
+    pub alert_thresholds: AlertThresholds,
+    pub webhook_signing_secret: Option<String>, // Never returned by queries
+    pub last_alert_time: Option<Timestamp>,
+}
+
+/// JSON body POSTed to the alert webhook
+#[derive(CandidType, Deserialize, SerdeSerialize, Clone, Debug)]
+pub struct HealthAlert {
+    pub canister_id: String,
+    pub reasons: Vec<String>,
+    pub cycle_balance: u64,
+    pub failure_rate_pct: f64,
+    pub timestamp: Timestamp,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CleanupSchedule {
+    pub cleanup_age_seconds: u64,
+    pub last_cleanup_time: Timestamp,
+    pub next_cleanup_time: Timestamp,
+}
+
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct WalletBalance {
     pub lamports: u64,
@@ -172,6 +513,4 @@ pub struct SystemMetrics {
 pub struct TimerInfo {
     pub subscription_id: SubscriptionId,
     pub timer_id: ic_cdk_timers::TimerId,
-    pub execution_time: Timestamp,
-    pub is_notification: bool,
 }
\ No newline at end of file
@@ -56,6 +56,31 @@ pub fn calculate_next_execution(
     }
 }
 
+/// Schedule the next execution from the actual trigger time, subtracting the observed drift
+/// (clamped to ±10% of the interval) so repeated late or early firings don't accumulate.
+/// Returns the next execution and the raw drift in nanoseconds (positive = fired late).
+pub fn calculate_drift_corrected_next_execution(
+    scheduled_execution: Timestamp,
+    actual_trigger_time: Timestamp,
+    interval_seconds: u64,
+) -> (Timestamp, i64) {
+    let interval_nanos = (interval_seconds * 1_000_000_000) as i64;
+    let drift_nanos = actual_trigger_time as i64 - scheduled_execution as i64;
+    let correction = drift_nanos.clamp(-interval_nanos / 10, interval_nanos / 10);
+    let next_execution = (actual_trigger_time as i64 + interval_nanos - correction).max(0) as Timestamp;
+    (next_execution, drift_nanos)
+}
+
+/// Zero-based page of `items`; an out-of-range page is empty
+pub fn paginate<T: Clone>(items: &[T], page: usize, page_size: usize) -> Vec<T> {
+    items
+        .iter()
+        .skip(page.saturating_mul(page_size))
+        .take(page_size)
+        .cloned()
+        .collect()
+}
+
 pub fn sanitize_string(input: &str, max_length: usize) -> String {
     let mut sanitized = String::new();
     let mut length = 0;
@@ -183,6 +208,7 @@ pub fn format_subscription_status(status: &SubscriptionStatus) -> &'static str {
         SubscriptionStatus::Paused => "Paused",
         SubscriptionStatus::Cancelled => "Cancelled",
         SubscriptionStatus::Expired => "Expired",
+        SubscriptionStatus::Archived => "Archived",
     }
 }
 
@@ -225,10 +251,67 @@ pub fn calculate_uptime_seconds(start_time: Timestamp) -> u64 {
     }
 }
 
+/// Check whether a Solana RPC error carries the given Anchor custom error code
+/// Matches both the decimal code and the "custom program error: 0x..." form
+pub fn is_solana_program_error(error: &str, code: u32) -> bool {
+    error.contains(&format!("Error Number: {}", code))
+        || error.contains(&format!("\"Custom\":{}", code))
+        || error.to_lowercase().contains(&format!("custom program error: {:#x}", code))
+}
+
+/// Classify a trigger error (Solana program error codes, falling back to backoff for anything unrecognised)
+pub fn classify_trigger_error(error: &str) -> ErrorCategory {
+    const IMMEDIATE_RETRY_CODES: [u32; 3] = [
+        SOLANA_ERROR_SIGNATURE_EXPIRED,
+        SOLANA_ERROR_TIMESTAMP_EXPIRED,
+        SOLANA_ERROR_SIGNATURE_REPLAYED,
+    ];
+    const PERMANENT_FAILURE_CODES: [u32; 7] = [
+        SOLANA_ERROR_INVALID_SIGNATURE,
+        SOLANA_ERROR_UNAUTHORIZED_CANISTER,
+        SOLANA_ERROR_UNAUTHORIZED_ACCESS,
+        SOLANA_ERROR_AUTHORIZATION_FAILED,
+        SOLANA_ERROR_DELEGATE_NOT_SET,
+        SOLANA_ERROR_INSUFFICIENT_DELEGATION, // Subscriber must renew the approval
+        SOLANA_ERROR_SUBSCRIBER_NOT_APPROVED,
+    ];
+
+    if IMMEDIATE_RETRY_CODES.iter().any(|code| is_solana_program_error(error, *code)) {
+        ErrorCategory::ImmediateRetry
+    } else if error.starts_with(INSUFFICIENT_TOKEN_BALANCE_ERROR) {
+        // A swap payment can't succeed until the subscriber tops up - don't burn retries on it
+        ErrorCategory::PermanentFailure
+    } else if PERMANENT_FAILURE_CODES.iter().any(|code| is_solana_program_error(error, *code)) {
+        ErrorCategory::PermanentFailure
+    } else {
+        ErrorCategory::ExponentialBackoff
+    }
+}
+
+/// First 16 bytes of the SHA-256 of a trace ID, appended to Solana instruction data so a
+/// transaction can be matched back to the canister trace that sent it
+pub fn trace_tag(trace_id: &str) -> [u8; 16] {
+    use sha2::{Digest, Sha256};
+    let hash = Sha256::digest(trace_id.as_bytes());
+    let mut tag = [0u8; 16];
+    tag.copy_from_slice(&hash[..16]);
+    tag
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_insufficient_token_balance_is_permanent() {
+        let error = format!("{} for mint in account", INSUFFICIENT_TOKEN_BALANCE_ERROR);
+        assert_eq!(classify_trigger_error(&error), ErrorCategory::PermanentFailure);
+        assert_eq!(
+            classify_trigger_error("Insufficient subscriber balance: 1 available, 2 required"),
+            ErrorCategory::ExponentialBackoff
+        );
+    }
+
     #[test]
     fn test_valid_subscription_id() {
         assert!(is_valid_subscription_id("valid-id-123"));
@@ -259,4 +342,69 @@ mod tests {
         assert!(validate_interval(MIN_INTERVAL_SECONDS - 1).is_err());
         assert!(validate_interval(MAX_INTERVAL_SECONDS + 1).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_solana_program_error_detection() {
+        let code = SOLANA_ERROR_SUBSCRIPTION_EXPIRED;
+        assert!(is_solana_program_error("custom program error: 0x179c", code));
+        assert!(is_solana_program_error("{\"InstructionError\":[0,{\"Custom\":6044}]}", code));
+        assert!(is_solana_program_error("Error Code: SubscriptionExpired. Error Number: 6044.", code));
+        assert!(!is_solana_program_error("custom program error: 0x1770", code));
+    }
+
+    #[test]
+    fn test_drift_corrected_next_execution() {
+        const SECOND: u64 = 1_000_000_000;
+        let interval_seconds = 1000;
+        let scheduled = 10_000 * SECOND;
+
+        // Fired 5s late: next execution stays on the nominal schedule
+        let (next, drift) = calculate_drift_corrected_next_execution(scheduled, scheduled + 5 * SECOND, interval_seconds);
+        assert_eq!(drift, 5 * SECOND as i64);
+        assert_eq!(next, scheduled + interval_seconds * SECOND);
+
+        // Fired 3s early
+        let (next, drift) = calculate_drift_corrected_next_execution(scheduled, scheduled - 3 * SECOND, interval_seconds);
+        assert_eq!(drift, -3 * SECOND as i64);
+        assert_eq!(next, scheduled + interval_seconds * SECOND);
+
+        // Fired 500s late: correction is capped at 10% of the interval
+        let (next, drift) = calculate_drift_corrected_next_execution(scheduled, scheduled + 500 * SECOND, interval_seconds);
+        assert_eq!(drift, 500 * SECOND as i64);
+        assert_eq!(next, scheduled + 500 * SECOND + 900 * SECOND);
+    }
+
+    #[test]
+    fn test_paginate() {
+        let items: Vec<u32> = (0..25).collect();
+        assert_eq!(paginate(&items, 0, 10), (0..10).collect::<Vec<_>>());
+        assert_eq!(paginate(&items, 2, 10), (20..25).collect::<Vec<_>>());
+        assert!(paginate(&items, 3, 10).is_empty());
+        assert!(paginate(&items, usize::MAX, 10).is_empty());
+    }
+
+    #[test]
+    fn test_classify_trigger_error() {
+        assert_eq!(classify_trigger_error("custom program error: 0x1779"), ErrorCategory::ImmediateRetry); // 6009
+        assert_eq!(classify_trigger_error("{\"InstructionError\":[2,{\"Custom\":6010}]}"), ErrorCategory::ImmediateRetry);
+        assert_eq!(classify_trigger_error("Error Code: SignatureReplayed. Error Number: 6066."), ErrorCategory::ImmediateRetry);
+
+        assert_eq!(classify_trigger_error("custom program error: 0x177b"), ErrorCategory::PermanentFailure); // 6011
+        assert_eq!(classify_trigger_error("Error Number: 6012."), ErrorCategory::PermanentFailure);
+        assert_eq!(classify_trigger_error("{\"Custom\":6020}"), ErrorCategory::PermanentFailure);
+        assert_eq!(classify_trigger_error("custom program error: 0x1785"), ErrorCategory::PermanentFailure); // 6021
+
+        assert_eq!(classify_trigger_error("HTTP outcall failed: timeout"), ErrorCategory::ExponentialBackoff);
+        assert_eq!(classify_trigger_error("Insufficient subscriber balance: 1 available, 2 required"), ErrorCategory::ExponentialBackoff);
+        assert_eq!(classify_trigger_error("Error Number: 6007."), ErrorCategory::ExponentialBackoff);
+    }
+
+    #[test]
+    fn test_trace_tag() {
+        let tag = trace_tag("sub_12345-1700000000000000000");
+        assert_eq!(tag, trace_tag("sub_12345-1700000000000000000"));
+        assert_ne!(tag, trace_tag("sub_12345-1700000000000000001"));
+        // SHA-256("abc") starts ba7816bf 8f01cfea 414140de 5dae2223
+        assert_eq!(hex::encode(trace_tag("abc")), "ba7816bf8f01cfea414140de5dae2223");
+    }
+}
@@ -0,0 +1,176 @@
+/**
+ * Builder for the timer canister's create_subscription argument.
+ *
+ * Applies the same checks as validate_create_request in subscription_manager.rs, so a
+ * bad request fails in the browser instead of costing an update call. build() returns the
+ * branded type, which marks the request as validated.
+ */
+
+import type {
+  CreateSubscriptionRequest,
+  CreateSubscriptionRequestBrand,
+} from '../src/generated/ourc_canister';
+
+// Keep in sync with types.rs
+const SUBSCRIPTION_ID_MIN_LENGTH = 4;
+const SUBSCRIPTION_ID_MAX_LENGTH = 64;
+const MIN_INTERVAL_SECONDS = 10n;
+const MAX_INTERVAL_SECONDS = 31_536_000n;
+const MAX_AMOUNT_USDC = 1_000_000_000_000n;
+const DEFAULT_REMINDER_DAYS = 1;
+const MAX_REMINDER_DAYS = 30;
+
+const SUBSCRIPTION_ID_PATTERN = /^[A-Za-z0-9_-]+$/;
+const SOLANA_ADDRESS_PATTERN = /^[1-9A-HJ-NP-Za-km-z]{32,44}$/;
+
+export class CreateSubscriptionRequestBuilder {
+  private subscriptionId?: string;
+  private apiKey?: string;
+  private solanaContractAddress?: string;
+  private paymentTokenMint?: string;
+  private subscriberAddress?: string;
+  private merchantAddress?: string;
+  private amount?: bigint;
+  private intervalSeconds?: bigint;
+  private startTime?: bigint;
+  private trialEndTime?: bigint;
+  private reminderDaysBeforePayment: number = DEFAULT_REMINDER_DAYS;
+
+  withSubscriptionId(id: string): this {
+    this.subscriptionId = id;
+    return this;
+  }
+
+  withApiKey(apiKey: string): this {
+    this.apiKey = apiKey;
+    return this;
+  }
+
+  withSolanaContract(address: string): this {
+    this.solanaContractAddress = address;
+    return this;
+  }
+
+  withPaymentTokenMint(mint: string): this {
+    this.paymentTokenMint = mint;
+    return this;
+  }
+
+  withSubscriber(address: string): this {
+    this.subscriberAddress = address;
+    return this;
+  }
+
+  withMerchant(address: string): this {
+    this.merchantAddress = address;
+    return this;
+  }
+
+  /** Amount in the token's smallest unit (micro-USDC for USDC) */
+  withAmount(amount: bigint): this {
+    this.amount = amount;
+    return this;
+  }
+
+  withIntervalSeconds(seconds: bigint): this {
+    this.intervalSeconds = seconds;
+    return this;
+  }
+
+  /** First execution time in nanoseconds since the epoch */
+  withStartTime(nanos: bigint): this {
+    this.startTime = nanos;
+    return this;
+  }
+
+  /** Free trial end in nanoseconds since the epoch; the first payment happens then */
+  withTrialEnd(nanos: bigint): this {
+    this.trialEndTime = nanos;
+    return this;
+  }
+
+  withReminderDays(days: number): this {
+    this.reminderDaysBeforePayment = days;
+    return this;
+  }
+
+  /** Validation errors for the current fields; empty when build() would succeed */
+  validate(nowNanos: bigint = BigInt(Date.now()) * 1_000_000n): string[] {
+    const errors: string[] = [];
+
+    const id = this.subscriptionId ?? '';
+    if (id.length < SUBSCRIPTION_ID_MIN_LENGTH) {
+      errors.push(`Subscription ID too short (min ${SUBSCRIPTION_ID_MIN_LENGTH} chars)`);
+    } else if (id.length > SUBSCRIPTION_ID_MAX_LENGTH) {
+      errors.push(`Subscription ID too long (max ${SUBSCRIPTION_ID_MAX_LENGTH} chars)`);
+    } else if (!SUBSCRIPTION_ID_PATTERN.test(id)) {
+      errors.push('Subscription ID must be alphanumeric with - or _ only');
+    }
+
+    if (!this.apiKey) {
+      errors.push('API key is required');
+    }
+
+    if (this.intervalSeconds === undefined) {
+      errors.push('Interval is required');
+    } else if (this.intervalSeconds < MIN_INTERVAL_SECONDS) {
+      errors.push(`Minimum interval is ${MIN_INTERVAL_SECONDS} seconds`);
+    } else if (this.intervalSeconds > MAX_INTERVAL_SECONDS) {
+      errors.push(`Maximum interval is ${MAX_INTERVAL_SECONDS} seconds (1 year)`);
+    }
+
+    if (
+      !Number.isInteger(this.reminderDaysBeforePayment) ||
+      this.reminderDaysBeforePayment < 1 ||
+      this.reminderDaysBeforePayment > MAX_REMINDER_DAYS
+    ) {
+      errors.push(`Reminder days must be between 1 and ${MAX_REMINDER_DAYS}`);
+    }
+
+    if (this.amount === undefined || this.amount <= 0n) {
+      errors.push('Amount must be greater than 0');
+    } else if (this.amount > MAX_AMOUNT_USDC) {
+      errors.push('Amount exceeds maximum allowed (1M USDC)');
+    }
+
+    const addresses: [string, string | undefined][] = [
+      ['Solana contract address', this.solanaContractAddress],
+      ['payment token mint address', this.paymentTokenMint],
+      ['subscriber address', this.subscriberAddress],
+      ['merchant address', this.merchantAddress],
+    ];
+    for (const [label, address] of addresses) {
+      if (!address || !SOLANA_ADDRESS_PATTERN.test(address)) {
+        errors.push(`Invalid ${label} format`);
+      }
+    }
+
+    if (this.trialEndTime !== undefined && this.trialEndTime <= nowNanos) {
+      errors.push('Trial end time must be in the future');
+    }
+
+    return errors;
+  }
+
+  build(nowNanos?: bigint): CreateSubscriptionRequestBrand {
+    const errors = this.validate(nowNanos);
+    if (errors.length > 0) {
+      throw new Error(`Invalid subscription request: ${errors.join('; ')}`);
+    }
+
+    const request: CreateSubscriptionRequest = {
+      subscription_id: this.subscriptionId!,
+      api_key: this.apiKey!,
+      solana_contract_address: this.solanaContractAddress!,
+      payment_token_mint: this.paymentTokenMint!,
+      subscriber_address: this.subscriberAddress!,
+      merchant_address: this.merchantAddress!,
+      amount: this.amount!,
+      interval_seconds: this.intervalSeconds!,
+      start_time: this.startTime === undefined ? [] : [this.startTime],
+      trial_end_time: this.trialEndTime === undefined ? [] : [this.trialEndTime],
+      reminder_days_before_payment: [this.reminderDaysBeforePayment],
+    };
+    return request as CreateSubscriptionRequestBrand;
+  }
+}
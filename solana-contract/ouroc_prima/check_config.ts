@@ -36,7 +36,7 @@ async function checkConfig() {
         console.log('   Total Subscriptions:', (config as any).totalSubscriptions?.toString() || '0');
         console.log('   Paused:', (config as any).paused);
         console.log('   Authorization Mode:', (config as any).authorizationMode);
-        console.log('   ICP Public Key:', (config as any).icpPublicKey || 'Not set');
+        console.log('   ICP Public Keys:', (config as any).icpKeyCount ?? 0, 'active');
         console.log('   Manual Processing:', (config as any).manualProcessingEnabled);
         console.log('   Time-Based Processing:', (config as any).timeBasedProcessingEnabled);
         console.log('   ICP Fee Address:', (config as any).icpFeeCollectionAddress || 'Not set');
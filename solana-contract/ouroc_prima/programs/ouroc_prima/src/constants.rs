@@ -20,8 +20,88 @@ pub fn get_program_id() -> Pubkey {
 pub const BASIS_POINTS_DIVISOR: u64 = 10000; // 100% = 10000 basis points
 pub const MAX_FEE_BPS: u16 = 1000; // 10% maximum fee
 pub const MAX_SLIPPAGE_BPS: u16 = 500; // 5% maximum slippage
+pub const DEFAULT_SLIPPAGE_BPS: u16 = 100; // 1% swap tolerance until the subscriber changes it
+pub const SLIPPAGE_UPDATE_COOLDOWN_SECONDS: i64 = 24 * 60 * 60; // One slippage change per day (limits MEV manipulation)
 pub const MAX_APPROVAL_AMOUNT: u64 = 1_000_000_000_000; // 1M USDC (6 decimals)
 pub const MAX_REMINDER_DAYS: u32 = 30; // Maximum days before payment for reminder
+pub const MAX_SNOOZE_PERIODS: u8 = 12; // Billing cycles a subscriber can skip in one snooze
+pub const MAX_FEE_RECIPIENTS: usize = 4; // Maximum revenue share partners per platform fee
+pub const MAX_FEE_TIERS: usize = 4; // Volume discount tiers keyed on a subscription's total_paid
+
+// Notification rate limiting
+pub const DEFAULT_NOTIFICATION_COOLDOWN_SECONDS: i64 = 3600; // 1 hour between notifications
+
+// Subscription creation fee (spam deterrent, paid to config.authority)
+pub const DEFAULT_CREATION_FEE_LAMPORTS: u64 = 5000; // 0.000005 SOL
+pub const MAX_CREATION_FEE_LAMPORTS: u64 = 10_000_000; // 0.01 SOL
+
+// ICP canister public key allowlist (dev/staging/prod canisters)
+pub const MAX_ICP_PUBLIC_KEYS: usize = 8;
+
+// ICP key rotation: old and new keys are both accepted for at least this long
+pub const ROTATION_WINDOW_SECONDS: i64 = 600; // 10 minutes
+
+// Merchant opt-in approval list
+pub const MAX_APPROVED_SUBSCRIBERS: usize = 50;
+
+// Per-subscriber and per-merchant subscription index PDAs
+pub const MAX_INDEXED_SUBSCRIPTIONS: usize = 64;
+
+// Multisig admin
+pub const MAX_ADMIN_SIGNERS: usize = 5;
+pub const MAX_ADMIN_ACTION_DATA_LEN: usize = 256; // Borsh-encoded action arguments
+pub const ADMIN_PROPOSAL_TIMEOUT_SECONDS: i64 = 7 * 24 * 60 * 60; // Abandoned proposals expire after 7 days
+
+// Fee cap
+pub const DEFAULT_MIN_FEE_AMOUNT: u64 = 1000; // 0.001 USDC minimum fee
+
+// Oracle staleness
+pub const DEFAULT_MAX_PRICE_AGE_SECONDS: u64 = 60; // Reject Pyth prices older than 1 minute
+pub const MAX_PRICE_FUTURE_SKEW_SECONDS: u64 = 10; // Tolerated publish time ahead of the validator clock
+
+// Oracle fallback chain (Pyth -> Switchboard -> conservative default)
+pub const ORACLE_FALLBACK_PYTH: u8 = 0;
+pub const ORACLE_FALLBACK_SWITCHBOARD: u8 = 1;
+pub const ORACLE_FALLBACK_CONSERVATIVE: u8 = 2;
+
+// Switchboard v2 AggregatorAccountData layout (packed, offsets include the 8-byte discriminator)
+pub const SWITCHBOARD_AGGREGATOR_DISCRIMINATOR: [u8; 8] = [217, 230, 65, 101, 201, 162, 27, 125];
+pub const SWITCHBOARD_MIN_ORACLE_RESULTS_OFFSET: usize = 236;
+pub const SWITCHBOARD_LATEST_ROUND_OFFSET: usize = 341;
+
+// Subscription transfer timelock
+pub const SUBSCRIPTION_TRANSFER_TIMELOCK_SECONDS: i64 = 24 * 60 * 60; // 24 hours
+
+// Cancelled subscription accounts can be closed this long after creation
+pub const CLOSE_COOLDOWN_SECONDS: i64 = 7 * 24 * 60 * 60; // 7 days
+
+// Subscriber reputation scores (0 - REPUTATION_MAX_SCORE, new subscribers start in the middle)
+pub const REPUTATION_INITIAL_SCORE: u16 = 500;
+pub const REPUTATION_MAX_SCORE: u16 = 1000;
+pub const REPUTATION_ON_TIME_DELTA: i32 = 10;
+pub const REPUTATION_LATE_DELTA: i32 = 3;
+pub const REPUTATION_FAILED_DELTA: i32 = -15;
+
+// Merchant wallet migration timelock
+pub const MERCHANT_CHANGE_TIMELOCK_SECONDS: i64 = 48 * 60 * 60; // 48 hours
+
+// How long a subscriber has to accept proposed new terms
+pub const CHANGE_PROPOSAL_TTL_SECONDS: i64 = 7 * 24 * 60 * 60; // 7 days
+
+// Batch processing
+pub const MAX_BATCH_SIZE: usize = 6; // Maximum subscriptions per batch_process_payments call (fits the 64-account limit)
+// (subscription, subscriber_token_account, merchant_token_account, payment_nonce, used_nonce,
+//  merchant_dashboard, subscriber_reputation, previous_payment_nonce)
+// previous_payment_nonce is closed once the entry is paid; pass any non-program account for a first payment
+pub const BATCH_ACCOUNTS_PER_ENTRY: usize = 8;
+
+// Compute budget for hot-path transactions. A program can't raise its own budget: clients put
+// ComputeBudget SetComputeUnitLimit/SetComputeUnitPrice instructions first in the transaction.
+// The limits are headroom over the 200k default for the CPI-heavy paths and haven't been
+// profiled with `cargo test-sbf` yet - tighten them once measured.
+pub const DEFAULT_COMPUTE_UNIT_PRICE_MICROLAMPORTS: u64 = 1_000; // Matches the ICP canister's default
+pub const PROCESS_TRIGGER_COMPUTE_UNIT_LIMIT: u32 = 300_000; // Ed25519 check + fee split transfers + memo
+pub const PROCESS_PAYMENT_WITH_SWAP_COMPUTE_UNIT_LIMIT: u32 = 600_000; // Adds the swap CPI and oracle read
 
 // Timestamp validation
 pub const MAX_TIMESTAMP_DRIFT: i64 = 300; // 5 minutes max drift for signature validation
@@ -37,6 +117,29 @@ pub const USDC_MINT: &str = USDC_MINT_MAINNET;
 #[cfg(not(feature = "mainnet"))]
 pub const USDC_MINT: &str = USDC_MINT_DEVNET;
 
+// EURC (Circle Euro Coin) Mint Addresses
+pub const EURC_MINT_MAINNET: &str = "HzwqbKZw8HxMN6bF2yFZNrht3c2iXXzpKcFu7uBEDKtr";
+pub const EURC_MINT_DEVNET: &str = "HzwqbKZw8HxMN6bF2yFZNrht3c2iXXzpKcFu7uBEDKtr"; // Placeholder until a devnet EURC mint is chosen
+
+#[cfg(feature = "mainnet")]
+pub const EURC_MINT: &str = EURC_MINT_MAINNET;
+
+#[cfg(not(feature = "mainnet"))]
+pub const EURC_MINT: &str = EURC_MINT_DEVNET;
+
+// PayPal USD (Token-2022) Mint Addresses
+pub const PYUSD_MINT_MAINNET: &str = "2b1kV6DkPAnxd5ixfnxCpjxmKwqjjaYmCZfHsFu24GXo";
+pub const PYUSD_MINT_DEVNET: &str = "CXk2AMBfi3TwaEL2468s6zP8xq9NxTXjp9gjMgzeUynM";
+
+#[cfg(feature = "mainnet")]
+pub const PYUSD_MINT: &str = PYUSD_MINT_MAINNET;
+
+#[cfg(not(feature = "mainnet"))]
+pub const PYUSD_MINT: &str = PYUSD_MINT_DEVNET;
+
+// Token-2022 stablecoins a Token-2022 subscription may pay with
+pub const TOKEN_2022_PAYMENT_MINTS: &[&str] = &[PYUSD_MINT];
+
 // Helper function to check if token is USDC (only supported token)
 pub fn is_supported_token(mint_address: &str) -> bool {
     let usdc_mint = if cfg!(feature = "mainnet") {
@@ -47,11 +150,36 @@ pub fn is_supported_token(mint_address: &str) -> bool {
     mint_address == usdc_mint
 }
 
+// Helper function to check if token is a supported stablecoin (USDC or EURC)
+pub fn is_supported_stablecoin(mint_address: &str) -> bool {
+    is_supported_token(mint_address) || mint_address == EURC_MINT
+}
+
 // Helper to get USDC mint Pubkey (efficient comparison)
 pub fn get_usdc_mint() -> Pubkey {
     Pubkey::from_str(USDC_MINT).unwrap()
 }
 
+// Helper to check a payment mint: USDC for Token v1 subscriptions, the Token-2022 allowlist otherwise
+pub fn is_allowed_payment_mint(mint: &Pubkey, use_token_2022: bool) -> bool {
+    if use_token_2022 {
+        TOKEN_2022_PAYMENT_MINTS
+            .iter()
+            .any(|allowed| Pubkey::from_str(allowed).is_ok_and(|allowed| allowed == *mint))
+    } else {
+        *mint == get_usdc_mint()
+    }
+}
+
+// Helper to get the token program a subscription pays through (Token v1 or Token-2022)
+pub fn get_token_program_id(use_token_2022: bool) -> Pubkey {
+    if use_token_2022 {
+        anchor_spl::token_2022::ID
+    } else {
+        anchor_spl::token::ID
+    }
+}
+
 // Derive escrow PDA for a subscription
 pub fn derive_escrow_pda(subscription_id: &str, program_id: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(
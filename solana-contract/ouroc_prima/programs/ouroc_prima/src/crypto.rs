@@ -1,23 +1,5 @@
 use anchor_lang::prelude::*;
 
-/// Verify Ed25519 signature from ICP canister (DEPRECATED)
-///
-/// ⚠️ DEPRECATED: This function is no longer used.
-/// Use verify_ed25519_ix() instead which leverages Solana's Ed25519Program precompile.
-///
-/// The contract now uses verify_ed25519_ix() throughout for production-ready
-/// signature verification with the Ed25519Program precompile.
-#[deprecated(since = "0.1.0", note = "Use verify_ed25519_ix() instead")]
-pub fn verify_icp_signature(
-    _message: &[u8],
-    _signature: &[u8; 64],
-    _public_key: &[u8; 32],
-) -> Result<bool> {
-    // This function is deprecated and should not be used.
-    // All signature verification now uses verify_ed25519_ix().
-    Err(crate::errors::ErrorCode::InvalidSignature.into())
-}
-
 /// Create message for ICP canister to sign
 pub fn create_payment_message(
     subscription_id: &str,
@@ -31,6 +13,19 @@ pub fn create_payment_message(
     message
 }
 
+/// Create message for ICP canister to sign when authorizing a batch of payments
+/// Concatenates the per-subscription payment messages in batch order
+pub fn create_batch_payment_message(
+    payments: &[(String, u64)],
+    timestamp: i64,
+) -> Vec<u8> {
+    let mut message = Vec::new();
+    for (subscription_id, amount) in payments {
+        message.extend_from_slice(&create_payment_message(subscription_id, timestamp, *amount));
+    }
+    message
+}
+
 /// Verify the timestamp is within acceptable window (prevents replay attacks)
 /// With enhanced replay protection using timestamp uniqueness checking
 pub fn verify_timestamp(timestamp: i64, current_time: i64, max_age_seconds: i64) -> Result<bool> {
@@ -95,13 +90,13 @@ pub fn verify_replay_protection(
     Ok(true)
 }
 
-/// Verify Ed25519 signature using Solana's Ed25519 Program (cheaper gas)
+/// Verify Ed25519 signature using Solana's Ed25519 Program precompile
 ///
-/// This checks if the transaction includes an Ed25519 instruction that was already
-/// validated by Solana runtime. This is more gas-efficient than manual verification.
+/// The caller must place an Ed25519Program instruction immediately before this program's
+/// instruction. The runtime rejects the whole transaction if the precompile signature check
+/// fails, so here we only confirm it covered the expected public key and message.
 ///
 /// Usage: Pass the Instructions sysvar account to your instruction
-#[allow(dead_code)]
 pub fn verify_ed25519_ix(
     instructions_sysvar: &AccountInfo,
     expected_pubkey: &[u8; 32],
@@ -109,7 +104,6 @@ pub fn verify_ed25519_ix(
 ) -> Result<bool> {
     use anchor_lang::solana_program::sysvar::instructions;
 
-    // Load instruction at index 0 (should be Ed25519Program instruction)
     let current_index = instructions::load_current_index_checked(instructions_sysvar)?;
 
     // Check if there's an Ed25519 instruction before our program instruction
@@ -128,44 +122,79 @@ pub fn verify_ed25519_ix(
     )?;
 
     // Verify it's the Ed25519 program
-    let ed25519_program_id = anchor_lang::solana_program::ed25519_program::ID;
     require!(
-        ed25519_ix.program_id == ed25519_program_id,
+        ed25519_ix.program_id == anchor_lang::solana_program::ed25519_program::ID,
         crate::errors::ErrorCode::InvalidSignature
     );
 
-    // Parse Ed25519 instruction data format:
-    // [num_signatures: u8] + [padding: u8] + [signature_offset: u16] + [signature_instruction_index: u16] +
-    // [public_key_offset: u16] + [public_key_instruction_index: u16] + [message_data_offset: u16] +
-    // [message_data_size: u16] + [message_instruction_index: u16] + [public_key: 32 bytes] +
-    // [signature: 64 bytes] + [message: variable]
+    verify_ed25519_instruction_data(&ed25519_ix.data, expected_pubkey, expected_message)
+}
+
+/// Verify the Ed25519Program instruction was signed by any key in the allowlist
+pub fn verify_ed25519_ix_any(
+    instructions_sysvar: &AccountInfo,
+    allowed_pubkeys: &[[u8; 32]],
+    expected_message: &[u8],
+) -> Result<bool> {
+    Ok(allowed_pubkeys
+        .iter()
+        .any(|pubkey| verify_ed25519_ix(instructions_sysvar, pubkey, expected_message).unwrap_or(false)))
+}
+
+/// Check that Ed25519Program instruction data covers exactly one signature by
+/// `expected_pubkey` over `expected_message`
+///
+/// Layout: [num_signatures: u8] [padding: u8] then per signature
+/// [signature_offset: u16] [signature_instruction_index: u16] [public_key_offset: u16]
+/// [public_key_instruction_index: u16] [message_data_offset: u16] [message_data_size: u16]
+/// [message_instruction_index: u16], followed by the referenced data
+pub fn verify_ed25519_instruction_data(
+    data: &[u8],
+    expected_pubkey: &[u8; 32],
+    expected_message: &[u8],
+) -> Result<bool> {
+    const OFFSETS_START: usize = 2;
+    const OFFSETS_SIZE: usize = 14;
+    // Instruction index meaning "data lives in the Ed25519 instruction itself"
+    const CURRENT_INSTRUCTION: u16 = u16::MAX;
 
     require!(
-        ed25519_ix.data.len() >= 112,
+        data.len() >= OFFSETS_START + OFFSETS_SIZE && data[0] == 1,
         crate::errors::ErrorCode::InvalidSignature
     );
 
-    // Extract public key (offset 15, 32 bytes)
-    let pubkey_in_ix = &ed25519_ix.data[15..47];
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let signature_offset = read_u16(OFFSETS_START) as usize;
+    let signature_ix_index = read_u16(OFFSETS_START + 2);
+    let pubkey_offset = read_u16(OFFSETS_START + 4) as usize;
+    let pubkey_ix_index = read_u16(OFFSETS_START + 6);
+    let message_offset = read_u16(OFFSETS_START + 8) as usize;
+    let message_size = read_u16(OFFSETS_START + 10) as usize;
+    let message_ix_index = read_u16(OFFSETS_START + 12);
+
+    // SECURITY: The checked bytes must be the ones the precompile verified, not data
+    // pulled from another instruction in the transaction
     require!(
-        pubkey_in_ix == expected_pubkey,
+        signature_ix_index == CURRENT_INSTRUCTION
+            && pubkey_ix_index == CURRENT_INSTRUCTION
+            && message_ix_index == CURRENT_INSTRUCTION,
         crate::errors::ErrorCode::InvalidSignature
     );
 
-    // Extract message offset and size
-    let msg_offset = u16::from_le_bytes([ed25519_ix.data[9], ed25519_ix.data[10]]) as usize;
-    let msg_size = u16::from_le_bytes([ed25519_ix.data[11], ed25519_ix.data[12]]) as usize;
-
-    // Verify message matches expected
-    let msg_start = msg_offset + 15;
-    let msg_end = msg_start + msg_size;
+    require!(
+        data.len() >= signature_offset + 64
+            && data.len() >= pubkey_offset + 32
+            && data.len() >= message_offset + message_size,
+        crate::errors::ErrorCode::InvalidSignature
+    );
 
+    let pubkey_in_ix = &data[pubkey_offset..pubkey_offset + 32];
     require!(
-        ed25519_ix.data.len() >= msg_end,
+        pubkey_in_ix == expected_pubkey,
         crate::errors::ErrorCode::InvalidSignature
     );
 
-    let message_in_ix = &ed25519_ix.data[msg_start..msg_end];
+    let message_in_ix = &data[message_offset..message_offset + message_size];
     require!(
         message_in_ix == expected_message,
         crate::errors::ErrorCode::InvalidSignature
@@ -174,4 +203,4 @@ pub fn verify_ed25519_ix(
     // If we got here, the Ed25519Program already verified the signature
     // and we've confirmed the public key and message match expectations
     Ok(true)
-}
\ No newline at end of file
+}
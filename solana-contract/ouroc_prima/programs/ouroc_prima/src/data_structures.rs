@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use crate::constants::{MAX_ADMIN_ACTION_DATA_LEN, MAX_ADMIN_SIGNERS, MAX_APPROVED_SUBSCRIBERS, MAX_FEE_RECIPIENTS, MAX_FEE_TIERS, MAX_ICP_PUBLIC_KEYS, MAX_INDEXED_SUBSCRIPTIONS};
 
 // ============================================================================
 // Data Structures
@@ -10,33 +11,140 @@ pub struct Config {
     pub total_subscriptions: u64,
     pub paused: bool,
     pub authorization_mode: AuthorizationMode,
-    pub icp_public_key: Option<[u8; 32]>,
+    pub icp_public_keys: [[u8; 32]; MAX_ICP_PUBLIC_KEYS], // Authorized ICP canister keys (first icp_key_count used)
+    pub icp_key_count: u8,
     pub manual_processing_enabled: bool,
     pub time_based_processing_enabled: bool,
     pub fee_config: FeeConfig,
     pub icp_fee_collection_address: Option<Pubkey>, // ICP canister's Solana wallet for fees
+    pub notification_cooldown_seconds: i64, // Minimum time between opcode-1 notifications per subscription
+    pub oracle_max_age: u64, // Maximum age of a Pyth price update in seconds
+    pub multisig_mode: bool, // When true, admin actions go through propose/approve/execute
+    pub admin_threshold: u8, // Approvals required to execute an admin action
+    pub admin_signers: [Pubkey; MAX_ADMIN_SIGNERS], // Multisig members (first admin_signer_count used)
+    pub admin_signer_count: u8,
+    pub next_admin_action_id: u64,
+    pub pending_admin_action: Option<PendingAdminAction>,
+    pub pending_icp_public_key: Option<[u8; 32]>, // Replacement primary key, accepted alongside the current keys
+    pub key_rotation_start: Option<i64>,          // When the rotation window opened
+    pub creation_fee_lamports: u64,               // Charged to the subscriber on create_subscription (0 = waived)
 }
 
 impl Config {
-    pub const LEN: usize = 32 + 8 + 1 + 1 + 33 + 1 + 1 + FeeConfig::LEN + 33;
+    pub const LEN: usize = 32 + 8 + 1 + 1 + 32 * MAX_ICP_PUBLIC_KEYS + 1 + 1 + 1 + FeeConfig::LEN + 33 + 8 + 8
+        + 1 + 1 + 32 * MAX_ADMIN_SIGNERS + 1 + 8 + 1 + PendingAdminAction::LEN
+        + 33 + 9 + 8;
+}
+
+/// Config layout of the original program (single ICP key, two-field FeeConfig)
+/// The key allowlist and FeeConfig grew in place, so migrate_config rewrites the whole account
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacyConfig {
+    pub authority: Pubkey,
+    pub total_subscriptions: u64,
+    pub paused: bool,
+    pub authorization_mode: AuthorizationMode,
+    pub icp_public_key: Option<[u8; 32]>,
+    pub manual_processing_enabled: bool,
+    pub time_based_processing_enabled: bool,
+    pub fee_config: LegacyFeeConfig,
+    pub icp_fee_collection_address: Option<Pubkey>,
+}
+
+impl LegacyConfig {
+    pub const LEN: usize = 32 + 8 + 1 + 1 + 33 + 1 + 1 + LegacyFeeConfig::LEN + 33;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct LegacyFeeConfig {
+    pub fee_percentage_basis_points: u16,
+    pub min_fee_amount: u64,
+}
+
+impl LegacyFeeConfig {
+    pub const LEN: usize = 2 + 8;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PendingAdminAction {
+    pub action_id: u64,                  // 8 bytes
+    pub action_type: AdminActionType,    // 1 byte
+    pub data: Vec<u8>,                   // 4 + MAX_ADMIN_ACTION_DATA_LEN bytes - Borsh-encoded arguments
+    pub proposer: Pubkey,                // 32 bytes
+    pub proposed_at: i64,                // 8 bytes - Proposal expires ADMIN_PROPOSAL_TIMEOUT_SECONDS later
+    pub approvals: u8,                   // 1 byte - Bitmap over admin_signers indexes
+    pub approval_count: u8,              // 1 byte
+}
+
+impl PendingAdminAction {
+    pub const LEN: usize = 8 + 1 + 4 + MAX_ADMIN_ACTION_DATA_LEN + 32 + 8 + 1 + 1;
+}
+
+/// Admin instructions that can run through the multisig
+/// `data` layout per type: UpdateFeeDestination = (Pubkey, Vec<FeeRecipient>, u64),
+/// UpdateAuthorizationMode = (AuthorizationMode, Option<[u8; 32]>),
+/// AddIcpPublicKey/RemoveIcpPublicKey = [u8; 32], WithdrawFees = (Pubkey mint, u64, Pubkey recipient),
+/// InitializeFeeVault = Pubkey mint, UpdateCreationFee = u64, ResolveDispute = (Pubkey subscription, DisputeRuling), others = empty
+/// WithdrawFees, InitializeFeeVault, ResetFeeAnalytics and ResolveDispute need accounts, so once approved
+/// the authority calls the instruction itself, which consumes the proposal
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AdminActionType {
+    EmergencyPause,
+    ResumeProgram,
+    UpdateFeeDestination,
+    UpdateAuthorizationMode,
+    AddIcpPublicKey,
+    RemoveIcpPublicKey,
+    DisableMultisig,
+    InitiateKeyRotation,
+    WithdrawFees,
+    InitializeFeeVault,
+    ResetFeeAnalytics,
+    ResolveDispute,
+    UpdateCreationFee,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
 pub struct FeeConfig {
     pub fee_percentage_basis_points: u16, // e.g., 100 = 1%, 10 = 0.1%
     pub min_fee_amount: u64,               // Minimum fee in micro-USDC
+    pub max_fee_amount: u64,               // Maximum fee in micro-USDC (caps fees on large payments)
+    pub fee_recipients: [FeeRecipient; MAX_FEE_RECIPIENTS], // Revenue share partners (first recipient_count used)
+    pub recipient_count: u8,               // 0 = whole fee goes to icp_fee_collection_address
+    pub fee_tiers: [FeeTier; MAX_FEE_TIERS], // Volume discounts (first tier_count used, first tier starts at 0)
+    pub tier_count: u8,                    // 0 = flat fee_percentage_basis_points
 }
 
 impl FeeConfig {
-    pub const LEN: usize = 2 + 8;
+    pub const LEN: usize = 2 + 8 + 8 + FeeRecipient::LEN * MAX_FEE_RECIPIENTS + 1 + FeeTier::LEN * MAX_FEE_TIERS + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct FeeTier {
+    pub threshold_total_paid: u64, // Tier applies once a subscription's total_paid reaches this amount
+    pub fee_bps: u16,              // Platform fee in basis points for this tier
+}
+
+impl FeeTier {
+    pub const LEN: usize = 8 + 2;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct FeeRecipient {
+    pub address: Pubkey,   // Wallet that owns the recipient's USDC token account
+    pub share_bps: u16,    // Share of the platform fee in basis points
+}
+
+impl FeeRecipient {
+    pub const LEN: usize = 32 + 2;
 }
 
 #[account]
 pub struct Subscription {
-    pub id: String,                      // 32 bytes max
+    pub id: String,                      // 36 bytes (4 + 32 max)
     pub subscriber: Pubkey,              // 32 bytes
     pub merchant: Pubkey,                // 32 bytes
-    pub merchant_name: String,           // 32 bytes max - Merchant's app/business name for notifications
+    pub merchant_name: String,           // 36 bytes (4 + 32 max) - Merchant's app/business name for notifications
     pub amount: u64,                     // 8 bytes - USDC amount in micro-units
     pub interval_seconds: i64,           // 8 bytes
     pub next_payment_time: i64,          // 8 bytes
@@ -49,17 +157,248 @@ pub struct Subscription {
     pub reminder_days_before_payment: u32, // 4 bytes - Days before payment to send reminder (configured by merchant)
     pub escrow_pda: Pubkey,              // 32 bytes - Escrow PDA that holds funds before off-ramp
     pub escrow_balance: u64,             // 8 bytes - Current USDC balance in escrow
+    pub grace_period_seconds: i64,       // 8 bytes - Tolerance window around next_payment_time for triggers
+    pub trial_end_time: Option<i64>,     // 9 bytes (1 + 8) - Free trial end; no billing before this time
+    pub max_payments: Option<u64>,       // 9 bytes (1 + 8) - Installment plan length (None = unlimited)
+    pub remaining_payments: Option<u64>, // 9 bytes (1 + 8) - Installments left before auto-cancel
+    pub use_token_2022: bool,            // 1 byte - Pay through Token-2022 instead of Token v1
+    pub last_notification_time: Option<i64>, // 9 bytes (1 + 8) - Last opcode-1 notification (rate limiting)
+    pub paused_at: Option<i64>,          // 9 bytes (1 + 8) - When the subscription was paused (for resume credit)
+    pub expires_at: Option<i64>,         // 9 bytes (1 + 8) - Contract end; no payments at or after this time
+    pub paused_by: PausedBy,             // 1 byte - Who paused the subscription (only they can resume)
+    pub authorization_mode_override: Option<AuthorizationMode>, // 2 bytes (1 + 1) - Set at creation; None = config.authorization_mode
+    pub spending_cap_per_period: u64,    // 8 bytes - Max billed per period (0 = no cap)
+    pub period_start: i64,               // 8 bytes - Start of the current spending window
+    pub spent_in_period: u64,            // 8 bytes - Billed so far in the current window
+    pub slippage_bps: u16,               // 2 bytes - Swap tolerance for non-USDC payments
+    pub slippage_updated_at: Option<i64>, // 9 bytes (1 + 8) - Last update_slippage_bps (cooldown)
+    pub integrity_hash: [u8; 32],        // 32 bytes - SHA-256 of the billing terms, see subscription_integrity_hash
+    pub disputed: bool,                  // 1 byte - Charges blocked until resolve_dispute
+    pub dispute_payment_number: u64,     // 8 bytes - Payment the open dispute is about
+    pub version: u64,                    // 8 bytes - Bumped by every trigger; triggers name the version they expect
+    pub last_ruled_payment_number: u64,  // 8 bytes - Payments up to this one were already ruled on and can't be disputed again
+    pub vault_fees_paid: u64,            // 8 bytes - Platform fees deposited into the fee vault, net of refunds
 }
 
 impl Subscription {
+    pub const LEN: usize = 36 + 32 + 32 + 36 + 8 + 8 + 8 + 1 + 8 + 9 + 8 + 8 + 64 + 4 + 32 + 8 + 8 + 9 + 9 + 9 + 1 + 9 + 9 + 9 + 1 + 2 + 8 + 8 + 8 + 2 + 9 + 32 + 1 + 8 + 8 + 8 + 8;
+}
+
+/// Subscription layout of the original program, which ended at escrow_balance
+/// Newer fields are only ever appended, so migrate_subscription reads this prefix and fills in defaults
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacySubscription {
+    pub id: String,
+    pub subscriber: Pubkey,
+    pub merchant: Pubkey,
+    pub merchant_name: String,
+    pub amount: u64,
+    pub interval_seconds: i64,
+    pub next_payment_time: i64,
+    pub status: SubscriptionStatus,
+    pub created_at: i64,
+    pub last_payment_time: Option<i64>,
+    pub payments_made: u64,
+    pub total_paid: u64,
+    pub icp_canister_signature: [u8; 64],
+    pub reminder_days_before_payment: u32,
+    pub escrow_pda: Pubkey,
+    pub escrow_balance: u64,
+}
+
+impl LegacySubscription {
+    /// Space the original program allocated (it under-counted the String prefixes)
     pub const LEN: usize = 32 + 32 + 32 + 32 + 8 + 8 + 8 + 1 + 8 + 9 + 8 + 8 + 64 + 4 + 32 + 8;
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+/// Pending change of subscriber wallet, held in a PDA until the timelock expires
+#[account]
+pub struct TransferProposal {
+    pub subscription: Pubkey,            // 32 bytes - Subscription being transferred
+    pub pending: PendingTransfer,        // 40 bytes
+}
+
+impl TransferProposal {
+    pub const LEN: usize = 32 + PendingTransfer::LEN;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct PendingTransfer {
+    pub new_subscriber: Pubkey,          // 32 bytes - Wallet that will take over the subscription
+    pub proposed_at: i64,                // 8 bytes - Timelock starts here
+}
+
+impl PendingTransfer {
+    pub const LEN: usize = 32 + 8;
+}
+
+/// Program-wide fee totals for on-chain reporting, seeds = [b"fee_analytics"]
+#[account]
+pub struct FeeAnalytics {
+    pub total_fees_collected: u128,      // 16 bytes - Platform fees since last_reset (micro-USDC)
+    pub total_volume: u128,              // 16 bytes - Gross payment volume since last_reset
+    pub payment_count: u64,              // 8 bytes
+    pub last_reset: i64,                 // 8 bytes - Start of the current reporting window
+}
+
+impl FeeAnalytics {
+    pub const LEN: usize = 16 + 16 + 8 + 8;
+}
+
+#[account]
+pub struct PaymentNonce {
+    pub subscription: Pubkey,            // 32 bytes - Subscription that was charged
+    pub cycle: u64,                      // 8 bytes - payments_made at the time of the charge
+    pub created_at: i64,                 // 8 bytes
+}
+
+impl PaymentNonce {
+    pub const LEN: usize = 32 + 8 + 8;
+}
+
+#[account]
+pub struct UsedNonce {
+    pub subscription: Pubkey,            // 32 bytes - Subscription the signature authorized
+    pub timestamp: i64,                  // 8 bytes - Signed timestamp (part of the PDA seeds)
+    pub payer: Pubkey,                   // 32 bytes - Receives the rent back when pruned
+    pub created_at: i64,                 // 8 bytes
+}
+
+impl UsedNonce {
+    pub const LEN: usize = 32 + 8 + 32 + 8;
+}
+
+/// Merchant opt-in settings, seeds = [b"merchant_config", merchant]
+/// Merchants without this PDA accept subscriptions from anyone
+#[account]
+pub struct MerchantConfig {
+    pub merchant: Pubkey,                // 32 bytes
+    pub requires_approval: bool,         // 1 byte - Only approved subscribers may subscribe
+    pub approved_subscribers: Vec<Pubkey>, // 4 + 32 * MAX_APPROVED_SUBSCRIBERS bytes
+    pub active: bool,                    // 1 byte - Approval gating is enforced only while active
+}
+
+impl MerchantConfig {
+    pub const LEN: usize = 32 + 1 + 4 + 32 * MAX_APPROVED_SUBSCRIBERS + 1;
+}
+
+/// Subscription IDs created by a subscriber, seeds = [b"sub_index", subscriber]
+/// IDs are zero-padded to 32 bytes; cancelled or closed subscriptions are removed
+#[account]
+pub struct SubscriberIndex {
+    pub subscriber: Pubkey,              // 32 bytes
+    pub subscription_ids: Vec<[u8; 32]>, // 4 + 32 * MAX_INDEXED_SUBSCRIPTIONS bytes
+    pub count: u8,                       // 1 byte
+}
+
+impl SubscriberIndex {
+    pub const LEN: usize = 32 + 4 + 32 * MAX_INDEXED_SUBSCRIPTIONS + 1;
+}
+
+/// Subscription IDs billed to a merchant, seeds = [b"merchant_index", merchant]
+#[account]
+pub struct MerchantIndex {
+    pub merchant: Pubkey,                // 32 bytes
+    pub subscription_ids: Vec<[u8; 32]>, // 4 + 32 * MAX_INDEXED_SUBSCRIPTIONS bytes
+    pub count: u8,                       // 1 byte
+}
+
+impl MerchantIndex {
+    pub const LEN: usize = 32 + 4 + 32 * MAX_INDEXED_SUBSCRIPTIONS + 1;
+}
+
+/// Running merchant totals for dashboards, seeds = [b"merchant_dashboard", merchant]
+/// Created on the merchant's first process_payment. active_subscriptions counts subscriptions that are
+/// not cancelled or expired, and only sees changes made while the dashboard exists
+#[account]
+pub struct MerchantDashboard {
+    pub merchant: Pubkey,                // 32 bytes
+    pub active_subscriptions: u32,       // 4 bytes
+    pub total_revenue_usdc: u64,         // 8 bytes - Merchant share of all payments (micro-USDC)
+    pub total_fees_paid: u64,            // 8 bytes - Platform fees taken from those payments
+    pub last_payment: Option<i64>,       // 9 bytes (1 + 8)
+    pub failed_payments: u32,            // 4 bytes - Failed transactions revert, so not counted on-chain yet
+}
+
+impl MerchantDashboard {
+    pub const LEN: usize = 32 + 4 + 8 + 8 + 9 + 4;
+}
+
+/// Payment track record of a subscriber across all their subscriptions, seeds = [b"reputation", subscriber]
+#[account]
+pub struct SubscriberReputation {
+    pub subscriber: Pubkey,              // 32 bytes
+    pub score: u16,                      // 2 bytes - 0 to REPUTATION_MAX_SCORE
+    pub on_time_payments: u64,           // 8 bytes - Charged within the grace period
+    pub late_payments: u64,              // 8 bytes - Charged after the grace period
+    pub failed_payments: u64,            // 8 bytes - Reported by the authority via record_failed_payment
+    pub last_updated: i64,               // 8 bytes
+}
+
+impl SubscriberReputation {
+    pub const LEN: usize = 32 + 2 + 8 + 8 + 8 + 8;
+}
+
+#[account]
+pub struct MerchantChangeProposal {
+    pub subscription: Pubkey,            // 32 bytes - Subscription whose merchant is changing
+    pub new_merchant: Pubkey,            // 32 bytes - Wallet that will receive future payments
+    pub proposed_at: i64,                // 8 bytes - Timelock starts here
+}
+
+impl MerchantChangeProposal {
+    pub const LEN: usize = 32 + 32 + 8;
+}
+
+/// New billing terms proposed by the merchant, seeds = [b"change_proposal", subscription_id]
+/// Takes effect only when the subscriber accepts before expires_at
+#[account]
+pub struct ProposedSubscriptionChange {
+    pub subscription: Pubkey,            // 32 bytes - Subscription whose terms would change
+    pub proposer: Pubkey,                // 32 bytes - Merchant who proposed (and paid rent)
+    pub proposed_amount: u64,            // 8 bytes
+    pub proposed_interval: i64,          // 8 bytes
+    pub expires_at: i64,                 // 8 bytes
+}
+
+impl ProposedSubscriptionChange {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8;
+}
+
+/// Open charge dispute raised by the subscriber, seeds = [b"dispute", subscription_id]
+/// Closed by resolve_dispute, rent returned to the subscriber
+#[account]
+pub struct DisputeRecord {
+    pub subscription: Pubkey,            // 32 bytes - Subscription the disputed charge belongs to
+    pub subscriber: Pubkey,              // 32 bytes - Subscriber who opened the dispute (and paid rent)
+    pub payment_number: u64,             // 8 bytes - Disputed charge (1-based, as in PaymentProcessed)
+    pub reason: [u8; 128],               // 128 bytes - Free-form UTF-8, zero-padded
+    pub opened_at: i64,                  // 8 bytes
+}
+
+impl DisputeRecord {
+    pub const LEN: usize = 32 + 32 + 8 + 128 + 8;
+}
+
+/// Admin decision on a dispute
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DisputeRuling {
+    Approved, // Charge was wrong - refund it when the refund accounts are supplied
+    Rejected, // Charge stands
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
 pub enum SubscriptionStatus {
     Active,
     Paused,
     Cancelled,
+    Expired,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PausedBy {
+    Subscriber,        // Paused by the subscriber (UpdateSubscription)
+    Merchant,          // Paused by the merchant (MerchantAction), e.g. payment disputes
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
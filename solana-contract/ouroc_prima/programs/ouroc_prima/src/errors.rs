@@ -101,4 +101,211 @@ pub enum ErrorCode {
 
     #[msg("Token swap not implemented - only USDC supported")]
     SwapNotImplemented,
-}
\ No newline at end of file
+
+    #[msg("Invalid grace period - must be non-negative and shorter than the interval")]
+    InvalidGracePeriod,
+
+    #[msg("Subscription is still in its free trial period")]
+    TrialPeriodActive,
+
+    #[msg("Invalid trial end time - must be in the future and within one year")]
+    InvalidTrialEndTime,
+
+    #[msg("Refund amount exceeds total paid on this subscription")]
+    RefundExceedsTotalPaid,
+
+    #[msg("Invalid max payments - must be greater than 0")]
+    InvalidMaxPayments,
+
+    #[msg("Invalid token program - must match the subscription's token program")]
+    InvalidTokenProgram,
+
+    #[msg("Invalid batch - provide 1 to 8 (subscription, subscriber, merchant) account triplets")]
+    InvalidBatchSize,
+
+    #[msg("Notification cooldown active - try again later")]
+    NotificationCooldown,
+
+    #[msg("Invalid fee recipients - up to 4 recipients whose shares sum to 10000 basis points")]
+    InvalidFeeRecipients,
+
+    #[msg("Fee recipient token account missing or does not match configured recipient")]
+    InvalidFeeRecipientAccount,
+
+    #[msg("Subscription transfer timelock has not expired - wait 24 hours after proposal")]
+    TransferTimelockActive,
+
+    #[msg("Invalid subscription transfer - new subscriber must differ from the current one")]
+    InvalidSubscriptionTransfer,
+
+    #[msg("Subscription has expired")]
+    SubscriptionExpired,
+
+    #[msg("Invalid expiry - must be in the future")]
+    InvalidExpiry,
+
+    #[msg("Subscription was paused by the other party - only they can resume it")]
+    WrongPauseAuthority,
+
+    #[msg("Template subscription must be active or paused to be cloned")]
+    InvalidCloneTemplate,
+
+    #[msg("Timelock has not expired yet")]
+    TimelockNotExpired,
+
+    #[msg("Invalid merchant address")]
+    InvalidMerchantAddress,
+
+    #[msg("Oracle price feed is stale")]
+    StalePriceFeed,
+
+    #[msg("Payment for this cycle has already been processed")]
+    DuplicatePayment,
+
+    #[msg("Maximum fee amount must be at least the minimum fee amount")]
+    InvalidMaxFeeAmount,
+
+    #[msg("ICP public key allowlist is full")]
+    ICPKeyAllowlistFull,
+
+    #[msg("ICP public key is already in the allowlist")]
+    DuplicateICPKey,
+
+    #[msg("ICP public key not found in the allowlist")]
+    ICPKeyNotFound,
+
+    #[msg("Program is in multisig mode - use propose_admin_action")]
+    MultisigRequired,
+
+    #[msg("Multisig mode is not enabled")]
+    MultisigNotEnabled,
+
+    #[msg("Invalid multisig signers or threshold")]
+    InvalidMultisigConfig,
+
+    #[msg("Another admin action is pending")]
+    AdminProposalPending,
+
+    #[msg("Admin action proposal not found")]
+    AdminProposalNotFound,
+
+    #[msg("Admin action proposal has expired")]
+    AdminProposalExpired,
+
+    #[msg("Signer already approved this admin action")]
+    AdminActionAlreadyApproved,
+
+    #[msg("Admin action does not have enough approvals")]
+    AdminThresholdNotMet,
+
+    #[msg("Invalid admin action data")]
+    InvalidAdminActionData,
+
+    #[msg("Payment would exceed the subscriber's spending cap for this period")]
+    SpendingCapExceeded,
+
+    #[msg("ICP signature has already been used")]
+    SignatureReplayed,
+
+    #[msg("Used nonce account is required for ICP signature payments")]
+    MissingUsedNonce,
+
+    #[msg("Used nonce has not expired yet")]
+    NonceNotExpired,
+
+    #[msg("Subscriber is not approved by this merchant")]
+    SubscriberNotApproved,
+
+    #[msg("Merchant approval list is full")]
+    ApprovedSubscribersFull,
+
+    #[msg("Subscriber is already approved")]
+    SubscriberAlreadyApproved,
+
+    #[msg("An ICP key rotation is already in progress")]
+    KeyRotationPending,
+
+    #[msg("No ICP key rotation in progress")]
+    NoKeyRotationPending,
+
+    #[msg("ICP key rotation window has not elapsed")]
+    KeyRotationWindowActive,
+
+    #[msg("Fee tiers must start at 0 with increasing thresholds and non-increasing fees")]
+    InvalidFeeTiers,
+
+    #[msg("Fee vault balance is too low for this withdrawal")]
+    InsufficientVaultBalance,
+
+    #[msg("Slippage must be between 1 and MAX_SLIPPAGE_BPS basis points")]
+    InvalidSlippage,
+
+    #[msg("Slippage was updated less than 24 hours ago")]
+    SlippageUpdateCooldown,
+
+    #[msg("Snooze must skip between 1 and MAX_SNOOZE_PERIODS billing periods")]
+    InvalidSnoozePeriods,
+
+    #[msg("Payment amount must be greater than the minimum fee")]
+    PaymentTooSmall,
+
+    #[msg("Only cancelled subscriptions can be closed")]
+    SubscriptionNotCancelled,
+
+    #[msg("Subscription account cannot be closed until CLOSE_COOLDOWN_SECONDS after creation")]
+    CloseCooldownActive,
+
+    #[msg("Escrow still holds funds for this subscription")]
+    EscrowNotEmpty,
+
+    #[msg("Subscription index is full (MAX_INDEXED_SUBSCRIPTIONS)")]
+    SubscriptionIndexFull,
+
+    #[msg("Invalid subscriber reputation account")]
+    InvalidReputationAccount,
+
+    #[msg("Subscription change proposal has expired")]
+    ChangeProposalExpired,
+
+    #[msg("Subscription change proposal has not expired yet")]
+    ChangeProposalNotExpired,
+
+    #[msg("Subscription terms do not match their integrity hash")]
+    SubscriptionTampered,
+
+    #[msg("Subscription was modified by another trigger, re-read the version and retry")]
+    ConcurrentModification,
+
+    #[msg("Delegation already covers a year of payments")]
+    DelegationNotExhausted,
+
+    #[msg("Creation fee must be at most 10,000,000 lamports")]
+    InvalidCreationFee,
+
+    #[msg("Config authority account required to pay the creation fee")]
+    MissingCreationFeeRecipient,
+
+    #[msg("Subscription has an open payment dispute")]
+    SubscriptionDisputed,
+
+    #[msg("Disputed payment number must refer to a charge already made")]
+    InvalidDisputedPayment,
+
+    #[msg("Account already uses the current layout")]
+    AccountAlreadyMigrated,
+
+    #[msg("Payment triggers need the fee vault and payment nonce accounts")]
+    MissingPaymentAccounts,
+
+    #[msg("A subscription appears more than once in the batch")]
+    DuplicateBatchEntry,
+
+    #[msg("This admin action is executed by calling its instruction once approved")]
+    AdminActionNeedsAccounts,
+
+    #[msg("A longer interval must be proposed by the merchant")]
+    IntervalIncreaseNeedsProposal,
+
+    #[msg("Settle the due payment before changing the interval")]
+    PaymentDue,
+}
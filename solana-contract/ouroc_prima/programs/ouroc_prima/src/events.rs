@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use crate::data_structures::{AdminActionType, DisputeRuling};
 
 // ============================================================================
 // Events
@@ -20,9 +21,82 @@ pub struct PaymentProcessed {
     pub amount: u64,
     pub merchant_amount: u64,
     pub fee_amount: u64,
+    pub tier: TierApplied,
     pub timestamp: i64,
 }
 
+/// Fee tier used for a payment
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct TierApplied {
+    pub tier_index: u8,
+    pub fee_bps: u16,
+}
+
+#[event]
+pub struct SlippageUpdated {
+    pub subscription_id: String,
+    pub old_bps: u16,
+    pub new_bps: u16,
+}
+
+#[event]
+pub struct SubscriptionIntervalUpdated {
+    pub subscription_id: String,
+    pub old_interval: i64,
+    pub new_interval: i64,
+    pub new_delegation: u64,
+}
+
+/// Event emitted when a subscriber disputes a charge
+#[event]
+pub struct PaymentDisputed {
+    pub subscription_id: String,
+    pub payment_number: u64,
+    pub reason: [u8; 128],
+}
+
+/// Event emitted when the admin rules on a dispute (refund_amount is 0 without a refund)
+#[event]
+pub struct DisputeResolved {
+    pub subscription_id: String,
+    pub payment_number: u64,
+    pub ruling: DisputeRuling,
+    pub refund_amount: u64,
+}
+
+/// Event emitted when create_subscription charges the anti-spam SOL fee
+#[event]
+pub struct SubscriptionCreationFeeCharged {
+    pub subscription_id: String,
+    pub subscriber: Pubkey,
+    pub fee_lamports: u64,
+}
+
+/// Event emitted when a subscriber tops the delegation back up to one year
+#[event]
+pub struct DelegationRenewed {
+    pub subscription_id: String,
+    pub previous_delegation: u64,
+    pub new_delegation: u64,
+}
+
+#[event]
+pub struct DelegationStatusChecked {
+    pub subscription_id: String,
+    pub is_delegate_correct: bool,
+    pub delegated_amount: u64,
+    pub required_amount: u64,
+    pub payments_remaining: u64,
+    pub months_remaining: f32,
+}
+
+#[event]
+pub struct SubscriptionSnoozed {
+    pub subscription_id: String,
+    pub periods: u8,
+    pub next_payment_time: i64,
+}
+
 #[event]
 pub struct SubscriptionPaused {
     pub subscription_id: String,
@@ -35,6 +109,29 @@ pub struct SubscriptionResumed {
     pub resumed_at: i64,
 }
 
+#[event]
+pub struct SubscriptionClosed {
+    pub subscription_id: String,
+    pub subscriber: Pubkey,
+    pub closed_at: i64,
+}
+
+#[event]
+pub struct PaymentFailureRecorded {
+    pub subscription_id: String,
+    pub subscriber: Pubkey,
+    pub reputation_score: u16,
+    pub recorded_at: i64,
+}
+
+/// Emitted when a swap payment was priced without a fresh Pyth feed
+/// fallback_level: 1 = Switchboard, 2 = conservative default
+#[event]
+pub struct OracleFallbackUsed {
+    pub subscription_id: String,
+    pub fallback_level: u8,
+}
+
 #[event]
 pub struct SubscriptionCancelled {
     pub subscription_id: String,
@@ -43,6 +140,14 @@ pub struct SubscriptionCancelled {
     pub total_paid: u64,
 }
 
+#[event]
+pub struct SubscriptionExpired {
+    pub subscription_id: String,
+    pub expired_at: i64,
+    pub total_payments_made: u64,
+    pub total_paid: u64,
+}
+
 #[event]
 pub struct DelegateApproved {
     pub subscription_id: String,
@@ -58,4 +163,125 @@ pub struct FeeDestinationUpdated {
     pub new_address: Pubkey,
     pub updated_by: Pubkey,
     pub timestamp: i64,
-}
\ No newline at end of file
+}
+
+/// Event emitted when platform fees leave the fee vault
+#[event]
+pub struct FeesWithdrawn {
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub recipient: Pubkey,
+    pub withdrawn_by: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a merchant refunds a subscriber
+#[event]
+pub struct RefundProcessed {
+    pub subscription_id: String,
+    pub refund_amount: u64,
+    pub reason: [u8; 64],
+}
+
+/// Event emitted when a subscriber wallet change is proposed
+#[event]
+pub struct SubscriptionTransferProposed {
+    pub subscription_id: String,
+    pub old_subscriber: Pubkey,
+    pub new_subscriber: Pubkey,
+    pub unlocks_at: i64,
+}
+
+/// Event emitted when a subscription moves to a new subscriber wallet
+/// The new subscriber must approve delegation before the next payment
+#[event]
+pub struct SubscriptionTransferred {
+    pub subscription_id: String,
+    pub old_subscriber: Pubkey,
+    pub new_subscriber: Pubkey,
+    pub old_delegation_revoked: bool,
+    pub timestamp: i64,
+}
+
+/// Amount of the platform fee paid to one revenue share recipient
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct FeeShare {
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
+
+/// Event emitted when a platform fee is split among revenue share recipients
+#[event]
+pub struct FeeDistributed {
+    pub subscription_id: String,
+    pub recipients: Vec<FeeShare>,
+}
+
+/// Per-subscription outcome of a batch payment
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BatchPaymentResult {
+    pub index: u8,
+    pub success: bool,
+    pub error_code: Option<u32>,
+}
+
+/// Event emitted once per batch_process_payments call
+#[event]
+pub struct BatchPaymentProcessed {
+    pub results: Vec<BatchPaymentResult>,
+    pub successful_count: u8,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a merchant wallet change passes its timelock
+#[event]
+pub struct SubscriptionChangeProposed {
+    pub subscription_id: String,
+    pub proposer: Pubkey,
+    pub proposed_amount: u64,
+    pub proposed_interval: i64,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct SubscriptionChangeAccepted {
+    pub subscription_id: String,
+    pub old_amount: u64,
+    pub new_amount: u64,
+    pub old_interval: i64,
+    pub new_interval: i64,
+}
+
+#[event]
+pub struct MerchantAddressUpdated {
+    pub subscription_id: String,
+    pub old: Pubkey,
+    pub new: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a multisig admin action is proposed
+#[event]
+pub struct AdminActionProposed {
+    pub action_id: u64,
+    pub action_type: AdminActionType,
+    pub proposer: Pubkey,
+    pub expires_at: i64,
+}
+
+/// Event emitted when a multisig member approves a pending admin action
+#[event]
+pub struct AdminActionApproved {
+    pub action_id: u64,
+    pub approver: Pubkey,
+    pub approval_count: u8,
+}
+
+/// Event emitted when a multisig admin action is executed
+#[event]
+pub struct AdminActionExecuted {
+    pub action_id: u64,
+    pub action_type: AdminActionType,
+    pub executed_by: Pubkey,
+    pub timestamp: i64,
+}
@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount};
+use anchor_spl::token;
+use anchor_spl::token_interface;
 use std::str::FromStr;
 use crate::constants::*;
 use crate::data_structures::*;
@@ -17,23 +18,61 @@ pub fn initialize(
     ctx: Context<crate::Initialize>,
     authorization_mode: AuthorizationMode,
     icp_public_key: Option<[u8; 32]>,
+    fee_recipients: Vec<FeeRecipient>,
+    max_fee_amount: u64,
 ) -> Result<()> {
-    let config = &mut ctx.accounts.config;
+    // Revenue share recipients must cover exactly 100% of the platform fee
+    let (fee_recipients, recipient_count) = build_fee_recipients(&fee_recipients)?;
+    require!(max_fee_amount >= DEFAULT_MIN_FEE_AMOUNT, ErrorCode::InvalidMaxFeeAmount);
+
+    let config: &mut Config = &mut ctx.accounts.config;
     config.authority = ctx.accounts.authority.key();
     config.total_subscriptions = 0;
     config.paused = false;
     config.authorization_mode = authorization_mode;
-    config.icp_public_key = icp_public_key;
-    config.manual_processing_enabled = matches!(authorization_mode, AuthorizationMode::ManualOnly | AuthorizationMode::Hybrid);
-    config.time_based_processing_enabled = matches!(authorization_mode, AuthorizationMode::TimeBased | AuthorizationMode::Hybrid);
+    // Start the allowlist with the given key; more canisters are added via add_icp_public_key
+    config.icp_public_keys = [[0u8; 32]; MAX_ICP_PUBLIC_KEYS];
+    config.icp_key_count = 0;
+    if let Some(key) = icp_public_key {
+        add_icp_key(&mut config.icp_public_keys, &mut config.icp_key_count, key)?;
+    }
+    config.manual_processing_enabled = allows_manual_processing(authorization_mode);
+    config.time_based_processing_enabled = allows_time_based_processing(authorization_mode);
 
     // SECURITY: No hardcoded fee address - must be set via update_fee_destination
     // This prevents single point of failure and enables proper governance
     config.icp_fee_collection_address = None; // Must be set explicitly by admin
 
+    config.notification_cooldown_seconds = DEFAULT_NOTIFICATION_COOLDOWN_SECONDS;
+    config.oracle_max_age = DEFAULT_MAX_PRICE_AGE_SECONDS;
+
+    // Single-authority admin until enable_multisig is called
+    config.multisig_mode = false;
+    config.admin_threshold = 0;
+    config.admin_signers = [Pubkey::default(); MAX_ADMIN_SIGNERS];
+    config.admin_signer_count = 0;
+    config.next_admin_action_id = 0;
+    config.pending_admin_action = None;
+    config.pending_icp_public_key = None;
+    config.key_rotation_start = None;
+    config.creation_fee_lamports = DEFAULT_CREATION_FEE_LAMPORTS;
+
+    let fee_analytics = &mut ctx.accounts.fee_analytics;
+    fee_analytics.total_fees_collected = 0;
+    fee_analytics.total_volume = 0;
+    fee_analytics.payment_count = 0;
+    fee_analytics.last_reset = Clock::get()?.unix_timestamp;
+
+    // A single tier at the base fee until update_fee_destination configures volume discounts
+    let (fee_tiers, tier_count) = build_fee_tiers(&[], 200)?;
     config.fee_config = FeeConfig {
         fee_percentage_basis_points: 200, // 2% fee (hardcoded)
-        min_fee_amount: 1000, // 0.001 USDC minimum fee
+        min_fee_amount: DEFAULT_MIN_FEE_AMOUNT,
+        max_fee_amount,
+        fee_recipients,
+        recipient_count,
+        fee_tiers,
+        tier_count,
     };
 
     msg!("⚠️ FEE COLLECTION ADDRESS NOT SET - Admin must call update_fee_destination() to set fee destination");
@@ -52,12 +91,42 @@ pub fn initialize(
 pub fn update_fee_destination(
     ctx: Context<crate::UpdateFeeDestination>,
     new_fee_address: Pubkey,
+    fee_recipients: Vec<FeeRecipient>,
+    max_fee_amount: u64,
+    fee_tiers: Vec<FeeTier>,
+) -> Result<()> {
+    require!(!ctx.accounts.config.multisig_mode, ErrorCode::MultisigRequired);
+
+    let updated_by = ctx.accounts.authority.key();
+    apply_fee_destination_update(&mut ctx.accounts.config, new_fee_address, fee_recipients, max_fee_amount, fee_tiers, updated_by)
+}
+
+fn apply_fee_destination_update(
+    config: &mut Config,
+    new_fee_address: Pubkey,
+    fee_recipients: Vec<FeeRecipient>,
+    max_fee_amount: u64,
+    fee_tiers: Vec<FeeTier>,
+    updated_by: Pubkey,
 ) -> Result<()> {
-    let config = &mut ctx.accounts.config;
+    // Revenue share recipients must cover exactly 100% of the platform fee
+    let (fee_recipients, recipient_count) = build_fee_recipients(&fee_recipients)?;
+    // Volume tiers: increasing thresholds, non-increasing fees (empty = flat base fee)
+    let (fee_tiers, tier_count) = build_fee_tiers(&fee_tiers, config.fee_config.fee_percentage_basis_points)?;
+
+    require!(
+        max_fee_amount >= config.fee_config.min_fee_amount,
+        ErrorCode::InvalidMaxFeeAmount
+    );
     let old_address = config.icp_fee_collection_address;
 
-    // Update the fee collection address
+    // Update the fee collection address and revenue share recipients
     config.icp_fee_collection_address = Some(new_fee_address);
+    config.fee_config.fee_recipients = fee_recipients;
+    config.fee_config.recipient_count = recipient_count;
+    config.fee_config.max_fee_amount = max_fee_amount;
+    config.fee_config.fee_tiers = fee_tiers;
+    config.fee_config.tier_count = tier_count;
 
     msg!(
         "Fee destination updated from {:?} to {}",
@@ -69,7 +138,109 @@ pub fn update_fee_destination(
     emit!(FeeDestinationUpdated {
         old_address,
         new_address: new_fee_address,
-        updated_by: ctx.accounts.authority.key(),
+        updated_by,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Let an account-bound admin instruction through: directly while multisig is off,
+/// otherwise by consuming the matching proposal that reached admin_threshold
+fn authorize_admin_instruction(
+    config: &mut Config,
+    action_type: AdminActionType,
+    data: &[u8],
+    executed_by: Pubkey,
+) -> Result<()> {
+    if !config.multisig_mode {
+        return Ok(());
+    }
+
+    let timestamp = Clock::get()?.unix_timestamp;
+    let pending = take_approved_admin_action(
+        &mut config.pending_admin_action,
+        config.admin_threshold,
+        action_type,
+        data,
+        timestamp,
+    )?;
+
+    msg!("Admin action {} executed: {:?}", pending.action_id, action_type);
+
+    emit!(AdminActionExecuted {
+        action_id: pending.action_id,
+        action_type,
+        executed_by,
+        timestamp,
+    });
+
+    Ok(())
+}
+
+/// Create the fee vault for a payment mint (admin only, multisig-approved when enabled)
+/// The vault is a token account owned by its own PDA so only withdraw_fees can move funds
+pub fn initialize_fee_vault(ctx: Context<crate::InitializeFeeVault>) -> Result<()> {
+    let mint_key = ctx.accounts.mint.key();
+    authorize_admin_instruction(
+        &mut ctx.accounts.config,
+        AdminActionType::InitializeFeeVault,
+        &mint_key.try_to_vec()?,
+        ctx.accounts.authority.key(),
+    )?;
+
+    msg!(
+        "Fee vault {} created for mint {}",
+        ctx.accounts.fee_vault.key(),
+        ctx.accounts.mint.key()
+    );
+    Ok(())
+}
+
+/// Withdraw accumulated platform fees from the vault (admin only, multisig-approved when enabled)
+pub fn withdraw_fees(ctx: Context<crate::WithdrawFees>, amount: u64, recipient: Pubkey) -> Result<()> {
+    let mint_key = ctx.accounts.mint.key();
+    authorize_admin_instruction(
+        &mut ctx.accounts.config,
+        AdminActionType::WithdrawFees,
+        &(mint_key, amount, recipient).try_to_vec()?,
+        ctx.accounts.authority.key(),
+    )?;
+    require!(amount > 0, ErrorCode::InvalidAmount);
+    require!(
+        ctx.accounts.recipient_token_account.owner == recipient,
+        ErrorCode::UnauthorizedAccess
+    );
+    require!(
+        ctx.accounts.fee_vault.amount >= amount,
+        ErrorCode::InsufficientVaultBalance
+    );
+
+    let seeds = &[b"fee_vault".as_ref(), mint_key.as_ref(), &[ctx.bumps.fee_vault]];
+    let signer_seeds = &[&seeds[..]];
+
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token_interface::TransferChecked {
+                from: ctx.accounts.fee_vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.recipient_token_account.to_account_info(),
+                authority: ctx.accounts.fee_vault.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        amount,
+        ctx.accounts.mint.decimals,
+    )?;
+
+    msg!("Withdrew {} from fee vault to {}", amount, recipient);
+
+    emit!(FeesWithdrawn {
+        mint: mint_key,
+        amount,
+        recipient,
+        withdrawn_by: ctx.accounts.authority.key(),
         timestamp: Clock::get()?.unix_timestamp,
     });
 
@@ -144,13 +315,26 @@ pub fn create_subscription(
     merchant_name: String, // Merchant's app/business name for notifications (max 32 chars)
     reminder_days_before_payment: u32, // Days before payment to send reminder (merchant configured)
     icp_canister_signature: [u8; 64], // Ed25519 signature from ICP canister
+    grace_period_seconds: Option<i64>, // Tolerance window for late/early triggers (defaults to 0)
+    trial_end_time: Option<i64>, // Free trial end timestamp - first payment is due at this time
+    max_payments: Option<u64>, // Installment plan length (None = open-ended)
+    use_token_2022: bool, // Pay through Token-2022 instead of Token v1
+    expires_at: Option<i64>, // Contract end for time-limited subscriptions (None = no expiry)
+    authorization_mode_override: Option<AuthorizationMode>, // Per-subscription mode (None = global config mode)
 ) -> Result<()> {
     require!(!ctx.accounts.config.paused, ErrorCode::ProgramPaused);
 
+    // Token program must match the requested token standard
+    require!(
+        ctx.accounts.token_program.key() == get_token_program_id(use_token_2022),
+        ErrorCode::InvalidTokenProgram
+    );
+
     // Enhanced input validation
     require!(amount > 0, ErrorCode::InvalidAmount);
     require!(amount >= 1000, ErrorCode::InvalidAmount); // Minimum 0.001 USDC
     require!(amount <= 1_000_000_000_000_000, ErrorCode::InvalidAmount); // Maximum 1B USDC
+    require!(amount > ctx.accounts.config.fee_config.min_fee_amount, ErrorCode::PaymentTooSmall);
 
     // Interval validation: -1 for one-time, or >= 10 seconds for recurring (10s for demo purposes)
     require!(interval_seconds == -1 || interval_seconds >= 10, ErrorCode::InvalidInterval);
@@ -174,13 +358,55 @@ pub fn create_subscription(
     // Enhanced reminder days validation
     require!(reminder_days_before_payment > 0 && reminder_days_before_payment <= MAX_REMINDER_DAYS, ErrorCode::InvalidReminderDays);
 
+    // Grace period validation: must be shorter than the billing interval
+    let grace_period_seconds = grace_period_seconds.unwrap_or(0);
+    require!(grace_period_seconds >= 0, ErrorCode::InvalidGracePeriod);
+    require!(
+        interval_seconds == -1 || grace_period_seconds < interval_seconds,
+        ErrorCode::InvalidGracePeriod
+    );
+
+    // Installment validation: a plan must have at least one payment
+    require!(max_payments != Some(0), ErrorCode::InvalidMaxPayments);
+
     // Additional security: Prevent unreasonable payment amounts
     let amount_usdc = amount as f64 / 1_000_000.0;
     require!(amount_usdc <= 1_000_000.0, ErrorCode::InvalidAmount); // Max $1M per payment
 
+    // Merchant opt-in: only approved subscribers if the merchant requires it
+    check_merchant_approval(
+        &ctx.accounts.merchant_config,
+        &merchant_address,
+        &ctx.accounts.subscriber.key(),
+        ctx.program_id,
+    )?;
+
+    // Spam deterrent: creating a subscription costs a small SOL fee unless the admin waived it
+    charge_creation_fee(
+        ctx.accounts.config.creation_fee_lamports,
+        ctx.accounts.config_authority.as_ref(),
+        &ctx.accounts.subscriber,
+        &ctx.accounts.system_program,
+        &subscription_id,
+    )?;
+
     let subscription = &mut ctx.accounts.subscription;
     let clock = Clock::get()?;
 
+    // Trial validation: must end in the future, at most one year from now
+    if let Some(trial_end) = trial_end_time {
+        require!(trial_end > clock.unix_timestamp, ErrorCode::InvalidTrialEndTime);
+        require!(
+            trial_end <= clock.unix_timestamp + 365 * 24 * 60 * 60,
+            ErrorCode::InvalidTrialEndTime
+        );
+    }
+
+    // Expiry validation: must be in the future
+    if let Some(expiry) = expires_at {
+        require!(expiry > clock.unix_timestamp, ErrorCode::InvalidExpiry);
+    }
+
     // Derive escrow PDA for this subscription
     let (escrow_pda, _bump) = crate::constants::derive_escrow_pda(&subscription_id, ctx.program_id);
 
@@ -190,9 +416,13 @@ pub fn create_subscription(
     subscription.merchant_name = merchant_name.clone(); // Store merchant name for notifications
     subscription.amount = amount; // Amount merchant receives in USDC
     subscription.interval_seconds = interval_seconds;
+    refresh_integrity_hash(subscription);
+    // With a free trial, the first payment is due when the trial ends
     // For one-time payments (interval = -1), payment is due immediately
     // For recurring, payment is due after the interval
-    subscription.next_payment_time = if interval_seconds == -1 {
+    subscription.next_payment_time = if let Some(trial_end) = trial_end_time {
+        trial_end // Trial: due when trial ends
+    } else if interval_seconds == -1 {
         clock.unix_timestamp // One-time: due immediately
     } else {
         clock.unix_timestamp + interval_seconds // Recurring: due after interval
@@ -200,17 +430,36 @@ pub fn create_subscription(
     subscription.status = SubscriptionStatus::Active;
     subscription.created_at = clock.unix_timestamp;
     subscription.payments_made = 0;
+    subscription.version = 0;
     subscription.total_paid = 0;
     subscription.icp_canister_signature = icp_canister_signature;
     subscription.reminder_days_before_payment = reminder_days_before_payment; // Merchant-configured reminder timing
     subscription.escrow_pda = escrow_pda; // Store escrow PDA for off-ramp integration
     subscription.escrow_balance = 0; // Initial balance is 0
+    subscription.grace_period_seconds = grace_period_seconds;
+    subscription.trial_end_time = trial_end_time;
+    subscription.max_payments = max_payments;
+    subscription.remaining_payments = max_payments; // Counts down with each payment
+    subscription.use_token_2022 = use_token_2022;
+    subscription.expires_at = expires_at;
+    subscription.paused_by = PausedBy::Subscriber;
+    subscription.authorization_mode_override = authorization_mode_override; // Fixed for the subscription's lifetime
+    subscription.spending_cap_per_period = 0; // No cap until set_spending_cap
+    subscription.period_start = clock.unix_timestamp;
+    subscription.spent_in_period = 0;
+    subscription.slippage_bps = DEFAULT_SLIPPAGE_BPS;
+    subscription.slippage_updated_at = None;
+    subscription.disputed = false;
+    subscription.dispute_payment_number = 0;
+    subscription.last_ruled_payment_number = 0;
+    subscription.vault_fees_paid = 0;
 
     // Automatically approve delegation (one-click UX improvement)
     // Calculate one year of delegation to minimize user interactions
     let delegation_amount = crate::constants::calculate_one_year_delegation(amount, interval_seconds)?;
 
-    let cpi_accounts = token::Approve {
+    // token_interface routes the CPI to whichever token program owns the account
+    let cpi_accounts = token_interface::Approve {
         to: ctx.accounts.subscriber_token_account.to_account_info(),
         delegate: ctx.accounts.subscription_pda.to_account_info(),
         authority: ctx.accounts.subscriber.to_account_info(),
@@ -219,7 +468,7 @@ pub fn create_subscription(
     let cpi_program = ctx.accounts.token_program.to_account_info();
     let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
 
-    token::approve(cpi_ctx, delegation_amount)?;
+    token_interface::approve(cpi_ctx, delegation_amount)?;
 
     msg!(
         "Auto-approved subscription PDA {} to spend {} USDC ({} USDC × {} payments ≈ 1 year)",
@@ -229,6 +478,18 @@ pub fn create_subscription(
         delegation_amount / amount.max(1)
     );
 
+    // Index the new subscription under its subscriber and merchant
+    let index_key = subscription_id_key(&subscription.id);
+    let subscriber_index = &mut ctx.accounts.subscriber_index;
+    subscriber_index.subscriber = ctx.accounts.subscriber.key();
+    subscriber_index.count = insert_index_entry(&mut subscriber_index.subscription_ids, index_key)?;
+    let merchant_index = &mut ctx.accounts.merchant_index;
+    merchant_index.merchant = merchant_address;
+    merchant_index.count = insert_index_entry(&mut merchant_index.subscription_ids, index_key)?;
+    let dashboard = &mut ctx.accounts.merchant_dashboard;
+    dashboard.merchant = merchant_address;
+    dashboard.active_subscriptions = dashboard.active_subscriptions.saturating_add(1);
+
     // Update global config
     ctx.accounts.config.total_subscriptions += 1;
 
@@ -253,6 +514,193 @@ pub fn create_subscription(
     Ok(())
 }
 
+/// Transfer the SOL creation fee from the subscriber to the config authority (no-op when waived)
+fn charge_creation_fee<'info>(
+    creation_fee: u64,
+    config_authority: Option<&UncheckedAccount<'info>>,
+    subscriber: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    subscription_id: &str,
+) -> Result<()> {
+    if creation_fee == 0 {
+        return Ok(());
+    }
+
+    let fee_recipient = config_authority.ok_or(ErrorCode::MissingCreationFeeRecipient)?;
+
+    let transfer_ix = anchor_lang::solana_program::system_instruction::transfer(
+        &subscriber.key(),
+        &fee_recipient.key(),
+        creation_fee,
+    );
+    anchor_lang::solana_program::program::invoke(
+        &transfer_ix,
+        &[
+            subscriber.to_account_info(),
+            fee_recipient.to_account_info(),
+            system_program.to_account_info(),
+        ],
+    )?;
+
+    emit!(SubscriptionCreationFeeCharged {
+        subscription_id: subscription_id.to_string(),
+        subscriber: subscriber.key(),
+        fee_lamports: creation_fee,
+    });
+
+    Ok(())
+}
+
+/// Clone a subscription from an existing template (same plan, fresh state)
+/// Copies amount, interval, merchant, reminder timing, and merchant name
+pub fn clone_subscription(
+    ctx: Context<crate::CloneSubscription>,
+    template_id: String,
+    new_subscription_id: String,
+) -> Result<()> {
+    require!(!ctx.accounts.config.paused, ErrorCode::ProgramPaused);
+
+    let template = &ctx.accounts.template_subscription;
+    require!(
+        template.status == SubscriptionStatus::Active || template.status == SubscriptionStatus::Paused,
+        ErrorCode::InvalidCloneTemplate
+    );
+
+    // Token program must match the template's token standard
+    require!(
+        ctx.accounts.token_program.key() == get_token_program_id(template.use_token_2022),
+        ErrorCode::InvalidTokenProgram
+    );
+
+    // Validate new subscription ID format and content
+    require!(!new_subscription_id.is_empty(), ErrorCode::InvalidSubscriptionId);
+    require!(new_subscription_id.len() <= 32, ErrorCode::InvalidSubscriptionId);
+    require!(
+        new_subscription_id.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-'),
+        ErrorCode::InvalidSubscriptionId
+    );
+
+    let amount = template.amount;
+    let interval_seconds = template.interval_seconds;
+    let merchant_address = template.merchant;
+    let slippage_bps = template.slippage_bps;
+
+    // Merchant opt-in applies to clones as well
+    check_merchant_approval(
+        &ctx.accounts.merchant_config,
+        &merchant_address,
+        &ctx.accounts.subscriber.key(),
+        ctx.program_id,
+    )?;
+
+    // Clones are new subscriptions, so they pay the same creation fee
+    charge_creation_fee(
+        ctx.accounts.config.creation_fee_lamports,
+        ctx.accounts.config_authority.as_ref(),
+        &ctx.accounts.subscriber,
+        &ctx.accounts.system_program,
+        &new_subscription_id,
+    )?;
+    let template = &ctx.accounts.template_subscription;
+    let merchant_name = template.merchant_name.clone();
+    let reminder_days_before_payment = template.reminder_days_before_payment;
+    let use_token_2022 = template.use_token_2022;
+
+    let subscription = &mut ctx.accounts.subscription;
+    let clock = Clock::get()?;
+
+    // Derive escrow PDA for the new subscription
+    let (escrow_pda, _bump) = crate::constants::derive_escrow_pda(&new_subscription_id, ctx.program_id);
+
+    subscription.id = new_subscription_id.clone();
+    subscription.subscriber = ctx.accounts.subscriber.key();
+    subscription.merchant = merchant_address;
+    subscription.merchant_name = merchant_name;
+    subscription.amount = amount;
+    subscription.interval_seconds = interval_seconds;
+    refresh_integrity_hash(subscription);
+    subscription.next_payment_time = if interval_seconds == -1 {
+        clock.unix_timestamp // One-time: due immediately
+    } else {
+        clock.unix_timestamp + interval_seconds // Recurring: due after interval
+    };
+    subscription.status = SubscriptionStatus::Active;
+    subscription.created_at = clock.unix_timestamp;
+    subscription.payments_made = 0;
+    subscription.version = 0;
+    subscription.total_paid = 0;
+    subscription.icp_canister_signature = [0u8; 64]; // Template signature is bound to the template ID
+    subscription.reminder_days_before_payment = reminder_days_before_payment;
+    subscription.escrow_pda = escrow_pda;
+    subscription.escrow_balance = 0;
+    subscription.grace_period_seconds = 0;
+    subscription.trial_end_time = None;
+    subscription.max_payments = None;
+    subscription.remaining_payments = None;
+    subscription.use_token_2022 = use_token_2022;
+    subscription.last_notification_time = None;
+    subscription.paused_at = None;
+    subscription.expires_at = None;
+    subscription.paused_by = PausedBy::Subscriber;
+    subscription.authorization_mode_override = None;
+    subscription.spending_cap_per_period = 0;
+    subscription.period_start = clock.unix_timestamp;
+    subscription.spent_in_period = 0;
+    subscription.slippage_bps = slippage_bps;
+    subscription.slippage_updated_at = None;
+    subscription.disputed = false;
+    subscription.dispute_payment_number = 0;
+    subscription.last_ruled_payment_number = 0;
+    subscription.vault_fees_paid = 0;
+
+    // Automatically approve delegation, same as create_subscription
+    let delegation_amount = crate::constants::calculate_one_year_delegation(amount, interval_seconds)?;
+
+    let cpi_accounts = token_interface::Approve {
+        to: ctx.accounts.subscriber_token_account.to_account_info(),
+        delegate: ctx.accounts.subscription_pda.to_account_info(),
+        authority: ctx.accounts.subscriber.to_account_info(),
+    };
+
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+    token_interface::approve(cpi_ctx, delegation_amount)?;
+
+    // Index the new subscription under its subscriber and merchant
+    let index_key = subscription_id_key(&subscription.id);
+    let subscriber_index = &mut ctx.accounts.subscriber_index;
+    subscriber_index.subscriber = ctx.accounts.subscriber.key();
+    subscriber_index.count = insert_index_entry(&mut subscriber_index.subscription_ids, index_key)?;
+    let merchant_index = &mut ctx.accounts.merchant_index;
+    merchant_index.merchant = merchant_address;
+    merchant_index.count = insert_index_entry(&mut merchant_index.subscription_ids, index_key)?;
+    let dashboard = &mut ctx.accounts.merchant_dashboard;
+    dashboard.merchant = merchant_address;
+    dashboard.active_subscriptions = dashboard.active_subscriptions.saturating_add(1);
+
+    // Update global config
+    ctx.accounts.config.total_subscriptions += 1;
+
+    msg!(
+        "Subscription {} cloned from template {}: {} USDC every {} seconds",
+        new_subscription_id,
+        template_id,
+        amount,
+        interval_seconds
+    );
+
+    emit!(SubscriptionCreated {
+        subscription_id: new_subscription_id,
+        subscriber: ctx.accounts.subscriber.key(),
+        merchant: merchant_address,
+        amount,
+        interval_seconds,
+    });
+
+    Ok(())
+}
+
 /// Process payment with automatic swap (Router function for multi-token support)
 /// COMMENTED OUT - Only USDC supported
 /*
@@ -276,14 +724,26 @@ pub fn process_payment_with_swap<'info>(
         msg!("Payment token is non-USDC ({}), swapping to USDC via Jupiter", usdc_mint_str);
 
         // Step 1: Get price from Pyth oracle for validation
+        // Falls back to the optional Switchboard feed, then a conservative minimum, when Pyth is stale
         let price_feed = &ctx.accounts.price_feed;
-        let conversion = crate::price_oracle::get_price_conversion(
+        let switchboard_feed = ctx.accounts.switchboard_feed.as_ref().map(|feed| feed.to_account_info());
+        let (conversion, fallback_level) = crate::price_oracle::get_price_conversion_with_fallback(
             &subscription.payment_token_mint,
             subscription.amount,
             price_feed,
+            switchboard_feed.as_ref(),
             subscription.slippage_bps, // Use subscription's configured slippage
+            ctx.accounts.config.oracle_max_age,
+            Clock::get()?.unix_timestamp,
         )?;
 
+        if fallback_level != ORACLE_FALLBACK_PYTH {
+            emit!(OracleFallbackUsed {
+                subscription_id: subscription.id.clone(),
+                fallback_level,
+            });
+        }
+
         // Step 2: Validate price confidence
         crate::price_oracle::validate_price_confidence(&conversion)?;
 
@@ -343,168 +803,1299 @@ pub fn process_payment_with_swap<'info>(
 
 /// Process payment for a subscription (supports multiple authorization modes)
 /// Standard entry point for USDC-only subscriptions
-pub fn process_payment(
-    ctx: Context<crate::ProcessPayment>,
+pub fn process_payment<'info>(
+    ctx: Context<'_, '_, '_, 'info, crate::ProcessPayment<'info>>,
     icp_signature: Option<[u8; 64]>,
     timestamp: i64,
+    expected_version: u64,
 ) -> Result<()> {
-    process_payment_core(
-        &mut ctx.accounts.subscription,
-        &ctx.accounts.config,
-        &ctx.accounts.trigger_authority,
-        &ctx.accounts.subscriber_token_account,
-        &ctx.accounts.merchant_token_account,
-        &ctx.accounts.icp_fee_token_account,
-        &ctx.accounts.token_program,
+    process_payment_accounts(ctx.accounts, ctx.program_id, icp_signature, timestamp, expected_version, ctx.remaining_accounts)
+}
+
+/// Process payment, then attach an "OuroC: <merchant> #<n>" memo to the same transaction
+pub fn process_payment_with_memo<'info>(
+    ctx: Context<'_, '_, '_, 'info, crate::ProcessPaymentWithMemo<'info>>,
+    icp_signature: Option<[u8; 64]>,
+    timestamp: i64,
+    expected_version: u64,
+) -> Result<()> {
+    let payments_before = ctx.accounts.payment.subscription.payments_made;
+    process_payment_accounts(
+        &mut ctx.accounts.payment,
         ctx.program_id,
         icp_signature,
         timestamp,
-        &ctx.accounts.instructions_sysvar,
-    )
+        expected_version,
+        ctx.remaining_accounts,
+    )?;
+
+    // No memo when nothing was charged (e.g. the subscription expired instead)
+    let subscription = &ctx.accounts.payment.subscription;
+    if subscription.payments_made > payments_before {
+        send_payment_memo(
+            &ctx.accounts.memo_program.to_account_info(),
+            &subscription.merchant_name,
+            subscription.payments_made,
+        )?;
+    }
+    Ok(())
 }
 
-/// Pause a subscription
-pub fn pause_subscription(ctx: Context<crate::UpdateSubscription>) -> Result<()> {
-    let subscription = &mut ctx.accounts.subscription;
-    require!(subscription.status == SubscriptionStatus::Active, ErrorCode::SubscriptionNotActive);
+fn process_payment_accounts<'info>(
+    accounts: &mut crate::ProcessPayment<'info>,
+    program_id: &Pubkey,
+    icp_signature: Option<[u8; 64]>,
+    timestamp: i64,
+    expected_version: u64,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    claim_subscription_version(&mut accounts.subscription, expected_version)?;
+    process_payment_core(
+        &mut accounts.subscription,
+        &accounts.config,
+        &accounts.trigger_authority,
+        &accounts.subscriber_token_account,
+        &accounts.merchant_token_account,
+        &accounts.fee_vault,
+        &accounts.usdc_mint,
+        &accounts.token_program,
+        program_id,
+        icp_signature,
+        timestamp,
+        &accounts.instructions_sysvar,
+        remaining_accounts,
+        &accounts.payment_nonce,
+        accounts.previous_payment_nonce.as_ref(),
+        accounts.used_nonce.as_ref(),
+        &accounts.system_program,
+        &mut accounts.fee_analytics,
+        &accounts.merchant_dashboard,
+        accounts.subscriber_reputation.as_ref(),
+    )
+}
 
-    let clock = Clock::get()?;
-    let subscription_id = subscription.id.clone();
+/// Record a payment attempt that failed off-chain (e.g. insufficient funds), authority only
+/// Failed transfers revert their own transaction, so the ICP timer reports them here instead
+pub fn record_failed_payment(ctx: Context<crate::RecordFailedPayment>, subscription_id: String) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    let current_time = Clock::get()?.unix_timestamp;
 
-    subscription.status = SubscriptionStatus::Paused;
+    let score = record_subscriber_reputation(
+        &ctx.accounts.subscriber_reputation,
+        &ctx.accounts.authority,
+        &ctx.accounts.system_program,
+        &subscription.subscriber,
+        PaymentOutcome::Failed,
+        current_time,
+        ctx.program_id,
+    )?;
 
-    msg!("Subscription {} paused", subscription_id);
+    if let Some(dashboard) = ctx.accounts.merchant_dashboard.as_mut() {
+        dashboard.failed_payments = dashboard.failed_payments.saturating_add(1);
+    }
 
-    emit!(SubscriptionPaused {
-        subscription_id,
-        paused_at: clock.unix_timestamp,
+    emit!(PaymentFailureRecorded {
+        subscription_id: subscription_id.clone(),
+        subscriber: subscription.subscriber,
+        reputation_score: score,
+        recorded_at: current_time,
     });
 
+    msg!("Failed payment recorded for {}: reputation {}", subscription_id, score);
     Ok(())
 }
 
-/// Resume a subscription
-pub fn resume_subscription(ctx: Context<crate::UpdateSubscription>) -> Result<()> {
-    let subscription = &mut ctx.accounts.subscription;
-    require!(subscription.status == SubscriptionStatus::Paused, ErrorCode::SubscriptionNotPaused);
-
-    let clock = Clock::get()?;
-    let subscription_id = subscription.id.clone();
+/// Close expired UsedNonce PDAs and return their rent to the original payer
+/// A nonce is expired once its signed timestamp can no longer pass verify_timestamp
+pub fn prune_expired_nonces<'info>(
+    ctx: Context<'_, '_, 'info, 'info, crate::PruneExpiredNonces<'info>>,
+) -> Result<()> {
+    let current_time = Clock::get()?.unix_timestamp;
+    let rent_receiver = ctx.accounts.rent_receiver.to_account_info();
 
-    subscription.status = SubscriptionStatus::Active;
-    subscription.next_payment_time = clock.unix_timestamp + subscription.interval_seconds;
+    for nonce_info in ctx.remaining_accounts.iter() {
+        {
+            let used_nonce: Account<UsedNonce> = Account::try_from(nonce_info)?;
+            require!(used_nonce.payer == rent_receiver.key(), ErrorCode::UnauthorizedAccess);
+            require!(
+                is_used_nonce_expired(used_nonce.timestamp, current_time),
+                ErrorCode::NonceNotExpired
+            );
+        }
 
-    msg!("Subscription {} resumed", subscription_id);
+        close_program_account(nonce_info, &rent_receiver)?;
+    }
 
-    emit!(SubscriptionResumed {
-        subscription_id,
-        resumed_at: clock.unix_timestamp,
-    });
+    msg!("Pruned {} expired nonces", ctx.remaining_accounts.len());
 
     Ok(())
 }
 
-/// Cancel a subscription
-pub fn cancel_subscription(ctx: Context<crate::UpdateSubscription>) -> Result<()> {
-    let subscription = &mut ctx.accounts.subscription;
+/// Process several subscription payments in one transaction
+/// Each entry is checked independently; entries that are not payable are reported
+/// in the BatchPaymentProcessed event instead of failing the whole batch
+pub fn batch_process_payments<'info>(
+    ctx: Context<'_, '_, 'info, 'info, crate::BatchProcessPayments<'info>>,
+    timestamp: i64,
+) -> Result<()> {
+    let config = &ctx.accounts.config;
+    require!(!config.paused, ErrorCode::ProgramPaused);
     require!(
-        subscription.status == SubscriptionStatus::Active ||
-        subscription.status == SubscriptionStatus::Paused,
-        ErrorCode::SubscriptionAlreadyCancelled
+        config.icp_fee_collection_address.is_some(),
+        ErrorCode::FeeCollectionAddressNotSet
+    );
+
+    // Batches are authorized by a single ICP signature over every entry
+    require!(
+        config.authorization_mode == AuthorizationMode::ICPSignature,
+        ErrorCode::AuthorizationFailed
+    );
+
+    // Entries come first, then the fee recipients' token accounts shared by every entry
+    let recipient_count = config.fee_config.recipient_count as usize;
+    let entry_accounts_len = ctx.remaining_accounts.len().saturating_sub(recipient_count);
+    let (entry_accounts, fee_recipient_accounts) = ctx.remaining_accounts.split_at(entry_accounts_len);
+    require!(
+        !entry_accounts.is_empty()
+            && entry_accounts.len() % BATCH_ACCOUNTS_PER_ENTRY == 0
+            && entry_accounts.len() / BATCH_ACCOUNTS_PER_ENTRY <= MAX_BATCH_SIZE,
+        ErrorCode::InvalidBatchSize
     );
 
     let clock = Clock::get()?;
-    let subscription_id = subscription.id.clone();
-    let total_payments = subscription.payments_made;
-    let total = subscription.total_paid;
+    require!(
+        verify_timestamp(timestamp, clock.unix_timestamp, 300)?,
+        ErrorCode::TimestampExpired
+    );
 
-    subscription.status = SubscriptionStatus::Cancelled;
+    // A subscription listed twice would be charged twice under one signature
+    let entry_keys: Vec<Pubkey> = entry_accounts
+        .chunks(BATCH_ACCOUNTS_PER_ENTRY)
+        .map(|entry| entry[0].key())
+        .collect();
+    require!(!has_duplicate_keys(&entry_keys), ErrorCode::DuplicateBatchEntry);
+
+    let mut subscriptions = Vec::with_capacity(entry_keys.len());
+    for entry in entry_accounts.chunks(BATCH_ACCOUNTS_PER_ENTRY) {
+        require!(entry[0].is_writable, ErrorCode::InvalidSubscriptionPDA);
+        subscriptions.push(Account::<Subscription>::try_from(&entry[0])?);
+    }
 
-    msg!("Subscription {} cancelled", subscription_id);
+    // Verify the ICP canister signed the whole batch
+    let payments: Vec<(String, u64)> = subscriptions
+        .iter()
+        .map(|subscription| (subscription.id.clone(), subscription.amount))
+        .collect();
+    let message = create_batch_payment_message(&payments, timestamp);
+    let icp_public_keys = accepted_icp_keys(config, clock.unix_timestamp);
+    require!(!icp_public_keys.is_empty(), ErrorCode::MissingICPKey);
+    require!(
+        verify_ed25519_ix_any(&ctx.accounts.instructions_sysvar, &icp_public_keys, &message)?,
+        ErrorCode::InvalidSignature
+    );
 
-    emit!(SubscriptionCancelled {
-        subscription_id,
-        cancelled_at: clock.unix_timestamp,
-        total_payments_made: total_payments,
-        total_paid: total,
+    let mut results = Vec::with_capacity(subscriptions.len());
+    let mut successful_count: u8 = 0;
+
+    for (index, (subscription, entry)) in subscriptions
+        .iter_mut()
+        .zip(entry_accounts.chunks(BATCH_ACCOUNTS_PER_ENTRY))
+        .enumerate()
+    {
+        let outcome = process_batch_entry(
+            subscription,
+            entry,
+            fee_recipient_accounts,
+            &mut *ctx.accounts,
+            ctx.program_id,
+            timestamp,
+            clock.unix_timestamp,
+        );
+
+        // An entry that ran out is persisted as Expired but reported as not paid
+        let error_code = match outcome {
+            Ok(true) => None,
+            Ok(false) => Some(u32::from(ErrorCode::SubscriptionExpired)),
+            Err(anchor_lang::error::Error::AnchorError(error)) => Some(error.error_code_number),
+            Err(anchor_lang::error::Error::ProgramError(error)) => Some(u64::from(error.program_error) as u32),
+        };
+
+        if error_code.is_none() {
+            successful_count += 1;
+        } else {
+            msg!("Batch entry {} ({}) skipped: {:?}", index, subscription.id, error_code);
+        }
+
+        results.push(BatchPaymentResult {
+            index: index as u8,
+            success: error_code.is_none(),
+            error_code,
+        });
+    }
+
+    msg!("Batch processed: {}/{} payments succeeded", successful_count, results.len());
+
+    emit!(BatchPaymentProcessed {
+        results,
+        successful_count,
+        timestamp: clock.unix_timestamp,
     });
 
     Ok(())
 }
 
-/// Revoke subscription PDA delegate (after cancellation)
-pub fn revoke_subscription_delegate(
-    ctx: Context<crate::RevokeDelegate>,
+/// Validate and pay a single batch entry through settle_payment, persisting the subscription on success
+/// Returns false when the entry had run out and was expired instead of paid
+/// validate_settlement runs before the used nonce is written, so an entry skipped by a failed check can be retried
+fn process_batch_entry<'info>(
+    subscription: &mut Account<'info, Subscription>,
+    entry: &'info [AccountInfo<'info>],
+    fee_recipient_accounts: &'info [AccountInfo<'info>],
+    accounts: &mut crate::BatchProcessPayments<'info>,
+    program_id: &Pubkey,
+    signed_timestamp: i64,
+    current_time: i64,
+) -> Result<bool> {
+    let config = &accounts.config;
+    require!(subscription.status == SubscriptionStatus::Active, ErrorCode::SubscriptionNotActive);
+    verify_subscription_integrity(subscription)?;
+    require!(!subscription.disputed, ErrorCode::SubscriptionDisputed);
+    require!(!subscription.use_token_2022, ErrorCode::InvalidTokenProgram);
+    // Batches are ICP-signed; a subscription pinned to another mode is not payable here
+    require!(
+        effective_authorization_mode(subscription.authorization_mode_override, config.authorization_mode)
+            == AuthorizationMode::ICPSignature,
+        ErrorCode::AuthorizationFailed
+    );
+
+    // Time-limited subscription reached its end: expire instead of charging, as process_payment does
+    if expire_if_run_out(subscription, current_time) {
+        let merchant_dashboard = UncheckedAccount::try_from(&entry[5]);
+        update_merchant_dashboard(&merchant_dashboard, &subscription.merchant, program_id, |d| {
+            d.active_subscriptions = d.active_subscriptions.saturating_sub(1);
+            Ok(())
+        })?;
+        bump_subscription_version(subscription)?;
+        subscription.exit(program_id)?;
+        return Ok(false);
+    }
+    require!(
+        !is_trial_active(subscription.trial_end_time, current_time),
+        ErrorCode::TrialPeriodActive
+    );
+    require!(
+        is_payment_due(current_time, subscription.next_payment_time, subscription.grace_period_seconds),
+        ErrorCode::PaymentNotDue
+    );
+
+    let subscriber_token_account =
+        InterfaceAccount::<token_interface::TokenAccount>::try_from(&entry[1])?;
+    let merchant_token_account =
+        InterfaceAccount::<token_interface::TokenAccount>::try_from(&entry[2])?;
+
+    require!(subscriber_token_account.owner == subscription.subscriber, ErrorCode::UnauthorizedAccess);
+    require!(subscriber_token_account.mint == accounts.usdc_mint.key(), ErrorCode::InvalidTokenMint);
+    require!(
+        subscriber_token_account.delegated_amount >= subscription.amount,
+        ErrorCode::InsufficientDelegation
+    );
+    require!(merchant_token_account.owner == subscription.merchant, ErrorCode::UnauthorizedAccess);
+    require!(merchant_token_account.mint == accounts.usdc_mint.key(), ErrorCode::InvalidTokenMint);
+
+    let payment_nonce = UncheckedAccount::try_from(&entry[3]);
+    let used_nonce = UncheckedAccount::try_from(&entry[4]);
+    let merchant_dashboard = UncheckedAccount::try_from(&entry[5]);
+    let subscriber_reputation = UncheckedAccount::try_from(&entry[6]);
+    let previous_payment_nonce = UncheckedAccount::try_from(&entry[7]);
+
+    let settlement_accounts = SettlementAccounts {
+        config,
+        payer: &accounts.trigger_authority,
+        subscriber_token_account: &subscriber_token_account,
+        merchant_destination: &entry[2],
+        fee_vault: &accounts.fee_vault,
+        payment_mint: &accounts.usdc_mint,
+        token_program: &accounts.token_program,
+        system_program: &accounts.system_program,
+        fee_recipient_accounts,
+        payment_nonce: &payment_nonce,
+        previous_payment_nonce: Some(&previous_payment_nonce),
+        merchant_dashboard: &merchant_dashboard,
+        subscriber_reputation: Some(&subscriber_reputation),
+    };
+    let settlement = validate_settlement(subscription, &settlement_accounts, program_id, current_time)?;
+
+    // The batch signature is single-use per subscription, like a process_payment signature
+    create_used_nonce(
+        &used_nonce,
+        &NonceAccounts { payer: &accounts.trigger_authority, system_program: &accounts.system_program, program_id },
+        &subscription.id,
+        subscription.key(),
+        signed_timestamp,
+        current_time,
+    )?;
+
+    settle_payment(
+        subscription,
+        &settlement_accounts,
+        settlement,
+        &mut accounts.fee_analytics,
+        program_id,
+        current_time,
+    )?;
+
+    // Batch entries carry no expected version, but still invalidate triggers built before them
+    bump_subscription_version(subscription)?;
+    subscription.exit(program_id)?;
+
+    Ok(true)
+}
+
+/// Pause a subscription
+pub fn pause_subscription(ctx: Context<crate::UpdateSubscription>) -> Result<()> {
+    pause_subscription_by(&mut ctx.accounts.subscription, PausedBy::Subscriber)
+}
+
+/// Set the subscriber's spending cap per billing period (0 removes the cap)
+pub fn set_spending_cap(ctx: Context<crate::UpdateSubscription>, spending_cap_per_period: u64) -> Result<()> {
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.spending_cap_per_period = spending_cap_per_period;
+
+    msg!(
+        "Subscription {} spending cap set to {} per period",
+        subscription.id,
+        spending_cap_per_period
+    );
+
+    Ok(())
+}
+
+/// Change the swap slippage tolerance (subscriber only, once per 24 hours)
+pub fn update_slippage_bps(ctx: Context<crate::UpdateSlippage>, new_slippage: u16) -> Result<()> {
+    require!(
+        new_slippage > 0 && new_slippage <= MAX_SLIPPAGE_BPS,
+        ErrorCode::InvalidSlippage
+    );
+
+    let subscription = &mut ctx.accounts.subscription;
+    let current_time = Clock::get()?.unix_timestamp;
+    require!(
+        is_slippage_update_allowed(subscription.slippage_updated_at, current_time),
+        ErrorCode::SlippageUpdateCooldown
+    );
+
+    let old_bps = subscription.slippage_bps;
+    subscription.slippage_bps = new_slippage;
+    subscription.slippage_updated_at = Some(current_time);
+
+    msg!("Subscription {} slippage updated: {} -> {} bps", subscription.id, old_bps, new_slippage);
+
+    emit!(SlippageUpdated {
+        subscription_id: subscription.id.clone(),
+        old_bps,
+        new_bps: new_slippage,
+    });
+
+    Ok(())
+}
+
+/// Top the delegation back up to one year of payments (subscriber only)
+/// SPL approve replaces the allowance, so the full year is approved rather than added to what is left
+pub fn auto_renew_delegation(ctx: Context<crate::AutoRenewDelegation>) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    require!(
+        subscription.status == SubscriptionStatus::Active || subscription.status == SubscriptionStatus::Paused,
+        ErrorCode::SubscriptionNotActive
+    );
+
+    // An allowance granted to some other delegate doesn't count towards this subscription
+    let token_account = &ctx.accounts.subscriber_token_account;
+    let previous_delegation = if Option::<Pubkey>::from(token_account.delegate) == Some(subscription.key()) {
+        token_account.delegated_amount
+    } else {
+        0
+    };
+
+    let new_delegation =
+        crate::constants::calculate_one_year_delegation(subscription.amount, subscription.interval_seconds)?;
+    let top_up = new_delegation.saturating_sub(previous_delegation);
+    require!(top_up > 0, ErrorCode::DelegationNotExhausted);
+
+    // The subscription account is the PDA that holds the delegation
+    let cpi_accounts = token_interface::Approve {
+        to: ctx.accounts.subscriber_token_account.to_account_info(),
+        delegate: subscription.to_account_info(),
+        authority: ctx.accounts.subscriber.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+    token_interface::approve(cpi_ctx, new_delegation)?;
+
+    msg!(
+        "Subscription {} delegation renewed: {} -> {} (+{})",
+        subscription.id,
+        previous_delegation,
+        new_delegation,
+        top_up
+    );
+
+    emit!(DelegationRenewed {
+        subscription_id: subscription.id.clone(),
+        previous_delegation,
+        new_delegation,
+    });
+
+    Ok(())
+}
+
+/// Shorten the billing interval (subscriber only), e.g. monthly to weekly
+/// Re-approves one year of delegation at the new interval; longer intervals go through propose_subscription_change
+pub fn update_subscription_interval(ctx: Context<crate::UpdateInterval>, new_interval_seconds: i64) -> Result<()> {
+    let subscription = &mut ctx.accounts.subscription;
+    require!(subscription.status == SubscriptionStatus::Active, ErrorCode::SubscriptionNotActive);
+    require!(subscription.interval_seconds != -1, ErrorCode::InvalidInterval); // One-time payments have no interval
+    require!(
+        is_valid_interval_update(new_interval_seconds, subscription.grace_period_seconds),
+        ErrorCode::InvalidInterval
+    );
+    // Billing less often changes the merchant's terms, so it goes through propose_subscription_change
+    require!(
+        new_interval_seconds <= subscription.interval_seconds,
+        ErrorCode::IntervalIncreaseNeedsProposal
+    );
+
+    let clock = Clock::get()?;
+    // Moving next_payment_time must not skip a charge that is already due
+    require!(
+        !is_payment_due(clock.unix_timestamp, subscription.next_payment_time, subscription.grace_period_seconds),
+        ErrorCode::PaymentDue
+    );
+
+    let new_delegation = crate::constants::calculate_one_year_delegation(subscription.amount, new_interval_seconds)?;
+
+    // The subscription account is the PDA that holds the delegation
+    let cpi_accounts = token_interface::Approve {
+        to: ctx.accounts.subscriber_token_account.to_account_info(),
+        delegate: subscription.to_account_info(),
+        authority: ctx.accounts.subscriber.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+    token_interface::approve(cpi_ctx, new_delegation)?;
+
+    let old_interval = subscription.interval_seconds;
+    subscription.interval_seconds = new_interval_seconds;
+    refresh_integrity_hash(subscription);
+    // A shorter interval never pushes the already scheduled payment further out
+    subscription.next_payment_time = clock
+        .unix_timestamp
+        .checked_add(new_interval_seconds)
+        .ok_or(ErrorCode::MathOverflow)?
+        .min(subscription.next_payment_time);
+
+    msg!(
+        "Subscription {} interval updated: {} -> {} seconds, delegation {}, next payment at {}",
+        subscription.id,
+        old_interval,
+        new_interval_seconds,
+        new_delegation,
+        subscription.next_payment_time
+    );
+
+    emit!(SubscriptionIntervalUpdated {
+        subscription_id: subscription.id.clone(),
+        old_interval,
+        new_interval: new_interval_seconds,
+        new_delegation,
+    });
+
+    Ok(())
+}
+
+/// Skip the next `periods` billing cycles without cancelling (subscriber only)
+pub fn snooze_next_payment(ctx: Context<crate::UpdateSubscription>, periods: u8) -> Result<()> {
+    require!(
+        (1..=MAX_SNOOZE_PERIODS).contains(&periods),
+        ErrorCode::InvalidSnoozePeriods
+    );
+
+    let subscription = &mut ctx.accounts.subscription;
+    require!(subscription.status == SubscriptionStatus::Active, ErrorCode::SubscriptionNotActive);
+
+    let next_payment_time = snoozed_next_payment_time(
+        subscription.next_payment_time,
+        subscription.interval_seconds,
+        periods,
+    ).ok_or(ErrorCode::MathOverflow)?;
+    subscription.next_payment_time = next_payment_time;
+
+    msg!(
+        "Subscription {} snoozed for {} periods, next payment at {}",
+        subscription.id,
+        periods,
+        next_payment_time
+    );
+
+    emit!(SubscriptionSnoozed {
+        subscription_id: subscription.id.clone(),
+        periods,
+        next_payment_time,
+    });
+
+    Ok(())
+}
+
+/// Resume a subscription
+pub fn resume_subscription(ctx: Context<crate::UpdateSubscription>) -> Result<()> {
+    resume_subscription_by(&mut ctx.accounts.subscription, PausedBy::Subscriber)
+}
+
+/// Pause a subscription as the merchant (e.g., suspend service during a dispute)
+pub fn pause_subscription_as_merchant(ctx: Context<crate::MerchantAction>) -> Result<()> {
+    pause_subscription_by(&mut ctx.accounts.subscription, PausedBy::Merchant)
+}
+
+/// Resume a merchant-paused subscription
+pub fn resume_subscription_as_merchant(ctx: Context<crate::MerchantAction>) -> Result<()> {
+    resume_subscription_by(&mut ctx.accounts.subscription, PausedBy::Merchant)
+}
+
+fn pause_subscription_by(subscription: &mut Subscription, paused_by: PausedBy) -> Result<()> {
+    require!(subscription.status == SubscriptionStatus::Active, ErrorCode::SubscriptionNotActive);
+
+    let clock = Clock::get()?;
+    let subscription_id = subscription.id.clone();
+
+    subscription.status = SubscriptionStatus::Paused;
+    subscription.paused_at = Some(clock.unix_timestamp); // Remaining billing time is credited on resume
+    subscription.paused_by = paused_by;
+
+    msg!("Subscription {} paused by {:?}", subscription_id, paused_by);
+
+    emit!(SubscriptionPaused {
+        subscription_id,
+        paused_at: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+fn resume_subscription_by(subscription: &mut Subscription, resumed_by: PausedBy) -> Result<()> {
+    require!(subscription.status == SubscriptionStatus::Paused, ErrorCode::SubscriptionNotPaused);
+    // Symmetry: only the party that paused can resume
+    require!(subscription.paused_by == resumed_by, ErrorCode::WrongPauseAuthority);
+
+    let clock = Clock::get()?;
+    let subscription_id = subscription.id.clone();
+
+    subscription.status = SubscriptionStatus::Active;
+    // Credit the unused part of the interval that was left when the subscription was paused
+    subscription.next_payment_time = calculate_resume_payment_time(
+        subscription.next_payment_time,
+        subscription.paused_at,
+        clock.unix_timestamp,
+    )?;
+    subscription.paused_at = None;
+
+    msg!("Subscription {} resumed by {:?}", subscription_id, resumed_by);
+
+    emit!(SubscriptionResumed {
+        subscription_id,
+        resumed_at: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Cancel a subscription
+pub fn cancel_subscription(ctx: Context<crate::CancelSubscription>) -> Result<()> {
+    let subscription = &mut ctx.accounts.subscription;
+    require!(
+        subscription.status == SubscriptionStatus::Active ||
+        subscription.status == SubscriptionStatus::Paused,
+        ErrorCode::SubscriptionAlreadyCancelled
+    );
+
+    let clock = Clock::get()?;
+    let subscription_id = subscription.id.clone();
+    let total_payments = subscription.payments_made;
+    let total = subscription.total_paid;
+
+    subscription.status = SubscriptionStatus::Cancelled;
+    let dashboard = &mut ctx.accounts.merchant_dashboard;
+    dashboard.merchant = subscription.merchant;
+    dashboard.active_subscriptions = dashboard.active_subscriptions.saturating_sub(1);
+    unindex_subscription(
+        &subscription_id,
+        ctx.accounts.subscriber_index.as_mut(),
+        ctx.accounts.merchant_index.as_mut(),
+    );
+
+    msg!("Subscription {} cancelled", subscription_id);
+
+    emit!(SubscriptionCancelled {
+        subscription_id,
+        cancelled_at: clock.unix_timestamp,
+        total_payments_made: total_payments,
+        total_paid: total,
+    });
+
+    Ok(())
+}
+
+/// Upgrade a subscription written by the original program to the current layout
+/// Anyone can call; the payer funds the extra rent. Already-migrated accounts are rejected by size
+pub fn migrate_subscription(ctx: Context<crate::MigrateSubscription>) -> Result<()> {
+    let subscription_info = ctx.accounts.subscription.to_account_info();
+    require!(subscription_info.owner == ctx.program_id, ErrorCode::InvalidSubscriptionPDA);
+    require!(
+        subscription_info.data_len() == 8 + LegacySubscription::LEN,
+        ErrorCode::AccountAlreadyMigrated
+    );
+
+    let legacy = {
+        let data = subscription_info.try_borrow_data()?;
+        require!(data[..8] == *Subscription::DISCRIMINATOR, ErrorCode::InvalidSubscriptionPDA);
+        LegacySubscription::deserialize(&mut &data[8..])?
+    };
+    let (expected_pda, _bump) = Pubkey::find_program_address(
+        &[b"subscription", legacy.id.as_bytes()],
+        ctx.program_id,
+    );
+    require!(expected_pda == subscription_info.key(), ErrorCode::InvalidSubscriptionPDA);
+
+    let subscription = migrate_legacy_subscription(legacy);
+    grow_program_account(
+        &subscription_info,
+        &ctx.accounts.payer,
+        &ctx.accounts.system_program,
+        8 + Subscription::LEN,
+    )?;
+    subscription.try_serialize(&mut &mut subscription_info.try_borrow_mut_data()?[..])?;
+
+    msg!("Subscription {} migrated to the current layout", subscription.id);
+    Ok(())
+}
+
+/// Upgrade the config written by the original program to the current layout (authority only)
+pub fn migrate_config(ctx: Context<crate::MigrateConfig>) -> Result<()> {
+    let config_info = ctx.accounts.config.to_account_info();
+    require!(config_info.owner == ctx.program_id, ErrorCode::UnauthorizedAccess);
+    require!(
+        config_info.data_len() == 8 + LegacyConfig::LEN,
+        ErrorCode::AccountAlreadyMigrated
+    );
+
+    let legacy = {
+        let data = config_info.try_borrow_data()?;
+        require!(data[..8] == *Config::DISCRIMINATOR, ErrorCode::UnauthorizedAccess);
+        LegacyConfig::deserialize(&mut &data[8..])?
+    };
+    require!(legacy.authority == ctx.accounts.authority.key(), ErrorCode::UnauthorizedAccess);
+
+    let config = migrate_legacy_config(legacy)?;
+    grow_program_account(
+        &config_info,
+        &ctx.accounts.authority,
+        &ctx.accounts.system_program,
+        8 + Config::LEN,
+    )?;
+    config.try_serialize(&mut &mut config_info.try_borrow_mut_data()?[..])?;
+
+    msg!("Config migrated to the current layout; fee cap and recipients use their defaults");
+    Ok(())
+}
+
+/// Close a cancelled subscription account; the `close = subscriber` constraint returns the rent
+/// Only cancelled accounts past the cooldown with an empty escrow can be closed, so active
+/// subscriptions and unclaimed merchant funds are never lost
+pub fn close_subscription(ctx: Context<crate::CloseSubscription>, subscription_id: String) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    require!(subscription.status == SubscriptionStatus::Cancelled, ErrorCode::SubscriptionNotCancelled);
+    require!(subscription.escrow_balance == 0, ErrorCode::EscrowNotEmpty);
+
+    let clock = Clock::get()?;
+    require!(
+        is_close_allowed(subscription.created_at, clock.unix_timestamp),
+        ErrorCode::CloseCooldownActive
+    );
+
+    // Installment plans that finish are cancelled without the index accounts, so drop them here too
+    unindex_subscription(
+        &subscription_id,
+        ctx.accounts.subscriber_index.as_mut(),
+        ctx.accounts.merchant_index.as_mut(),
+    );
+
+    msg!("Subscription {} closed, rent returned to {}", subscription_id, subscription.subscriber);
+
+    emit!(SubscriptionClosed {
+        subscription_id,
+        subscriber: subscription.subscriber,
+        closed_at: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Revoke subscription PDA delegate (after cancellation)
+pub fn revoke_subscription_delegate(
+    ctx: Context<crate::RevokeDelegate>,
 ) -> Result<()> {
     // Revoke the subscription PDA's delegate authority
-    let cpi_accounts = token::Revoke {
+    let cpi_accounts = token_interface::Revoke {
         source: ctx.accounts.subscriber_token_account.to_account_info(),
         authority: ctx.accounts.subscriber.to_account_info(),
     };
 
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+    token_interface::revoke(cpi_ctx)?;
+
+    msg!("Revoked subscription PDA delegate for {}", ctx.accounts.subscription.id);
+    Ok(())
+}
+
+/// Merchant claims USDC from escrow after off-ramp API confirmation
+/// This allows merchants to withdraw funds from escrow once fiat transfer is complete
+pub fn claim_from_escrow(
+    ctx: Context<crate::ClaimFromEscrow>,
+    subscription_id: String,
+    amount: u64,
+) -> Result<()> {
+    let subscription = &mut ctx.accounts.subscription;
+
+    // Validate claim amount
+    require!(amount > 0, ErrorCode::InvalidAmount);
+    require!(amount <= subscription.escrow_balance, ErrorCode::InsufficientAmount);
+
+    // Get escrow PDA bump for signing
+    let (_escrow_pda, bump) = crate::constants::derive_escrow_pda(&subscription_id, ctx.program_id);
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"escrow",
+        subscription_id.as_bytes(),
+        &[bump],
+    ]];
+
+    // Transfer from escrow to merchant
+    let transfer_to_merchant = token::Transfer {
+        from: ctx.accounts.escrow_token_account.to_account_info(),
+        to: ctx.accounts.merchant_token_account.to_account_info(),
+        authority: ctx.accounts.escrow_pda.to_account_info(),
+    };
+
+    token::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_to_merchant,
+            signer_seeds,
+        ),
+        amount,
+    )?;
+
+    // Update escrow balance
+    subscription.escrow_balance = subscription.escrow_balance
+        .checked_sub(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    msg!(
+        "Merchant claimed {} micro-USDC from escrow for subscription {}. Remaining escrow: {}",
+        amount,
+        subscription_id,
+        subscription.escrow_balance
+    );
+
+    Ok(())
+}
+
+/// Propose moving a subscription to a new subscriber wallet
+/// The new subscriber can accept once SUBSCRIPTION_TRANSFER_TIMELOCK_SECONDS have passed
+pub fn propose_subscription_transfer(
+    ctx: Context<crate::ProposeSubscriptionTransfer>,
+    new_subscriber: Pubkey,
+) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    require!(
+        subscription.status != SubscriptionStatus::Cancelled,
+        ErrorCode::SubscriptionAlreadyCancelled
+    );
+    require!(
+        new_subscriber != subscription.subscriber,
+        ErrorCode::InvalidSubscriptionTransfer
+    );
+
+    let clock = Clock::get()?;
+    let transfer_proposal = &mut ctx.accounts.transfer_proposal;
+    transfer_proposal.subscription = subscription.key();
+    transfer_proposal.pending = PendingTransfer {
+        new_subscriber,
+        proposed_at: clock.unix_timestamp,
+    };
+
+    let unlocks_at = clock.unix_timestamp
+        .checked_add(SUBSCRIPTION_TRANSFER_TIMELOCK_SECONDS)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    msg!(
+        "Subscription {} transfer proposed to {} (unlocks at {})",
+        subscription.id,
+        new_subscriber,
+        unlocks_at
+    );
+
+    emit!(SubscriptionTransferProposed {
+        subscription_id: subscription.id.clone(),
+        old_subscriber: subscription.subscriber,
+        new_subscriber,
+        unlocks_at,
+    });
+
+    Ok(())
+}
+
+/// Create a merchant's opt-in settings with an empty approval list
+pub fn initialize_merchant_config(
+    ctx: Context<crate::InitializeMerchantConfig>,
+    requires_approval: bool,
+) -> Result<()> {
+    let merchant_config = &mut ctx.accounts.merchant_config;
+    merchant_config.merchant = ctx.accounts.merchant.key();
+    merchant_config.requires_approval = requires_approval;
+    merchant_config.approved_subscribers = Vec::new();
+    merchant_config.active = true;
+
+    msg!(
+        "Merchant config initialized for {} (requires approval: {})",
+        merchant_config.merchant,
+        requires_approval
+    );
+
+    Ok(())
+}
+
+/// Add a subscriber to the merchant's approval list
+pub fn approve_subscriber(ctx: Context<crate::UpdateMerchantConfig>, subscriber: Pubkey) -> Result<()> {
+    let merchant_config = &mut ctx.accounts.merchant_config;
+    require!(
+        !merchant_config.approved_subscribers.contains(&subscriber),
+        ErrorCode::SubscriberAlreadyApproved
+    );
+    require!(
+        merchant_config.approved_subscribers.len() < MAX_APPROVED_SUBSCRIBERS,
+        ErrorCode::ApprovedSubscribersFull
+    );
+
+    merchant_config.approved_subscribers.push(subscriber);
+
+    msg!("Merchant {} approved subscriber {}", merchant_config.merchant, subscriber);
+
+    Ok(())
+}
+
+/// Remove a subscriber from the merchant's approval list
+/// Existing subscriptions are unaffected; only new subscriptions are gated
+pub fn revoke_subscriber_approval(ctx: Context<crate::UpdateMerchantConfig>, subscriber: Pubkey) -> Result<()> {
+    let merchant_config = &mut ctx.accounts.merchant_config;
+    let index = merchant_config.approved_subscribers
+        .iter()
+        .position(|approved| *approved == subscriber)
+        .ok_or(ErrorCode::SubscriberNotApproved)?;
+
+    merchant_config.approved_subscribers.swap_remove(index);
+
+    msg!("Merchant {} revoked approval for {}", merchant_config.merchant, subscriber);
+
+    Ok(())
+}
+
+/// Propose moving a subscription's payments to a new merchant wallet (48-hour timelock)
+pub fn propose_merchant_address_change(
+    ctx: Context<crate::ProposeMerchantAddressChange>,
+    new_merchant: Pubkey,
+) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    require!(new_merchant != Pubkey::default(), ErrorCode::InvalidMerchantAddress);
+    require!(new_merchant != subscription.merchant, ErrorCode::InvalidMerchantAddress);
+
+    let clock = Clock::get()?;
+    let proposal = &mut ctx.accounts.merchant_change_proposal;
+    proposal.subscription = subscription.key();
+    proposal.new_merchant = new_merchant;
+    proposal.proposed_at = clock.unix_timestamp;
+
+    msg!(
+        "Subscription {} merchant change proposed: {} -> {}",
+        subscription.id,
+        subscription.merchant,
+        new_merchant
+    );
+
+    Ok(())
+}
+
+/// Execute a proposed merchant wallet change after the timelock
+pub fn execute_merchant_address_change(ctx: Context<crate::ExecuteMerchantAddressChange>) -> Result<()> {
+    let clock = Clock::get()?;
+    let proposal = &ctx.accounts.merchant_change_proposal;
+
+    require!(
+        is_merchant_change_unlocked(proposal.proposed_at, clock.unix_timestamp),
+        ErrorCode::TimelockNotExpired
+    );
+
+    let new_merchant = proposal.new_merchant;
+    let subscription = &mut ctx.accounts.subscription;
+    let old_merchant = subscription.merchant;
+    subscription.merchant = new_merchant;
+    refresh_integrity_hash(subscription);
+
+    msg!(
+        "Subscription {} merchant updated from {} to {}",
+        subscription.id,
+        old_merchant,
+        new_merchant
+    );
+
+    emit!(MerchantAddressUpdated {
+        subscription_id: subscription.id.clone(),
+        old: old_merchant,
+        new: new_merchant,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Propose new amount and interval for a subscription (merchant only)
+/// Nothing changes until the subscriber accepts within CHANGE_PROPOSAL_TTL_SECONDS
+pub fn propose_subscription_change(
+    ctx: Context<crate::ProposeSubscriptionChange>,
+    new_amount: u64,
+    new_interval: i64,
+) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    require!(
+        subscription.status == SubscriptionStatus::Active || subscription.status == SubscriptionStatus::Paused,
+        ErrorCode::SubscriptionNotActive
+    );
+    require!(subscription.interval_seconds != -1, ErrorCode::InvalidInterval); // One-time payments have no terms to amend
+    require!(new_amount >= 1000, ErrorCode::InvalidAmount); // Same bounds as create_subscription
+    require!(new_amount <= 1_000_000_000_000_000, ErrorCode::InvalidAmount);
+    require!(
+        is_valid_interval_update(new_interval, subscription.grace_period_seconds),
+        ErrorCode::InvalidInterval
+    );
+
+    let expires_at = Clock::get()?
+        .unix_timestamp
+        .checked_add(CHANGE_PROPOSAL_TTL_SECONDS)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    let proposal = &mut ctx.accounts.change_proposal;
+    proposal.subscription = subscription.key();
+    proposal.proposer = ctx.accounts.merchant.key();
+    proposal.proposed_amount = new_amount;
+    proposal.proposed_interval = new_interval;
+    proposal.expires_at = expires_at;
+
+    msg!(
+        "Subscription {} change proposed: {} every {}s (expires at {})",
+        subscription.id,
+        new_amount,
+        new_interval,
+        expires_at
+    );
+
+    emit!(SubscriptionChangeProposed {
+        subscription_id: subscription.id.clone(),
+        proposer: proposal.proposer,
+        proposed_amount: new_amount,
+        proposed_interval: new_interval,
+        expires_at,
+    });
+
+    Ok(())
+}
+
+/// Accept the merchant's proposed terms and re-approve the delegation for them (subscriber only)
+/// next_payment_time is kept, the new terms apply from the next charge
+pub fn accept_subscription_change(ctx: Context<crate::AcceptSubscriptionChange>) -> Result<()> {
+    let proposal = &ctx.accounts.change_proposal;
+    require!(
+        !is_change_proposal_expired(proposal.expires_at, Clock::get()?.unix_timestamp),
+        ErrorCode::ChangeProposalExpired
+    );
+    let new_amount = proposal.proposed_amount;
+    let new_interval = proposal.proposed_interval;
+
+    let subscription = &mut ctx.accounts.subscription;
+    require!(
+        subscription.status == SubscriptionStatus::Active || subscription.status == SubscriptionStatus::Paused,
+        ErrorCode::SubscriptionNotActive
+    );
+
+    let new_delegation = crate::constants::calculate_one_year_delegation(new_amount, new_interval)?;
+
+    // The subscription account is the PDA that holds the delegation
+    let cpi_accounts = token_interface::Approve {
+        to: ctx.accounts.subscriber_token_account.to_account_info(),
+        delegate: subscription.to_account_info(),
+        authority: ctx.accounts.subscriber.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+    token_interface::approve(cpi_ctx, new_delegation)?;
+
+    let old_amount = subscription.amount;
+    let old_interval = subscription.interval_seconds;
+    subscription.amount = new_amount;
+    subscription.interval_seconds = new_interval;
+    refresh_integrity_hash(subscription);
+
+    msg!(
+        "Subscription {} terms updated: {} every {}s -> {} every {}s, delegation {}",
+        subscription.id,
+        old_amount,
+        old_interval,
+        new_amount,
+        new_interval,
+        new_delegation
+    );
+
+    emit!(SubscriptionChangeAccepted {
+        subscription_id: subscription.id.clone(),
+        old_amount,
+        new_amount,
+        old_interval,
+        new_interval,
+    });
+
+    Ok(())
+}
+
+/// Decline the merchant's proposed terms (subscriber only); rent goes back to the proposer
+pub fn reject_subscription_change(ctx: Context<crate::RejectSubscriptionChange>) -> Result<()> {
+    msg!("Subscription {} change rejected by subscriber", ctx.accounts.subscription.id);
+    Ok(())
+}
+
+/// Close a proposal the subscriber never answered; anyone can call after expires_at
+pub fn expire_proposal(ctx: Context<crate::ExpireProposal>) -> Result<()> {
+    require!(
+        is_change_proposal_expired(ctx.accounts.change_proposal.expires_at, Clock::get()?.unix_timestamp),
+        ErrorCode::ChangeProposalNotExpired
+    );
 
-    token::revoke(cpi_ctx)?;
+    msg!("Subscription {} change proposal expired", ctx.accounts.subscription.id);
+    Ok(())
+}
+
+/// Accept a proposed subscription transfer after the timelock
+/// Revoking the old delegation needs the old owner's signature; without it the old
+/// delegation is unusable anyway because payment contexts check ownership against subscription.subscriber
+pub fn accept_subscription_transfer(ctx: Context<crate::AcceptSubscriptionTransfer>) -> Result<()> {
+    let clock = Clock::get()?;
+    let pending = ctx.accounts.transfer_proposal.pending;
+
+    require!(
+        is_transfer_unlocked(pending.proposed_at, clock.unix_timestamp),
+        ErrorCode::TransferTimelockActive
+    );
+
+    let old_subscriber = ctx.accounts.subscription.subscriber;
+
+    // EFFECTS: Update subscription state BEFORE external calls (CEI pattern)
+    ctx.accounts.subscription.subscriber = pending.new_subscriber;
+    refresh_integrity_hash(&mut ctx.accounts.subscription);
+
+    // INTERACTIONS: Revoke the old delegation when the old owner co-signs
+    let old_delegation_revoked = ctx.accounts.old_subscriber.is_signer;
+    if old_delegation_revoked {
+        let cpi_accounts = token_interface::Revoke {
+            source: ctx.accounts.old_subscriber_token_account.to_account_info(),
+            authority: ctx.accounts.old_subscriber.to_account_info(),
+        };
+
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token_interface::revoke(cpi_ctx)?;
+    }
+
+    let subscription = &ctx.accounts.subscription;
+    msg!(
+        "Subscription {} transferred from {} to {} - new subscriber must call approve_subscription_delegate",
+        subscription.id,
+        old_subscriber,
+        pending.new_subscriber
+    );
+
+    emit!(SubscriptionTransferred {
+        subscription_id: subscription.id.clone(),
+        old_subscriber,
+        new_subscriber: pending.new_subscriber,
+        old_delegation_revoked,
+        timestamp: clock.unix_timestamp,
+    });
 
-    msg!("Revoked subscription PDA delegate for {}", ctx.accounts.subscription.id);
     Ok(())
 }
 
-/// Merchant claims USDC from escrow after off-ramp API confirmation
-/// This allows merchants to withdraw funds from escrow once fiat transfer is complete
-pub fn claim_from_escrow(
-    ctx: Context<crate::ClaimFromEscrow>,
-    subscription_id: String,
-    amount: u64,
+/// Merchant refunds USDC to the subscriber
+/// Funds move out of the merchant's own token account, so the merchant signs the transfer directly
+pub fn process_refund(
+    ctx: Context<crate::ProcessRefund>,
+    refund_amount: u64,
+    reason: [u8; 64],
 ) -> Result<()> {
-    let subscription = &mut ctx.accounts.subscription;
+    require!(!ctx.accounts.config.paused, ErrorCode::ProgramPaused);
 
-    // Validate claim amount
-    require!(amount > 0, ErrorCode::InvalidAmount);
-    require!(amount <= subscription.escrow_balance, ErrorCode::InsufficientAmount);
+    let subscription = &mut ctx.accounts.subscription;
+    let subscription_id = subscription.id.clone();
 
-    // Get escrow PDA bump for signing
-    let (_escrow_pda, bump) = crate::constants::derive_escrow_pda(&subscription_id, ctx.program_id);
-    let signer_seeds: &[&[&[u8]]] = &[&[
-        b"escrow",
-        subscription_id.as_bytes(),
-        &[bump],
-    ]];
+    // EFFECTS: Update subscription state BEFORE external calls (CEI pattern)
+    subscription.total_paid = apply_refund(subscription.total_paid, refund_amount)?;
 
-    // Transfer from escrow to merchant
-    let transfer_to_merchant = token::Transfer {
-        from: ctx.accounts.escrow_token_account.to_account_info(),
-        to: ctx.accounts.merchant_token_account.to_account_info(),
-        authority: ctx.accounts.escrow_pda.to_account_info(),
+    // INTERACTIONS: Transfer from merchant back to subscriber with merchant authority
+    let transfer_to_subscriber = token::Transfer {
+        from: ctx.accounts.merchant_token_account.to_account_info(),
+        to: ctx.accounts.subscriber_token_account.to_account_info(),
+        authority: ctx.accounts.merchant.to_account_info(),
     };
 
     token::transfer(
-        CpiContext::new_with_signer(
+        CpiContext::new(
             ctx.accounts.token_program.to_account_info(),
-            transfer_to_merchant,
-            signer_seeds,
+            transfer_to_subscriber,
         ),
-        amount,
+        refund_amount,
     )?;
 
-    // Update escrow balance
-    subscription.escrow_balance = subscription.escrow_balance
-        .checked_sub(amount)
-        .ok_or(ErrorCode::MathOverflow)?;
-
     msg!(
-        "Merchant claimed {} micro-USDC from escrow for subscription {}. Remaining escrow: {}",
-        amount,
+        "Merchant refunded {} micro-USDC for subscription {}. Total paid now: {}",
+        refund_amount,
         subscription_id,
-        subscription.escrow_balance
+        subscription.total_paid
     );
 
+    emit!(RefundProcessed {
+        subscription_id,
+        refund_amount,
+        reason,
+    });
+
+    Ok(())
+}
+
+/// Subscriber disputes a charge: blocks further charges until the admin rules on it
+pub fn dispute_payment(
+    ctx: Context<crate::DisputePayment>,
+    payment_number: u64,
+    reason: [u8; 128],
+) -> Result<()> {
+    let subscription = &mut ctx.accounts.subscription;
+    require!(
+        is_disputable_payment(payment_number, subscription.payments_made, subscription.last_ruled_payment_number),
+        ErrorCode::InvalidDisputedPayment
+    );
+
+    subscription.disputed = true;
+    subscription.dispute_payment_number = payment_number;
+
+    let record = &mut ctx.accounts.dispute_record;
+    record.subscription = subscription.key();
+    record.subscriber = ctx.accounts.subscriber.key();
+    record.payment_number = payment_number;
+    record.reason = reason;
+    record.opened_at = Clock::get()?.unix_timestamp;
+
+    msg!("Payment #{} of subscription {} disputed, charges blocked", payment_number, subscription.id);
+
+    emit!(PaymentDisputed {
+        subscription_id: subscription.id.clone(),
+        payment_number,
+        reason,
+    });
+
+    Ok(())
+}
+
+/// Rule on an open dispute (admin only, multisig-approved when enabled) and unblock charges
+/// An Approved ruling refunds one charge from the platform fee vault when the refund accounts are supplied,
+/// capped at the fees this subscription paid into the vault
+pub fn resolve_dispute(ctx: Context<crate::ResolveDispute>, ruling: DisputeRuling) -> Result<()> {
+    let subscription_key = ctx.accounts.subscription.key();
+    authorize_admin_instruction(
+        &mut ctx.accounts.config,
+        AdminActionType::ResolveDispute,
+        &(subscription_key, ruling).try_to_vec()?,
+        ctx.accounts.authority.key(),
+    )?;
+
+    let payment_number = ctx.accounts.dispute_record.payment_number;
+    let mut refund_amount = 0;
+
+    if ruling == DisputeRuling::Approved {
+        if let (Some(fee_vault), Some(subscriber_token_account), Some(usdc_mint), Some(token_program)) = (
+            ctx.accounts.fee_vault.as_ref(),
+            ctx.accounts.subscriber_token_account.as_ref(),
+            ctx.accounts.usdc_mint.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+        ) {
+            let subscription = &mut ctx.accounts.subscription;
+            refund_amount = dispute_refund_amount(subscription.amount, subscription.vault_fees_paid);
+            require!(fee_vault.amount >= refund_amount, ErrorCode::InsufficientVaultBalance);
+
+            // EFFECTS before the transfer (CEI pattern)
+            subscription.total_paid = apply_refund(subscription.total_paid, refund_amount)?;
+            subscription.vault_fees_paid -= refund_amount;
+
+            let mint_key = usdc_mint.key();
+            let (_, vault_bump) = Pubkey::find_program_address(&[b"fee_vault", mint_key.as_ref()], ctx.program_id);
+            let seeds = &[b"fee_vault".as_ref(), mint_key.as_ref(), &[vault_bump]];
+            let signer_seeds = &[&seeds[..]];
+
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    token_interface::TransferChecked {
+                        from: fee_vault.to_account_info(),
+                        mint: usdc_mint.to_account_info(),
+                        to: subscriber_token_account.to_account_info(),
+                        authority: fee_vault.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                refund_amount,
+                usdc_mint.decimals,
+            )?;
+        }
+    }
+
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.disputed = false;
+    subscription.dispute_payment_number = 0;
+    subscription.last_ruled_payment_number = payment_number;
+
+    msg!(
+        "Dispute on payment #{} of subscription {} resolved: {:?}, refunded {}",
+        payment_number,
+        subscription.id,
+        ruling,
+        refund_amount
+    );
+
+    emit!(DisputeResolved {
+        subscription_id: subscription.id.clone(),
+        payment_number,
+        ruling,
+        refund_amount,
+    });
+
     Ok(())
 }
 
 /// Emergency pause the entire program (admin only)
 pub fn emergency_pause(ctx: Context<crate::AdminAction>) -> Result<()> {
+    require!(!ctx.accounts.config.multisig_mode, ErrorCode::MultisigRequired);
     ctx.accounts.config.paused = true;
     msg!("Ouro-C Subscriptions emergency paused");
     Ok(())
@@ -512,37 +2103,423 @@ pub fn emergency_pause(ctx: Context<crate::AdminAction>) -> Result<()> {
 
 /// Resume the program (admin only)
 pub fn resume_program(ctx: Context<crate::AdminAction>) -> Result<()> {
+    require!(!ctx.accounts.config.multisig_mode, ErrorCode::MultisigRequired);
     ctx.accounts.config.paused = false;
     msg!("Ouro-C Subscriptions resumed");
     Ok(())
 }
 
+/// Update the SOL fee charged on create_subscription (admin only, 0 waives it)
+pub fn update_creation_fee(ctx: Context<crate::AdminAction>, new_fee: u64) -> Result<()> {
+    require!(!ctx.accounts.config.multisig_mode, ErrorCode::MultisigRequired);
+    apply_creation_fee_update(&mut ctx.accounts.config, new_fee)
+}
+
+fn apply_creation_fee_update(config: &mut Config, new_fee: u64) -> Result<()> {
+    require!(new_fee <= MAX_CREATION_FEE_LAMPORTS, ErrorCode::InvalidCreationFee);
+
+    let old_fee = config.creation_fee_lamports;
+    config.creation_fee_lamports = new_fee;
+    msg!("Subscription creation fee updated: {} -> {} lamports", old_fee, new_fee);
+    Ok(())
+}
+
 /// Update authorization mode (admin only)
+/// Resets the ICP key allowlist to `icp_public_key` (or empties it for None)
 pub fn update_authorization_mode(
     ctx: Context<crate::AdminAction>,
     new_mode: AuthorizationMode,
     icp_public_key: Option<[u8; 32]>,
 ) -> Result<()> {
-    let config = &mut ctx.accounts.config;
+    require!(!ctx.accounts.config.multisig_mode, ErrorCode::MultisigRequired);
+    apply_authorization_mode_update(&mut ctx.accounts.config, new_mode, icp_public_key)
+}
+
+fn apply_authorization_mode_update(
+    config: &mut Config,
+    new_mode: AuthorizationMode,
+    icp_public_key: Option<[u8; 32]>,
+) -> Result<()> {
     config.authorization_mode = new_mode;
-    config.icp_public_key = icp_public_key;
-    config.manual_processing_enabled = matches!(new_mode, AuthorizationMode::ManualOnly | AuthorizationMode::Hybrid);
-    config.time_based_processing_enabled = matches!(new_mode, AuthorizationMode::TimeBased | AuthorizationMode::Hybrid);
+    config.icp_public_keys = [[0u8; 32]; MAX_ICP_PUBLIC_KEYS];
+    config.icp_key_count = 0;
+    config.pending_icp_public_key = None; // Reset allowlist supersedes any rotation in progress
+    config.key_rotation_start = None;
+    if let Some(key) = icp_public_key {
+        add_icp_key(&mut config.icp_public_keys, &mut config.icp_key_count, key)?;
+    }
+    config.manual_processing_enabled = allows_manual_processing(new_mode);
+    config.time_based_processing_enabled = allows_time_based_processing(new_mode);
 
     msg!("Authorization mode updated to: {:?}", new_mode);
     Ok(())
 }
 
+/// Add an ICP canister public key to the allowlist (admin only)
+pub fn add_icp_public_key(ctx: Context<crate::AdminAction>, key: [u8; 32]) -> Result<()> {
+    require!(!ctx.accounts.config.multisig_mode, ErrorCode::MultisigRequired);
+    let config: &mut Config = &mut ctx.accounts.config;
+    add_icp_key(&mut config.icp_public_keys, &mut config.icp_key_count, key)?;
+
+    msg!("ICP public key added ({} active)", config.icp_key_count);
+    Ok(())
+}
+
+/// Remove an ICP canister public key from the allowlist (admin only)
+pub fn remove_icp_public_key(ctx: Context<crate::AdminAction>, key: [u8; 32]) -> Result<()> {
+    require!(!ctx.accounts.config.multisig_mode, ErrorCode::MultisigRequired);
+    let config: &mut Config = &mut ctx.accounts.config;
+    remove_icp_key(&mut config.icp_public_keys, &mut config.icp_key_count, key)?;
+
+    msg!("ICP public key removed ({} active)", config.icp_key_count);
+    Ok(())
+}
+
+/// Log the program-wide fee totals since the last reset
+pub fn get_fee_analytics(ctx: Context<crate::GetFeeAnalytics>) -> Result<()> {
+    let fee_analytics = &ctx.accounts.fee_analytics;
+    msg!(
+        "Fee analytics since {}: fees={}, volume={}, payments={}",
+        fee_analytics.last_reset,
+        fee_analytics.total_fees_collected,
+        fee_analytics.total_volume,
+        fee_analytics.payment_count
+    );
+    Ok(())
+}
+
+/// Emit DelegationStatusChecked for DApp readiness indicators; reads only, changes no state
+pub fn check_delegation_status(ctx: Context<crate::CheckDelegationStatus>, subscription_id: String) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    let token_account = &ctx.accounts.subscriber_token_account;
+    let status = delegation_status(
+        &subscription_id,
+        subscription.amount,
+        subscription.interval_seconds,
+        &subscription.key(),
+        token_account.delegate.into(),
+        token_account.delegated_amount,
+    );
+
+    msg!(
+        "Subscription {} delegation: correct={}, delegated={}, payments_remaining={}",
+        subscription_id,
+        status.is_delegate_correct,
+        status.delegated_amount,
+        status.payments_remaining
+    );
+
+    emit!(status);
+    Ok(())
+}
+
+/// A subscriber's payment track record
+pub fn get_subscriber_reputation(ctx: Context<crate::GetSubscriberReputation>, subscriber: Pubkey) -> Result<SubscriberReputation> {
+    let reputation = &ctx.accounts.subscriber_reputation;
+    msg!(
+        "Subscriber {} reputation: score={}, on_time={}, late={}, failed={}",
+        subscriber,
+        reputation.score,
+        reputation.on_time_payments,
+        reputation.late_payments,
+        reputation.failed_payments
+    );
+    Ok((**reputation).clone())
+}
+
+/// A merchant's running totals, for dashboards
+pub fn get_merchant_dashboard(ctx: Context<crate::GetMerchantDashboard>, merchant: Pubkey) -> Result<MerchantDashboard> {
+    let dashboard = &ctx.accounts.merchant_dashboard;
+    msg!(
+        "Merchant {} dashboard: active={}, revenue={}, fees={}",
+        merchant,
+        dashboard.active_subscriptions,
+        dashboard.total_revenue_usdc,
+        dashboard.total_fees_paid
+    );
+    Ok((**dashboard).clone())
+}
+
+/// Subscription IDs indexed for a subscriber, zero-padded to 32 bytes
+pub fn get_subscriber_subscriptions(ctx: Context<crate::GetSubscriberSubscriptions>, subscriber: Pubkey) -> Result<Vec<[u8; 32]>> {
+    let index = &ctx.accounts.subscriber_index;
+    msg!("Subscriber {} has {} indexed subscriptions", subscriber, index.count);
+    Ok(index.subscription_ids.clone())
+}
+
+/// Subscription IDs indexed for a merchant, zero-padded to 32 bytes
+pub fn get_merchant_subscriptions(ctx: Context<crate::GetMerchantSubscriptions>, merchant: Pubkey) -> Result<Vec<[u8; 32]>> {
+    let index = &ctx.accounts.merchant_index;
+    msg!("Merchant {} has {} indexed subscriptions", merchant, index.count);
+    Ok(index.subscription_ids.clone())
+}
+
+/// Start a new fee reporting window (admin only, multisig-approved when enabled)
+pub fn reset_fee_analytics(ctx: Context<crate::ResetFeeAnalytics>) -> Result<()> {
+    authorize_admin_instruction(
+        &mut ctx.accounts.config,
+        AdminActionType::ResetFeeAnalytics,
+        &[],
+        ctx.accounts.authority.key(),
+    )?;
+
+    let fee_analytics = &mut ctx.accounts.fee_analytics;
+    msg!(
+        "Fee analytics reset: fees={}, volume={}, payments={} since {}",
+        fee_analytics.total_fees_collected,
+        fee_analytics.total_volume,
+        fee_analytics.payment_count,
+        fee_analytics.last_reset
+    );
+
+    fee_analytics.total_fees_collected = 0;
+    fee_analytics.total_volume = 0;
+    fee_analytics.payment_count = 0;
+    fee_analytics.last_reset = Clock::get()?.unix_timestamp;
+    Ok(())
+}
+
+fn apply_key_rotation_start(config: &mut Config, new_key: [u8; 32], current_time: i64) -> Result<()> {
+    require!(config.pending_icp_public_key.is_none(), ErrorCode::KeyRotationPending);
+    require!(!active_icp_keys(config).contains(&new_key), ErrorCode::DuplicateICPKey);
+
+    config.pending_icp_public_key = Some(new_key);
+    config.key_rotation_start = Some(current_time);
+
+    msg!(
+        "ICP key rotation started; both keys accepted until {}",
+        current_time + ROTATION_WINDOW_SECONDS
+    );
+    Ok(())
+}
+
+/// Start rotating the primary ICP public key (admin only)
+/// Signatures from the current and the new key are both accepted for ROTATION_WINDOW_SECONDS, then complete_key_rotation promotes the new key
+pub fn initiate_key_rotation(ctx: Context<crate::AdminAction>, new_key: [u8; 32]) -> Result<()> {
+    require!(!ctx.accounts.config.multisig_mode, ErrorCode::MultisigRequired);
+    let current_time = Clock::get()?.unix_timestamp;
+    apply_key_rotation_start(&mut ctx.accounts.config, new_key, current_time)
+}
+
+/// Finish an ICP key rotation once the dual-key window has elapsed (anyone can call)
+/// The pending key replaces the primary key; signatures from the old key stop verifying
+pub fn complete_key_rotation(ctx: Context<crate::CompleteKeyRotation>) -> Result<()> {
+    let config: &mut Config = &mut ctx.accounts.config;
+    let new_key = config.pending_icp_public_key.ok_or(ErrorCode::NoKeyRotationPending)?;
+    let rotation_start = config.key_rotation_start.ok_or(ErrorCode::NoKeyRotationPending)?;
+    require!(
+        is_key_rotation_window_over(rotation_start, Clock::get()?.unix_timestamp),
+        ErrorCode::KeyRotationWindowActive
+    );
+
+    rotate_primary_icp_key(&mut config.icp_public_keys, &mut config.icp_key_count, new_key)?;
+    config.pending_icp_public_key = None;
+    config.key_rotation_start = None;
+
+    msg!("ICP key rotation completed ({} active)", config.icp_key_count);
+    Ok(())
+}
+
+/// Enable multisig admin mode (admin only, while multisig is off)
+/// Afterwards every admin instruction must go through propose/approve/execute
+pub fn enable_multisig(
+    ctx: Context<crate::AdminAction>,
+    signers: Vec<Pubkey>,
+    threshold: u8,
+) -> Result<()> {
+    let config: &mut Config = &mut ctx.accounts.config;
+    require!(!config.multisig_mode, ErrorCode::MultisigRequired);
+
+    let (admin_signers, admin_signer_count) = build_admin_signers(&signers, threshold)?;
+    config.admin_signers = admin_signers;
+    config.admin_signer_count = admin_signer_count;
+    config.admin_threshold = threshold;
+    config.pending_admin_action = None;
+    config.multisig_mode = true;
+
+    msg!("Multisig enabled: {} of {} signers", threshold, admin_signer_count);
+    Ok(())
+}
+
+/// Propose an admin action (multisig member only)
+/// The proposer's approval is counted; a new proposal replaces only an expired one
+pub fn propose_admin_action(
+    ctx: Context<crate::MultisigAdminAction>,
+    action_type: AdminActionType,
+    data: Vec<u8>,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let proposer = ctx.accounts.signer.key();
+    let config: &mut Config = &mut ctx.accounts.config;
+
+    require!(config.multisig_mode, ErrorCode::MultisigNotEnabled);
+    require!(data.len() <= MAX_ADMIN_ACTION_DATA_LEN, ErrorCode::InvalidAdminActionData);
+    let signer_index = admin_signer_index(config, &proposer).ok_or(ErrorCode::UnauthorizedAccess)?;
+
+    if let Some(pending) = &config.pending_admin_action {
+        require!(
+            is_admin_proposal_expired(pending.proposed_at, clock.unix_timestamp),
+            ErrorCode::AdminProposalPending
+        );
+    }
+
+    let action_id = config.next_admin_action_id;
+    config.next_admin_action_id = action_id.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+
+    let mut pending = PendingAdminAction {
+        action_id,
+        action_type,
+        data,
+        proposer,
+        proposed_at: clock.unix_timestamp,
+        approvals: 0,
+        approval_count: 0,
+    };
+    record_admin_approval(&mut pending, signer_index)?;
+    config.pending_admin_action = Some(pending);
+
+    msg!("Admin action {} proposed: {:?}", action_id, action_type);
+
+    emit!(AdminActionProposed {
+        action_id,
+        action_type,
+        proposer,
+        expires_at: clock.unix_timestamp.saturating_add(ADMIN_PROPOSAL_TIMEOUT_SECONDS),
+    });
+
+    Ok(())
+}
+
+/// Approve the pending admin action (multisig member only)
+pub fn approve_admin_action(ctx: Context<crate::MultisigAdminAction>, action_id: u64) -> Result<()> {
+    let clock = Clock::get()?;
+    let approver = ctx.accounts.signer.key();
+    let config: &mut Config = &mut ctx.accounts.config;
+
+    require!(config.multisig_mode, ErrorCode::MultisigNotEnabled);
+    let signer_index = admin_signer_index(config, &approver).ok_or(ErrorCode::UnauthorizedAccess)?;
+
+    let pending = config
+        .pending_admin_action
+        .as_mut()
+        .filter(|pending| pending.action_id == action_id)
+        .ok_or(ErrorCode::AdminProposalNotFound)?;
+    require!(
+        !is_admin_proposal_expired(pending.proposed_at, clock.unix_timestamp),
+        ErrorCode::AdminProposalExpired
+    );
+
+    record_admin_approval(pending, signer_index)?;
+    let approval_count = pending.approval_count;
+
+    msg!("Admin action {} approved by {} ({} approvals)", action_id, approver, approval_count);
+
+    emit!(AdminActionApproved {
+        action_id,
+        approver,
+        approval_count,
+    });
+
+    Ok(())
+}
+
+/// Execute the pending admin action once it has admin_threshold approvals (any signer)
+pub fn execute_admin_action(ctx: Context<crate::MultisigAdminAction>, action_id: u64) -> Result<()> {
+    let clock = Clock::get()?;
+    let executed_by = ctx.accounts.signer.key();
+    let config: &mut Config = &mut ctx.accounts.config;
+
+    require!(config.multisig_mode, ErrorCode::MultisigNotEnabled);
+
+    let pending = config
+        .pending_admin_action
+        .take()
+        .filter(|pending| pending.action_id == action_id)
+        .ok_or(ErrorCode::AdminProposalNotFound)?;
+    require!(
+        !is_admin_proposal_expired(pending.proposed_at, clock.unix_timestamp),
+        ErrorCode::AdminProposalExpired
+    );
+    require!(
+        pending.approval_count >= config.admin_threshold,
+        ErrorCode::AdminThresholdNotMet
+    );
+
+    match pending.action_type {
+        AdminActionType::EmergencyPause => config.paused = true,
+        AdminActionType::ResumeProgram => config.paused = false,
+        AdminActionType::UpdateFeeDestination => {
+            let (new_fee_address, fee_recipients, max_fee_amount, fee_tiers) =
+                <(Pubkey, Vec<FeeRecipient>, u64, Vec<FeeTier>)>::try_from_slice(&pending.data)
+                    .map_err(|_| ErrorCode::InvalidAdminActionData)?;
+            apply_fee_destination_update(config, new_fee_address, fee_recipients, max_fee_amount, fee_tiers, executed_by)?;
+        }
+        AdminActionType::UpdateAuthorizationMode => {
+            let (new_mode, icp_public_key) =
+                <(AuthorizationMode, Option<[u8; 32]>)>::try_from_slice(&pending.data)
+                    .map_err(|_| ErrorCode::InvalidAdminActionData)?;
+            apply_authorization_mode_update(config, new_mode, icp_public_key)?;
+        }
+        AdminActionType::AddIcpPublicKey => {
+            let key = <[u8; 32]>::try_from_slice(&pending.data)
+                .map_err(|_| ErrorCode::InvalidAdminActionData)?;
+            add_icp_key(&mut config.icp_public_keys, &mut config.icp_key_count, key)?;
+        }
+        AdminActionType::RemoveIcpPublicKey => {
+            let key = <[u8; 32]>::try_from_slice(&pending.data)
+                .map_err(|_| ErrorCode::InvalidAdminActionData)?;
+            remove_icp_key(&mut config.icp_public_keys, &mut config.icp_key_count, key)?;
+        }
+        AdminActionType::DisableMultisig => config.multisig_mode = false,
+        AdminActionType::InitiateKeyRotation => {
+            let new_key = <[u8; 32]>::try_from_slice(&pending.data)
+                .map_err(|_| ErrorCode::InvalidAdminActionData)?;
+            apply_key_rotation_start(config, new_key, clock.unix_timestamp)?;
+        }
+        AdminActionType::UpdateCreationFee => {
+            let new_fee = u64::try_from_slice(&pending.data)
+                .map_err(|_| ErrorCode::InvalidAdminActionData)?;
+            apply_creation_fee_update(config, new_fee)?;
+        }
+        // Executed by calling the instruction itself with its accounts
+        AdminActionType::WithdrawFees
+        | AdminActionType::InitializeFeeVault
+        | AdminActionType::ResetFeeAnalytics
+        | AdminActionType::ResolveDispute => {
+            return err!(ErrorCode::AdminActionNeedsAccounts);
+        }
+    }
+
+    msg!("Admin action {} executed: {:?}", action_id, pending.action_type);
+
+    emit!(AdminActionExecuted {
+        action_id,
+        action_type: pending.action_type,
+        executed_by,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
 /// Manual payment processing (subscriber only)
-pub fn process_manual_payment(ctx: Context<crate::ProcessPayment>) -> Result<()> {
+pub fn process_manual_payment<'info>(
+    ctx: Context<'_, '_, '_, 'info, crate::ProcessPayment<'info>>,
+) -> Result<()> {
     require!(!ctx.accounts.config.paused, ErrorCode::ProgramPaused);
+    let authorization_mode = effective_authorization_mode(
+        ctx.accounts.subscription.authorization_mode_override,
+        ctx.accounts.config.authorization_mode,
+    );
     require!(
-        ctx.accounts.config.manual_processing_enabled,
+        allows_manual_processing(authorization_mode),
         ErrorCode::AuthorizationFailed
     );
 
     // Call main process_payment with manual authorization
-    process_payment(ctx, None, 0)
+    // The subscriber signs this themselves, so there is no stale read to guard against
+    let current_version = ctx.accounts.subscription.version;
+    process_payment(ctx, None, 0, current_version)
 }
 
 /// Send notification to subscriber via Solana memo transaction
@@ -551,25 +2528,29 @@ pub fn process_manual_payment(ctx: Context<crate::ProcessPayment>) -> Result<()>
 /// Main entry point from ICP: Process trigger with opcode routing
 /// Opcode 0: Payment (direct USDC only - use process_trigger_with_swap for swaps)
 /// Opcode 1: Notification (send memo to subscriber)
-pub fn process_trigger(
-    ctx: Context<crate::ProcessTrigger>,
+pub fn process_trigger<'info>(
+    ctx: Context<'_, '_, '_, 'info, crate::ProcessTrigger<'info>>,
     opcode: u8,
     icp_signature: Option<[u8; 64]>,
     timestamp: i64,
+    expected_version: u64,
 ) -> Result<()> {
     require!(!ctx.accounts.config.paused, ErrorCode::ProgramPaused);
+    // Two canister instances that read the same state can't both trigger
+    claim_subscription_version(&mut ctx.accounts.subscription, expected_version)?;
 
     let subscription = &ctx.accounts.subscription;
     let config = &ctx.accounts.config;
 
     // Verify trigger authority based on authorization mode
-    match config.authorization_mode {
+    // A verified ICP signature is single-use: the payment path records its timestamp as a UsedNonce
+    let mut signed_timestamp = None;
+    match effective_authorization_mode(subscription.authorization_mode_override, config.authorization_mode) {
         AuthorizationMode::ICPSignature => {
             // ICP signature required
             let _sig = icp_signature.ok_or(ErrorCode::InvalidSignature)?;
-            let icp_pubkey = config
-                .icp_public_key
-                .ok_or(ErrorCode::InvalidSignature)?;
+            let icp_public_keys = accepted_icp_keys(config, Clock::get()?.unix_timestamp);
+            require!(!icp_public_keys.is_empty(), ErrorCode::InvalidSignature);
 
             // Create message: subscription_id + timestamp + amount
             let message = create_payment_message(
@@ -585,14 +2566,28 @@ pub fn process_trigger(
                 ErrorCode::TimestampExpired
             );
 
-            // Verify Ed25519 signature using precompile
-            let is_valid = verify_ed25519_ix(
+            // Verify Ed25519 signature using precompile (any allowlisted canister)
+            let is_valid = verify_ed25519_ix_any(
                 &ctx.accounts.instructions_sysvar,
-                &icp_pubkey,
+                &icp_public_keys,
                 &message,
             )?;
 
             require!(is_valid, ErrorCode::InvalidSignature);
+
+            // A valid signature doesn't make the payment due; without this a repeated trigger
+            // charges again as soon as the version check passes
+            if opcode == 0 {
+                require!(
+                    is_payment_due(
+                        current_time,
+                        subscription.next_payment_time,
+                        subscription.grace_period_seconds,
+                    ),
+                    ErrorCode::PaymentNotDue
+                );
+            }
+            signed_timestamp = Some(timestamp);
         }
         AuthorizationMode::ManualOnly => {
             // Verify signer is subscriber or merchant
@@ -606,43 +2601,60 @@ pub fn process_trigger(
             // Anyone can trigger if payment is due
             let current_time = Clock::get()?.unix_timestamp;
             require!(
-                current_time >= subscription.next_payment_time,
+                is_payment_due(
+                    current_time,
+                    subscription.next_payment_time,
+                    subscription.grace_period_seconds,
+                ),
                 ErrorCode::PaymentNotDue
             );
         }
         AuthorizationMode::Hybrid => {
             // Try ICP signature first, fallback to manual if overdue
+            // A signature that is supplied must verify, otherwise it would authorize nothing
             if let Some(_sig) = icp_signature {
-                if let Some(icp_pubkey) = config.icp_public_key {
-                    let message = create_payment_message(
-                        &subscription.id,
-                        timestamp,
-                        subscription.amount,
-                    );
-
-                    let current_time = Clock::get()?.unix_timestamp;
-                    let timestamp_valid = verify_timestamp(timestamp, current_time, 300)?;
+                let icp_public_keys = accepted_icp_keys(config, Clock::get()?.unix_timestamp);
+                require!(!icp_public_keys.is_empty(), ErrorCode::InvalidSignature);
+                let message = create_payment_message(
+                    &subscription.id,
+                    timestamp,
+                    subscription.amount,
+                );
 
-                    if timestamp_valid {
-                        let is_valid = verify_ed25519_ix(
-                            &ctx.accounts.instructions_sysvar,
-                            &icp_pubkey,
-                            &message,
-                        )?;
+                let current_time = Clock::get()?.unix_timestamp;
+                require!(
+                    verify_timestamp(timestamp, current_time, 300)?,
+                    ErrorCode::TimestampExpired
+                );
 
-                        if is_valid {
-                            // ICP signature valid, proceed
-                        } else {
-                            return Err(ErrorCode::InvalidSignature.into());
-                        }
-                    }
+                let is_valid = verify_ed25519_ix_any(
+                    &ctx.accounts.instructions_sysvar,
+                    &icp_public_keys,
+                    &message,
+                )?;
+                require!(is_valid, ErrorCode::InvalidSignature);
+
+                // Same as ICPSignature: the signature authorizes the trigger, not an early payment
+                if opcode == 0 {
+                    require!(
+                        is_payment_due(
+                            current_time,
+                            subscription.next_payment_time,
+                            subscription.grace_period_seconds,
+                        ),
+                        ErrorCode::PaymentNotDue
+                    );
                 }
+                signed_timestamp = Some(timestamp);
             } else {
                 // No signature - check if payment is overdue (5 min grace period)
                 let current_time = Clock::get()?.unix_timestamp;
                 let grace_period = 60; // 1 minute
+                let overdue_time = subscription.next_payment_time
+                    .checked_add(grace_period)
+                    .ok_or(ErrorCode::MathOverflow)?;
                 require!(
-                    current_time >= subscription.next_payment_time + grace_period,
+                    is_payment_due(current_time, overdue_time, subscription.grace_period_seconds),
                     ErrorCode::PaymentNotDue
                 );
 
@@ -660,9 +2672,20 @@ pub fn process_trigger(
         0 => {
             // Payment: Direct USDC only
             msg!("Processing direct USDC payment for subscription: {}", subscription.id);
-            process_direct_usdc_payment(ctx)?;
+            process_direct_usdc_payment(ctx, signed_timestamp)?;
         },
         1 => {
+            // Rate limit: one notification per cooldown window
+            let current_time = Clock::get()?.unix_timestamp;
+            require!(
+                is_notification_allowed(
+                    subscription.last_notification_time,
+                    current_time,
+                    config.notification_cooldown_seconds,
+                ),
+                ErrorCode::NotificationCooldown
+            );
+
             // Notification: Send memo to subscriber
             msg!("Sending notification for subscription: {}", subscription.id);
 
@@ -674,6 +2697,9 @@ pub fn process_trigger(
                 subscription.amount as f64 / 1_000_000.0
             );
 
+            // EFFECTS before INTERACTIONS (CEI pattern) - reverted if the send fails
+            ctx.accounts.subscription.last_notification_time = Some(current_time);
+
             send_notification_internal(ctx, memo)?;
         },
         _ => {
@@ -704,7 +2730,7 @@ pub fn process_trigger_with_swap(
     require!(token_mint != usdc_mint, ErrorCode::InvalidTokenMint);
 
     // Verify trigger authority (same logic as process_trigger)
-    match config.authorization_mode {
+    match effective_authorization_mode(subscription.authorization_mode_override, config.authorization_mode) {
         AuthorizationMode::ICPSignature => {
             let _sig = icp_signature.ok_or(ErrorCode::InvalidSignature)?;
             let icp_pubkey = config.icp_public_key.ok_or(ErrorCode::InvalidSignature)?;
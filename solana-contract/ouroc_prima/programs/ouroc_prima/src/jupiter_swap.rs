@@ -5,9 +5,82 @@ use anchor_spl::token::{Token, TokenAccount};
 /// Jupiter V6 Program ID (Mainnet & Devnet)
 pub const JUPITER_PROGRAM_ID: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
 
+/// Anchor discriminator for Jupiter V6 `shared_accounts_route`
+/// sha256("global:shared_accounts_route")[..8]
+pub const SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR: [u8; 8] = [0xc1, 0x20, 0x9b, 0x33, 0x41, 0xd6, 0x9c, 0x81];
+
+/// AMM used by a single route plan leg
+/// Borsh-encoded as the variant index from the Jupiter V6 IDL followed by its fields
+#[derive(Clone, Debug, PartialEq)]
+pub enum SwapType {
+    Raydium,                             // IDL index 7
+    Whirlpool { a_to_b: bool },          // IDL index 17
+    Meteora,                             // IDL index 19
+    /// Any other leg returned by the quote API: raw variant index + Borsh-encoded fields
+    Other { index: u8, data: Vec<u8> },
+}
+
+impl SwapType {
+    fn serialize_into(&self, buf: &mut Vec<u8>) {
+        match self {
+            SwapType::Raydium => buf.push(7),
+            SwapType::Whirlpool { a_to_b } => {
+                buf.push(17);
+                buf.push(*a_to_b as u8);
+            }
+            SwapType::Meteora => buf.push(19),
+            SwapType::Other { index, data } => {
+                buf.push(*index);
+                buf.extend_from_slice(data);
+            }
+        }
+    }
+}
+
+/// One leg of a Jupiter route plan
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoutePlanStep {
+    pub swap: SwapType,
+    pub percent: u8,       // Share of the input routed through this leg
+    pub input_index: u8,   // Index into the routing accounts for the leg's input
+    pub output_index: u8,  // Index into the routing accounts for the leg's output
+}
+
+/// Build `shared_accounts_route` instruction data per the Jupiter V6 IDL:
+/// discriminator, id: u8, route_plan: Vec<RoutePlanStep>, in_amount: u64,
+/// quoted_out_amount: u64, slippage_bps: u16, platform_fee_bps: u8
+/// Borsh encodes the Vec length as a little-endian u32
+pub fn build_jupiter_swap_instruction(
+    id: u8,
+    route_plan: &[RoutePlanStep],
+    in_amount: u64,
+    quoted_out_amount: u64,
+    slippage_bps: u16,
+    platform_fee_bps: u8,
+) -> Vec<u8> {
+    let mut data = Vec::with_capacity(8 + 1 + 4 + route_plan.len() * 5 + 8 + 8 + 2 + 1);
+
+    data.extend_from_slice(&SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR);
+    data.push(id);
+
+    data.extend_from_slice(&(route_plan.len() as u32).to_le_bytes());
+    for step in route_plan {
+        step.swap.serialize_into(&mut data);
+        data.push(step.percent);
+        data.push(step.input_index);
+        data.push(step.output_index);
+    }
+
+    data.extend_from_slice(&in_amount.to_le_bytes());
+    data.extend_from_slice(&quoted_out_amount.to_le_bytes());
+    data.extend_from_slice(&slippage_bps.to_le_bytes());
+    data.push(platform_fee_bps);
+
+    data
+}
+
 /// Minimum output amount calculator with slippage
 /// Uses Pyth oracle price as reference and applies slippage tolerance
-#[allow(dead_code)]
 pub fn calculate_min_output_with_slippage(
     input_amount: u64,
     _oracle_exchange_rate: i64, // From Pyth, with 8 decimals (reserved for future use)
@@ -35,9 +108,8 @@ pub fn calculate_min_output_with_slippage(
 
 /// Execute Jupiter V6 swap via CPI
 /// This uses Jupiter's shared accounts model for efficient routing
-///
-/// ⚠️ PRODUCTION NOTE: This implementation requires actual Jupiter V6 discriminator
-/// from their IDL. Current discriminator is for development/testing only.
+/// The route plan comes from Jupiter's quote API; slippage is enforced both by
+/// Jupiter (slippage_bps) and by the minimum_amount_out check after the CPI
 pub fn execute_jupiter_swap<'info>(
     jupiter_program: &AccountInfo<'info>,
     source_token_account: &Account<'info, TokenAccount>,
@@ -47,6 +119,9 @@ pub fn execute_jupiter_swap<'info>(
     destination_mint: AccountInfo<'info>,
     amount_in: u64,
     minimum_amount_out: u64,
+    route_plan: &[RoutePlanStep],
+    quoted_out_amount: u64,
+    slippage_bps: u16,
     remaining_accounts: &[AccountInfo<'info>], // Jupiter routing accounts
     token_program: &Program<'info, Token>,
 ) -> Result<u64> {
@@ -68,25 +143,20 @@ pub fn execute_jupiter_swap<'info>(
         crate::jupiter_swap::JupiterErrorCode::InvalidRoutingAccounts
     );
 
-    // Build Jupiter swap instruction data
-    // Jupiter V6 uses a discriminator + parameters format
-    let mut instruction_data = Vec::with_capacity(32);
-
-    // ⚠️ PRODUCTION REQUIRED: Update discriminator from actual Jupiter V6 IDL
-    // Current discriminator may not match mainnet Jupiter program
-    let discriminator = std::env::var("JUPITER_V6_DISCRIMINATOR")
-        .unwrap_or_else(|_| "d309428cc51c583d".to_string());
-
-    if let Ok(hex_str) = hex::decode(discriminator) {
-        instruction_data.extend_from_slice(&hex_str);
-    } else {
-        // Fallback discriminator (may not work in production)
-        instruction_data.extend_from_slice(&[0xd3, 0x09, 0x42, 0x8c, 0xc5, 0x1c, 0x58, 0x3d]);
-    }
+    // Snapshot the destination balance so the output is measured as a delta,
+    // not as whatever the account happened to hold before the swap
+    let pre_swap_balance = destination_token_account.amount;
 
-    // Parameters: amount_in (u64) + minimum_amount_out (u64)
-    instruction_data.extend_from_slice(&amount_in.to_le_bytes());
-    instruction_data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+    // Build Jupiter swap instruction data (sharedAccountsRoute, no platform fee)
+    require!(!route_plan.is_empty(), crate::jupiter_swap::JupiterErrorCode::InvalidRoutingAccounts);
+    let instruction_data = build_jupiter_swap_instruction(
+        0,
+        route_plan,
+        amount_in,
+        quoted_out_amount,
+        slippage_bps,
+        0,
+    );
 
     // Build account metas for Jupiter CPI
     let mut account_metas = vec![
@@ -129,12 +199,23 @@ pub fn execute_jupiter_swap<'info>(
 
     solana_program::program::invoke(&jupiter_ix, &account_infos)?;
 
-    // Get actual output amount from destination account
+    // Get actual output amount from destination account (post - pre balance)
+    // Slippage means Jupiter may deliver less than amount_in even for stablecoins
     destination_token_account.reload()?;
-    let output_amount = destination_token_account.amount;
+    let output_amount = swap_output_amount(pre_swap_balance, destination_token_account.amount, minimum_amount_out)?;
 
     msg!("Jupiter swap completed: received {} tokens", output_amount);
 
+    Ok(output_amount)
+}
+
+/// Tokens a swap delivered, measured as the destination balance delta,
+/// rejected if below the slippage-protected minimum
+pub fn swap_output_amount(pre_swap_balance: u64, post_swap_balance: u64, minimum_amount_out: u64) -> Result<u64> {
+    let output_amount = post_swap_balance
+        .checked_sub(pre_swap_balance)
+        .ok_or(crate::jupiter_swap::JupiterErrorCode::InsufficientOutputAmount)?;
+
     // Verify we got at least minimum amount
     require!(
         output_amount >= minimum_amount_out,
@@ -155,6 +236,8 @@ pub fn swap_stablecoin_to_usdc<'info>(
     usdc_mint: &Account<'info, anchor_spl::token::Mint>,
     amount: u64,
     min_output: u64,
+    route_plan: &[RoutePlanStep],
+    slippage_bps: u16,
     routing_accounts: &[AccountInfo<'info>],
     token_program: &Program<'info, Token>,
 ) -> Result<u64> {
@@ -172,6 +255,9 @@ pub fn swap_stablecoin_to_usdc<'info>(
         usdc_mint.to_account_info(),
         amount,
         min_output,
+        route_plan,
+        amount, // Stablecoins quote 1:1
+        slippage_bps,
         routing_accounts,
         token_program,
     )
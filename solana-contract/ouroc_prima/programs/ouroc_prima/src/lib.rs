@@ -3,7 +3,8 @@
 #![allow(deprecated)]
 
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Mint};
+use anchor_spl::token::{Token, TokenAccount};
+use anchor_spl::token_interface::{self, TokenInterface};
 use std::str::FromStr;
 
 // Import modules
@@ -14,11 +15,16 @@ mod data_structures;
 mod payment_helpers;
 mod instruction_handlers;
 mod crypto;
+pub mod price_oracle;
+pub mod jupiter_swap;
 
 // Re-export commonly used items
 pub use constants::*;
 pub use events::*;
 pub use data_structures::*;
+pub use payment_helpers::{
+    bound_fee_amount, claim_subscription_version, migrate_legacy_config, migrate_legacy_subscription, split_payment_amount,
+};
 
 // ============================================================================
 // Account Structures
@@ -61,6 +67,15 @@ pub struct Initialize<'info> {
     )]
     pub config: Account<'info, Config>,
 
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + FeeAnalytics::LEN,
+        seeds = [b"fee_analytics"],
+        bump
+    )]
+    pub fee_analytics: Account<'info, FeeAnalytics>,
+
     #[account(mut)]
     pub authority: Signer<'info>,
 
@@ -79,8 +94,71 @@ pub struct UpdateFeeDestination<'info> {
     pub authority: Signer<'info>,
 }
 
+/// Context for creating the fee vault token account for a payment mint (admin only)
 #[derive(Accounts)]
-#[instruction(subscription_id: String)]
+pub struct InitializeFeeVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = authority @ ErrorCode::UnauthorizedAccess
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Token account owned by its own PDA, seeds = [b"fee_vault", mint]
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"fee_vault", mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = fee_vault,
+        token::token_program = token_program
+    )]
+    pub fee_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for withdrawing accumulated platform fees (admin only)
+#[derive(Accounts)]
+pub struct WithdrawFees<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = authority @ ErrorCode::UnauthorizedAccess
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_vault", mint.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = recipient_token_account.mint == mint.key() @ ErrorCode::InvalidTokenMint
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(subscription_id: String, amount: u64, interval_seconds: i64, merchant_address: Pubkey)]
 pub struct CreateSubscription<'info> {
     #[account(
         init,
@@ -99,17 +177,139 @@ pub struct CreateSubscription<'info> {
     )]
     pub subscription_pda: UncheckedAccount<'info>,
 
-    /// Subscriber's USDC token account (for automatic delegation)
+    /// Subscriber's token account (for automatic delegation) - Token v1 or Token-2022
     #[account(mut)]
-    pub subscriber_token_account: Account<'info, TokenAccount>,
+    pub subscriber_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
 
     #[account(seeds = [b"config"], bump)]
     pub config: Account<'info, Config>,
 
+    /// Merchant opt-in settings (may be uninitialized)
+    /// CHECK: PDA verified by seeds; deserialized in the handler only if it exists
+    #[account(
+        seeds = [b"merchant_config", merchant_address.as_ref()],
+        bump
+    )]
+    pub merchant_config: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = subscriber,
+        space = 8 + SubscriberIndex::LEN,
+        seeds = [b"sub_index", subscriber.key().as_ref()],
+        bump
+    )]
+    pub subscriber_index: Account<'info, SubscriberIndex>,
+
+    #[account(
+        init_if_needed,
+        payer = subscriber,
+        space = 8 + MerchantIndex::LEN,
+        seeds = [b"merchant_index", merchant_address.as_ref()],
+        bump
+    )]
+    pub merchant_index: Account<'info, MerchantIndex>,
+
+    /// Merchant totals, created with the merchant's first subscription
+    #[account(
+        init_if_needed,
+        payer = subscriber,
+        space = 8 + MerchantDashboard::LEN,
+        seeds = [b"merchant_dashboard", merchant_address.as_ref()],
+        bump
+    )]
+    pub merchant_dashboard: Account<'info, MerchantDashboard>,
+
+    /// Creation fee recipient, required while config.creation_fee_lamports > 0
+    /// CHECK: Must be the config authority; only receives lamports
+    #[account(mut, address = config.authority @ ErrorCode::UnauthorizedAccess)]
+    pub config_authority: Option<UncheckedAccount<'info>>,
+
+    #[account(mut)]
+    pub subscriber: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(template_id: String, new_subscription_id: String)]
+pub struct CloneSubscription<'info> {
+    #[account(
+        seeds = [b"subscription", template_id.as_bytes()],
+        bump
+    )]
+    pub template_subscription: Account<'info, Subscription>,
+
+    #[account(
+        init,
+        payer = subscriber,
+        space = 8 + Subscription::LEN,
+        seeds = [b"subscription", new_subscription_id.as_bytes()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// Subscription PDA (same as subscription account key, for delegation)
+    /// CHECK: PDA derived from new_subscription_id
+    #[account(
+        seeds = [b"subscription", new_subscription_id.as_bytes()],
+        bump
+    )]
+    pub subscription_pda: UncheckedAccount<'info>,
+
+    /// Subscriber's token account (for automatic delegation) - Token v1 or Token-2022
+    #[account(mut)]
+    pub subscriber_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+
+    /// Merchant opt-in settings for the template's merchant (may be uninitialized)
+    /// CHECK: PDA verified by seeds; deserialized in the handler only if it exists
+    #[account(
+        seeds = [b"merchant_config", template_subscription.merchant.as_ref()],
+        bump
+    )]
+    pub merchant_config: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = subscriber,
+        space = 8 + SubscriberIndex::LEN,
+        seeds = [b"sub_index", subscriber.key().as_ref()],
+        bump
+    )]
+    pub subscriber_index: Account<'info, SubscriberIndex>,
+
+    #[account(
+        init_if_needed,
+        payer = subscriber,
+        space = 8 + MerchantIndex::LEN,
+        seeds = [b"merchant_index", template_subscription.merchant.as_ref()],
+        bump
+    )]
+    pub merchant_index: Account<'info, MerchantIndex>,
+
+    /// Merchant totals, created with the merchant's first subscription
+    #[account(
+        init_if_needed,
+        payer = subscriber,
+        space = 8 + MerchantDashboard::LEN,
+        seeds = [b"merchant_dashboard", template_subscription.merchant.as_ref()],
+        bump
+    )]
+    pub merchant_dashboard: Account<'info, MerchantDashboard>,
+
+    /// Creation fee recipient, required while config.creation_fee_lamports > 0
+    /// CHECK: Must be the config authority; only receives lamports
+    #[account(mut, address = config.authority @ ErrorCode::UnauthorizedAccess)]
+    pub config_authority: Option<UncheckedAccount<'info>>,
+
     #[account(mut)]
     pub subscriber: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -122,152 +322,899 @@ pub struct ProcessPayment<'info> {
     pub config: Account<'info, Config>,
 
     /// CHECK: ICP canister or anyone can trigger payment (not subscriber)
+    /// Pays rent for the payment nonce
+    #[account(mut)]
     pub trigger_authority: Signer<'info>,
 
-    /// CHECK: This is the subscriber's wallet (does not need to sign)
-    pub subscriber: UncheckedAccount<'info>,
+    /// CHECK: This is the subscriber's wallet (does not need to sign)
+    pub subscriber: UncheckedAccount<'info>,
+
+    /// Payment nonce for the current cycle - created in process_payment_core,
+    /// an existing nonce means this cycle was already charged
+    /// CHECK: PDA verified by seeds; initialized manually to return DuplicatePayment
+    #[account(
+        mut,
+        seeds = [b"payment_nonce", subscription.id.as_bytes(), &subscription.payments_made.to_le_bytes()],
+        bump
+    )]
+    pub payment_nonce: UncheckedAccount<'info>,
+
+    /// Payment nonce from the previous cycle - closed to reclaim rent (omit on the first payment)
+    /// CHECK: PDA verified in process_payment_core before closing
+    #[account(mut)]
+    pub previous_payment_nonce: Option<UncheckedAccount<'info>>,
+
+    /// Used nonce for the ICP signature, seeds = [b"nonce", subscription.id, timestamp]
+    /// Required when the payment is authorized by an ICP signature
+    /// CHECK: PDA verified in process_payment_core; initialized manually to return SignatureReplayed
+    #[account(mut)]
+    pub used_nonce: Option<UncheckedAccount<'info>>,
+
+    /// Program-wide fee totals
+    #[account(mut, seeds = [b"fee_analytics"], bump)]
+    pub fee_analytics: Account<'info, FeeAnalytics>,
+
+    /// Merchant totals, seeds = [b"merchant_dashboard", subscription.merchant] - created on first payment
+    /// CHECK: PDA verified in process_payment_core; initialized manually when missing
+    #[account(mut)]
+    pub merchant_dashboard: UncheckedAccount<'info>,
+
+    /// Subscriber track record, seeds = [b"reputation", subscription.subscriber] - created on first payment
+    /// CHECK: PDA verified in record_subscriber_reputation; initialized manually when missing
+    #[account(mut)]
+    pub subscriber_reputation: Option<UncheckedAccount<'info>>,
+
+    /// USDC Token accounts with mint verification (Token v1 or Token-2022)
+    #[account(
+        mut,
+        constraint = subscriber_token_account.mint == usdc_mint.key() @ ErrorCode::InvalidTokenMint
+    )]
+    pub subscriber_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = merchant_token_account.mint == usdc_mint.key() @ ErrorCode::InvalidTokenMint
+    )]
+    pub merchant_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// Fee vault for the payment mint - platform fees accumulate here until withdraw_fees
+    #[account(
+        mut,
+        seeds = [b"fee_vault", usdc_mint.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// Payment mint - the official USDC mint, or an allowlisted Token-2022 stablecoin for Token-2022
+    /// subscriptions; the token program must be the one that owns it
+    #[account(
+        constraint = is_allowed_payment_mint(&usdc_mint.key(), subscription.use_token_2022) @ ErrorCode::InvalidTokenMint,
+        constraint = *usdc_mint.to_account_info().owner == token_program.key() @ ErrorCode::InvalidTokenProgram
+    )]
+    pub usdc_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Instructions sysvar for Ed25519 signature verification
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+}
+
+/// process_payment accounts plus the SPL Memo program, for payments that carry an explorer-visible memo
+#[derive(Accounts)]
+pub struct ProcessPaymentWithMemo<'info> {
+    pub payment: ProcessPayment<'info>,
+
+    /// CHECK: SPL Memo Program
+    #[account(address = Pubkey::from_str(SPL_MEMO_PROGRAM_ID).unwrap())]
+    pub memo_program: UncheckedAccount<'info>,
+}
+
+/// Context for closing expired UsedNonce PDAs (anyone can call)
+/// remaining_accounts: UsedNonce accounts paid for by rent_receiver
+#[derive(Accounts)]
+pub struct PruneExpiredNonces<'info> {
+    /// CHECK: Must match the payer recorded in each nonce; receives the reclaimed rent
+    #[account(mut)]
+    pub rent_receiver: UncheckedAccount<'info>,
+
+    pub caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSubscription<'info> {
+    #[account(
+        mut,
+        has_one = subscriber @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    pub subscriber: Signer<'info>,
+}
+
+/// Context for a subscriber cancelling a subscription
+/// The index PDAs are optional so subscriptions created before indexing existed can still be cancelled
+#[derive(Accounts)]
+pub struct CancelSubscription<'info> {
+    #[account(
+        mut,
+        has_one = subscriber @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(mut, seeds = [b"sub_index", subscription.subscriber.as_ref()], bump)]
+    pub subscriber_index: Option<Account<'info, SubscriberIndex>>,
+
+    #[account(mut, seeds = [b"merchant_index", subscription.merchant.as_ref()], bump)]
+    pub merchant_index: Option<Account<'info, MerchantIndex>>,
+
+    /// Merchant totals; created here for merchants whose subscriptions predate dashboards
+    #[account(
+        init_if_needed,
+        payer = subscriber,
+        space = 8 + MerchantDashboard::LEN,
+        seeds = [b"merchant_dashboard", subscription.merchant.as_ref()],
+        bump
+    )]
+    pub merchant_dashboard: Account<'info, MerchantDashboard>,
+
+    #[account(mut)]
+    pub subscriber: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for reading a subscription's delegation status (no signer needed)
+#[derive(Accounts)]
+#[instruction(subscription_id: String)]
+pub struct CheckDelegationStatus<'info> {
+    #[account(seeds = [b"subscription", subscription_id.as_bytes()], bump)]
+    pub subscription: Account<'info, Subscription>,
+
+    /// Subscriber's token account (Token v1 or Token-2022)
+    #[account(
+        constraint = subscriber_token_account.owner == subscription.subscriber @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscriber_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+}
+
+/// Context for reporting a failed payment attempt (authority only)
+#[derive(Accounts)]
+#[instruction(subscription_id: String)]
+pub struct RecordFailedPayment<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = authority @ ErrorCode::UnauthorizedAccess
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(seeds = [b"subscription", subscription_id.as_bytes()], bump)]
+    pub subscription: Account<'info, Subscription>,
+
+    /// CHECK: PDA verified in record_subscriber_reputation; initialized manually when missing
+    #[account(mut)]
+    pub subscriber_reputation: UncheckedAccount<'info>,
+
+    #[account(mut, seeds = [b"merchant_dashboard", subscription.merchant.as_ref()], bump)]
+    pub merchant_dashboard: Option<Account<'info, MerchantDashboard>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for reading a subscriber's reputation (no signer needed)
+#[derive(Accounts)]
+#[instruction(subscriber: Pubkey)]
+pub struct GetSubscriberReputation<'info> {
+    #[account(seeds = [b"reputation", subscriber.as_ref()], bump)]
+    pub subscriber_reputation: Account<'info, SubscriberReputation>,
+}
+
+/// Context for reading a merchant's dashboard totals (no signer needed)
+#[derive(Accounts)]
+#[instruction(merchant: Pubkey)]
+pub struct GetMerchantDashboard<'info> {
+    #[account(seeds = [b"merchant_dashboard", merchant.as_ref()], bump)]
+    pub merchant_dashboard: Account<'info, MerchantDashboard>,
+}
+
+/// Context for reading a subscriber's indexed subscription IDs (no signer needed)
+#[derive(Accounts)]
+#[instruction(subscriber: Pubkey)]
+pub struct GetSubscriberSubscriptions<'info> {
+    #[account(seeds = [b"sub_index", subscriber.as_ref()], bump)]
+    pub subscriber_index: Account<'info, SubscriberIndex>,
+}
+
+/// Context for reading a merchant's indexed subscription IDs (no signer needed)
+#[derive(Accounts)]
+#[instruction(merchant: Pubkey)]
+pub struct GetMerchantSubscriptions<'info> {
+    #[account(seeds = [b"merchant_index", merchant.as_ref()], bump)]
+    pub merchant_index: Account<'info, MerchantIndex>,
+}
+
+/// Context for reading the fee totals (no signer needed)
+#[derive(Accounts)]
+pub struct GetFeeAnalytics<'info> {
+    #[account(seeds = [b"fee_analytics"], bump)]
+    pub fee_analytics: Account<'info, FeeAnalytics>,
+}
+
+/// Context for starting a new fee reporting window (admin only)
+#[derive(Accounts)]
+pub struct ResetFeeAnalytics<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = authority @ ErrorCode::UnauthorizedAccess
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(mut, seeds = [b"fee_analytics"], bump)]
+    pub fee_analytics: Account<'info, FeeAnalytics>,
+
+    pub authority: Signer<'info>,
+}
+
+/// Context for completing an ICP key rotation (permissionless once the window has elapsed)
+#[derive(Accounts)]
+pub struct CompleteKeyRotation<'info> {
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+
+    pub caller: Signer<'info>,
+}
+
+/// Context for a subscriber to change their swap slippage tolerance
+#[derive(Accounts)]
+pub struct UpdateSlippage<'info> {
+    #[account(
+        mut,
+        has_one = subscriber @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    pub subscriber: Signer<'info>,
+}
+
+/// Context for a subscriber to change the billing interval and re-approve the delegation
+#[derive(Accounts)]
+pub struct UpdateInterval<'info> {
+    #[account(
+        mut,
+        has_one = subscriber @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// Subscriber's token account the subscription PDA is delegate of - Token v1 or Token-2022
+    #[account(
+        mut,
+        constraint = subscriber_token_account.owner == subscriber.key() @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscriber_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    pub subscriber: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Context for a subscriber to renew an exhausted delegation
+#[derive(Accounts)]
+pub struct AutoRenewDelegation<'info> {
+    #[account(
+        has_one = subscriber @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// Subscriber's token account the subscription PDA is delegate of - Token v1 or Token-2022
+    #[account(
+        mut,
+        constraint = subscriber_token_account.owner == subscriber.key() @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscriber_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    pub subscriber: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Context for multisig admin proposals, approvals, and execution
+#[derive(Accounts)]
+pub struct MultisigAdminAction<'info> {
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+
+    /// Multisig member (propose/approve) or any signer (execute)
+    pub signer: Signer<'info>,
+}
+
+/// Context for merchant-side subscription management
+#[derive(Accounts)]
+pub struct MerchantAction<'info> {
+    #[account(
+        mut,
+        has_one = merchant @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    pub merchant: Signer<'info>,
+}
+
+/// Context for closing a cancelled subscription account, rent returned to the subscriber who paid for it
+#[derive(Accounts)]
+#[instruction(subscription_id: String)]
+pub struct CloseSubscription<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription_id.as_bytes()],
+        bump,
+        has_one = subscriber @ ErrorCode::UnauthorizedAccess,
+        close = subscriber
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(mut, seeds = [b"sub_index", subscription.subscriber.as_ref()], bump)]
+    pub subscriber_index: Option<Account<'info, SubscriberIndex>>,
+
+    #[account(mut, seeds = [b"merchant_index", subscription.merchant.as_ref()], bump)]
+    pub merchant_index: Option<Account<'info, MerchantIndex>>,
+
+    /// Subscriber (must sign to close)
+    #[account(mut)]
+    pub subscriber: Signer<'info>,
+}
+
+/// Context for upgrading a subscription written by the original program
+#[derive(Accounts)]
+pub struct MigrateSubscription<'info> {
+    /// CHECK: Owner, discriminator, legacy size and PDA are verified in migrate_subscription
+    #[account(mut)]
+    pub subscription: UncheckedAccount<'info>,
+
+    /// Funds the rent for the larger account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for upgrading the config written by the original program
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    /// CHECK: Owner, discriminator, legacy size and authority are verified in migrate_config
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    /// Config authority (funds the rent for the larger account)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeDelegate<'info> {
+    #[account(
+        has_one = subscriber @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// Subscriber's token account (Token v1 or Token-2022)
+    #[account(mut)]
+    pub subscriber_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// Subscriber (must sign to revoke delegation)
+    pub subscriber: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct AdminAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = authority @ ErrorCode::UnauthorizedAccess
+    )]
+    pub config: Account<'info, Config>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SendNotification<'info> {
+    #[account(
+        seeds = [b"subscription", subscription.id.as_bytes()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: Notification sender (must be authorized - ICP canister or admin)
+    #[account(mut)]
+    pub notification_sender: Signer<'info>,
+
+    /// CHECK: Subscriber wallet (receives notification)
+    #[account(mut)]
+    pub subscriber: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: SPL Memo Program
+    #[account(address = Pubkey::from_str(SPL_MEMO_PROGRAM_ID).unwrap())]
+    pub memo_program: UncheckedAccount<'info>,
+}
+
+/// Context for merchant to claim USDC from escrow after off-ramp confirmation
+#[derive(Accounts)]
+#[instruction(subscription_id: String)]
+pub struct ClaimFromEscrow<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription_id.as_bytes()],
+        bump,
+        has_one = merchant @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// Escrow PDA token account (holds USDC before claim)
+    #[account(
+        mut,
+        constraint = escrow_token_account.owner == subscription.escrow_pda @ ErrorCode::UnauthorizedAccess,
+        constraint = escrow_token_account.mint == get_usdc_mint() @ ErrorCode::InvalidTokenMint
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    /// Merchant's USDC token account (receives claimed funds)
+    #[account(
+        mut,
+        constraint = merchant_token_account.owner == subscription.merchant @ ErrorCode::UnauthorizedAccess,
+        constraint = merchant_token_account.mint == get_usdc_mint() @ ErrorCode::InvalidTokenMint
+    )]
+    pub merchant_token_account: Account<'info, TokenAccount>,
+
+    /// Merchant (must sign to claim)
+    pub merchant: Signer<'info>,
+
+    /// Escrow PDA (has authority over escrow token account)
+    /// CHECK: Verified via seeds
+    #[account(
+        seeds = [b"escrow", subscription_id.as_bytes()],
+        bump
+    )]
+    pub escrow_pda: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Context for processing up to MAX_BATCH_SIZE payments in one transaction
+/// remaining_accounts: (subscription, subscriber_token_account, merchant_token_account) triplets
+#[derive(Accounts)]
+pub struct BatchProcessPayments<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+
+    /// ICP canister authority (verified via batch signature)
+    /// Pays rent for each entry's nonce, dashboard and reputation PDAs
+    #[account(mut)]
+    pub trigger_authority: Signer<'info>,
+
+    /// Fee vault for USDC (shared across the batch), same destination as process_payment
+    #[account(
+        mut,
+        seeds = [b"fee_vault", usdc_mint.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// USDC Mint - must be the official USDC mint
+    #[account(
+        constraint = usdc_mint.key() == get_usdc_mint() @ ErrorCode::InvalidTokenMint
+    )]
+    pub usdc_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    /// Program-wide fee totals
+    #[account(mut, seeds = [b"fee_analytics"], bump)]
+    pub fee_analytics: Account<'info, FeeAnalytics>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Instructions sysvar for Ed25519 signature verification
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+}
+
+/// Context for the current subscriber to propose moving the subscription to a new wallet
+#[derive(Accounts)]
+pub struct ProposeSubscriptionTransfer<'info> {
+    #[account(
+        seeds = [b"subscription", subscription.id.as_bytes()],
+        bump,
+        has_one = subscriber @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        init,
+        payer = subscriber,
+        space = 8 + TransferProposal::LEN,
+        seeds = [b"transfer", subscription.id.as_bytes()],
+        bump
+    )]
+    pub transfer_proposal: Account<'info, TransferProposal>,
+
+    /// Current subscriber (must sign to propose)
+    #[account(mut)]
+    pub subscriber: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for a merchant to create their opt-in settings
+#[derive(Accounts)]
+pub struct InitializeMerchantConfig<'info> {
+    #[account(
+        init,
+        payer = merchant,
+        space = 8 + MerchantConfig::LEN,
+        seeds = [b"merchant_config", merchant.key().as_ref()],
+        bump
+    )]
+    pub merchant_config: Account<'info, MerchantConfig>,
+
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for a merchant to manage their approved subscribers
+#[derive(Accounts)]
+pub struct UpdateMerchantConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant_config", merchant.key().as_ref()],
+        bump,
+        has_one = merchant @ ErrorCode::UnauthorizedAccess
+    )]
+    pub merchant_config: Account<'info, MerchantConfig>,
+
+    pub merchant: Signer<'info>,
+}
+
+/// Context for a merchant to propose a new payout wallet
+#[derive(Accounts)]
+pub struct ProposeMerchantAddressChange<'info> {
+    #[account(
+        seeds = [b"subscription", subscription.id.as_bytes()],
+        bump,
+        has_one = merchant @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        init,
+        payer = merchant,
+        space = 8 + MerchantChangeProposal::LEN,
+        seeds = [b"merchant_change", subscription.id.as_bytes()],
+        bump
+    )]
+    pub merchant_change_proposal: Account<'info, MerchantChangeProposal>,
+
+    /// Current merchant (must sign to propose)
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for executing a merchant wallet change after the timelock
+#[derive(Accounts)]
+pub struct ExecuteMerchantAddressChange<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.id.as_bytes()],
+        bump,
+        has_one = merchant @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// Proposal PDA - closed on execute, rent returned to the merchant who paid for it
+    #[account(
+        mut,
+        seeds = [b"merchant_change", subscription.id.as_bytes()],
+        bump,
+        constraint = merchant_change_proposal.subscription == subscription.key() @ ErrorCode::InvalidMerchantAddress,
+        close = merchant
+    )]
+    pub merchant_change_proposal: Account<'info, MerchantChangeProposal>,
+
+    /// Current merchant (must sign to execute)
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+}
+
+/// Context for a merchant to propose new billing terms
+#[derive(Accounts)]
+pub struct ProposeSubscriptionChange<'info> {
+    #[account(
+        seeds = [b"subscription", subscription.id.as_bytes()],
+        bump,
+        has_one = merchant @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        init,
+        payer = merchant,
+        space = 8 + ProposedSubscriptionChange::LEN,
+        seeds = [b"change_proposal", subscription.id.as_bytes()],
+        bump
+    )]
+    pub change_proposal: Account<'info, ProposedSubscriptionChange>,
+
+    /// Current merchant (must sign to propose)
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for the subscriber to accept proposed terms and re-approve the delegation
+#[derive(Accounts)]
+pub struct AcceptSubscriptionChange<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.id.as_bytes()],
+        bump,
+        has_one = subscriber @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// Proposal PDA - closed on accept, rent returned to the proposer
+    #[account(
+        mut,
+        seeds = [b"change_proposal", subscription.id.as_bytes()],
+        bump,
+        constraint = change_proposal.subscription == subscription.key() @ ErrorCode::InvalidSubscriptionPDA,
+        close = proposer
+    )]
+    pub change_proposal: Account<'info, ProposedSubscriptionChange>,
+
+    /// CHECK: Rent recipient, must be the proposal's proposer
+    #[account(mut, address = change_proposal.proposer @ ErrorCode::UnauthorizedAccess)]
+    pub proposer: UncheckedAccount<'info>,
+
+    /// Subscriber's token account the subscription PDA is delegate of - Token v1 or Token-2022
+    #[account(
+        mut,
+        constraint = subscriber_token_account.owner == subscriber.key() @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscriber_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    pub subscriber: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Context for the subscriber to decline proposed terms
+#[derive(Accounts)]
+pub struct RejectSubscriptionChange<'info> {
+    #[account(
+        seeds = [b"subscription", subscription.id.as_bytes()],
+        bump,
+        has_one = subscriber @ ErrorCode::UnauthorizedAccess
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// Proposal PDA - closed on reject, rent returned to the proposer
+    #[account(
+        mut,
+        seeds = [b"change_proposal", subscription.id.as_bytes()],
+        bump,
+        constraint = change_proposal.subscription == subscription.key() @ ErrorCode::InvalidSubscriptionPDA,
+        close = proposer
+    )]
+    pub change_proposal: Account<'info, ProposedSubscriptionChange>,
+
+    /// CHECK: Rent recipient, must be the proposal's proposer
+    #[account(mut, address = change_proposal.proposer @ ErrorCode::UnauthorizedAccess)]
+    pub proposer: UncheckedAccount<'info>,
+
+    pub subscriber: Signer<'info>,
+}
+
+/// Context for closing an unanswered proposal after it expires (no signer needed)
+#[derive(Accounts)]
+pub struct ExpireProposal<'info> {
+    #[account(seeds = [b"subscription", subscription.id.as_bytes()], bump)]
+    pub subscription: Account<'info, Subscription>,
 
-    /// USDC Token accounts with mint verification
+    /// Proposal PDA - closed, rent returned to the proposer
     #[account(
         mut,
-        constraint = subscriber_token_account.mint == usdc_mint.key() @ ErrorCode::InvalidTokenMint
+        seeds = [b"change_proposal", subscription.id.as_bytes()],
+        bump,
+        constraint = change_proposal.subscription == subscription.key() @ ErrorCode::InvalidSubscriptionPDA,
+        close = proposer
     )]
-    pub subscriber_token_account: Account<'info, TokenAccount>,
+    pub change_proposal: Account<'info, ProposedSubscriptionChange>,
+
+    /// CHECK: Rent recipient, must be the proposal's proposer
+    #[account(mut, address = change_proposal.proposer @ ErrorCode::UnauthorizedAccess)]
+    pub proposer: UncheckedAccount<'info>,
+}
 
+/// Context for the new subscriber to accept a proposed transfer after the timelock
+#[derive(Accounts)]
+pub struct AcceptSubscriptionTransfer<'info> {
     #[account(
         mut,
-        constraint = merchant_token_account.mint == usdc_mint.key() @ ErrorCode::InvalidTokenMint
+        seeds = [b"subscription", subscription.id.as_bytes()],
+        bump
     )]
-    pub merchant_token_account: Account<'info, TokenAccount>,
+    pub subscription: Account<'info, Subscription>,
 
+    /// Proposal PDA - closed on accept, rent returned to the old subscriber who paid for it
     #[account(
         mut,
-        constraint = icp_fee_token_account.mint == usdc_mint.key() @ ErrorCode::InvalidTokenMint
+        seeds = [b"transfer", subscription.id.as_bytes()],
+        bump,
+        constraint = transfer_proposal.subscription == subscription.key() @ ErrorCode::InvalidSubscriptionTransfer,
+        constraint = transfer_proposal.pending.new_subscriber == new_subscriber.key() @ ErrorCode::UnauthorizedAccess,
+        close = old_subscriber
     )]
-    pub icp_fee_token_account: Account<'info, TokenAccount>,
+    pub transfer_proposal: Account<'info, TransferProposal>,
 
-    /// USDC Mint - must be the official USDC mint
+    /// New subscriber (must sign to accept)
+    pub new_subscriber: Signer<'info>,
+
+    /// CHECK: Old subscriber wallet - receives proposal rent; may optionally sign to revoke delegation
     #[account(
-        constraint = usdc_mint.key() == get_usdc_mint() @ ErrorCode::InvalidTokenMint
+        mut,
+        constraint = old_subscriber.key() == subscription.subscriber @ ErrorCode::UnauthorizedAccess
     )]
-    pub usdc_mint: Account<'info, Mint>,
+    pub old_subscriber: UncheckedAccount<'info>,
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+    /// Old subscriber's token account (delegation to the subscription PDA is revoked)
+    #[account(
+        mut,
+        constraint = old_subscriber_token_account.owner == subscription.subscriber @ ErrorCode::UnauthorizedAccess
+    )]
+    pub old_subscriber_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
 
-    /// CHECK: Instructions sysvar for Ed25519 signature verification
-    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
-    pub instructions_sysvar: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
-
+/// Context for merchant-initiated refunds back to the subscriber
 #[derive(Accounts)]
-pub struct UpdateSubscription<'info> {
+pub struct ProcessRefund<'info> {
     #[account(
         mut,
-        has_one = subscriber @ ErrorCode::UnauthorizedAccess
+        seeds = [b"subscription", subscription.id.as_bytes()],
+        bump,
+        has_one = merchant @ ErrorCode::UnauthorizedAccess
     )]
     pub subscription: Account<'info, Subscription>,
 
-    pub subscriber: Signer<'info>,
-}
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
 
-#[derive(Accounts)]
-pub struct RevokeDelegate<'info> {
+    /// Merchant's USDC token account (source of refund)
     #[account(
-        has_one = subscriber @ ErrorCode::UnauthorizedAccess
+        mut,
+        constraint = merchant_token_account.owner == subscription.merchant @ ErrorCode::UnauthorizedAccess,
+        constraint = merchant_token_account.mint == get_usdc_mint() @ ErrorCode::InvalidTokenMint
     )]
-    pub subscription: Account<'info, Subscription>,
+    pub merchant_token_account: Account<'info, TokenAccount>,
 
-    /// Subscriber's USDC token account
-    #[account(mut)]
+    /// Subscriber's USDC token account (receives refund)
+    #[account(
+        mut,
+        constraint = subscriber_token_account.owner == subscription.subscriber @ ErrorCode::UnauthorizedAccess,
+        constraint = subscriber_token_account.mint == get_usdc_mint() @ ErrorCode::InvalidTokenMint
+    )]
     pub subscriber_token_account: Account<'info, TokenAccount>,
 
-    /// Subscriber (must sign to revoke delegation)
-    pub subscriber: Signer<'info>,
+    /// Merchant (must sign - owns the refunded funds)
+    pub merchant: Signer<'info>,
 
     pub token_program: Program<'info, Token>,
 }
 
+/// Context for a subscriber to dispute a charge
 #[derive(Accounts)]
-pub struct AdminAction<'info> {
+#[instruction(subscription_id: String)]
+pub struct DisputePayment<'info> {
     #[account(
         mut,
-        seeds = [b"config"],
+        seeds = [b"subscription", subscription_id.as_bytes()],
         bump,
-        has_one = authority @ ErrorCode::UnauthorizedAccess
+        has_one = subscriber @ ErrorCode::UnauthorizedAccess
     )]
-    pub config: Account<'info, Config>,
-
-    pub authority: Signer<'info>,
-}
+    pub subscription: Account<'info, Subscription>,
 
-#[derive(Accounts)]
-pub struct SendNotification<'info> {
+    /// One open dispute per subscription
     #[account(
-        seeds = [b"subscription", subscription.id.as_bytes()],
+        init,
+        payer = subscriber,
+        space = 8 + DisputeRecord::LEN,
+        seeds = [b"dispute", subscription_id.as_bytes()],
         bump
     )]
-    pub subscription: Account<'info, Subscription>,
+    pub dispute_record: Account<'info, DisputeRecord>,
 
-    #[account(seeds = [b"config"], bump)]
-    pub config: Account<'info, Config>,
-
-    /// CHECK: Notification sender (must be authorized - ICP canister or admin)
-    #[account(mut)]
-    pub notification_sender: Signer<'info>,
-
-    /// CHECK: Subscriber wallet (receives notification)
     #[account(mut)]
-    pub subscriber: UncheckedAccount<'info>,
+    pub subscriber: Signer<'info>,
 
     pub system_program: Program<'info, System>,
-
-    /// CHECK: SPL Memo Program
-    #[account(address = Pubkey::from_str(SPL_MEMO_PROGRAM_ID).unwrap())]
-    pub memo_program: UncheckedAccount<'info>,
 }
 
-/// Context for merchant to claim USDC from escrow after off-ramp confirmation
+/// Context for the admin to rule on a dispute
+/// The refund accounts are optional; without them an Approved ruling only unblocks charges
 #[derive(Accounts)]
-#[instruction(subscription_id: String)]
-pub struct ClaimFromEscrow<'info> {
+pub struct ResolveDispute<'info> {
     #[account(
         mut,
-        seeds = [b"subscription", subscription_id.as_bytes()],
+        seeds = [b"config"],
         bump,
-        has_one = merchant @ ErrorCode::UnauthorizedAccess
+        has_one = authority @ ErrorCode::UnauthorizedAccess
     )]
-    pub subscription: Account<'info, Subscription>,
+    pub config: Account<'info, Config>,
 
-    /// Escrow PDA token account (holds USDC before claim)
     #[account(
         mut,
-        constraint = escrow_token_account.owner == subscription.escrow_pda @ ErrorCode::UnauthorizedAccess,
-        constraint = escrow_token_account.mint == get_usdc_mint() @ ErrorCode::InvalidTokenMint
+        seeds = [b"subscription", subscription.id.as_bytes()],
+        bump
     )]
-    pub escrow_token_account: Account<'info, TokenAccount>,
+    pub subscription: Account<'info, Subscription>,
 
-    /// Merchant's USDC token account (receives claimed funds)
+    /// Dispute PDA - closed on resolution, rent returned to the subscriber
     #[account(
         mut,
-        constraint = merchant_token_account.owner == subscription.merchant @ ErrorCode::UnauthorizedAccess,
-        constraint = merchant_token_account.mint == get_usdc_mint() @ ErrorCode::InvalidTokenMint
+        seeds = [b"dispute", subscription.id.as_bytes()],
+        bump,
+        constraint = dispute_record.subscription == subscription.key() @ ErrorCode::InvalidSubscriptionPDA,
+        close = subscriber
     )]
-    pub merchant_token_account: Account<'info, TokenAccount>,
+    pub dispute_record: Account<'info, DisputeRecord>,
 
-    /// Merchant (must sign to claim)
-    pub merchant: Signer<'info>,
+    /// CHECK: Rent recipient, must be the subscription's subscriber
+    #[account(mut, address = subscription.subscriber @ ErrorCode::UnauthorizedAccess)]
+    pub subscriber: UncheckedAccount<'info>,
 
-    /// Escrow PDA (has authority over escrow token account)
-    /// CHECK: Verified via seeds
+    /// Platform USDC fee vault, funds the refund
     #[account(
-        seeds = [b"escrow", subscription_id.as_bytes()],
+        mut,
+        seeds = [b"fee_vault", get_usdc_mint().as_ref()],
         bump
     )]
-    pub escrow_pda: UncheckedAccount<'info>,
+    pub fee_vault: Option<InterfaceAccount<'info, token_interface::TokenAccount>>,
 
-    pub token_program: Program<'info, Token>,
+    /// Subscriber's USDC token account (receives the refund)
+    #[account(
+        mut,
+        constraint = subscriber_token_account.owner == subscription.subscriber @ ErrorCode::UnauthorizedAccess,
+        constraint = subscriber_token_account.mint == get_usdc_mint() @ ErrorCode::InvalidTokenMint
+    )]
+    pub subscriber_token_account: Option<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(address = get_usdc_mint() @ ErrorCode::InvalidTokenMint)]
+    pub usdc_mint: Option<InterfaceAccount<'info, token_interface::Mint>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
@@ -283,45 +1230,83 @@ pub struct ProcessTrigger<'info> {
     pub config: Account<'info, Config>,
 
     /// ICP canister authority (verified via signature)
+    /// Pays rent for the payment nonce, used nonce, dashboard and reputation PDAs
+    #[account(mut)]
     pub trigger_authority: Signer<'info>,
 
     /// Subscriber's USDC token account (source of payment)
     #[account(
         mut,
         constraint = subscriber_token_account.owner == subscription.subscriber @ ErrorCode::UnauthorizedAccess,
-        constraint = subscriber_token_account.mint == get_usdc_mint() @ ErrorCode::InvalidTokenMint,
+        constraint = subscriber_token_account.mint == usdc_mint.key() @ ErrorCode::InvalidTokenMint,
         constraint = subscriber_token_account.delegate.is_some() @ ErrorCode::DelegateNotSet,
         constraint = subscriber_token_account.delegated_amount >= subscription.amount @ ErrorCode::InsufficientDelegation
     )]
-    pub subscriber_token_account: Account<'info, TokenAccount>,
+    pub subscriber_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
 
     /// Escrow USDC token account (receives payment before off-ramp)
     #[account(
         mut,
         constraint = escrow_usdc_account.owner == subscription.escrow_pda @ ErrorCode::UnauthorizedAccess,
-        constraint = escrow_usdc_account.mint == get_usdc_mint() @ ErrorCode::InvalidTokenMint
+        constraint = escrow_usdc_account.mint == usdc_mint.key() @ ErrorCode::InvalidTokenMint
+    )]
+    pub escrow_usdc_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// Fee vault for the payment mint - platform fees accumulate here until withdraw_fees
+    /// Required for opcode 0 (payment), may be omitted for notifications
+    #[account(
+        mut,
+        seeds = [b"fee_vault", usdc_mint.key().as_ref()],
+        bump
     )]
-    pub escrow_usdc_account: Account<'info, TokenAccount>,
+    pub fee_vault: Option<InterfaceAccount<'info, token_interface::TokenAccount>>,
 
-    /// ICP fee collection USDC account (receives treasury fee)
+    /// Payment nonce for the current cycle - required for opcode 0, same as process_payment
+    /// CHECK: PDA verified by seeds; initialized manually to return DuplicatePayment
     #[account(
         mut,
-        constraint = icp_fee_usdc_account.mint == get_usdc_mint() @ ErrorCode::InvalidTokenMint
+        seeds = [b"payment_nonce", subscription.id.as_bytes(), &subscription.payments_made.to_le_bytes()],
+        bump
     )]
-    pub icp_fee_usdc_account: Account<'info, TokenAccount>,
+    pub payment_nonce: Option<UncheckedAccount<'info>>,
 
-    /// USDC Mint for validation
-    pub usdc_mint: Account<'info, Mint>,
+    /// Payment nonce from the previous cycle - closed to reclaim rent
+    /// CHECK: PDA verified in settle_payment before closing
+    #[account(mut)]
+    pub previous_payment_nonce: Option<UncheckedAccount<'info>>,
 
-    /// Subscription PDA (has delegate authority)
-    /// CHECK: Verified via seeds
-    pub subscription_pda: UncheckedAccount<'info>,
+    /// Used nonce for the ICP signature, seeds = [b"nonce", subscription.id, timestamp]
+    /// CHECK: PDA verified in create_used_nonce; initialized manually to return SignatureReplayed
+    #[account(mut)]
+    pub used_nonce: Option<UncheckedAccount<'info>>,
+
+    /// Merchant totals, seeds = [b"merchant_dashboard", subscription.merchant]
+    /// CHECK: PDA verified in settle_payment; initialized manually when missing
+    #[account(mut)]
+    pub merchant_dashboard: UncheckedAccount<'info>,
+
+    /// Subscriber track record, seeds = [b"reputation", subscription.subscriber]
+    /// CHECK: PDA verified in settle_payment; initialized manually when missing
+    #[account(mut)]
+    pub subscriber_reputation: Option<UncheckedAccount<'info>>,
+
+    /// Payment mint - the official USDC mint, or an allowlisted Token-2022 stablecoin for Token-2022
+    /// subscriptions; the token program must be the one that owns it
+    #[account(
+        constraint = is_allowed_payment_mint(&usdc_mint.key(), subscription.use_token_2022) @ ErrorCode::InvalidTokenMint,
+        constraint = *usdc_mint.to_account_info().owner == token_program.key() @ ErrorCode::InvalidTokenProgram
+    )]
+    pub usdc_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    /// Program-wide fee totals
+    #[account(mut, seeds = [b"fee_analytics"], bump)]
+    pub fee_analytics: Account<'info, FeeAnalytics>,
 
     /// CHECK: Subscriber wallet (for notifications)
     #[account(mut)]
     pub subscriber: UncheckedAccount<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 
     /// CHECK: SPL Memo Program
@@ -345,20 +1330,37 @@ pub mod ouroc_prima {
         ctx: Context<Initialize>,
         authorization_mode: AuthorizationMode,
         icp_public_key: Option<[u8; 32]>,
+        fee_recipients: Vec<FeeRecipient>,
+        max_fee_amount: u64, // Fee cap per payment in micro-USDC
     ) -> Result<()> {
         instruction_handlers::initialize(
             ctx,
             authorization_mode,
             icp_public_key,
+            fee_recipients,
+            max_fee_amount,
         )
     }
 
-    /// Update fee collection address (admin only)
+    /// Update fee collection address and revenue share recipients (admin only)
     pub fn update_fee_destination(
         ctx: Context<UpdateFeeDestination>,
         new_fee_address: Pubkey,
+        fee_recipients: Vec<FeeRecipient>,
+        max_fee_amount: u64, // Fee cap per payment in micro-USDC
+        fee_tiers: Vec<FeeTier>, // Volume discounts by total_paid (empty = flat fee)
     ) -> Result<()> {
-        instruction_handlers::update_fee_destination(ctx, new_fee_address)
+        instruction_handlers::update_fee_destination(ctx, new_fee_address, fee_recipients, max_fee_amount, fee_tiers)
+    }
+
+    /// Create the fee vault for a payment mint (admin only)
+    pub fn initialize_fee_vault(ctx: Context<InitializeFeeVault>) -> Result<()> {
+        instruction_handlers::initialize_fee_vault(ctx)
+    }
+
+    /// Withdraw accumulated platform fees from the vault (admin only)
+    pub fn withdraw_fees(ctx: Context<WithdrawFees>, amount: u64, recipient: Pubkey) -> Result<()> {
+        instruction_handlers::withdraw_fees(ctx, amount, recipient)
     }
 
     /// Approve subscription PDA to spend USDC tokens
@@ -382,6 +1384,12 @@ pub mod ouroc_prima {
         merchant_name: String, // Merchant's app/business name for notifications (max 32 chars)
         reminder_days_before_payment: u32, // Days before payment to send reminder (merchant configured)
         icp_canister_signature: [u8; 64], // Ed25519 signature from ICP canister
+        grace_period_seconds: Option<i64>, // Tolerance window for triggers (defaults to 0)
+        trial_end_time: Option<i64>, // Free trial end - no billing before this time
+        max_payments: Option<u64>, // Installment plan length (None = open-ended)
+        use_token_2022: bool, // Pay through Token-2022 (SPL Token Extensions) instead of Token v1
+        expires_at: Option<i64>, // Contract end for time-limited subscriptions (None = no expiry)
+        authorization_mode_override: Option<AuthorizationMode>, // Per-subscription mode (None = global config mode)
     ) -> Result<()> {
         instruction_handlers::create_subscription(
             ctx,
@@ -392,9 +1400,24 @@ pub mod ouroc_prima {
             merchant_name,
             reminder_days_before_payment,
             icp_canister_signature,
+            grace_period_seconds,
+            trial_end_time,
+            max_payments,
+            use_token_2022,
+            expires_at,
+            authorization_mode_override,
         )
     }
 
+    /// Create a new subscription by cloning an existing one's plan
+    pub fn clone_subscription(
+        ctx: Context<CloneSubscription>,
+        template_id: String,
+        new_subscription_id: String,
+    ) -> Result<()> {
+        instruction_handlers::clone_subscription(ctx, template_id, new_subscription_id)
+    }
+
     /// Process payment with automatic swap (Router function for multi-token support)
     // COMMENTED OUT - Only USDC supported
     // pub fn process_payment_with_swap<'info>(
@@ -406,12 +1429,56 @@ pub mod ouroc_prima {
     // }
 
     /// Process payment for a subscription (supports multiple authorization modes)
-    pub fn process_payment(
-        ctx: Context<ProcessPayment>,
+    pub fn process_payment<'info>(
+        ctx: Context<'_, '_, '_, 'info, ProcessPayment<'info>>,
         icp_signature: Option<[u8; 64]>,
         timestamp: i64,
+        expected_version: u64,
+    ) -> Result<()> {
+        instruction_handlers::process_payment(ctx, icp_signature, timestamp, expected_version)
+    }
+
+    /// Process payment and attach an "OuroC: <merchant> #<n>" memo for block explorers
+    pub fn process_payment_with_memo<'info>(
+        ctx: Context<'_, '_, '_, 'info, ProcessPaymentWithMemo<'info>>,
+        icp_signature: Option<[u8; 64]>,
+        timestamp: i64,
+        expected_version: u64,
+    ) -> Result<()> {
+        instruction_handlers::process_payment_with_memo(ctx, icp_signature, timestamp, expected_version)
+    }
+
+    /// Close used ICP signature nonces that can no longer be replayed (anyone can call)
+    pub fn prune_expired_nonces<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PruneExpiredNonces<'info>>,
+    ) -> Result<()> {
+        instruction_handlers::prune_expired_nonces(ctx)
+    }
+
+    /// Process several due subscriptions in one transaction (ICP signature mode only)
+    pub fn batch_process_payments<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchProcessPayments<'info>>,
+        timestamp: i64,
     ) -> Result<()> {
-        instruction_handlers::process_payment(ctx, icp_signature, timestamp)
+        instruction_handlers::batch_process_payments(ctx, timestamp)
+    }
+
+    /// Create merchant opt-in settings (merchant only)
+    pub fn initialize_merchant_config(
+        ctx: Context<InitializeMerchantConfig>,
+        requires_approval: bool,
+    ) -> Result<()> {
+        instruction_handlers::initialize_merchant_config(ctx, requires_approval)
+    }
+
+    /// Allow a subscriber to subscribe to this merchant
+    pub fn approve_subscriber(ctx: Context<UpdateMerchantConfig>, subscriber: Pubkey) -> Result<()> {
+        instruction_handlers::approve_subscriber(ctx, subscriber)
+    }
+
+    /// Remove a subscriber from the merchant's approval list
+    pub fn revoke_subscriber_approval(ctx: Context<UpdateMerchantConfig>, subscriber: Pubkey) -> Result<()> {
+        instruction_handlers::revoke_subscriber_approval(ctx, subscriber)
     }
 
     /// Pause a subscription
@@ -419,16 +1486,66 @@ pub mod ouroc_prima {
         instruction_handlers::pause_subscription(ctx)
     }
 
+    /// Skip the next 1-12 billing cycles without cancelling (subscriber only)
+    pub fn snooze_next_payment(ctx: Context<UpdateSubscription>, periods: u8) -> Result<()> {
+        instruction_handlers::snooze_next_payment(ctx, periods)
+    }
+
+    /// Change the billing interval and re-approve one year of delegation (subscriber only)
+    pub fn update_subscription_interval(ctx: Context<UpdateInterval>, new_interval_seconds: i64) -> Result<()> {
+        instruction_handlers::update_subscription_interval(ctx, new_interval_seconds)
+    }
+
+    /// Re-approve one year of delegation once payments have used it up (subscriber only)
+    pub fn auto_renew_delegation(ctx: Context<AutoRenewDelegation>) -> Result<()> {
+        instruction_handlers::auto_renew_delegation(ctx)
+    }
+
     /// Resume a subscription
     pub fn resume_subscription(ctx: Context<UpdateSubscription>) -> Result<()> {
         instruction_handlers::resume_subscription(ctx)
     }
 
+    /// Pause a subscription as the merchant
+    pub fn pause_subscription_as_merchant(ctx: Context<MerchantAction>) -> Result<()> {
+        instruction_handlers::pause_subscription_as_merchant(ctx)
+    }
+
+    /// Set a spending cap per billing period (subscriber only, 0 = no cap)
+    pub fn set_spending_cap(ctx: Context<UpdateSubscription>, spending_cap_per_period: u64) -> Result<()> {
+        instruction_handlers::set_spending_cap(ctx, spending_cap_per_period)
+    }
+
+    /// Change the swap slippage tolerance (subscriber only, 24-hour cooldown)
+    pub fn update_slippage_bps(ctx: Context<UpdateSlippage>, new_slippage: u16) -> Result<()> {
+        instruction_handlers::update_slippage_bps(ctx, new_slippage)
+    }
+
+    /// Resume a merchant-paused subscription
+    pub fn resume_subscription_as_merchant(ctx: Context<MerchantAction>) -> Result<()> {
+        instruction_handlers::resume_subscription_as_merchant(ctx)
+    }
+
     /// Cancel a subscription
-    pub fn cancel_subscription(ctx: Context<UpdateSubscription>) -> Result<()> {
+    pub fn cancel_subscription(ctx: Context<CancelSubscription>) -> Result<()> {
         instruction_handlers::cancel_subscription(ctx)
     }
 
+    /// Close a cancelled subscription account and return its rent (subscriber only, 7 days after creation)
+    pub fn close_subscription(ctx: Context<CloseSubscription>, subscription_id: String) -> Result<()> {
+        instruction_handlers::close_subscription(ctx, subscription_id)
+    }
+
+    /// Upgrade a subscription created before the current account layout (anyone can call)
+    pub fn migrate_subscription(ctx: Context<MigrateSubscription>) -> Result<()> {
+        instruction_handlers::migrate_subscription(ctx)
+    }
+
+    /// Upgrade the config created before the current account layout (authority only)
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        instruction_handlers::migrate_config(ctx)
+    }
+
     /// Revoke subscription PDA delegate (after cancellation)
     pub fn revoke_subscription_delegate(
         ctx: Context<RevokeDelegate>,
@@ -445,6 +1562,80 @@ pub mod ouroc_prima {
         instruction_handlers::claim_from_escrow(ctx, subscription_id, amount)
     }
 
+    /// Propose moving the subscription to a new subscriber wallet (24-hour timelock)
+    pub fn propose_subscription_transfer(
+        ctx: Context<ProposeSubscriptionTransfer>,
+        new_subscriber: Pubkey,
+    ) -> Result<()> {
+        instruction_handlers::propose_subscription_transfer(ctx, new_subscriber)
+    }
+
+    /// Accept a proposed subscription transfer (new subscriber, after the timelock)
+    pub fn accept_subscription_transfer(ctx: Context<AcceptSubscriptionTransfer>) -> Result<()> {
+        instruction_handlers::accept_subscription_transfer(ctx)
+    }
+
+    /// Propose a new merchant payout wallet (merchant, 48-hour timelock)
+    pub fn propose_merchant_address_change(
+        ctx: Context<ProposeMerchantAddressChange>,
+        new_merchant: Pubkey,
+    ) -> Result<()> {
+        instruction_handlers::propose_merchant_address_change(ctx, new_merchant)
+    }
+
+    /// Execute a proposed merchant wallet change (merchant, after the timelock)
+    pub fn execute_merchant_address_change(ctx: Context<ExecuteMerchantAddressChange>) -> Result<()> {
+        instruction_handlers::execute_merchant_address_change(ctx)
+    }
+
+    /// Propose new amount and interval (merchant, subscriber must accept)
+    pub fn propose_subscription_change(
+        ctx: Context<ProposeSubscriptionChange>,
+        new_amount: u64,
+        new_interval: i64,
+    ) -> Result<()> {
+        instruction_handlers::propose_subscription_change(ctx, new_amount, new_interval)
+    }
+
+    /// Accept proposed terms and re-approve the delegation (subscriber)
+    pub fn accept_subscription_change(ctx: Context<AcceptSubscriptionChange>) -> Result<()> {
+        instruction_handlers::accept_subscription_change(ctx)
+    }
+
+    /// Decline proposed terms (subscriber)
+    pub fn reject_subscription_change(ctx: Context<RejectSubscriptionChange>) -> Result<()> {
+        instruction_handlers::reject_subscription_change(ctx)
+    }
+
+    /// Close an expired change proposal (anyone)
+    pub fn expire_proposal(ctx: Context<ExpireProposal>) -> Result<()> {
+        instruction_handlers::expire_proposal(ctx)
+    }
+
+    /// Merchant refunds USDC to the subscriber
+    pub fn process_refund(
+        ctx: Context<ProcessRefund>,
+        refund_amount: u64,
+        reason: [u8; 64],
+    ) -> Result<()> {
+        instruction_handlers::process_refund(ctx, refund_amount, reason)
+    }
+
+    /// Dispute a charge; blocks further charges until resolve_dispute (subscriber only)
+    pub fn dispute_payment(
+        ctx: Context<DisputePayment>,
+        _subscription_id: String,
+        payment_number: u64,
+        reason: [u8; 128],
+    ) -> Result<()> {
+        instruction_handlers::dispute_payment(ctx, payment_number, reason)
+    }
+
+    /// Rule on a dispute and optionally refund the charge from the fee vault (admin only)
+    pub fn resolve_dispute(ctx: Context<ResolveDispute>, ruling: DisputeRuling) -> Result<()> {
+        instruction_handlers::resolve_dispute(ctx, ruling)
+    }
+
     /// Emergency pause the entire program (admin only)
     pub fn emergency_pause(ctx: Context<AdminAction>) -> Result<()> {
         instruction_handlers::emergency_pause(ctx)
@@ -455,6 +1646,11 @@ pub mod ouroc_prima {
         instruction_handlers::resume_program(ctx)
     }
 
+    /// Update the SOL fee charged on create_subscription (admin only, 0 waives it)
+    pub fn update_creation_fee(ctx: Context<AdminAction>, new_fee: u64) -> Result<()> {
+        instruction_handlers::update_creation_fee(ctx, new_fee)
+    }
+
     /// Update authorization mode (admin only)
     pub fn update_authorization_mode(
         ctx: Context<AdminAction>,
@@ -464,19 +1660,111 @@ pub mod ouroc_prima {
         instruction_handlers::update_authorization_mode(ctx, new_mode, icp_public_key)
     }
 
+    /// Add an ICP canister public key to the allowlist (admin only)
+    pub fn add_icp_public_key(ctx: Context<AdminAction>, key: [u8; 32]) -> Result<()> {
+        instruction_handlers::add_icp_public_key(ctx, key)
+    }
+
+    /// Remove an ICP canister public key from the allowlist (admin only)
+    pub fn remove_icp_public_key(ctx: Context<AdminAction>, key: [u8; 32]) -> Result<()> {
+        instruction_handlers::remove_icp_public_key(ctx, key)
+    }
+
+    /// Log the program-wide fee totals
+    pub fn get_fee_analytics(ctx: Context<GetFeeAnalytics>) -> Result<()> {
+        instruction_handlers::get_fee_analytics(ctx)
+    }
+
+    /// Report whether the subscription PDA's delegation covers upcoming payments (anyone can call)
+    pub fn check_delegation_status(ctx: Context<CheckDelegationStatus>, subscription_id: String) -> Result<()> {
+        instruction_handlers::check_delegation_status(ctx, subscription_id)
+    }
+
+    /// Report a payment attempt that failed off-chain (authority only)
+    pub fn record_failed_payment(ctx: Context<RecordFailedPayment>, subscription_id: String) -> Result<()> {
+        instruction_handlers::record_failed_payment(ctx, subscription_id)
+    }
+
+    /// Read a subscriber's payment track record
+    pub fn get_subscriber_reputation(ctx: Context<GetSubscriberReputation>, subscriber: Pubkey) -> Result<SubscriberReputation> {
+        instruction_handlers::get_subscriber_reputation(ctx, subscriber)
+    }
+
+    /// Read a merchant's running payment totals
+    pub fn get_merchant_dashboard(ctx: Context<GetMerchantDashboard>, merchant: Pubkey) -> Result<MerchantDashboard> {
+        instruction_handlers::get_merchant_dashboard(ctx, merchant)
+    }
+
+    /// List a subscriber's subscription IDs (zero-padded to 32 bytes)
+    pub fn get_subscriber_subscriptions(ctx: Context<GetSubscriberSubscriptions>, subscriber: Pubkey) -> Result<Vec<[u8; 32]>> {
+        instruction_handlers::get_subscriber_subscriptions(ctx, subscriber)
+    }
+
+    /// List a merchant's subscription IDs (zero-padded to 32 bytes)
+    pub fn get_merchant_subscriptions(ctx: Context<GetMerchantSubscriptions>, merchant: Pubkey) -> Result<Vec<[u8; 32]>> {
+        instruction_handlers::get_merchant_subscriptions(ctx, merchant)
+    }
+
+    /// Zero the fee totals to start a new reporting window (admin only)
+    pub fn reset_fee_analytics(ctx: Context<ResetFeeAnalytics>) -> Result<()> {
+        instruction_handlers::reset_fee_analytics(ctx)
+    }
+
+    /// Start a two-step rotation of the primary ICP public key (admin only)
+    pub fn initiate_key_rotation(ctx: Context<AdminAction>, new_key: [u8; 32]) -> Result<()> {
+        instruction_handlers::initiate_key_rotation(ctx, new_key)
+    }
+
+    /// Complete an ICP key rotation after the dual-key window (anyone can call)
+    pub fn complete_key_rotation(ctx: Context<CompleteKeyRotation>) -> Result<()> {
+        instruction_handlers::complete_key_rotation(ctx)
+    }
+
+    /// Switch admin instructions to multisig approval (admin only)
+    pub fn enable_multisig(
+        ctx: Context<AdminAction>,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        instruction_handlers::enable_multisig(ctx, signers, threshold)
+    }
+
+    /// Propose an admin action (multisig member)
+    pub fn propose_admin_action(
+        ctx: Context<MultisigAdminAction>,
+        action_type: AdminActionType,
+        data: Vec<u8>, // Borsh-encoded arguments for action_type
+    ) -> Result<()> {
+        instruction_handlers::propose_admin_action(ctx, action_type, data)
+    }
+
+    /// Approve the pending admin action (multisig member)
+    pub fn approve_admin_action(ctx: Context<MultisigAdminAction>, action_id: u64) -> Result<()> {
+        instruction_handlers::approve_admin_action(ctx, action_id)
+    }
+
+    /// Execute the pending admin action once approved by the threshold (any signer)
+    pub fn execute_admin_action(ctx: Context<MultisigAdminAction>, action_id: u64) -> Result<()> {
+        instruction_handlers::execute_admin_action(ctx, action_id)
+    }
+
     /// Manual payment processing (subscriber only)
-    pub fn process_manual_payment(ctx: Context<ProcessPayment>) -> Result<()> {
+    pub fn process_manual_payment<'info>(
+        ctx: Context<'_, '_, '_, 'info, ProcessPayment<'info>>,
+    ) -> Result<()> {
         instruction_handlers::process_manual_payment(ctx)
     }
 
     /// Main entry point from ICP: Process trigger with opcode routing
-    pub fn process_trigger(
-        ctx: Context<ProcessTrigger>,
+    /// Fee recipients' token accounts follow in remaining_accounts, same as process_payment
+    pub fn process_trigger<'info>(
+        ctx: Context<'_, '_, '_, 'info, ProcessTrigger<'info>>,
         opcode: u8,
         icp_signature: Option<[u8; 64]>,
         timestamp: i64,
+        expected_version: u64,
     ) -> Result<()> {
-        instruction_handlers::process_trigger(ctx, opcode, icp_signature, timestamp)
+        instruction_handlers::process_trigger(ctx, opcode, icp_signature, timestamp, expected_version)
     }
 
     /// Process trigger with Jupiter swap (opcode 0 only for non-USDC tokens)
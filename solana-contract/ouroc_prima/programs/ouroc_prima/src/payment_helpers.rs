@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount};
+use anchor_spl::token;
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface};
 use crate::constants::*;
 use crate::data_structures::*;
 use crate::errors::ErrorCode;
@@ -10,363 +11,1871 @@ use crate::crypto::*;
 // Payment Helpers Module (USDC Only)
 // ============================================================================
 
-/// Core payment processing logic for USDC-only payments
-pub fn process_payment_core<'info>(
-    subscription: &mut Account<'info, Subscription>,
-    config: &Account<'info, Config>,
-    trigger_authority: &Signer<'info>,
-    subscriber_token_account: &Account<'info, TokenAccount>,
-    merchant_token_account: &Account<'info, TokenAccount>,
-    icp_fee_token_account: &Account<'info, TokenAccount>,
-    token_program: &Program<'info, Token>,
-    program_id: &Pubkey,
-    icp_signature: Option<[u8; 64]>,
-    timestamp: i64,
-    instructions_sysvar: &UncheckedAccount<'info>,
-) -> Result<()> {
-    require!(!config.paused, ErrorCode::ProgramPaused);
-    require!(subscription.status == SubscriptionStatus::Active, ErrorCode::SubscriptionNotActive);
+/// Check whether a payment is due, honoring the subscription's grace period
+/// Triggers arriving up to `grace_period_seconds` around `next_payment_time` are accepted
+pub fn is_payment_due(current_time: i64, next_payment_time: i64, grace_period_seconds: i64) -> bool {
+    current_time >= next_payment_time.saturating_sub(grace_period_seconds)
+}
 
-    // SECURITY: Validate fee collection address is set
-    require!(
-        config.icp_fee_collection_address.is_some(),
-        ErrorCode::FeeCollectionAddressNotSet
-    );
+/// Compute the subscription's total_paid after a refund
+/// Refunds can never exceed what the subscriber has actually paid
+pub fn apply_refund(total_paid: u64, refund_amount: u64) -> Result<u64> {
+    require!(refund_amount > 0, ErrorCode::InvalidAmount);
+    require!(refund_amount <= total_paid, ErrorCode::RefundExceedsTotalPaid);
+    total_paid
+        .checked_sub(refund_amount)
+        .ok_or(ErrorCode::MathOverflow.into())
+}
 
-    let clock = Clock::get()?;
+/// Count down an installment plan after a successful payment
+/// Returns None for open-ended subscriptions
+pub fn decrement_remaining_payments(remaining_payments: Option<u64>) -> Result<Option<u64>> {
+    match remaining_payments {
+        Some(remaining) => Ok(Some(
+            remaining.checked_sub(1).ok_or(ErrorCode::MathOverflow)?
+        )),
+        None => Ok(None),
+    }
+}
 
-    // Authorization based on configured mode
-    match config.authorization_mode {
-        AuthorizationMode::ICPSignature => {
-            // Original ICP signature verification
-            require!(icp_signature.is_some(), ErrorCode::MissingSignature);
-            let signature = icp_signature.unwrap();
+/// Check whether the notification cooldown has elapsed since the last notification
+pub fn is_notification_allowed(
+    last_notification_time: Option<i64>,
+    current_time: i64,
+    cooldown_seconds: i64,
+) -> bool {
+    current_time >= last_notification_time.unwrap_or(0).saturating_add(cooldown_seconds)
+}
 
-            require!(
-                clock.unix_timestamp >= subscription.next_payment_time,
-                ErrorCode::PaymentNotDue
-            );
+/// Compute next_payment_time on resume, preserving billing time left when the subscription was paused
+pub fn calculate_resume_payment_time(
+    next_payment_time: i64,
+    paused_at: Option<i64>,
+    current_time: i64,
+) -> Result<i64> {
+    let credit = next_payment_time
+        .checked_sub(paused_at.unwrap_or(next_payment_time))
+        .ok_or(ErrorCode::MathOverflow)?;
 
-            // Verify timestamp is recent (prevent replay attacks)
-            let max_age_seconds = 300; // 5 minutes - reduced from 60 minutes for security
-            require!(
-                verify_timestamp(timestamp, clock.unix_timestamp, max_age_seconds)?,
-                ErrorCode::SignatureExpired
-            );
+    current_time
+        .checked_add(credit.max(0))
+        .ok_or(ErrorCode::MathOverflow.into())
+}
 
-            // Create message that ICP canister should have signed
-            let message = create_payment_message(
-                &subscription.id,
-                timestamp,
-                subscription.amount
-            );
+/// Check whether a proposed subscription transfer has passed its timelock
+pub fn is_transfer_unlocked(proposed_at: i64, current_time: i64) -> bool {
+    current_time >= proposed_at.saturating_add(SUBSCRIPTION_TRANSFER_TIMELOCK_SECONDS)
+}
 
-            // Verify ICP canister signature
-            let icp_public_key = config.icp_public_key.ok_or(ErrorCode::MissingICPKey)?;
-            require!(
-                verify_ed25519_ix(instructions_sysvar, &icp_public_key, &message)?,
-                ErrorCode::InvalidSignature
-            );
+/// Check whether a subscription account is old enough to be closed
+pub fn is_close_allowed(created_at: i64, current_time: i64) -> bool {
+    current_time >= created_at.saturating_add(CLOSE_COOLDOWN_SECONDS)
+}
 
-            // Update signature for next payment verification
-            subscription.icp_canister_signature = signature;
-        },
-        AuthorizationMode::ManualOnly => {
-            // Manual processing - subscriber or authorized party can trigger
-            require!(
-                trigger_authority.key() == subscription.subscriber ||
-                trigger_authority.key() == config.authority,
-                ErrorCode::UnauthorizedAccess
-            );
-            // No time restriction for manual processing
-        },
-        AuthorizationMode::TimeBased => {
-            // Time-based processing - anyone can trigger if payment is due
-            require!(
-                clock.unix_timestamp >= subscription.next_payment_time,
-                ErrorCode::PaymentNotDue
-            );
-        },
-        AuthorizationMode::Hybrid => {
-            // Multiple authorization methods
-            let is_icp_valid = if let Some(_signature) = icp_signature {
-                if let Some(icp_key) = config.icp_public_key {
-                    let message = create_payment_message(
-                        &subscription.id,
-                        timestamp,
-                        subscription.amount
-                    );
-                    verify_ed25519_ix(instructions_sysvar, &icp_key, &message).unwrap_or(false)
-                } else { false }
-            } else { false };
+/// Check whether a proposed merchant wallet change has passed its timelock
+pub fn is_merchant_change_unlocked(proposed_at: i64, current_time: i64) -> bool {
+    current_time >= proposed_at.saturating_add(MERCHANT_CHANGE_TIMELOCK_SECONDS)
+}
 
-            let is_manual_valid = trigger_authority.key() == subscription.subscriber;
-            let is_time_valid = clock.unix_timestamp >= subscription.next_payment_time;
+/// A change proposal can be accepted up to and including expires_at
+pub fn is_change_proposal_expired(expires_at: i64, current_time: i64) -> bool {
+    current_time > expires_at
+}
 
-            require!(
-                is_icp_valid || (is_manual_valid && config.manual_processing_enabled) ||
-                (is_time_valid && config.time_based_processing_enabled),
-                ErrorCode::AuthorizationFailed
-            );
+/// SHA-256(subscriber || merchant || amount || interval_seconds), integers little-endian
+pub fn subscription_integrity_hash(subscriber: &Pubkey, merchant: &Pubkey, amount: u64, interval_seconds: i64) -> [u8; 32] {
+    anchor_lang::solana_program::hash::hashv(&[
+        subscriber.as_ref(),
+        merchant.as_ref(),
+        &amount.to_le_bytes(),
+        &interval_seconds.to_le_bytes(),
+    ])
+    .to_bytes()
+}
 
-            if is_icp_valid && icp_signature.is_some() {
-                subscription.icp_canister_signature = icp_signature.unwrap();
-            }
-        }
-    }
+/// Recompute the integrity hash after an authorized change to the billing terms
+pub fn refresh_integrity_hash(subscription: &mut Subscription) {
+    subscription.integrity_hash = subscription_integrity_hash(
+        &subscription.subscriber,
+        &subscription.merchant,
+        subscription.amount,
+        subscription.interval_seconds,
+    );
+}
 
-    // Execute USDC transfer from subscriber to merchant
+/// Reject a charge if the billing terms were changed outside the program's own instructions
+pub fn verify_subscription_integrity(subscription: &Subscription) -> Result<()> {
+    let expected = subscription_integrity_hash(
+        &subscription.subscriber,
+        &subscription.merchant,
+        subscription.amount,
+        subscription.interval_seconds,
+    );
+    require!(subscription.integrity_hash == expected, ErrorCode::SubscriptionTampered);
+    Ok(())
+}
 
-    // Calculate fee (e.g., 1% of payment amount)
-    let fee_config = &config.fee_config;
-    let platform_fee = subscription.amount
-        .checked_mul(fee_config.fee_percentage_basis_points as u64)
-        .ok_or(ErrorCode::MathOverflow)?
-        .checked_div(BASIS_POINTS_DIVISOR)
-        .ok_or(ErrorCode::MathOverflow)?;
+/// Move a subscription to its next version; every charge or trigger does this
+pub fn bump_subscription_version(subscription: &mut Subscription) -> Result<()> {
+    subscription.version = subscription.version.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+    Ok(())
+}
 
-    let merchant_amount = subscription.amount
-        .checked_sub(platform_fee)
-        .ok_or(ErrorCode::InsufficientAmount)?;
+/// Optimistic concurrency check for triggers: only the caller that read the current
+/// version gets through, any other trigger built from the same read fails
+pub fn claim_subscription_version(subscription: &mut Subscription, expected_version: u64) -> Result<()> {
+    require!(subscription.version == expected_version, ErrorCode::ConcurrentModification);
+    bump_subscription_version(subscription)
+}
 
-    // Use subscription PDA as authority (subscriber must delegate to this PDA)
-    // Derive PDA signer seeds for CPI - Clone ID to avoid borrow issues
-    let subscription_id = subscription.id.clone();
-    let subscription_key = subscription.key();
+/// Rebuild a subscription written by the original program, defaulting every appended field
+/// The defaults match create_subscription's, with the spending window starting at created_at
+pub fn migrate_legacy_subscription(legacy: LegacySubscription) -> Subscription {
+    let mut subscription = Subscription {
+        id: legacy.id,
+        subscriber: legacy.subscriber,
+        merchant: legacy.merchant,
+        merchant_name: legacy.merchant_name,
+        amount: legacy.amount,
+        interval_seconds: legacy.interval_seconds,
+        next_payment_time: legacy.next_payment_time,
+        status: legacy.status,
+        created_at: legacy.created_at,
+        last_payment_time: legacy.last_payment_time,
+        payments_made: legacy.payments_made,
+        total_paid: legacy.total_paid,
+        icp_canister_signature: legacy.icp_canister_signature,
+        reminder_days_before_payment: legacy.reminder_days_before_payment,
+        escrow_pda: legacy.escrow_pda,
+        escrow_balance: legacy.escrow_balance,
+        grace_period_seconds: 0,
+        trial_end_time: None,
+        max_payments: None,
+        remaining_payments: None,
+        use_token_2022: false,
+        last_notification_time: None,
+        paused_at: None,
+        expires_at: None,
+        paused_by: PausedBy::Subscriber,
+        authorization_mode_override: None,
+        spending_cap_per_period: 0,
+        period_start: legacy.created_at,
+        spent_in_period: 0,
+        slippage_bps: DEFAULT_SLIPPAGE_BPS,
+        slippage_updated_at: None,
+        integrity_hash: [0u8; 32],
+        disputed: false,
+        dispute_payment_number: 0,
+        version: 0,
+        last_ruled_payment_number: 0,
+        vault_fees_paid: 0,
+    };
+    refresh_integrity_hash(&mut subscription);
+    subscription
+}
 
-    // Find the bump seed for this subscription PDA
-    let (subscription_pda, bump) = Pubkey::find_program_address(
-        &[b"subscription", subscription_id.as_bytes()],
-        program_id
-    );
+/// Rebuild a config written by the original program
+/// The single ICP key seeds the allowlist; the fee stays uncapped and multisig stays off
+pub fn migrate_legacy_config(legacy: LegacyConfig) -> Result<Config> {
+    let mut icp_public_keys = [[0u8; 32]; MAX_ICP_PUBLIC_KEYS];
+    let mut icp_key_count = 0;
+    if let Some(key) = legacy.icp_public_key {
+        add_icp_key(&mut icp_public_keys, &mut icp_key_count, key)?;
+    }
 
-    // Verify the subscription account matches the derived PDA
-    require!(
-        subscription_pda == subscription_key,
-        ErrorCode::InvalidSubscriptionPDA
-    );
+    let fee_bps = legacy.fee_config.fee_percentage_basis_points;
+    let (fee_recipients, recipient_count) = build_fee_recipients(&[])?;
+    let (fee_tiers, tier_count) = build_fee_tiers(&[], fee_bps)?;
 
-    let seeds = &[
-        b"subscription".as_ref(),
-        subscription_id.as_bytes(),
-        &[bump],
-    ];
-    let signer_seeds = &[&seeds[..]];
+    Ok(Config {
+        authority: legacy.authority,
+        total_subscriptions: legacy.total_subscriptions,
+        paused: legacy.paused,
+        authorization_mode: legacy.authorization_mode,
+        icp_public_keys,
+        icp_key_count,
+        manual_processing_enabled: legacy.manual_processing_enabled,
+        time_based_processing_enabled: legacy.time_based_processing_enabled,
+        fee_config: FeeConfig {
+            fee_percentage_basis_points: fee_bps,
+            min_fee_amount: legacy.fee_config.min_fee_amount,
+            max_fee_amount: u64::MAX,
+            fee_recipients,
+            recipient_count,
+            fee_tiers,
+            tier_count,
+        },
+        icp_fee_collection_address: legacy.icp_fee_collection_address,
+        notification_cooldown_seconds: DEFAULT_NOTIFICATION_COOLDOWN_SECONDS,
+        oracle_max_age: DEFAULT_MAX_PRICE_AGE_SECONDS,
+        multisig_mode: false,
+        admin_threshold: 0,
+        admin_signers: [Pubkey::default(); MAX_ADMIN_SIGNERS],
+        admin_signer_count: 0,
+        next_admin_action_id: 0,
+        pending_admin_action: None,
+        pending_icp_public_key: None,
+        key_rotation_start: None,
+        creation_fee_lamports: DEFAULT_CREATION_FEE_LAMPORTS,
+    })
+}
 
-    // EFFECTS: Update subscription state BEFORE external calls (CEI pattern)
-    subscription.payments_made += 1;
-    subscription.total_paid += subscription.amount;
+/// Check whether an oracle price is older than the allowed age
+/// A publish time slightly in the future (clock skew) is treated as fresh, anything further ahead is rejected
+pub fn is_price_stale(publish_time: i64, current_time: i64, max_age_seconds: u64) -> bool {
+    let age = current_time.saturating_sub(publish_time);
+    if age < 0 {
+        return age.unsigned_abs() > MAX_PRICE_FUTURE_SKEW_SECONDS;
+    }
+    age as u64 > max_age_seconds
+}
 
-    // Schedule next payment based on interval type
-    if subscription.interval_seconds == -1 {
-        // One-time payment: auto-cancel after payment
-        subscription.status = SubscriptionStatus::Cancelled;
-        msg!("One-time payment completed - subscription auto-cancelled");
-    } else {
-        // Recurring payment: schedule next payment relative to scheduled time (not current time) to prevent drift
-        subscription.next_payment_time = subscription.next_payment_time
-            .checked_add(subscription.interval_seconds)
-            .ok_or(ErrorCode::MathOverflow)?;
+/// Latest confirmed round of a Switchboard v2 aggregator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwitchboardRound {
+    pub mantissa: i128,
+    pub scale: u32,
+    pub round_open_timestamp: i64,
+}
 
-        // Handle multiple missed payments by advancing until future
-        while subscription.next_payment_time < clock.unix_timestamp {
-            subscription.next_payment_time = subscription.next_payment_time
-                .checked_add(subscription.interval_seconds)
-                .ok_or(ErrorCode::MathOverflow)?;
-        }
+/// Decode the latest confirmed round from raw AggregatorAccountData
+/// Returns None for foreign accounts and rounds without enough oracle responses
+pub fn parse_switchboard_aggregator(data: &[u8]) -> Option<SwitchboardRound> {
+    let round = SWITCHBOARD_LATEST_ROUND_OFFSET;
+    // num_success u32, num_error u32, is_closed bool, round_open_slot u64,
+    // round_open_timestamp i64, result { mantissa i128, scale u32 }
+    if data.len() < round + 45 || data[..8] != SWITCHBOARD_AGGREGATOR_DISCRIMINATOR {
+        return None;
     }
 
-    subscription.last_payment_time = Some(clock.unix_timestamp);
+    let read_u32 = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+    let min_oracle_results = read_u32(SWITCHBOARD_MIN_ORACLE_RESULTS_OFFSET);
+    let num_success = read_u32(round);
+    if num_success == 0 || num_success < min_oracle_results {
+        return None;
+    }
 
-    // Get subscription account info after state updates
-    let subscription_account_info = subscription.to_account_info();
+    Some(SwitchboardRound {
+        round_open_timestamp: i64::from_le_bytes(data[round + 17..round + 25].try_into().unwrap()),
+        mantissa: i128::from_le_bytes(data[round + 25..round + 41].try_into().unwrap()),
+        scale: read_u32(round + 41),
+    })
+}
 
-    // INTERACTIONS: External token transfers AFTER state updates (CEI pattern)
-    // Transfer merchant_amount to merchant via CPI with PDA authority
-    let transfer_to_merchant = token::Transfer {
-        from: subscriber_token_account.to_account_info(),
-        to: merchant_token_account.to_account_info(),
-        authority: subscription_account_info.clone(),
+/// Convert a Switchboard decimal (mantissa * 10^-scale) to a price with 8 decimals
+pub fn normalize_switchboard_price(mantissa: i128, scale: u32) -> Option<i64> {
+    let normalized = if scale >= 8 {
+        mantissa.checked_div(10i128.checked_pow(scale - 8)?)?
+    } else {
+        mantissa.checked_mul(10i128.checked_pow(8 - scale)?)?
     };
+    i64::try_from(normalized).ok()
+}
 
-    token::transfer(
-        CpiContext::new_with_signer(
-            token_program.to_account_info(),
-            transfer_to_merchant,
-            signer_seeds,
-        ),
-        merchant_amount,
-    )?;
+/// Worst-case swap output when no oracle price is usable: twice the slippage tolerance off the amount
+pub fn conservative_output_amount_min(amount: u64, slippage_bps: u16) -> u64 {
+    let multiplier = BASIS_POINTS_DIVISOR.saturating_sub(2 * slippage_bps as u64);
+    ((amount as u128 * multiplier as u128) / BASIS_POINTS_DIVISOR as u128) as u64
+}
 
-    msg!("Transferred {} micro-USDC to merchant", merchant_amount);
+/// Whether `payment_number` refers to a charge the subscription has already made (1-based)
+/// and that comes after the last one the admin ruled on, so each charge is disputed at most once
+pub fn is_disputable_payment(payment_number: u64, payments_made: u64, last_ruled_payment_number: u64) -> bool {
+    payment_number > last_ruled_payment_number && payment_number >= 1 && payment_number <= payments_made
+}
 
-    // Transfer platform_fee to ICP canister fee collection account
-    if platform_fee > 0 {
-        let transfer_to_icp = token::Transfer {
-            from: subscriber_token_account.to_account_info(),
-            to: icp_fee_token_account.to_account_info(),
-            authority: subscription_account_info.clone(),
-        };
+/// Refund for an approved dispute: one charge, but never more than this subscription's
+/// fees still held by the fee vault, so refunds can't drain other subscriptions' fees
+pub fn dispute_refund_amount(amount: u64, vault_fees_paid: u64) -> u64 {
+    amount.min(vault_fees_paid)
+}
 
-        token::transfer(
-            CpiContext::new_with_signer(
-                token_program.to_account_info(),
-                transfer_to_icp,
-                signer_seeds,
-            ),
-            platform_fee,
-        )?;
+/// Check whether a time-limited subscription has reached its expiry
+pub fn is_expired(expires_at: Option<i64>, current_time: i64) -> bool {
+    matches!(expires_at, Some(expiry) if current_time >= expiry)
+}
+
+/// Whether a subscription can never be charged again: its expiry is reached, or it is
+/// recurring and its schedule can't advance without overflowing i64
+pub fn has_run_out(subscription: &Subscription, current_time: i64) -> bool {
+    is_expired(subscription.expires_at, current_time)
+        || (subscription.interval_seconds > 0
+            && next_recurring_payment_time(subscription.next_payment_time, subscription.interval_seconds, current_time).is_none())
+}
 
-        msg!("Transferred {} micro-USDC fee to ICP canister", platform_fee);
+/// Mark the subscription Expired once it has run out
+/// Returns true when the subscription was expired by this call (no payment should be made)
+pub fn expire_if_run_out(subscription: &mut Subscription, current_time: i64) -> bool {
+    if !has_run_out(subscription, current_time) {
+        return false;
     }
 
-    msg!(
-        "Payment #{} processed: total={}, merchant={}, platform_fee={}",
-        subscription.payments_made,
-        subscription.amount,
-        merchant_amount,
-        platform_fee
-    );
+    subscription.status = SubscriptionStatus::Expired;
+    msg!("Subscription {} expired - no further payments", subscription.id);
 
-    // Emit payment event
-    emit!(PaymentProcessed {
+    emit!(SubscriptionExpired {
         subscription_id: subscription.id.clone(),
-        payment_number: subscription.payments_made,
-        amount: subscription.amount,
-        merchant_amount,
-        fee_amount: platform_fee,
-        timestamp: clock.unix_timestamp,
+        expired_at: current_time,
+        total_payments_made: subscription.payments_made,
+        total_paid: subscription.total_paid,
     });
+    true
+}
 
-    Ok(())
+/// Next payment time after a payment: at least one interval past the scheduled time, skipping
+/// ahead over any missed periods to the first time not before `current_time`.
+/// Computed in one step, so a long-overdue subscription with a short interval can't exhaust
+/// the compute budget. None if the result would overflow i64.
+pub fn next_recurring_payment_time(next_payment_time: i64, interval_seconds: i64, current_time: i64) -> Option<i64> {
+    if interval_seconds <= 0 {
+        return None;
+    }
+    let behind = current_time.checked_sub(next_payment_time)?.max(0);
+    let periods = (behind / interval_seconds + i64::from(behind % interval_seconds != 0)).max(1);
+    next_payment_time.checked_add(periods.checked_mul(interval_seconds)?)
 }
 
-// Helper functions for process_trigger
-pub fn process_direct_usdc_payment(ctx: Context<crate::ProcessTrigger>) -> Result<()> {
-    let subscription = &mut ctx.accounts.subscription;
-    let config = &ctx.accounts.config;
-
-    // Calculate fee (treasury gets X%, merchant gets rest)
-    let payment_amount = subscription.amount;
-    let fee_amount_u128 = (payment_amount as u128)
-        .checked_mul(config.fee_config.fee_percentage_basis_points as u128)
-        .ok_or(ErrorCode::MathOverflow)?
-        .checked_div(BASIS_POINTS_DIVISOR as u128)
-        .ok_or(ErrorCode::MathOverflow)?;
-    // SECURITY: Safe cast with overflow check
-    let fee_amount = u64::try_from(fee_amount_u128)
-        .map_err(|_| ErrorCode::MathOverflow)?;
-    let fee_amount = fee_amount.max(config.fee_config.min_fee_amount);
-    let merchant_amount = payment_amount.checked_sub(fee_amount).ok_or(ErrorCode::InsufficientAmount)?;
+/// Check whether the subscription is still inside its free trial
+pub fn is_trial_active(trial_end_time: Option<i64>, current_time: i64) -> bool {
+    matches!(trial_end_time, Some(trial_end) if current_time < trial_end)
+}
 
-    // Get data needed for CPI before mutating subscription
-    let subscription_id = subscription.id.clone();
+/// Authorization mode for a subscription: its creation-time override, else the global mode
+pub fn effective_authorization_mode(
+    mode_override: Option<AuthorizationMode>,
+    global_mode: AuthorizationMode,
+) -> AuthorizationMode {
+    mode_override.unwrap_or(global_mode)
+}
 
-    // EFFECTS: Update subscription state BEFORE external calls (CEI pattern)
-    subscription.last_payment_time = Some(Clock::get()?.unix_timestamp);
-    subscription.payments_made = subscription.payments_made.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
-    subscription.total_paid = subscription.total_paid.checked_add(payment_amount).ok_or(ErrorCode::MathOverflow)?;
-    // Update escrow balance (merchant amount goes to escrow)
-    subscription.escrow_balance = subscription.escrow_balance.checked_add(merchant_amount).ok_or(ErrorCode::MathOverflow)?;
+/// Whether subscriber/manual triggering is allowed under a mode
+pub fn allows_manual_processing(mode: AuthorizationMode) -> bool {
+    matches!(mode, AuthorizationMode::ManualOnly | AuthorizationMode::Hybrid)
+}
 
-    // Handle one-time vs recurring payments
-    if subscription.interval_seconds == -1 {
-        // One-time payment: auto-cancel after payment
-        subscription.status = SubscriptionStatus::Cancelled;
-        msg!("One-time payment completed - subscription auto-cancelled");
-    } else {
-        // Recurring: schedule next payment
-        subscription.next_payment_time = subscription.next_payment_time
-            .checked_add(subscription.interval_seconds)
-            .ok_or(ErrorCode::MathOverflow)?;
+/// Whether permissionless time-based triggering is allowed under a mode
+pub fn allows_time_based_processing(mode: AuthorizationMode) -> bool {
+    matches!(mode, AuthorizationMode::TimeBased | AuthorizationMode::Hybrid)
+}
+
+/// Slippage can change at most once per SLIPPAGE_UPDATE_COOLDOWN_SECONDS
+pub fn is_slippage_update_allowed(slippage_updated_at: Option<i64>, current_time: i64) -> bool {
+    match slippage_updated_at {
+        None => true,
+        Some(updated_at) => current_time >= updated_at.saturating_add(SLIPPAGE_UPDATE_COOLDOWN_SECONDS),
     }
+}
 
-    // INTERACTIONS: External token transfers AFTER state updates (CEI pattern)
-    let seeds = &[b"subscription", subscription_id.as_bytes(), &[ctx.bumps.subscription]];
-    let signer_seeds = &[&seeds[..]];
+/// Next payment time after skipping `periods` billing cycles (None on overflow)
+pub fn snoozed_next_payment_time(next_payment_time: i64, interval_seconds: i64, periods: u8) -> Option<i64> {
+    interval_seconds
+        .checked_mul(periods as i64)
+        .and_then(|skipped| next_payment_time.checked_add(skipped))
+}
 
-    // Transfer fee to ICP treasury
-    let transfer_fee_ix = anchor_spl::token::spl_token::instruction::transfer(
-        ctx.accounts.token_program.key,
-        &ctx.accounts.subscriber_token_account.key(),
-        &ctx.accounts.icp_fee_usdc_account.key(),
-        ctx.accounts.subscription_pda.key,
-        &[],
-        fee_amount,
-    )?;
+/// Whether a recurring subscription can move to `new_interval_seconds`
+/// Same bounds as create_subscription, and the grace period must still fit inside one interval
+pub fn is_valid_interval_update(new_interval_seconds: i64, grace_period_seconds: i64) -> bool {
+    (10..=365 * 24 * 60 * 60).contains(&new_interval_seconds) // Maximum 1 year
+        && grace_period_seconds < new_interval_seconds
+}
 
-    anchor_lang::solana_program::program::invoke_signed(
-        &transfer_fee_ix,
-        &[
-            ctx.accounts.subscriber_token_account.to_account_info(),
-            ctx.accounts.icp_fee_usdc_account.to_account_info(),
-            ctx.accounts.subscription_pda.to_account_info(),
-        ],
-        signer_seeds,
-    )?;
+/// Subscription ID as stored in SubscriberIndex/MerchantIndex: UTF-8 bytes, zero-padded to 32
+pub fn subscription_id_key(subscription_id: &str) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    let bytes = subscription_id.as_bytes();
+    let len = bytes.len().min(32);
+    key[..len].copy_from_slice(&bytes[..len]);
+    key
+}
 
-    // Transfer remaining to ESCROW (not directly to merchant)
-    let transfer_escrow_ix = anchor_spl::token::spl_token::instruction::transfer(
-        ctx.accounts.token_program.key,
-        &ctx.accounts.subscriber_token_account.key(),
-        &ctx.accounts.escrow_usdc_account.key(),
-        ctx.accounts.subscription_pda.key,
-        &[],
-        merchant_amount,
-    )?;
+/// Add `key` to an index unless it's already there; returns the new entry count
+pub fn insert_index_entry(subscription_ids: &mut Vec<[u8; 32]>, key: [u8; 32]) -> Result<u8> {
+    if !subscription_ids.contains(&key) {
+        require!(subscription_ids.len() < MAX_INDEXED_SUBSCRIPTIONS, ErrorCode::SubscriptionIndexFull);
+        subscription_ids.push(key);
+    }
+    Ok(subscription_ids.len() as u8)
+}
 
-    anchor_lang::solana_program::program::invoke_signed(
-        &transfer_escrow_ix,
-        &[
-            ctx.accounts.subscriber_token_account.to_account_info(),
-            ctx.accounts.escrow_usdc_account.to_account_info(),
-            ctx.accounts.subscription_pda.to_account_info(),
-        ],
-        signer_seeds,
-    )?;
+/// Drop `key` from an index if present; returns the new entry count
+pub fn remove_index_entry(subscription_ids: &mut Vec<[u8; 32]>, key: &[u8; 32]) -> u8 {
+    subscription_ids.retain(|existing| existing != key);
+    subscription_ids.len() as u8
+}
 
-    msg!("USDC payment processed to ESCROW: {} USDC (fee: {}, escrow: {}, escrow_balance: {})",
-        payment_amount, fee_amount, merchant_amount, subscription.escrow_balance);
+/// Remove a subscription from whichever index accounts were passed
+pub fn unindex_subscription(
+    subscription_id: &str,
+    subscriber_index: Option<&mut Account<SubscriberIndex>>,
+    merchant_index: Option<&mut Account<MerchantIndex>>,
+) {
+    let key = subscription_id_key(subscription_id);
+    if let Some(index) = subscriber_index {
+        index.count = remove_index_entry(&mut index.subscription_ids, &key);
+    }
+    if let Some(index) = merchant_index {
+        index.count = remove_index_entry(&mut index.subscription_ids, &key);
+    }
+}
 
-    // Emit payment event
-    emit!(PaymentProcessed {
-        subscription_id: subscription_id.clone(),
-        payment_number: subscription.payments_made,
-        amount: payment_amount,
-        merchant_amount,
-        fee_amount,
-        timestamp: Clock::get()?.unix_timestamp,
-    });
+/// Whether a merchant's settings allow `subscriber` to subscribe
+pub fn is_subscriber_approved(merchant_config: &MerchantConfig, subscriber: &Pubkey) -> bool {
+    !merchant_config.active
+        || !merchant_config.requires_approval
+        || merchant_config.approved_subscribers.contains(subscriber)
+}
+
+/// Enforce merchant opt-in gating for a new subscription
+/// An uninitialized MerchantConfig PDA means the merchant accepts everyone
+pub fn check_merchant_approval(
+    merchant_config: &UncheckedAccount,
+    merchant: &Pubkey,
+    subscriber: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<()> {
+    if merchant_config.owner != program_id {
+        return Ok(());
+    }
+
+    let config = MerchantConfig::try_deserialize(&mut &merchant_config.try_borrow_data()?[..])?;
+    require!(config.merchant == *merchant, ErrorCode::InvalidMerchantAddress);
+    require!(is_subscriber_approved(&config, subscriber), ErrorCode::SubscriberNotApproved);
 
     Ok(())
 }
 
-pub fn send_notification_internal(ctx: Context<crate::ProcessTrigger>, memo: String) -> Result<()> {
-    require!(memo.len() <= 566, ErrorCode::MemoTooLong);
-
-    // 1. Transfer tiny SOL amount (0.000001 SOL = 1000 lamports)
-    let notification_amount = 1000u64;
+/// Compute the spending window after charging `amount`
+/// The window restarts once a full interval has passed since period_start
+/// Returns (period_start, spent_in_period), or SpendingCapExceeded; a cap of 0 disables the check
+pub fn apply_spending_cap(
+    spending_cap_per_period: u64,
+    period_start: i64,
+    spent_in_period: u64,
+    amount: u64,
+    interval_seconds: i64,
+    current_time: i64,
+) -> Result<(i64, u64)> {
+    let (period_start, spent_in_period) =
+        if interval_seconds > 0 && current_time >= period_start.saturating_add(interval_seconds) {
+            (current_time, 0)
+        } else {
+            (period_start, spent_in_period)
+        };
 
-    let transfer_ix = anchor_lang::solana_program::system_instruction::transfer(
-        &ctx.accounts.trigger_authority.key(),
-        &ctx.accounts.subscriber.key(),
-        notification_amount,
+    let spent_in_period = spent_in_period
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    require!(
+        spending_cap_per_period == 0 || spent_in_period <= spending_cap_per_period,
+        ErrorCode::SpendingCapExceeded
     );
 
-    anchor_lang::solana_program::program::invoke(
-        &transfer_ix,
-        &[
-            ctx.accounts.trigger_authority.to_account_info(),
-            ctx.accounts.subscriber.to_account_info(),
-        ],
-    )?;
+    Ok((period_start, spent_in_period))
+}
 
-    // 2. Add SPL Memo instruction to make message visible in wallets
-    let memo_ix = spl_memo::build_memo(
-        memo.as_bytes(),
-        &[&ctx.accounts.trigger_authority.key()],
+/// Validate multisig members and threshold and pack them into the fixed Config layout
+pub fn build_admin_signers(
+    signers: &[Pubkey],
+    threshold: u8,
+) -> Result<([Pubkey; MAX_ADMIN_SIGNERS], u8)> {
+    require!(
+        !signers.is_empty() && signers.len() <= MAX_ADMIN_SIGNERS,
+        ErrorCode::InvalidMultisigConfig
     );
-
+    require!(
+        threshold > 0 && threshold as usize <= signers.len(),
+        ErrorCode::InvalidMultisigConfig
+    );
+    for (i, signer) in signers.iter().enumerate() {
+        require!(*signer != Pubkey::default(), ErrorCode::InvalidMultisigConfig);
+        require!(!signers[..i].contains(signer), ErrorCode::InvalidMultisigConfig);
+    }
+
+    let mut packed = [Pubkey::default(); MAX_ADMIN_SIGNERS];
+    packed[..signers.len()].copy_from_slice(signers);
+    Ok((packed, signers.len() as u8))
+}
+
+/// Index of a multisig member in the admin signer list
+pub fn admin_signer_index(config: &Config, key: &Pubkey) -> Option<usize> {
+    config.admin_signers[..(config.admin_signer_count as usize).min(MAX_ADMIN_SIGNERS)]
+        .iter()
+        .position(|signer| signer == key)
+}
+
+/// Check whether an admin proposal was abandoned past its timeout
+pub fn is_admin_proposal_expired(proposed_at: i64, current_time: i64) -> bool {
+    current_time >= proposed_at.saturating_add(ADMIN_PROPOSAL_TIMEOUT_SECONDS)
+}
+
+/// Record a multisig member's approval (one approval per member)
+pub fn record_admin_approval(pending: &mut PendingAdminAction, signer_index: usize) -> Result<()> {
+    let bit = 1u8 << signer_index;
+    require!(pending.approvals & bit == 0, ErrorCode::AdminActionAlreadyApproved);
+
+    pending.approvals |= bit;
+    pending.approval_count = pending.approval_count
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(())
+}
+
+/// Consume the pending admin action authorizing an account-bound admin instruction
+/// The proposal must match the instruction's type and Borsh-encoded arguments exactly
+pub fn take_approved_admin_action(
+    pending_admin_action: &mut Option<PendingAdminAction>,
+    admin_threshold: u8,
+    action_type: AdminActionType,
+    data: &[u8],
+    current_time: i64,
+) -> Result<PendingAdminAction> {
+    let pending = pending_admin_action
+        .as_ref()
+        .filter(|pending| pending.action_type == action_type && pending.data == data)
+        .ok_or(ErrorCode::AdminProposalNotFound)?;
+    require!(
+        !is_admin_proposal_expired(pending.proposed_at, current_time),
+        ErrorCode::AdminProposalExpired
+    );
+    require!(
+        pending.approval_count >= admin_threshold,
+        ErrorCode::AdminThresholdNotMet
+    );
+
+    pending_admin_action.take().ok_or_else(|| error!(ErrorCode::AdminProposalNotFound))
+}
+
+/// Active portion of the ICP public key allowlist
+pub fn active_icp_keys(config: &Config) -> &[[u8; 32]] {
+    &config.icp_public_keys[..(config.icp_key_count as usize).min(MAX_ICP_PUBLIC_KEYS)]
+}
+
+/// Keys accepted for ICP signature verification: the allowlist plus any key being rotated in
+pub fn accepted_icp_keys(config: &Config, current_time: i64) -> Vec<[u8; 32]> {
+    with_pending_icp_key(
+        active_icp_keys(config),
+        config.pending_icp_public_key,
+        config.key_rotation_start,
+        current_time,
+    )
+}
+
+/// Active keys plus a pending rotation key, without duplicates
+/// The pending key only verifies inside its rotation window; past it, complete_key_rotation must run
+pub fn with_pending_icp_key(
+    active_keys: &[[u8; 32]],
+    pending_key: Option<[u8; 32]>,
+    rotation_start: Option<i64>,
+    current_time: i64,
+) -> Vec<[u8; 32]> {
+    let mut keys = active_keys.to_vec();
+    if let (Some(pending), Some(start)) = (pending_key, rotation_start) {
+        let in_window = current_time >= start && !is_key_rotation_window_over(start, current_time);
+        if in_window && !keys.contains(&pending) {
+            keys.push(pending);
+        }
+    }
+    keys
+}
+
+/// Whether the dual-key window of a rotation started at `rotation_start` has elapsed
+pub fn is_key_rotation_window_over(rotation_start: i64, current_time: i64) -> bool {
+    current_time >= rotation_start.saturating_add(ROTATION_WINDOW_SECONDS)
+}
+
+/// Replace the primary ICP key (slot 0) with `new_key`, or add it if the allowlist is empty
+pub fn rotate_primary_icp_key(
+    keys: &mut [[u8; 32]; MAX_ICP_PUBLIC_KEYS],
+    count: &mut u8,
+    new_key: [u8; 32],
+) -> Result<()> {
+    if *count == 0 {
+        return add_icp_key(keys, count, new_key);
+    }
+    require!(!keys[1..*count as usize].contains(&new_key), ErrorCode::DuplicateICPKey);
+    keys[0] = new_key;
+    Ok(())
+}
+
+/// Add a key to the ICP public key allowlist (appended after the active keys)
+pub fn add_icp_key(
+    keys: &mut [[u8; 32]; MAX_ICP_PUBLIC_KEYS],
+    count: &mut u8,
+    key: [u8; 32],
+) -> Result<()> {
+    let active = *count as usize;
+    require!(!keys[..active].contains(&key), ErrorCode::DuplicateICPKey);
+    require!(active < MAX_ICP_PUBLIC_KEYS, ErrorCode::ICPKeyAllowlistFull);
+
+    keys[active] = key;
+    *count += 1;
+    Ok(())
+}
+
+/// Remove a key from the ICP public key allowlist, keeping the active keys contiguous
+pub fn remove_icp_key(
+    keys: &mut [[u8; 32]; MAX_ICP_PUBLIC_KEYS],
+    count: &mut u8,
+    key: [u8; 32],
+) -> Result<()> {
+    let active = *count as usize;
+    let index = keys[..active]
+        .iter()
+        .position(|existing| *existing == key)
+        .ok_or(ErrorCode::ICPKeyNotFound)?;
+
+    // Shift the remaining keys down and clear the freed slot
+    keys.copy_within(index + 1..active, index);
+    keys[active - 1] = [0u8; 32];
+    *count -= 1;
+    Ok(())
+}
+
+/// Validate volume fee tiers and pack them into the fixed FeeConfig layout
+/// An empty list defaults to a single tier at `base_fee_bps`
+pub fn build_fee_tiers(tiers: &[FeeTier], base_fee_bps: u16) -> Result<([FeeTier; MAX_FEE_TIERS], u8)> {
+    let default_tier = [FeeTier { threshold_total_paid: 0, fee_bps: base_fee_bps }];
+    let tiers = if tiers.is_empty() { &default_tier[..] } else { tiers };
+
+    require!(tiers.len() <= MAX_FEE_TIERS, ErrorCode::InvalidFeeTiers);
+    require!(tiers[0].threshold_total_paid == 0, ErrorCode::InvalidFeeTiers);
+    require!(
+        tiers.iter().all(|tier| tier.fee_bps as u64 <= BASIS_POINTS_DIVISOR),
+        ErrorCode::InvalidFeeTiers
+    );
+    require!(
+        tiers.windows(2).all(|pair| {
+            pair[1].threshold_total_paid > pair[0].threshold_total_paid && pair[1].fee_bps <= pair[0].fee_bps
+        }),
+        ErrorCode::InvalidFeeTiers
+    );
+
+    let mut packed = [FeeTier::default(); MAX_FEE_TIERS];
+    packed[..tiers.len()].copy_from_slice(tiers);
+
+    Ok((packed, tiers.len() as u8))
+}
+
+/// Pick the fee tier for a subscription: the highest threshold at or below its total_paid
+/// Configs without tiers use the flat fee_percentage_basis_points
+pub fn select_fee_tier(fee_config: &FeeConfig, total_paid: u64) -> TierApplied {
+    let tier_count = (fee_config.tier_count as usize).min(MAX_FEE_TIERS);
+    fee_config.fee_tiers[..tier_count]
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(_, tier)| tier.threshold_total_paid <= total_paid)
+        .map(|(index, tier)| TierApplied { tier_index: index as u8, fee_bps: tier.fee_bps })
+        .unwrap_or(TierApplied { tier_index: 0, fee_bps: fee_config.fee_percentage_basis_points })
+}
+
+/// Apply the configured fee floor and cap, never taking more than `payment_amount - 1`
+/// so the merchant always receives something, even when the floor exceeds a tiny payment
+pub fn bound_fee_amount(fee_amount: u64, payment_amount: u64, fee_config: &FeeConfig) -> u64 {
+    fee_amount
+        .min(fee_config.max_fee_amount)
+        .max(fee_config.min_fee_amount)
+        .min(payment_amount.saturating_sub(1))
+}
+
+/// Split a payment into (platform_fee, merchant_amount) at `fee_bps`
+/// Every charge path uses this, so the fee floor and cap from bound_fee_amount apply everywhere
+pub fn split_payment_amount(amount: u64, fee_bps: u16, fee_config: &FeeConfig) -> Result<(u64, u64)> {
+    let platform_fee = amount
+        .checked_mul(fee_bps as u64)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(BASIS_POINTS_DIVISOR)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let platform_fee = bound_fee_amount(platform_fee, amount, fee_config);
+
+    let merchant_amount = amount
+        .checked_sub(platform_fee)
+        .ok_or(ErrorCode::InsufficientAmount)?;
+
+    Ok((platform_fee, merchant_amount))
+}
+
+/// Validate revenue share recipients and pack them into the fixed FeeConfig layout
+/// An empty list keeps the single icp_fee_collection_address destination
+pub fn build_fee_recipients(
+    recipients: &[FeeRecipient],
+) -> Result<([FeeRecipient; MAX_FEE_RECIPIENTS], u8)> {
+    require!(recipients.len() <= MAX_FEE_RECIPIENTS, ErrorCode::InvalidFeeRecipients);
+
+    if !recipients.is_empty() {
+        require!(
+            recipients.iter().all(|recipient| recipient.share_bps > 0),
+            ErrorCode::InvalidFeeRecipients
+        );
+        let total_bps: u64 = recipients.iter().map(|recipient| recipient.share_bps as u64).sum();
+        require!(total_bps == BASIS_POINTS_DIVISOR, ErrorCode::InvalidFeeRecipients);
+    }
+
+    let mut packed = [FeeRecipient::default(); MAX_FEE_RECIPIENTS];
+    packed[..recipients.len()].copy_from_slice(recipients);
+
+    Ok((packed, recipients.len() as u8))
+}
+
+/// Split the platform fee by recipient share; rounding dust goes to the last recipient
+pub fn calculate_fee_shares(platform_fee: u64, recipients: &[FeeRecipient]) -> Result<Vec<u64>> {
+    let mut shares = Vec::with_capacity(recipients.len());
+    let mut distributed: u64 = 0;
+
+    for recipient in recipients {
+        let share = (platform_fee as u128)
+            .checked_mul(recipient.share_bps as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(BASIS_POINTS_DIVISOR as u128)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let share = u64::try_from(share).map_err(|_| ErrorCode::MathOverflow)?;
+        distributed = distributed.checked_add(share).ok_or(ErrorCode::MathOverflow)?;
+        shares.push(share);
+    }
+
+    if let Some(last) = shares.last_mut() {
+        let dust = platform_fee.checked_sub(distributed).ok_or(ErrorCode::MathOverflow)?;
+        *last = last.checked_add(dust).ok_or(ErrorCode::MathOverflow)?;
+    }
+
+    Ok(shares)
+}
+
+/// Record a successful payment and schedule the next one
+/// Handles one-time auto-cancel, installment completion and missed-payment catch-up
+pub fn record_payment(subscription: &mut Subscription, current_time: i64) -> Result<()> {
+    subscription.payments_made += 1;
+    subscription.total_paid += subscription.amount;
+    subscription.remaining_payments = decrement_remaining_payments(subscription.remaining_payments)?;
+
+    // Schedule next payment based on interval type
+    if subscription.interval_seconds == -1 {
+        // One-time payment: auto-cancel after payment
+        subscription.status = SubscriptionStatus::Cancelled;
+        msg!("One-time payment completed - subscription auto-cancelled");
+    } else if subscription.remaining_payments == Some(0) {
+        // Installment plan: final payment made, auto-cancel
+        subscription.status = SubscriptionStatus::Cancelled;
+        msg!("Final installment completed - subscription auto-cancelled");
+
+        emit!(SubscriptionCancelled {
+            subscription_id: subscription.id.clone(),
+            cancelled_at: current_time,
+            total_payments_made: subscription.payments_made,
+            total_paid: subscription.total_paid,
+        });
+    } else {
+        // Recurring payment: schedule next payment relative to scheduled time (not current time) to prevent drift,
+        // skipping any missed payments
+        subscription.next_payment_time = next_recurring_payment_time(
+            subscription.next_payment_time,
+            subscription.interval_seconds,
+            current_time,
+        ).ok_or(ErrorCode::MathOverflow)?;
+    }
+
+    subscription.last_payment_time = Some(current_time);
+
+    Ok(())
+}
+
+/// How a payment affects the subscriber's reputation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaymentOutcome {
+    OnTime,
+    Late,
+    Failed,
+}
+
+/// A payment is on time when charged no later than `grace_period_seconds` after it was due
+pub fn classify_payment_timing(current_time: i64, next_payment_time: i64, grace_period_seconds: i64) -> PaymentOutcome {
+    if current_time <= next_payment_time.saturating_add(grace_period_seconds) {
+        PaymentOutcome::OnTime
+    } else {
+        PaymentOutcome::Late
+    }
+}
+
+/// Count a payment outcome and move the score by its delta, clamped to 0..=REPUTATION_MAX_SCORE
+pub fn apply_reputation_outcome(reputation: &mut SubscriberReputation, outcome: PaymentOutcome, current_time: i64) {
+    let delta = match outcome {
+        PaymentOutcome::OnTime => {
+            reputation.on_time_payments = reputation.on_time_payments.saturating_add(1);
+            REPUTATION_ON_TIME_DELTA
+        }
+        PaymentOutcome::Late => {
+            reputation.late_payments = reputation.late_payments.saturating_add(1);
+            REPUTATION_LATE_DELTA
+        }
+        PaymentOutcome::Failed => {
+            reputation.failed_payments = reputation.failed_payments.saturating_add(1);
+            REPUTATION_FAILED_DELTA
+        }
+    };
+    reputation.score = (reputation.score as i32 + delta).clamp(0, REPUTATION_MAX_SCORE as i32) as u16;
+    reputation.last_updated = current_time;
+}
+
+/// Add a payment outcome to a subscriber's reputation, creating the PDA on first use
+pub fn record_subscriber_reputation<'info>(
+    subscriber_reputation: &UncheckedAccount<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    subscriber: &Pubkey,
+    outcome: PaymentOutcome,
+    current_time: i64,
+    program_id: &Pubkey,
+) -> Result<u16> {
+    let (reputation_pda, bump) = Pubkey::find_program_address(&[b"reputation", subscriber.as_ref()], program_id);
+    require!(reputation_pda == subscriber_reputation.key(), ErrorCode::InvalidReputationAccount);
+
+    let reputation_info = subscriber_reputation.to_account_info();
+    let mut reputation = if subscriber_reputation.owner == program_id {
+        SubscriberReputation::try_deserialize(&mut &reputation_info.try_borrow_data()?[..])?
+    } else {
+        let seeds = &[b"reputation".as_ref(), subscriber.as_ref(), &[bump]];
+        let signer_seeds = &[&seeds[..]];
+        create_pda_account(&reputation_info, payer, system_program, signer_seeds, 8 + SubscriberReputation::LEN, program_id)?;
+        SubscriberReputation {
+            subscriber: *subscriber,
+            score: REPUTATION_INITIAL_SCORE,
+            on_time_payments: 0,
+            late_payments: 0,
+            failed_payments: 0,
+            last_updated: current_time,
+        }
+    };
+
+    apply_reputation_outcome(&mut reputation, outcome, current_time);
+    let mut data = reputation_info.try_borrow_mut_data()?;
+    reputation.try_serialize(&mut &mut data[..])?;
+    Ok(reputation.score)
+}
+
+/// Add a settled payment to a merchant's dashboard; `ended` when the payment finished the subscription
+pub fn apply_dashboard_payment(
+    dashboard: &mut MerchantDashboard,
+    merchant_amount: u64,
+    platform_fee: u64,
+    current_time: i64,
+    ended: bool,
+) -> Result<()> {
+    dashboard.total_revenue_usdc = dashboard.total_revenue_usdc
+        .checked_add(merchant_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    dashboard.total_fees_paid = dashboard.total_fees_paid
+        .checked_add(platform_fee)
+        .ok_or(ErrorCode::MathOverflow)?;
+    dashboard.last_payment = Some(current_time);
+    if ended {
+        dashboard.active_subscriptions = dashboard.active_subscriptions.saturating_sub(1);
+    }
+    Ok(())
+}
+
+/// Read-modify-write a merchant dashboard passed as an unchecked account
+/// Does nothing if the dashboard hasn't been created yet
+pub fn update_merchant_dashboard<'info>(
+    merchant_dashboard: &UncheckedAccount<'info>,
+    merchant: &Pubkey,
+    program_id: &Pubkey,
+    update: impl FnOnce(&mut MerchantDashboard) -> Result<()>,
+) -> Result<()> {
+    let (dashboard_pda, _) = Pubkey::find_program_address(&[b"merchant_dashboard", merchant.as_ref()], program_id);
+    require!(dashboard_pda == merchant_dashboard.key(), ErrorCode::InvalidMerchantAddress);
+    if merchant_dashboard.owner != program_id {
+        return Ok(());
+    }
+
+    let dashboard_info = merchant_dashboard.to_account_info();
+    let mut dashboard = MerchantDashboard::try_deserialize(&mut &dashboard_info.try_borrow_data()?[..])?;
+    update(&mut dashboard)?;
+    let mut data = dashboard_info.try_borrow_mut_data()?;
+    dashboard.try_serialize(&mut &mut data[..])?;
+    Ok(())
+}
+
+/// Create a merchant's dashboard on their first payment, counting the subscription being charged
+pub fn create_merchant_dashboard<'info>(
+    merchant_dashboard: &UncheckedAccount<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    merchant: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<()> {
+    if merchant_dashboard.owner == program_id {
+        return Ok(());
+    }
+
+    let (dashboard_pda, bump) = Pubkey::find_program_address(&[b"merchant_dashboard", merchant.as_ref()], program_id);
+    require!(dashboard_pda == merchant_dashboard.key(), ErrorCode::InvalidMerchantAddress);
+
+    let seeds = &[b"merchant_dashboard".as_ref(), merchant.as_ref(), &[bump]];
+    let signer_seeds = &[&seeds[..]];
+    let dashboard_info = merchant_dashboard.to_account_info();
+    create_pda_account(&dashboard_info, payer, system_program, signer_seeds, 8 + MerchantDashboard::LEN, program_id)?;
+
+    let dashboard = MerchantDashboard {
+        merchant: *merchant,
+        active_subscriptions: 1,
+        total_revenue_usdc: 0,
+        total_fees_paid: 0,
+        last_payment: None,
+        failed_payments: 0,
+    };
+    let mut data = dashboard_info.try_borrow_mut_data()?;
+    dashboard.try_serialize(&mut &mut data[..])?;
+    Ok(())
+}
+
+/// Add a settled payment to the program-wide fee totals
+pub fn record_fee_analytics(analytics: &mut FeeAnalytics, amount: u64, platform_fee: u64) -> Result<()> {
+    analytics.total_volume = analytics.total_volume
+        .checked_add(amount as u128)
+        .ok_or(ErrorCode::MathOverflow)?;
+    analytics.total_fees_collected = analytics.total_fees_collected
+        .checked_add(platform_fee as u128)
+        .ok_or(ErrorCode::MathOverflow)?;
+    analytics.payment_count = analytics.payment_count
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(())
+}
+
+/// Accounts a nonce PDA is created with
+pub struct NonceAccounts<'a, 'info> {
+    pub payer: &'a Signer<'info>, // Pays the nonce's rent
+    pub system_program: &'a Program<'info, System>,
+    pub program_id: &'a Pubkey,
+}
+
+/// Create the PaymentNonce PDA for a subscription's current payment cycle
+/// Returns DuplicatePayment if the nonce already exists (cycle already charged)
+pub fn create_payment_nonce<'info>(
+    payment_nonce: &UncheckedAccount<'info>,
+    accounts: &NonceAccounts<'_, 'info>,
+    subscription_id: &str,
+    subscription_key: Pubkey,
+    cycle: u64,
+    current_time: i64,
+) -> Result<()> {
+    let NonceAccounts { payer, system_program, program_id } = *accounts;
+    require!(payment_nonce.owner != program_id, ErrorCode::DuplicatePayment);
+
+    let cycle_bytes = cycle.to_le_bytes();
+    let (nonce_pda, bump) = Pubkey::find_program_address(
+        &[b"payment_nonce", subscription_id.as_bytes(), &cycle_bytes],
+        program_id
+    );
+    require!(nonce_pda == payment_nonce.key(), ErrorCode::InvalidSubscriptionPDA);
+
+    let seeds = &[
+        b"payment_nonce".as_ref(),
+        subscription_id.as_bytes(),
+        &cycle_bytes,
+        &[bump],
+    ];
+    let signer_seeds = &[&seeds[..]];
+
+    let nonce_info = payment_nonce.to_account_info();
+    create_pda_account(&nonce_info, payer, system_program, signer_seeds, 8 + PaymentNonce::LEN, program_id)?;
+
+    let nonce = PaymentNonce {
+        subscription: subscription_key,
+        cycle,
+        created_at: current_time,
+    };
+    let mut data = nonce_info.try_borrow_mut_data()?;
+    nonce.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+/// Create a program-owned PDA with `space` bytes, paid for by `payer`
+/// Handles addresses that were pre-funded (where create_account would fail)
+fn create_pda_account<'info>(
+    account_info: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    signer_seeds: &[&[&[u8]]],
+    space: usize,
+    program_id: &Pubkey,
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let current_lamports = account_info.lamports();
+
+    if current_lamports == 0 {
+        anchor_lang::system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                anchor_lang::system_program::CreateAccount {
+                    from: payer.to_account_info(),
+                    to: account_info.clone(),
+                },
+                signer_seeds,
+            ),
+            rent,
+            space as u64,
+            program_id,
+        )?;
+    } else {
+        // Address was pre-funded, so create_account would fail: top up, allocate, and assign instead
+        let top_up = rent.saturating_sub(current_lamports);
+        if top_up > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: payer.to_account_info(),
+                        to: account_info.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        anchor_lang::system_program::allocate(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Allocate {
+                    account_to_allocate: account_info.clone(),
+                },
+                signer_seeds,
+            ),
+            space as u64,
+        )?;
+        anchor_lang::system_program::assign(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Assign {
+                    account_to_assign: account_info.clone(),
+                },
+                signer_seeds,
+            ),
+            program_id,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Close a program-owned account: move its lamports to `destination` and hand it back to the system program
+pub fn close_program_account<'info>(
+    account_info: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+) -> Result<()> {
+    let reclaimed = destination.lamports()
+        .checked_add(account_info.lamports())
+        .ok_or(ErrorCode::MathOverflow)?;
+    **destination.lamports.borrow_mut() = reclaimed;
+    **account_info.lamports.borrow_mut() = 0;
+    account_info.assign(&anchor_lang::system_program::ID);
+    account_info.resize(0)?;
+    Ok(())
+}
+
+/// Grow a program-owned account to `space`, topping its rent up from `payer` first
+pub fn grow_program_account<'info>(
+    account_info: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+) -> Result<()> {
+    let top_up = Rent::get()?.minimum_balance(space).saturating_sub(account_info.lamports());
+    if top_up > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: account_info.clone(),
+                },
+            ),
+            top_up,
+        )?;
+    }
+    account_info.resize(space)?;
+    Ok(())
+}
+
+/// A used nonce can be pruned once its signature is outside any accepted timestamp window
+pub fn is_used_nonce_expired(signed_timestamp: i64, current_time: i64) -> bool {
+    current_time >= signed_timestamp.saturating_add(MAX_TIMESTAMP_DRIFT * 2)
+}
+
+/// Record an ICP signature as used by creating its UsedNonce PDA
+/// Returns SignatureReplayed if the PDA already exists (same subscription and signed timestamp)
+pub fn create_used_nonce<'info>(
+    used_nonce: &UncheckedAccount<'info>,
+    accounts: &NonceAccounts<'_, 'info>,
+    subscription_id: &str,
+    subscription_key: Pubkey,
+    signed_timestamp: i64,
+    current_time: i64,
+) -> Result<()> {
+    let NonceAccounts { payer, system_program, program_id } = *accounts;
+    require!(used_nonce.owner != program_id, ErrorCode::SignatureReplayed);
+
+    let timestamp_bytes = signed_timestamp.to_le_bytes();
+    let (nonce_pda, bump) = Pubkey::find_program_address(
+        &[b"nonce", subscription_id.as_bytes(), &timestamp_bytes],
+        program_id
+    );
+    require!(nonce_pda == used_nonce.key(), ErrorCode::InvalidSubscriptionPDA);
+
+    let seeds = &[
+        b"nonce".as_ref(),
+        subscription_id.as_bytes(),
+        &timestamp_bytes,
+        &[bump],
+    ];
+    let signer_seeds = &[&seeds[..]];
+
+    let nonce_info = used_nonce.to_account_info();
+    create_pda_account(&nonce_info, payer, system_program, signer_seeds, 8 + UsedNonce::LEN, program_id)?;
+
+    let nonce = UsedNonce {
+        subscription: subscription_key,
+        timestamp: signed_timestamp,
+        payer: payer.key(),
+        created_at: current_time,
+    };
+    let mut data = nonce_info.try_borrow_mut_data()?;
+    nonce.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+/// Core payment processing logic for USDC-only payments
+pub fn process_payment_core<'info>(
+    subscription: &mut Account<'info, Subscription>,
+    config: &Account<'info, Config>,
+    trigger_authority: &Signer<'info>,
+    subscriber_token_account: &InterfaceAccount<'info, TokenAccount>,
+    merchant_token_account: &InterfaceAccount<'info, TokenAccount>,
+    fee_vault: &InterfaceAccount<'info, TokenAccount>,
+    payment_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    program_id: &Pubkey,
+    icp_signature: Option<[u8; 64]>,
+    timestamp: i64,
+    instructions_sysvar: &UncheckedAccount<'info>,
+    fee_recipient_accounts: &[AccountInfo<'info>],
+    payment_nonce: &UncheckedAccount<'info>,
+    previous_payment_nonce: Option<&UncheckedAccount<'info>>,
+    used_nonce: Option<&UncheckedAccount<'info>>,
+    system_program: &Program<'info, System>,
+    fee_analytics: &mut Account<'info, FeeAnalytics>,
+    merchant_dashboard: &UncheckedAccount<'info>,
+    subscriber_reputation: Option<&UncheckedAccount<'info>>,
+) -> Result<()> {
+    require!(!config.paused, ErrorCode::ProgramPaused);
+    require!(subscription.status != SubscriptionStatus::Expired, ErrorCode::SubscriptionExpired);
+    require!(subscription.status == SubscriptionStatus::Active, ErrorCode::SubscriptionNotActive);
+    verify_subscription_integrity(subscription)?;
+    require!(!subscription.disputed, ErrorCode::SubscriptionDisputed);
+
+    // Time-limited subscription reached its end: expire instead of charging
+    // Same when the schedule can't move past this payment. Returning an error here would roll
+    // the Expired status back, so later triggers are the ones rejected with SubscriptionExpired.
+    if expire_if_run_out(subscription, Clock::get()?.unix_timestamp) {
+        update_merchant_dashboard(merchant_dashboard, &subscription.merchant, program_id, |d| {
+            d.active_subscriptions = d.active_subscriptions.saturating_sub(1);
+            Ok(())
+        })?;
+        return Ok(());
+    }
+
+    // Token program must match the one the subscription was created with
+    require!(
+        token_program.key() == get_token_program_id(subscription.use_token_2022),
+        ErrorCode::InvalidTokenProgram
+    );
+
+    let clock = Clock::get()?;
+
+    // No billing while the free trial is running
+    require!(
+        !is_trial_active(subscription.trial_end_time, clock.unix_timestamp),
+        ErrorCode::TrialPeriodActive
+    );
+
+    let merchant_destination = merchant_token_account.to_account_info();
+    let settlement_accounts = SettlementAccounts {
+        config,
+        payer: trigger_authority,
+        subscriber_token_account,
+        merchant_destination: &merchant_destination,
+        fee_vault,
+        payment_mint,
+        token_program,
+        system_program,
+        fee_recipient_accounts,
+        payment_nonce,
+        previous_payment_nonce,
+        merchant_dashboard,
+        subscriber_reputation,
+    };
+    // Validated before authorization records a UsedNonce, so a rejected charge leaves the signature unused
+    let settlement = validate_settlement(subscription, &settlement_accounts, program_id, clock.unix_timestamp)?;
+
+    // Authorization based on configured mode
+    let authorization_mode = effective_authorization_mode(
+        subscription.authorization_mode_override,
+        config.authorization_mode,
+    );
+    match authorization_mode {
+        AuthorizationMode::ICPSignature => {
+            // Original ICP signature verification
+            require!(icp_signature.is_some(), ErrorCode::MissingSignature);
+            let signature = icp_signature.unwrap();
+
+            require!(
+                is_payment_due(
+                    clock.unix_timestamp,
+                    subscription.next_payment_time,
+                    subscription.grace_period_seconds
+                ),
+                ErrorCode::PaymentNotDue
+            );
+
+            // Verify timestamp is recent (prevent replay attacks)
+            let max_age_seconds = 300; // 5 minutes - reduced from 60 minutes for security
+            require!(
+                verify_timestamp(timestamp, clock.unix_timestamp, max_age_seconds)?,
+                ErrorCode::SignatureExpired
+            );
+
+            // Create message that ICP canister should have signed
+            let message = create_payment_message(
+                &subscription.id,
+                timestamp,
+                subscription.amount
+            );
+
+            // Verify ICP canister signature
+            let icp_public_keys = accepted_icp_keys(config, clock.unix_timestamp);
+            require!(!icp_public_keys.is_empty(), ErrorCode::MissingICPKey);
+            require!(
+                verify_ed25519_ix_any(instructions_sysvar, &icp_public_keys, &message)?,
+                ErrorCode::InvalidSignature
+            );
+
+            // Replay protection: a signature is single-use even inside the timestamp window
+            create_used_nonce(
+                used_nonce.ok_or(ErrorCode::MissingUsedNonce)?,
+                &NonceAccounts { payer: trigger_authority, system_program, program_id },
+                &subscription.id,
+                subscription.key(),
+                timestamp,
+                clock.unix_timestamp,
+            )?;
+
+            // Update signature for next payment verification
+            subscription.icp_canister_signature = signature;
+        },
+        AuthorizationMode::ManualOnly => {
+            // Manual processing - subscriber or authorized party can trigger
+            require!(
+                trigger_authority.key() == subscription.subscriber ||
+                trigger_authority.key() == config.authority,
+                ErrorCode::UnauthorizedAccess
+            );
+            // No time restriction for manual processing
+        },
+        AuthorizationMode::TimeBased => {
+            // Time-based processing - anyone can trigger if payment is due
+            require!(
+                is_payment_due(
+                    clock.unix_timestamp,
+                    subscription.next_payment_time,
+                    subscription.grace_period_seconds
+                ),
+                ErrorCode::PaymentNotDue
+            );
+        },
+        AuthorizationMode::Hybrid => {
+            // Multiple authorization methods
+            let is_icp_valid = if let Some(_signature) = icp_signature {
+                let icp_public_keys = accepted_icp_keys(config, clock.unix_timestamp);
+                if !icp_public_keys.is_empty() {
+                    let message = create_payment_message(
+                        &subscription.id,
+                        timestamp,
+                        subscription.amount
+                    );
+                    verify_ed25519_ix_any(instructions_sysvar, &icp_public_keys, &message).unwrap_or(false)
+                } else { false }
+            } else { false };
+
+            let is_manual_valid = trigger_authority.key() == subscription.subscriber;
+            let is_time_valid = is_payment_due(
+                clock.unix_timestamp,
+                subscription.next_payment_time,
+                subscription.grace_period_seconds
+            );
+
+            // A valid signature authorizes a due payment, not an early one
+            require!(
+                (is_icp_valid && is_time_valid) ||
+                (is_manual_valid && allows_manual_processing(authorization_mode)) ||
+                (is_time_valid && allows_time_based_processing(authorization_mode)),
+                ErrorCode::AuthorizationFailed
+            );
+
+            if is_icp_valid && icp_signature.is_some() {
+                // Only a signature that has not been used before can authorize the payment
+                create_used_nonce(
+                    used_nonce.ok_or(ErrorCode::MissingUsedNonce)?,
+                    &NonceAccounts { payer: trigger_authority, system_program, program_id },
+                    &subscription.id,
+                    subscription.key(),
+                    timestamp,
+                    clock.unix_timestamp,
+                )?;
+                subscription.icp_canister_signature = icp_signature.unwrap();
+            }
+        }
+    }
+
+    settle_payment(
+        subscription,
+        &settlement_accounts,
+        settlement,
+        fee_analytics,
+        program_id,
+        clock.unix_timestamp,
+    )?;
+
+    Ok(())
+}
+
+/// Check whether any account key appears more than once (batches are small, so pairwise is fine)
+pub fn has_duplicate_keys(keys: &[Pubkey]) -> bool {
+    keys.iter()
+        .enumerate()
+        .any(|(index, key)| keys[index + 1..].contains(key))
+}
+
+/// Accounts a charge is settled with; process_payment, process_trigger and batch entries all fill this in
+pub struct SettlementAccounts<'a, 'info> {
+    pub config: &'a Config,
+    pub payer: &'a Signer<'info>, // Pays rent for the nonce, dashboard and reputation PDAs
+    pub subscriber_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    pub merchant_destination: &'a AccountInfo<'info>, // Merchant token account, or the escrow for process_trigger
+    pub fee_vault: &'a InterfaceAccount<'info, TokenAccount>,
+    pub payment_mint: &'a InterfaceAccount<'info, Mint>,
+    pub token_program: &'a Interface<'info, TokenInterface>,
+    pub system_program: &'a Program<'info, System>,
+    pub fee_recipient_accounts: &'a [AccountInfo<'info>],
+    pub payment_nonce: &'a UncheckedAccount<'info>,
+    pub previous_payment_nonce: Option<&'a UncheckedAccount<'info>>,
+    pub merchant_dashboard: &'a UncheckedAccount<'info>,
+    pub subscriber_reputation: Option<&'a UncheckedAccount<'info>>,
+}
+
+/// Checked inputs of a charge, produced by validate_settlement before any account is written
+pub struct Settlement {
+    tier: TierApplied,
+    platform_fee: u64,
+    merchant_amount: u64,
+    bump: u8,
+    split_fee: bool,
+    period_start: i64,
+    spent_in_period: u64,
+    close_previous_nonce: bool,
+}
+
+/// Run every check a charge depends on without writing anything
+/// Callers that record a UsedNonce call this first, so a rejected charge doesn't burn the signature.
+/// Only the token transfers themselves can still fail in settle_payment.
+pub fn validate_settlement(
+    subscription: &Account<'_, Subscription>,
+    accounts: &SettlementAccounts<'_, '_>,
+    program_id: &Pubkey,
+    current_time: i64,
+) -> Result<Settlement> {
+    let config = accounts.config;
+    require!(
+        config.icp_fee_collection_address.is_some(),
+        ErrorCode::FeeCollectionAddressNotSet
+    );
+
+    // Calculate fee at the subscription's volume tier (e.g., 1% of payment amount)
+    let tier = select_fee_tier(&config.fee_config, subscription.total_paid);
+    let (platform_fee, merchant_amount) = split_payment_amount(subscription.amount, tier.fee_bps, &config.fee_config)?;
+
+    // Use subscription PDA as authority (subscriber must delegate to this PDA)
+    let (subscription_pda, bump) = Pubkey::find_program_address(
+        &[b"subscription", subscription.id.as_bytes()],
+        program_id
+    );
+    require!(
+        subscription_pda == subscription.key(),
+        ErrorCode::InvalidSubscriptionPDA
+    );
+
+    // Subscriber budget: reject before any state change or transfer
+    let (period_start, spent_in_period) = apply_spending_cap(
+        subscription.spending_cap_per_period,
+        subscription.period_start,
+        subscription.spent_in_period,
+        subscription.amount,
+        subscription.interval_seconds,
+        current_time,
+    )?;
+
+    // Revenue share: one token account per configured recipient, in order
+    let fee_config = &config.fee_config;
+    let recipients = &fee_config.fee_recipients[..fee_config.recipient_count as usize];
+    let split_fee = platform_fee > 0 && !recipients.is_empty();
+    if split_fee {
+        require!(
+            accounts.fee_recipient_accounts.len() >= recipients.len(),
+            ErrorCode::InvalidFeeRecipientAccount
+        );
+        for (recipient, recipient_account) in recipients.iter().zip(accounts.fee_recipient_accounts.iter()) {
+            let recipient_token_account =
+                TokenAccount::try_deserialize(&mut &recipient_account.try_borrow_data()?[..])?;
+            require!(
+                recipient_account.owner == &accounts.token_program.key()
+                    && recipient_token_account.owner == recipient.address
+                    && recipient_token_account.mint == accounts.payment_mint.key(),
+                ErrorCode::InvalidFeeRecipientAccount
+            );
+        }
+    }
+
+    // This cycle's nonce must still be free
+    let cycle = subscription.payments_made;
+    require!(accounts.payment_nonce.owner != program_id, ErrorCode::DuplicatePayment);
+    let (nonce_pda, _) = Pubkey::find_program_address(
+        &[b"payment_nonce", subscription.id.as_bytes(), &cycle.to_le_bytes()],
+        program_id
+    );
+    require!(nonce_pda == accounts.payment_nonce.key(), ErrorCode::InvalidSubscriptionPDA);
+
+    // Optional PDAs are checked up front too, so nothing fails after the transfers
+    let close_previous_nonce = match accounts.previous_payment_nonce {
+        Some(previous_nonce) if cycle > 0 && previous_nonce.owner == program_id => {
+            let (previous_nonce_pda, _) = Pubkey::find_program_address(
+                &[b"payment_nonce", subscription.id.as_bytes(), &(cycle - 1).to_le_bytes()],
+                program_id
+            );
+            require!(previous_nonce.key() == previous_nonce_pda, ErrorCode::InvalidSubscriptionPDA);
+            true
+        }
+        _ => false,
+    };
+    let (dashboard_pda, _) = Pubkey::find_program_address(
+        &[b"merchant_dashboard", subscription.merchant.as_ref()],
+        program_id
+    );
+    require!(dashboard_pda == accounts.merchant_dashboard.key(), ErrorCode::InvalidMerchantAddress);
+    if let Some(reputation) = accounts.subscriber_reputation {
+        let (reputation_pda, _) = Pubkey::find_program_address(
+            &[b"reputation", subscription.subscriber.as_ref()],
+            program_id
+        );
+        require!(reputation_pda == reputation.key(), ErrorCode::InvalidReputationAccount);
+    }
+
+    Ok(Settlement {
+        tier,
+        platform_fee,
+        merchant_amount,
+        bump,
+        split_fee,
+        period_start,
+        spent_in_period,
+        close_previous_nonce,
+    })
+}
+
+/// Charge the subscription's current cycle once it has been authorized and validated
+/// Creates the cycle's PaymentNonce, splits the fee between the merchant and the fee recipients or
+/// fee vault, and records analytics, dashboard, reputation and PaymentProcessed.
+/// Returns (platform_fee, merchant_amount)
+pub fn settle_payment<'info>(
+    subscription: &mut Account<'info, Subscription>,
+    accounts: &SettlementAccounts<'_, 'info>,
+    settlement: Settlement,
+    fee_analytics: &mut FeeAnalytics,
+    program_id: &Pubkey,
+    current_time: i64,
+) -> Result<(u64, u64)> {
+    let Settlement {
+        tier,
+        platform_fee,
+        merchant_amount,
+        bump,
+        split_fee,
+        period_start,
+        spent_in_period,
+        close_previous_nonce,
+    } = settlement;
+
+    let subscription_id = subscription.id.clone();
+    let seeds = &[
+        b"subscription".as_ref(),
+        subscription_id.as_bytes(),
+        &[bump],
+    ];
+    let signer_seeds = &[&seeds[..]];
+
+    subscription.period_start = period_start;
+    subscription.spent_in_period = spent_in_period;
+
+    let fee_config = &accounts.config.fee_config;
+    let recipients = &fee_config.fee_recipients[..fee_config.recipient_count as usize];
+    let cycle = subscription.payments_made;
+
+    // Deduplication: one nonce per payment cycle, a retried trigger for the same cycle fails here
+    create_payment_nonce(
+        accounts.payment_nonce,
+        &NonceAccounts { payer: accounts.payer, system_program: accounts.system_program, program_id },
+        &subscription_id,
+        subscription.key(),
+        cycle,
+        current_time,
+    )?;
+
+    // Timing is judged against the due date of this cycle, before record_payment moves it
+    let timing = classify_payment_timing(
+        current_time,
+        subscription.next_payment_time,
+        subscription.grace_period_seconds,
+    );
+
+    // EFFECTS: Update subscription state BEFORE external calls (CEI pattern)
+    record_payment(subscription, current_time)?;
+
+    // The previous cycle's nonce is no longer needed once this cycle is charged (rent back to the payer)
+    if let Some(previous_nonce) = accounts.previous_payment_nonce.filter(|_| close_previous_nonce) {
+        close_program_account(&previous_nonce.to_account_info(), &accounts.payer.to_account_info())?;
+    }
+
+    // INTERACTIONS: External token transfers AFTER state updates (CEI pattern)
+    // Transfer merchant_amount to the merchant, then platform_fee to the fee recipients or fee vault
+    let subscription_account_info = subscription.to_account_info();
+    transfer_from_subscriber(
+        subscription.use_token_2022,
+        accounts.subscriber_token_account,
+        accounts.merchant_destination,
+        accounts.payment_mint,
+        &subscription_account_info,
+        accounts.token_program,
+        signer_seeds,
+        merchant_amount,
+    )?;
+
+    msg!("Transferred {} micro-USDC to merchant", merchant_amount);
+
+    if split_fee {
+        let shares = calculate_fee_shares(platform_fee, recipients)?;
+        let mut distributed = Vec::with_capacity(recipients.len());
+
+        for ((recipient, share), recipient_account) in recipients
+            .iter()
+            .zip(shares)
+            .zip(accounts.fee_recipient_accounts.iter())
+        {
+            if share > 0 {
+                transfer_from_subscriber(
+                    subscription.use_token_2022,
+                    accounts.subscriber_token_account,
+                    recipient_account,
+                    accounts.payment_mint,
+                    &subscription_account_info,
+                    accounts.token_program,
+                    signer_seeds,
+                    share,
+                )?;
+            }
+
+            distributed.push(FeeShare {
+                recipient: recipient.address,
+                amount: share,
+            });
+        }
+
+        msg!("Distributed {} micro-USDC fee among {} recipients", platform_fee, distributed.len());
+
+        emit!(FeeDistributed {
+            subscription_id: subscription_id.clone(),
+            recipients: distributed,
+        });
+    } else if platform_fee > 0 {
+        // Single destination: accumulate in the fee vault, paid out in batches via withdraw_fees
+        transfer_from_subscriber(
+            subscription.use_token_2022,
+            accounts.subscriber_token_account,
+            &accounts.fee_vault.to_account_info(),
+            accounts.payment_mint,
+            &subscription_account_info,
+            accounts.token_program,
+            signer_seeds,
+            platform_fee,
+        )?;
+
+        subscription.vault_fees_paid = subscription.vault_fees_paid
+            .checked_add(platform_fee)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        msg!("Deposited {} micro-USDC fee into fee vault", platform_fee);
+    }
+
+    record_fee_analytics(fee_analytics, subscription.amount, platform_fee)?;
+
+    let dashboard = accounts.merchant_dashboard;
+    create_merchant_dashboard(dashboard, accounts.payer, accounts.system_program, &subscription.merchant, program_id)?;
+    let ended = subscription.status != SubscriptionStatus::Active;
+    update_merchant_dashboard(dashboard, &subscription.merchant, program_id, |d| {
+        apply_dashboard_payment(d, merchant_amount, platform_fee, current_time, ended)
+    })?;
+
+    if let Some(reputation) = accounts.subscriber_reputation {
+        record_subscriber_reputation(
+            reputation,
+            accounts.payer,
+            accounts.system_program,
+            &subscription.subscriber,
+            timing,
+            current_time,
+            program_id,
+        )?;
+    }
+
+    msg!(
+        "Payment #{} processed: total={}, merchant={}, platform_fee={}",
+        subscription.payments_made,
+        subscription.amount,
+        merchant_amount,
+        platform_fee
+    );
+
+    // Emit payment event
+    emit!(PaymentProcessed {
+        subscription_id,
+        payment_number: subscription.payments_made,
+        amount: subscription.amount,
+        merchant_amount,
+        fee_amount: platform_fee,
+        tier,
+        timestamp: current_time,
+    });
+
+    Ok((platform_fee, merchant_amount))
+}
+
+/// Transfer from the subscriber's token account using the subscription PDA as delegate authority
+/// Token-2022 mints go through transfer_checked so extensions (e.g. transfer fees) are enforced
+pub fn transfer_from_subscriber<'info>(
+    use_token_2022: bool,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    to: &AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    authority: &AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+) -> Result<()> {
+    if use_token_2022 {
+        let transfer_accounts = token_interface::TransferChecked {
+            from: from.to_account_info(),
+            mint: mint.to_account_info(),
+            to: to.clone(),
+            authority: authority.clone(),
+        };
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                transfer_accounts,
+                signer_seeds,
+            ),
+            amount,
+            mint.decimals,
+        )
+    } else {
+        let transfer_accounts = token::Transfer {
+            from: from.to_account_info(),
+            to: to.clone(),
+            authority: authority.clone(),
+        };
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                transfer_accounts,
+                signer_seeds,
+            ),
+            amount,
+        )
+    }
+}
+
+// Helper functions for process_trigger
+/// Opcode 0: charge into the merchant's escrow through the same settlement as process_payment
+/// `signed_timestamp` is set when an ICP signature authorized the trigger, and consumes its UsedNonce
+pub fn process_direct_usdc_payment<'info>(
+    ctx: Context<'_, '_, '_, 'info, crate::ProcessTrigger<'info>>,
+    signed_timestamp: Option<i64>,
+) -> Result<()> {
+    let program_id = ctx.program_id;
+    let accounts = ctx.accounts;
+    let subscription = &mut accounts.subscription;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(subscription.status != SubscriptionStatus::Expired, ErrorCode::SubscriptionExpired);
+    require!(subscription.status == SubscriptionStatus::Active, ErrorCode::SubscriptionNotActive);
+    verify_subscription_integrity(subscription)?;
+    require!(!subscription.disputed, ErrorCode::SubscriptionDisputed);
+
+    // Time-limited subscription reached its end: expire instead of charging
+    if expire_if_run_out(subscription, current_time) {
+        update_merchant_dashboard(&accounts.merchant_dashboard, &subscription.merchant, program_id, |d| {
+            d.active_subscriptions = d.active_subscriptions.saturating_sub(1);
+            Ok(())
+        })?;
+        return Ok(());
+    }
+
+    // Token program must match the one the subscription was created with
+    require!(
+        accounts.token_program.key() == get_token_program_id(subscription.use_token_2022),
+        ErrorCode::InvalidTokenProgram
+    );
+
+    // No billing while the free trial is running
+    require!(
+        !is_trial_active(subscription.trial_end_time, current_time),
+        ErrorCode::TrialPeriodActive
+    );
+
+    let payment_nonce = accounts.payment_nonce.as_ref().ok_or(ErrorCode::MissingPaymentAccounts)?;
+    let fee_vault = accounts.fee_vault.as_ref().ok_or(ErrorCode::MissingPaymentAccounts)?;
+
+    let merchant_destination = accounts.escrow_usdc_account.to_account_info();
+    let settlement_accounts = SettlementAccounts {
+        config: &accounts.config,
+        payer: &accounts.trigger_authority,
+        subscriber_token_account: &accounts.subscriber_token_account,
+        merchant_destination: &merchant_destination,
+        fee_vault,
+        payment_mint: &accounts.usdc_mint,
+        token_program: &accounts.token_program,
+        system_program: &accounts.system_program,
+        fee_recipient_accounts: ctx.remaining_accounts,
+        payment_nonce,
+        previous_payment_nonce: accounts.previous_payment_nonce.as_ref(),
+        merchant_dashboard: &accounts.merchant_dashboard,
+        subscriber_reputation: accounts.subscriber_reputation.as_ref(),
+    };
+    let settlement = validate_settlement(subscription, &settlement_accounts, program_id, current_time)?;
+
+    // Replay protection: a signature is single-use even inside the timestamp window
+    if let Some(signed_timestamp) = signed_timestamp {
+        create_used_nonce(
+            accounts.used_nonce.as_ref().ok_or(ErrorCode::MissingUsedNonce)?,
+            &NonceAccounts { payer: &accounts.trigger_authority, system_program: &accounts.system_program, program_id },
+            &subscription.id,
+            subscription.key(),
+            signed_timestamp,
+            current_time,
+        )?;
+    }
+
+    let (platform_fee, merchant_amount) = settle_payment(
+        subscription,
+        &settlement_accounts,
+        settlement,
+        &mut accounts.fee_analytics,
+        program_id,
+        current_time,
+    )?;
+
+    // The merchant share sits in escrow until claim_from_escrow
+    subscription.escrow_balance = subscription.escrow_balance
+        .checked_add(merchant_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    send_payment_memo(&accounts.memo_program.to_account_info(), &subscription.merchant_name, subscription.payments_made)?;
+
+    msg!("USDC payment processed to ESCROW: {} USDC (fee: {}, escrow: {}, escrow_balance: {})",
+        subscription.amount, platform_fee, merchant_amount, subscription.escrow_balance);
+
+    Ok(())
+}
+
+/// Delegation readiness for DApp UIs: whether the subscription PDA is the delegate and how many
+/// payments the remaining allowance covers (months assume 30-day months; 0 for one-time payments)
+pub fn delegation_status(
+    subscription_id: &str,
+    amount: u64,
+    interval_seconds: i64,
+    subscription_key: &Pubkey,
+    delegate: Option<Pubkey>,
+    delegated_amount: u64,
+) -> DelegationStatusChecked {
+    let is_delegate_correct = delegate == Some(*subscription_key);
+    let delegated_amount = if is_delegate_correct { delegated_amount } else { 0 };
+    let payments_remaining = delegated_amount / amount.max(1);
+    let months_remaining = if interval_seconds > 0 {
+        (payments_remaining as f64 * interval_seconds as f64 / (30.0 * 24.0 * 60.0 * 60.0)) as f32
+    } else {
+        0.0
+    };
+
+    DelegationStatusChecked {
+        subscription_id: subscription_id.to_string(),
+        is_delegate_correct,
+        delegated_amount,
+        required_amount: amount,
+        payments_remaining,
+        months_remaining,
+    }
+}
+
+/// Memo attached to payments so a subscriber's explorer history shows what was charged
+pub fn payment_memo(merchant_name: &str, payment_number: u64) -> String {
+    format!("OuroC: {} #{}", merchant_name, payment_number)
+}
+
+/// Add the payment memo to the current transaction (no signers needed)
+pub fn send_payment_memo<'info>(memo_program: &AccountInfo<'info>, merchant_name: &str, payment_number: u64) -> Result<()> {
+    let memo = payment_memo(merchant_name, payment_number);
+    let memo_ix = spl_memo::build_memo(memo.as_bytes(), &[]);
+    anchor_lang::solana_program::program::invoke(&memo_ix, std::slice::from_ref(memo_program))?;
+    Ok(())
+}
+
+pub fn send_notification_internal(ctx: Context<crate::ProcessTrigger>, memo: String) -> Result<()> {
+    require!(memo.len() <= 566, ErrorCode::MemoTooLong);
+
+    // 1. Transfer tiny SOL amount (0.000001 SOL = 1000 lamports)
+    let notification_amount = 1000u64;
+
+    let transfer_ix = anchor_lang::solana_program::system_instruction::transfer(
+        &ctx.accounts.trigger_authority.key(),
+        &ctx.accounts.subscriber.key(),
+        notification_amount,
+    );
+
+    anchor_lang::solana_program::program::invoke(
+        &transfer_ix,
+        &[
+            ctx.accounts.trigger_authority.to_account_info(),
+            ctx.accounts.subscriber.to_account_info(),
+        ],
+    )?;
+
+    // 2. Add SPL Memo instruction to make message visible in wallets
+    let memo_ix = spl_memo::build_memo(
+        memo.as_bytes(),
+        &[&ctx.accounts.trigger_authority.key()],
+    );
+
     anchor_lang::solana_program::program::invoke(
         &memo_ix,
         &[
@@ -377,4 +1886,1014 @@ pub fn send_notification_internal(ctx: Context<crate::ProcessTrigger>, memo: Str
 
     msg!("Notification sent with memo: {}", memo);
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payment_due_without_grace_period() {
+        assert!(!is_payment_due(999, 1_000, 0));
+        assert!(is_payment_due(1_000, 1_000, 0));
+        assert!(is_payment_due(1_001, 1_000, 0));
+    }
+
+    #[test]
+    fn test_trigger_within_grace_period_succeeds() {
+        // Trigger fires inside the grace window
+        assert!(is_payment_due(995, 1_000, 10));
+        assert!(is_payment_due(990, 1_000, 10));
+        // Outside the grace window is still rejected
+        assert!(!is_payment_due(989, 1_000, 10));
+    }
+
+    #[test]
+    fn test_grace_period_does_not_overflow() {
+        assert!(is_payment_due(i64::MIN, i64::MIN, i64::MAX));
+    }
+
+    #[test]
+    fn test_no_payment_during_trial() {
+        let trial_end = 1_000;
+        assert!(is_trial_active(Some(trial_end), 0));
+        assert!(is_trial_active(Some(trial_end), trial_end - 1));
+    }
+
+    #[test]
+    fn test_payment_allowed_immediately_after_trial() {
+        let trial_end = 1_000;
+        // next_payment_time is set to trial_end at creation
+        assert!(!is_trial_active(Some(trial_end), trial_end));
+        assert!(is_payment_due(trial_end, trial_end, 0));
+        assert!(!is_trial_active(None, 0));
+    }
+
+    #[test]
+    fn test_partial_refund() {
+        assert_eq!(apply_refund(10_000_000, 4_000_000).unwrap(), 6_000_000);
+        // A second partial refund draws down the remainder
+        assert_eq!(apply_refund(6_000_000, 1_000_000).unwrap(), 5_000_000);
+    }
+
+    #[test]
+    fn test_full_refund() {
+        assert_eq!(apply_refund(10_000_000, 10_000_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_refund_exceeding_total_paid_rejected() {
+        assert!(apply_refund(10_000_000, 10_000_001).is_err());
+        assert!(apply_refund(0, 1).is_err());
+        assert!(apply_refund(10_000_000, 0).is_err());
+    }
+
+    #[test]
+    fn test_installments_count_down_to_final_payment() {
+        // "4 payments of $25"
+        let mut remaining = Some(4);
+        for expected in [3, 2, 1] {
+            remaining = decrement_remaining_payments(remaining).unwrap();
+            assert_eq!(remaining, Some(expected));
+        }
+        // Final payment lands exactly on zero, which triggers auto-cancel
+        remaining = decrement_remaining_payments(remaining).unwrap();
+        assert_eq!(remaining, Some(0));
+    }
+
+    #[test]
+    fn test_single_installment_plan() {
+        assert_eq!(decrement_remaining_payments(Some(1)).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn test_no_payment_past_final_installment() {
+        assert!(decrement_remaining_payments(Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_open_ended_subscription_has_no_installments() {
+        assert_eq!(decrement_remaining_payments(None).unwrap(), None);
+    }
+
+    /// Build a mock Token-2022 mint with the transfer fee extension enabled
+    fn mock_token_2022_mint_with_transfer_fee(decimals: u8, fee_basis_points: u16) -> Vec<u8> {
+        use anchor_lang::solana_program::program_option::COption;
+        use anchor_spl::token_2022::spl_token_2022::extension::{
+            transfer_fee::{TransferFee, TransferFeeConfig},
+            BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+        };
+        use anchor_spl::token_2022::spl_token_2022::state::Mint as MintState;
+
+        let space = ExtensionType::try_calculate_account_len::<MintState>(&[
+            ExtensionType::TransferFeeConfig,
+        ])
+        .unwrap();
+        let mut data = vec![0u8; space];
+        let mut state = StateWithExtensionsMut::<MintState>::unpack_uninitialized(&mut data).unwrap();
+
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: fee_basis_points.into(),
+        };
+        let extension = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        extension.older_transfer_fee = transfer_fee;
+        extension.newer_transfer_fee = transfer_fee;
+
+        state.base = MintState {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            supply: 1_000_000_000_000,
+            decimals,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    #[test]
+    fn test_token_2022_mint_with_transfer_fee_is_accepted() {
+        let data = mock_token_2022_mint_with_transfer_fee(6, 50);
+
+        // Interface mint deserializes Token-2022 mints carrying extensions
+        let mint = Mint::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(mint.decimals, 6);
+        assert!(mint.is_initialized);
+
+        // InterfaceAccount owner check accepts the Token-2022 program
+        assert!(<Mint as anchor_lang::Owners>::owners().contains(&anchor_spl::token_2022::ID));
+    }
+
+    #[test]
+    fn test_token_program_routing() {
+        assert_eq!(get_token_program_id(false), anchor_spl::token::ID);
+        assert_eq!(get_token_program_id(true), anchor_spl::token_2022::ID);
+    }
+
+    #[test]
+    fn test_split_payment_amount() {
+        let fee_config = FeeConfig {
+            fee_percentage_basis_points: 200,
+            min_fee_amount: 1000,
+            max_fee_amount: u64::MAX,
+            fee_recipients: [FeeRecipient::default(); MAX_FEE_RECIPIENTS],
+            recipient_count: 0,
+            fee_tiers: [FeeTier::default(); MAX_FEE_TIERS],
+            tier_count: 0,
+        };
+        assert_eq!(split_payment_amount(10_000_000, fee_config.fee_percentage_basis_points, &fee_config).unwrap(), (200_000, 9_800_000));
+        assert_eq!(split_payment_amount(1, fee_config.fee_percentage_basis_points, &fee_config).unwrap(), (0, 1));
+        // 2% of $0.01 is below the $0.001 floor, same as the direct process_trigger path
+        assert_eq!(split_payment_amount(10_000, fee_config.fee_percentage_basis_points, &fee_config).unwrap(), (1000, 9000));
+        assert!(split_payment_amount(u64::MAX, fee_config.fee_percentage_basis_points, &fee_config).is_err());
+    }
+
+    #[test]
+    fn test_fee_never_exceeds_max_fee_amount() {
+        let fee_config = FeeConfig {
+            fee_percentage_basis_points: 100,
+            min_fee_amount: 1000,
+            max_fee_amount: 100_000_000, // $100 cap
+            fee_recipients: [FeeRecipient::default(); MAX_FEE_RECIPIENTS],
+            recipient_count: 0,
+            fee_tiers: [FeeTier::default(); MAX_FEE_TIERS],
+            tier_count: 0,
+        };
+        // $1M at 1% would be $10,000 without the cap
+        assert_eq!(
+            split_payment_amount(1_000_000_000_000, fee_config.fee_percentage_basis_points, &fee_config).unwrap(),
+            (100_000_000, 999_900_000_000)
+        );
+
+        // Property: across the whole valid range the fee is capped and the split is lossless
+        let max_amount = u64::MAX / BASIS_POINTS_DIVISOR;
+        let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut amounts = vec![1, max_amount];
+        for _ in 0..10_000 {
+            // xorshift64 - deterministic pseudo-random amounts
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            amounts.push(seed % max_amount + 1);
+        }
+        for amount in amounts {
+            let (fee, merchant) = split_payment_amount(amount, fee_config.fee_percentage_basis_points, &fee_config).unwrap();
+            assert!(fee <= fee_config.max_fee_amount);
+            assert_eq!(fee + merchant, amount);
+        }
+    }
+
+    #[test]
+    fn test_first_notification_allowed() {
+        assert!(is_notification_allowed(None, 1_700_000_000, 3600));
+    }
+
+    #[test]
+    fn test_notification_cooldown_enforced() {
+        let last = 1_700_000_000;
+        assert!(!is_notification_allowed(Some(last), last, 3600));
+        assert!(!is_notification_allowed(Some(last), last + 3599, 3600));
+        assert!(is_notification_allowed(Some(last), last + 3600, 3600));
+    }
+
+    #[test]
+    fn test_resume_halfway_keeps_half_interval() {
+        let interval = 30 * 24 * 60 * 60;
+        let next_payment_time = 1_700_000_000 + interval;
+        // Paused halfway through the interval
+        let paused_at = next_payment_time - interval / 2;
+        // Resumed ten days later
+        let resumed_at = paused_at + 10 * 24 * 60 * 60;
+
+        let resumed_next = calculate_resume_payment_time(next_payment_time, Some(paused_at), resumed_at).unwrap();
+        assert_eq!(resumed_next - resumed_at, interval / 2);
+    }
+
+    #[test]
+    fn test_resume_when_paused_after_due_has_no_credit() {
+        let next_payment_time = 1_700_000_000;
+        let resumed_at = next_payment_time + 500;
+        assert_eq!(
+            calculate_resume_payment_time(next_payment_time, Some(next_payment_time + 100), resumed_at).unwrap(),
+            resumed_at
+        );
+        // Legacy subscriptions paused without paused_at resume as due immediately
+        assert_eq!(calculate_resume_payment_time(next_payment_time, None, resumed_at).unwrap(), resumed_at);
+    }
+
+    fn recipient(share_bps: u16) -> FeeRecipient {
+        FeeRecipient { address: Pubkey::new_unique(), share_bps }
+    }
+
+    #[test]
+    fn test_fee_recipients_must_sum_to_full_share() {
+        assert!(build_fee_recipients(&[recipient(7000), recipient(3000)]).is_ok());
+        assert!(build_fee_recipients(&[recipient(7000), recipient(2000)]).is_err());
+        assert!(build_fee_recipients(&[recipient(10000), recipient(0)]).is_err());
+        assert!(build_fee_recipients(&[recipient(2000); 5]).is_err());
+
+        let (packed, count) = build_fee_recipients(&[]).unwrap();
+        assert_eq!(count, 0);
+        assert_eq!(packed, [FeeRecipient::default(); MAX_FEE_RECIPIENTS]);
+    }
+
+    #[test]
+    fn test_fee_shares_distribute_whole_fee() {
+        let recipients = [recipient(5000), recipient(3333), recipient(1667)];
+        let shares = calculate_fee_shares(200_001, &recipients).unwrap();
+        assert_eq!(shares, vec![100_000, 66_660, 33_341]);
+        assert_eq!(shares.iter().sum::<u64>(), 200_001);
+    }
+
+    #[test]
+    fn test_subscription_index_entries() {
+        let key = subscription_id_key("sub_123");
+        assert_eq!(&key[..7], b"sub_123");
+        assert!(key[7..].iter().all(|b| *b == 0));
+
+        let mut ids = Vec::new();
+        assert_eq!(insert_index_entry(&mut ids, key).unwrap(), 1);
+        assert_eq!(insert_index_entry(&mut ids, key).unwrap(), 1); // Already indexed
+        assert_eq!(remove_index_entry(&mut ids, &subscription_id_key("other")), 1);
+        assert_eq!(remove_index_entry(&mut ids, &key), 0);
+
+        for i in 0..MAX_INDEXED_SUBSCRIPTIONS {
+            insert_index_entry(&mut ids, subscription_id_key(&format!("sub_{}", i))).unwrap();
+        }
+        assert!(insert_index_entry(&mut ids, key).is_err());
+        assert_eq!(ids.len(), MAX_INDEXED_SUBSCRIPTIONS);
+    }
+
+    #[test]
+    fn test_delegation_status() {
+        let key = Pubkey::new_unique();
+        let monthly = 30 * 24 * 60 * 60;
+
+        let status = delegation_status("sub_1", 10_000_000, monthly, &key, Some(key), 125_000_000);
+        assert!(status.is_delegate_correct);
+        assert_eq!(status.delegated_amount, 125_000_000);
+        assert_eq!(status.required_amount, 10_000_000);
+        assert_eq!(status.payments_remaining, 12);
+        assert_eq!(status.months_remaining, 12.0);
+
+        // Allowance for some other delegate doesn't count
+        let status = delegation_status("sub_1", 10_000_000, monthly, &key, Some(Pubkey::new_unique()), 125_000_000);
+        assert!(!status.is_delegate_correct);
+        assert_eq!(status.payments_remaining, 0);
+        assert!(!delegation_status("sub_1", 10_000_000, monthly, &key, None, 0).is_delegate_correct);
+
+        // Weekly billing: 4 payments is 28 days
+        let status = delegation_status("sub_1", 10_000_000, 7 * 24 * 60 * 60, &key, Some(key), 40_000_000);
+        assert_eq!(status.payments_remaining, 4);
+        assert!((status.months_remaining - 28.0 / 30.0).abs() < 1e-6);
+
+        assert_eq!(delegation_status("sub_1", 10_000_000, -1, &key, Some(key), 10_000_000).months_remaining, 0.0);
+    }
+
+    #[test]
+    fn test_classify_payment_timing() {
+        assert_eq!(classify_payment_timing(900, 1_000, 0), PaymentOutcome::OnTime); // Early manual payment
+        assert_eq!(classify_payment_timing(1_000, 1_000, 0), PaymentOutcome::OnTime);
+        assert_eq!(classify_payment_timing(1_001, 1_000, 0), PaymentOutcome::Late);
+        assert_eq!(classify_payment_timing(1_300, 1_000, 300), PaymentOutcome::OnTime);
+        assert_eq!(classify_payment_timing(1_301, 1_000, 300), PaymentOutcome::Late);
+        assert_eq!(classify_payment_timing(i64::MAX, i64::MAX, i64::MAX), PaymentOutcome::OnTime);
+    }
+
+    #[test]
+    fn test_apply_reputation_outcome() {
+        let mut reputation = SubscriberReputation {
+            subscriber: Pubkey::new_unique(),
+            score: REPUTATION_INITIAL_SCORE,
+            on_time_payments: 0,
+            late_payments: 0,
+            failed_payments: 0,
+            last_updated: 0,
+        };
+
+        apply_reputation_outcome(&mut reputation, PaymentOutcome::OnTime, 10);
+        apply_reputation_outcome(&mut reputation, PaymentOutcome::Late, 20);
+        apply_reputation_outcome(&mut reputation, PaymentOutcome::Failed, 30);
+        assert_eq!(reputation.score, 498);
+        assert_eq!((reputation.on_time_payments, reputation.late_payments, reputation.failed_payments), (1, 1, 1));
+        assert_eq!(reputation.last_updated, 30);
+
+        // Score stays within 0..=REPUTATION_MAX_SCORE
+        reputation.score = 5;
+        apply_reputation_outcome(&mut reputation, PaymentOutcome::Failed, 40);
+        assert_eq!(reputation.score, 0);
+        reputation.score = REPUTATION_MAX_SCORE - 1;
+        apply_reputation_outcome(&mut reputation, PaymentOutcome::OnTime, 50);
+        assert_eq!(reputation.score, REPUTATION_MAX_SCORE);
+    }
+
+    #[test]
+    fn test_apply_dashboard_payment() {
+        let mut dashboard = MerchantDashboard {
+            merchant: Pubkey::new_unique(),
+            active_subscriptions: 2,
+            total_revenue_usdc: 0,
+            total_fees_paid: 0,
+            last_payment: None,
+            failed_payments: 0,
+        };
+
+        apply_dashboard_payment(&mut dashboard, 9_800_000, 200_000, 1_000, false).unwrap();
+        apply_dashboard_payment(&mut dashboard, 9_800_000, 200_000, 2_000, true).unwrap();
+        assert_eq!(dashboard.total_revenue_usdc, 19_600_000);
+        assert_eq!(dashboard.total_fees_paid, 400_000);
+        assert_eq!(dashboard.last_payment, Some(2_000));
+        assert_eq!(dashboard.active_subscriptions, 1); // The second payment finished its subscription
+
+        dashboard.active_subscriptions = 0;
+        apply_dashboard_payment(&mut dashboard, 1, 0, 3_000, true).unwrap();
+        assert_eq!(dashboard.active_subscriptions, 0);
+
+        dashboard.total_revenue_usdc = u64::MAX;
+        assert!(apply_dashboard_payment(&mut dashboard, 1, 0, 4_000, false).is_err());
+    }
+
+    #[test]
+    fn test_payment_memo() {
+        assert_eq!(payment_memo("Acme Streaming", 3), "OuroC: Acme Streaming #3");
+        // Longest merchant name and payment number stay well under the 566-byte memo limit
+        assert!(payment_memo(&"m".repeat(32), u64::MAX).len() <= 566);
+    }
+
+    #[test]
+    fn test_close_cooldown() {
+        let created_at = 1_700_000_000;
+        assert!(!is_close_allowed(created_at, created_at));
+        assert!(!is_close_allowed(created_at, created_at + CLOSE_COOLDOWN_SECONDS - 1));
+        assert!(is_close_allowed(created_at, created_at + CLOSE_COOLDOWN_SECONDS));
+        assert!(!is_close_allowed(i64::MAX, i64::MAX - 1));
+    }
+
+    #[test]
+    fn test_transfer_timelock() {
+        let proposed_at = 1_700_000_000;
+        assert!(!is_transfer_unlocked(proposed_at, proposed_at));
+        assert!(!is_transfer_unlocked(proposed_at, proposed_at + SUBSCRIPTION_TRANSFER_TIMELOCK_SECONDS - 1));
+        assert!(is_transfer_unlocked(proposed_at, proposed_at + SUBSCRIPTION_TRANSFER_TIMELOCK_SECONDS));
+    }
+
+    #[test]
+    fn test_merchant_change_timelock() {
+        let proposed_at = 1_700_000_000;
+        assert!(!is_merchant_change_unlocked(proposed_at, proposed_at + SUBSCRIPTION_TRANSFER_TIMELOCK_SECONDS));
+        assert!(!is_merchant_change_unlocked(proposed_at, proposed_at + MERCHANT_CHANGE_TIMELOCK_SECONDS - 1));
+        assert!(is_merchant_change_unlocked(proposed_at, proposed_at + MERCHANT_CHANGE_TIMELOCK_SECONDS));
+    }
+
+    #[test]
+    fn test_integrity_hash_detects_direct_modification() {
+        // All-zero bytes decode to a valid Subscription (empty strings, first enum variants, None)
+        let mut subscription = Subscription::deserialize(&mut &[0u8; Subscription::LEN][..]).unwrap();
+        subscription.subscriber = Pubkey::new_unique();
+        subscription.merchant = Pubkey::new_unique();
+        subscription.amount = 10_000_000;
+        subscription.interval_seconds = 2_592_000;
+        assert!(verify_subscription_integrity(&subscription).is_err()); // Hash never set
+
+        refresh_integrity_hash(&mut subscription);
+        assert!(verify_subscription_integrity(&subscription).is_ok());
+
+        // Each covered field is detected on its own
+        subscription.amount += 1;
+        assert!(verify_subscription_integrity(&subscription).is_err());
+        subscription.amount -= 1;
+        subscription.interval_seconds = 60;
+        assert!(verify_subscription_integrity(&subscription).is_err());
+        subscription.interval_seconds = 2_592_000;
+        subscription.merchant = Pubkey::new_unique();
+        assert!(verify_subscription_integrity(&subscription).is_err());
+
+        // Authorized changes re-hash and pass again
+        refresh_integrity_hash(&mut subscription);
+        assert!(verify_subscription_integrity(&subscription).is_ok());
+    }
+
+    #[test]
+    fn test_change_proposal_expiry() {
+        let expires_at = 1_700_000_000 + CHANGE_PROPOSAL_TTL_SECONDS;
+        assert!(!is_change_proposal_expired(expires_at, expires_at));
+        assert!(is_change_proposal_expired(expires_at, expires_at + 1));
+    }
+
+    #[test]
+    fn test_price_staleness() {
+        let now = 1_700_000_000;
+        // Mainnet default (60s)
+        assert!(!is_price_stale(now - 60, now, DEFAULT_MAX_PRICE_AGE_SECONDS));
+        assert!(is_price_stale(now - 61, now, DEFAULT_MAX_PRICE_AGE_SECONDS));
+        // Devnet feeds update less often - a wider window accepts older prices
+        assert!(!is_price_stale(now - 300, now, 300));
+        assert!(is_price_stale(now - 301, now, 300));
+        // Publish time slightly ahead of the validator clock
+        assert!(!is_price_stale(now + 2, now, DEFAULT_MAX_PRICE_AGE_SECONDS));
+        assert!(!is_price_stale(now + MAX_PRICE_FUTURE_SKEW_SECONDS as i64, now, DEFAULT_MAX_PRICE_AGE_SECONDS));
+        // A publish time far ahead of the clock can't be trusted as fresh
+        assert!(is_price_stale(now + MAX_PRICE_FUTURE_SKEW_SECONDS as i64 + 1, now, DEFAULT_MAX_PRICE_AGE_SECONDS));
+    }
+
+    fn switchboard_account(num_success: u32, min_oracle_results: u32, mantissa: i128, scale: u32) -> Vec<u8> {
+        let round = SWITCHBOARD_LATEST_ROUND_OFFSET;
+        let mut data = vec![0u8; round + 64];
+        data[..8].copy_from_slice(&SWITCHBOARD_AGGREGATOR_DISCRIMINATOR);
+        data[SWITCHBOARD_MIN_ORACLE_RESULTS_OFFSET..SWITCHBOARD_MIN_ORACLE_RESULTS_OFFSET + 4]
+            .copy_from_slice(&min_oracle_results.to_le_bytes());
+        data[round..round + 4].copy_from_slice(&num_success.to_le_bytes());
+        data[round + 17..round + 25].copy_from_slice(&1_700_000_000i64.to_le_bytes());
+        data[round + 25..round + 41].copy_from_slice(&mantissa.to_le_bytes());
+        data[round + 41..round + 45].copy_from_slice(&scale.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_switchboard_aggregator_parsing() {
+        assert_eq!(
+            SWITCHBOARD_AGGREGATOR_DISCRIMINATOR,
+            anchor_lang::solana_program::hash::hash(b"account:AggregatorAccountData").to_bytes()[..8]
+        );
+
+        let data = switchboard_account(3, 2, 1_000_150_000, 9);
+        assert_eq!(
+            parse_switchboard_aggregator(&data),
+            Some(SwitchboardRound { mantissa: 1_000_150_000, scale: 9, round_open_timestamp: 1_700_000_000 })
+        );
+        // Not enough oracle responses for a confirmed result
+        assert_eq!(parse_switchboard_aggregator(&switchboard_account(1, 2, 1_000_150_000, 9)), None);
+        // Not an aggregator account
+        let mut foreign = data.clone();
+        foreign[0] ^= 1;
+        assert_eq!(parse_switchboard_aggregator(&foreign), None);
+        assert_eq!(parse_switchboard_aggregator(&data[..100]), None);
+
+        assert_eq!(normalize_switchboard_price(1_000_150_000, 9), Some(100_015_000));
+        assert_eq!(normalize_switchboard_price(108, 2), Some(108_000_000));
+        assert_eq!(normalize_switchboard_price(i128::MAX, 0), None);
+    }
+
+    #[test]
+    fn test_conservative_output_amount_min() {
+        // 1% slippage -> 2% haircut
+        assert_eq!(conservative_output_amount_min(10_000_000, 100), 9_800_000);
+        assert_eq!(conservative_output_amount_min(10_000_000, 0), 10_000_000);
+        // Slippage above 50% leaves no guaranteed output
+        assert_eq!(conservative_output_amount_min(10_000_000, 6_000), 0);
+    }
+
+    #[test]
+    fn test_disputable_payment() {
+        assert!(is_disputable_payment(1, 3, 0));
+        assert!(is_disputable_payment(3, 3, 0));
+        assert!(!is_disputable_payment(0, 3, 0));
+        assert!(!is_disputable_payment(4, 3, 0)); // Not charged yet
+        assert!(!is_disputable_payment(1, 0, 0));
+
+        // Once payment #2 is ruled on, it and earlier charges can't be disputed again
+        assert!(!is_disputable_payment(2, 3, 2));
+        assert!(!is_disputable_payment(1, 3, 2));
+        assert!(is_disputable_payment(3, 3, 2));
+    }
+
+    #[test]
+    fn test_dispute_refund_bounded_by_vault_fees() {
+        assert_eq!(dispute_refund_amount(10_000_000, 50_000), 50_000);
+        assert_eq!(dispute_refund_amount(10_000_000, 30_000_000), 10_000_000);
+        assert_eq!(dispute_refund_amount(10_000_000, 0), 0);
+    }
+
+    #[test]
+    fn test_expiry_boundary() {
+        let expires_at = 1_700_000_000;
+        assert!(!is_expired(None, expires_at));
+        assert!(!is_expired(Some(expires_at), expires_at - 1));
+        assert!(is_expired(Some(expires_at), expires_at));
+        assert!(is_expired(Some(expires_at), expires_at + 1));
+    }
+
+    #[test]
+    fn test_run_out_subscription_is_expired() {
+        let mut subscription = migrate_legacy_subscription(LegacySubscription {
+            id: "sub_1".to_string(),
+            subscriber: Pubkey::new_unique(),
+            merchant: Pubkey::new_unique(),
+            merchant_name: "Merchant".to_string(),
+            amount: 10_000_000,
+            interval_seconds: 2_592_000,
+            next_payment_time: 1_700_000_000,
+            status: SubscriptionStatus::Active,
+            created_at: 1_690_000_000,
+            last_payment_time: None,
+            payments_made: 0,
+            total_paid: 0,
+            icp_canister_signature: [0u8; 64],
+            reminder_days_before_payment: 3,
+            escrow_pda: Pubkey::new_unique(),
+            escrow_balance: 0,
+        });
+        assert!(!has_run_out(&subscription, 1_700_000_000));
+
+        subscription.expires_at = Some(1_700_000_000);
+        assert!(has_run_out(&subscription, 1_700_000_000));
+        assert!(expire_if_run_out(&mut subscription, 1_700_000_000));
+        assert_eq!(subscription.status, SubscriptionStatus::Expired);
+        subscription.status = SubscriptionStatus::Active;
+
+        // Schedule that can't advance past i64::MAX has run out as well
+        subscription.expires_at = None;
+        subscription.next_payment_time = i64::MAX - 10;
+        assert!(has_run_out(&subscription, i64::MAX - 10));
+    }
+
+    /// Ed25519Program instruction data as built by the ICP canister client:
+    /// pubkey at 16, signature at 48, message at 112, all in the same instruction
+    fn ed25519_ix_data(pubkey: &[u8; 32], signature: &[u8; 64], message: &[u8]) -> Vec<u8> {
+        let mut data = vec![1u8, 0];
+        for value in [48u16, u16::MAX, 16, u16::MAX, 112, message.len() as u16, u16::MAX] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(pubkey);
+        data.extend_from_slice(signature);
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn test_ed25519_precompile_data_verification() {
+        // RFC 8032 test vector 2 (single-byte message 0x72)
+        let pubkey: [u8; 32] = hex::decode("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c")
+            .unwrap().try_into().unwrap();
+        let signature: [u8; 64] = hex::decode(
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+             085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00"
+        ).unwrap().try_into().unwrap();
+        let message = [0x72u8];
+
+        let data = ed25519_ix_data(&pubkey, &signature, &message);
+        assert!(verify_ed25519_instruction_data(&data, &pubkey, &message).unwrap());
+
+        // Wrong key or message
+        assert!(verify_ed25519_instruction_data(&data, &[0u8; 32], &message).is_err());
+        assert!(verify_ed25519_instruction_data(&data, &pubkey, b"other").is_err());
+
+        // Public key pulled from another instruction is rejected
+        let mut cross_ix = data.clone();
+        cross_ix[8..10].copy_from_slice(&0u16.to_le_bytes());
+        assert!(verify_ed25519_instruction_data(&cross_ix, &pubkey, &message).is_err());
+
+        // Truncated data
+        assert!(verify_ed25519_instruction_data(&data[..100], &pubkey, &message).is_err());
+    }
+
+    #[test]
+    fn test_icp_key_allowlist_stays_compact() {
+        let mut keys = [[0u8; 32]; MAX_ICP_PUBLIC_KEYS];
+        let mut count = 0u8;
+        for i in 1..=MAX_ICP_PUBLIC_KEYS as u8 {
+            add_icp_key(&mut keys, &mut count, [i; 32]).unwrap();
+        }
+        assert_eq!(count as usize, MAX_ICP_PUBLIC_KEYS);
+        assert!(add_icp_key(&mut keys, &mut count, [99; 32]).is_err()); // Full
+
+        remove_icp_key(&mut keys, &mut count, [3; 32]).unwrap();
+        assert_eq!(count, 7);
+        assert_eq!(&keys[..3], &[[1; 32], [2; 32], [4; 32]]);
+        assert_eq!(keys[7], [0u8; 32]);
+        assert!(remove_icp_key(&mut keys, &mut count, [3; 32]).is_err()); // Not found
+
+        assert!(add_icp_key(&mut keys, &mut count, [1; 32]).is_err()); // Duplicate
+        add_icp_key(&mut keys, &mut count, [3; 32]).unwrap();
+        assert_eq!(keys[7], [3; 32]);
+    }
+
+    #[test]
+    fn test_authorization_mode_override_combinations() {
+        let modes = [
+            AuthorizationMode::ICPSignature,
+            AuthorizationMode::ManualOnly,
+            AuthorizationMode::TimeBased,
+            AuthorizationMode::Hybrid,
+        ];
+        for global_mode in modes {
+            // No override: global mode applies
+            assert_eq!(effective_authorization_mode(None, global_mode), global_mode);
+            for override_mode in modes {
+                let mode = effective_authorization_mode(Some(override_mode), global_mode);
+                assert_eq!(mode, override_mode);
+                assert_eq!(
+                    allows_manual_processing(mode),
+                    matches!(override_mode, AuthorizationMode::ManualOnly | AuthorizationMode::Hybrid)
+                );
+                assert_eq!(
+                    allows_time_based_processing(mode),
+                    matches!(override_mode, AuthorizationMode::TimeBased | AuthorizationMode::Hybrid)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_admin_signer_validation() {
+        let a = Pubkey::new_unique();
+        let b = Pubkey::new_unique();
+        let (signers, count) = build_admin_signers(&[a, b], 2).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(&signers[..2], &[a, b]);
+
+        assert!(build_admin_signers(&[], 1).is_err());
+        assert!(build_admin_signers(&[a, b], 0).is_err());
+        assert!(build_admin_signers(&[a, b], 3).is_err());
+        assert!(build_admin_signers(&[a, a], 1).is_err());
+        assert!(build_admin_signers(&[Pubkey::default()], 1).is_err());
+        assert!(build_admin_signers(&[Pubkey::new_unique(); MAX_ADMIN_SIGNERS + 1], 1).is_err());
+    }
+
+    #[test]
+    fn test_admin_approvals_and_timeout() {
+        let mut pending = PendingAdminAction {
+            action_id: 1,
+            action_type: AdminActionType::EmergencyPause,
+            data: vec![],
+            proposer: Pubkey::new_unique(),
+            proposed_at: 1_700_000_000,
+            approvals: 0,
+            approval_count: 0,
+        };
+        record_admin_approval(&mut pending, 0).unwrap();
+        record_admin_approval(&mut pending, 3).unwrap();
+        assert_eq!(pending.approval_count, 2);
+        assert_eq!(pending.approvals, 0b1001);
+        assert!(record_admin_approval(&mut pending, 3).is_err());
+
+        assert!(!is_admin_proposal_expired(pending.proposed_at, pending.proposed_at + ADMIN_PROPOSAL_TIMEOUT_SECONDS - 1));
+        assert!(is_admin_proposal_expired(pending.proposed_at, pending.proposed_at + ADMIN_PROPOSAL_TIMEOUT_SECONDS));
+    }
+
+    #[test]
+    fn test_account_bound_admin_action_needs_matching_approved_proposal() {
+        let mint = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let data = (mint, 500_000u64, recipient).try_to_vec().unwrap();
+        let proposed_at = 1_700_000_000;
+        let proposal = |approval_count| Some(PendingAdminAction {
+            action_id: 7,
+            action_type: AdminActionType::WithdrawFees,
+            data: data.clone(),
+            proposer: Pubkey::new_unique(),
+            proposed_at,
+            approvals: 0,
+            approval_count,
+        });
+
+        // Below threshold, wrong type, different arguments and expired proposals are all refused
+        let mut pending = proposal(1);
+        assert!(take_approved_admin_action(&mut pending, 2, AdminActionType::WithdrawFees, &data, proposed_at).is_err());
+        let mut pending = proposal(2);
+        assert!(take_approved_admin_action(&mut pending, 2, AdminActionType::ResetFeeAnalytics, &[], proposed_at).is_err());
+        let other = (mint, 900_000u64, recipient).try_to_vec().unwrap();
+        assert!(take_approved_admin_action(&mut pending, 2, AdminActionType::WithdrawFees, &other, proposed_at).is_err());
+        let expired = proposed_at + ADMIN_PROPOSAL_TIMEOUT_SECONDS;
+        assert!(take_approved_admin_action(&mut pending, 2, AdminActionType::WithdrawFees, &data, expired).is_err());
+        assert!(pending.is_some());
+
+        // An approved match is consumed, so it authorizes exactly one call
+        let taken = take_approved_admin_action(&mut pending, 2, AdminActionType::WithdrawFees, &data, proposed_at).unwrap();
+        assert_eq!(taken.action_id, 7);
+        assert!(pending.is_none());
+        assert!(take_approved_admin_action(&mut pending, 2, AdminActionType::WithdrawFees, &data, proposed_at).is_err());
+    }
+
+    #[test]
+    fn test_spending_cap_window() {
+        let interval = 30 * 24 * 60 * 60;
+        let start = 1_700_000_000;
+
+        // Within cap
+        assert_eq!(apply_spending_cap(20_000_000, start, 0, 10_000_000, interval, start + 10).unwrap(), (start, 10_000_000));
+        assert_eq!(apply_spending_cap(20_000_000, start, 10_000_000, 10_000_000, interval, start + 20).unwrap(), (start, 20_000_000));
+        // Would breach the cap within the same window
+        assert!(apply_spending_cap(20_000_000, start, 20_000_000, 10_000_000, interval, start + 30).is_err());
+        // Window resets after a full interval
+        assert_eq!(
+            apply_spending_cap(20_000_000, start, 20_000_000, 10_000_000, interval, start + interval).unwrap(),
+            (start + interval, 10_000_000)
+        );
+        // Single payment larger than the cap
+        assert!(apply_spending_cap(5_000_000, start, 0, 10_000_000, interval, start).is_err());
+        // No cap
+        assert!(apply_spending_cap(0, start, u64::MAX / 2, 10_000_000, interval, start).is_ok());
+    }
+
+    #[test]
+    fn test_used_nonce_expiry() {
+        let signed_at = 1_700_000_000;
+        assert!(!is_used_nonce_expired(signed_at, signed_at));
+        assert!(!is_used_nonce_expired(signed_at, signed_at + MAX_TIMESTAMP_DRIFT * 2 - 1));
+        assert!(is_used_nonce_expired(signed_at, signed_at + MAX_TIMESTAMP_DRIFT * 2));
+        assert!(!is_used_nonce_expired(i64::MAX, 0));
+    }
+
+    #[test]
+    fn test_concurrent_replays_share_used_nonce() {
+        // Two transactions replaying the same signature derive the same PDA, so only one can create it
+        let program_id = crate::ID;
+        let signed_at: i64 = 1_700_000_000;
+        let derive = |id: &str, ts: i64| {
+            Pubkey::find_program_address(&[b"nonce", id.as_bytes(), &ts.to_le_bytes()], &program_id).0
+        };
+        assert_eq!(derive("sub_1", signed_at), derive("sub_1", signed_at));
+        // A fresh signature (new timestamp) or another subscription gets its own nonce
+        assert_ne!(derive("sub_1", signed_at), derive("sub_1", signed_at + 1));
+        assert_ne!(derive("sub_1", signed_at), derive("sub_2", signed_at));
+    }
+
+    #[test]
+    fn test_merchant_approval_gating() {
+        let approved = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        let mut merchant_config = MerchantConfig {
+            merchant: Pubkey::new_unique(),
+            requires_approval: true,
+            approved_subscribers: vec![approved],
+            active: true,
+        };
+
+        assert!(is_subscriber_approved(&merchant_config, &approved));
+        assert!(!is_subscriber_approved(&merchant_config, &stranger));
+
+        // Open merchants and inactive configs accept anyone
+        merchant_config.requires_approval = false;
+        assert!(is_subscriber_approved(&merchant_config, &stranger));
+        merchant_config.requires_approval = true;
+        merchant_config.active = false;
+        assert!(is_subscriber_approved(&merchant_config, &stranger));
+    }
+
+    #[test]
+    fn test_key_rotation_accepts_old_and_new_signatures_during_overlap() {
+        let old_key = [1u8; 32];
+        let new_key = [2u8; 32];
+        let message = create_payment_message("sub_1", 1_700_000_000, 10_000_000);
+        let old_signed = ed25519_ix_data(&old_key, &[0u8; 64], &message);
+        let new_signed = ed25519_ix_data(&new_key, &[0u8; 64], &message);
+        let verifies = |data: &[u8], keys: &[[u8; 32]]| {
+            keys.iter().any(|key| verify_ed25519_instruction_data(data, key, &message).unwrap_or(false))
+        };
+
+        let mut keys = [[0u8; 32]; MAX_ICP_PUBLIC_KEYS];
+        let mut count = 0u8;
+        add_icp_key(&mut keys, &mut count, old_key).unwrap();
+
+        // Overlap window: in-flight signatures from the old key and fresh ones from the new key both verify
+        let rotation_start = 1_700_000_000;
+        let last_in_window = rotation_start + ROTATION_WINDOW_SECONDS - 1;
+        let accepted = with_pending_icp_key(&keys[..count as usize], Some(new_key), Some(rotation_start), last_in_window);
+        assert!(verifies(&old_signed, &accepted));
+        assert!(verifies(&new_signed, &accepted));
+        assert!(!is_key_rotation_window_over(rotation_start, last_in_window));
+
+        // A pending key that was never completed stops verifying once the window elapses
+        let window_end = rotation_start + ROTATION_WINDOW_SECONDS;
+        let accepted = with_pending_icp_key(&keys[..count as usize], Some(new_key), Some(rotation_start), window_end);
+        assert!(verifies(&old_signed, &accepted));
+        assert!(!verifies(&new_signed, &accepted));
+        let accepted = with_pending_icp_key(&keys[..count as usize], Some(new_key), None, last_in_window);
+        assert!(!verifies(&new_signed, &accepted));
+
+        // After completion only the new key verifies
+        assert!(is_key_rotation_window_over(rotation_start, rotation_start + ROTATION_WINDOW_SECONDS));
+        rotate_primary_icp_key(&mut keys, &mut count, new_key).unwrap();
+        let accepted = with_pending_icp_key(&keys[..count as usize], None, None, window_end);
+        assert!(!verifies(&old_signed, &accepted));
+        assert!(verifies(&new_signed, &accepted));
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_eurc_is_a_supported_stablecoin() {
+        assert!(is_supported_stablecoin(EURC_MINT));
+        assert!(is_supported_stablecoin(USDC_MINT));
+        assert!(!is_supported_stablecoin("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB")); // USDT
+        assert!(EURC_MINT_MAINNET.parse::<Pubkey>().is_ok());
+        assert!(EURC_MINT_DEVNET.parse::<Pubkey>().is_ok());
+    }
+
+    #[test]
+    fn test_fee_analytics_accumulates() {
+        let mut analytics = FeeAnalytics { total_fees_collected: 0, total_volume: 0, payment_count: 0, last_reset: 0 };
+        let fee_config = FeeConfig {
+            fee_percentage_basis_points: 100,
+            min_fee_amount: 1000,
+            max_fee_amount: u64::MAX,
+            fee_recipients: [FeeRecipient::default(); MAX_FEE_RECIPIENTS],
+            recipient_count: 0,
+            fee_tiers: [FeeTier::default(); MAX_FEE_TIERS],
+            tier_count: 0,
+        };
+        for _ in 0..3 {
+            let (platform_fee, _) = split_payment_amount(10_000_000, fee_config.fee_percentage_basis_points, &fee_config).unwrap();
+            record_fee_analytics(&mut analytics, 10_000_000, platform_fee).unwrap();
+        }
+        assert_eq!(analytics.total_volume, 30_000_000);
+        assert_eq!(analytics.total_fees_collected, 300_000);
+        assert_eq!(analytics.payment_count, 3);
+
+        // Totals past u64::MAX fit in the u128 counters
+        record_fee_analytics(&mut analytics, u64::MAX, u64::MAX).unwrap();
+        assert!(analytics.total_volume > u64::MAX as u128);
+    }
+
+    #[test]
+    fn test_fee_tier_validation() {
+        let tier = |threshold_total_paid, fee_bps| FeeTier { threshold_total_paid, fee_bps };
+
+        // Empty list defaults to the flat fee
+        let (tiers, count) = build_fee_tiers(&[], 200).unwrap();
+        assert_eq!((tiers[0], count), (tier(0, 200), 1));
+
+        assert!(build_fee_tiers(&[tier(0, 200), tier(100_000_000, 150), tier(1_000_000_000, 100)], 200).is_ok());
+        assert!(build_fee_tiers(&[tier(0, 200), tier(100_000_000, 200)], 200).is_ok()); // Equal fee allowed
+        assert!(build_fee_tiers(&[tier(10, 200)], 200).is_err()); // Must start at 0
+        assert!(build_fee_tiers(&[tier(0, 200), tier(0, 100)], 200).is_err()); // Threshold not increasing
+        assert!(build_fee_tiers(&[tier(0, 100), tier(5, 200)], 200).is_err()); // Fee increasing
+        assert!(build_fee_tiers(&[tier(0, 10_001)], 200).is_err());
+        assert!(build_fee_tiers(&[tier(0, 5), tier(1, 4), tier(2, 3), tier(3, 2), tier(4, 1)], 200).is_err()); // Too many
+    }
+
+    #[test]
+    fn test_select_fee_tier() {
+        let (fee_tiers, tier_count) = build_fee_tiers(&[
+            FeeTier { threshold_total_paid: 0, fee_bps: 200 },
+            FeeTier { threshold_total_paid: 100_000_000, fee_bps: 150 },
+            FeeTier { threshold_total_paid: 1_000_000_000, fee_bps: 100 },
+        ], 200).unwrap();
+        let mut fee_config = FeeConfig {
+            fee_percentage_basis_points: 200,
+            min_fee_amount: 1000,
+            max_fee_amount: u64::MAX,
+            fee_recipients: [FeeRecipient::default(); MAX_FEE_RECIPIENTS],
+            recipient_count: 0,
+            fee_tiers,
+            tier_count,
+        };
+
+        let applied = |fee_config: &FeeConfig, total_paid| {
+            let tier = select_fee_tier(fee_config, total_paid);
+            (tier.tier_index, tier.fee_bps)
+        };
+        assert_eq!(applied(&fee_config, 0), (0, 200));
+        assert_eq!(applied(&fee_config, 99_999_999), (0, 200));
+        assert_eq!(applied(&fee_config, 100_000_000), (1, 150));
+        assert_eq!(applied(&fee_config, u64::MAX), (2, 100));
+
+        // Legacy config without tiers uses the flat fee
+        fee_config.tier_count = 0;
+        assert_eq!(applied(&fee_config, u64::MAX), (0, 200));
+    }
+
+    #[test]
+    fn test_slippage_update_cooldown() {
+        let updated_at = 1_700_000_000;
+        assert!(is_slippage_update_allowed(None, updated_at));
+        assert!(!is_slippage_update_allowed(Some(updated_at), updated_at + SLIPPAGE_UPDATE_COOLDOWN_SECONDS - 1));
+        assert!(is_slippage_update_allowed(Some(updated_at), updated_at + SLIPPAGE_UPDATE_COOLDOWN_SECONDS));
+    }
+
+    #[test]
+    fn test_snoozed_next_payment_time() {
+        let interval = 30 * 24 * 60 * 60;
+        assert_eq!(snoozed_next_payment_time(1_700_000_000, interval, 1), Some(1_700_000_000 + interval));
+        assert_eq!(snoozed_next_payment_time(1_700_000_000, interval, 12), Some(1_700_000_000 + 12 * interval));
+        assert_eq!(snoozed_next_payment_time(i64::MAX - 1, interval, 1), None);
+    }
+
+    #[test]
+    fn test_is_valid_interval_update() {
+        assert!(is_valid_interval_update(7 * 24 * 60 * 60, 0));
+        assert!(is_valid_interval_update(10, 9));
+        assert!(is_valid_interval_update(365 * 24 * 60 * 60, 0));
+        assert!(!is_valid_interval_update(9, 0));
+        assert!(!is_valid_interval_update(-1, 0)); // Can't switch to one-time
+        assert!(!is_valid_interval_update(365 * 24 * 60 * 60 + 1, 0));
+        assert!(!is_valid_interval_update(3600, 3600)); // Grace period no longer fits
+    }
+
+    #[test]
+    fn test_next_recurring_payment_time() {
+        let interval = 30 * 24 * 60 * 60;
+        // On schedule: one interval past the scheduled time
+        assert_eq!(next_recurring_payment_time(1_000, interval, 1_000), Some(1_000 + interval));
+        // Missed payments are skipped up to the first time not before now
+        assert_eq!(next_recurring_payment_time(1_000, interval, 1_000 + 2 * interval + 5), Some(1_000 + 3 * interval));
+        assert_eq!(next_recurring_payment_time(1_000, interval, 1_000 + 2 * interval), Some(1_000 + 2 * interval));
+
+        // 1-second interval, ten years overdue: one step instead of ~315M loop iterations
+        let now = 1_700_000_000;
+        let ten_years_ago = now - 10 * 365 * 24 * 60 * 60;
+        assert_eq!(next_recurring_payment_time(ten_years_ago, 1, now), Some(now));
+
+        assert_eq!(next_recurring_payment_time(i64::MAX - 10, 60, i64::MAX - 10), None);
+        assert_eq!(next_recurring_payment_time(1_000, 0, 2_000), None);
+    }
+
+    #[test]
+    fn test_bound_fee_amount_leaves_merchant_share() {
+        let fee_config = FeeConfig {
+            fee_percentage_basis_points: 100,
+            min_fee_amount: 1000,
+            max_fee_amount: 100_000_000,
+            fee_recipients: [FeeRecipient::default(); MAX_FEE_RECIPIENTS],
+            recipient_count: 0,
+            fee_tiers: [FeeTier::default(); MAX_FEE_TIERS],
+            tier_count: 0,
+        };
+        assert_eq!(bound_fee_amount(200_000, 20_000_000, &fee_config), 200_000);
+        assert_eq!(bound_fee_amount(10, 1_000_000, &fee_config), 1000); // Floor
+        assert_eq!(bound_fee_amount(500_000_000, u64::MAX, &fee_config), 100_000_000); // Cap
+        // The floor alone would take the whole payment
+        assert_eq!(bound_fee_amount(5, 500, &fee_config), 499);
+        assert_eq!(bound_fee_amount(0, 1, &fee_config), 0);
+    }
+
+    #[test]
+    fn test_catch_up_is_constant_time() {
+        // Paused for a year at a 60-second interval: 525,600 missed periods
+        let now = 1_700_000_000;
+        let year = 365 * 24 * 60 * 60;
+        assert_eq!(next_recurring_payment_time(now - year - 30, 60, now), Some(now + 30));
+
+        // ~2.9e11 years of 1-second periods: only finishes because there is no per-period loop
+        let next = next_recurring_payment_time(i64::MIN / 2, 1, i64::MAX / 2).unwrap();
+        assert_eq!(next, i64::MAX / 2);
+    }
+
+    #[test]
+    fn test_batch_rejects_repeated_subscription() {
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+        assert!(!has_duplicate_keys(&[first, second]));
+        assert!(has_duplicate_keys(&[first, second, first]));
+        assert!(has_duplicate_keys(&[second, second]));
+        assert!(!has_duplicate_keys(&[]));
+    }
+
+    #[test]
+    fn test_payment_mint_allowlist() {
+        let usdc = get_usdc_mint();
+        let pyusd: Pubkey = PYUSD_MINT.parse().unwrap();
+        let arbitrary = Pubkey::new_unique();
+
+        assert!(is_allowed_payment_mint(&usdc, false));
+        assert!(!is_allowed_payment_mint(&pyusd, false));
+        assert!(!is_allowed_payment_mint(&arbitrary, false));
+
+        // Token-2022 subscriptions no longer accept any mint
+        assert!(is_allowed_payment_mint(&pyusd, true));
+        assert!(!is_allowed_payment_mint(&arbitrary, true));
+        assert!(!is_allowed_payment_mint(&usdc, true));
+    }
+}
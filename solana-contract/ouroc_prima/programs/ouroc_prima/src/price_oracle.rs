@@ -1,22 +1,30 @@
 use anchor_lang::prelude::*;
-use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, PriceUpdateV2};
+use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, PriceUpdateV2, VerificationLevel};
+use crate::constants::{ORACLE_FALLBACK_CONSERVATIVE, ORACLE_FALLBACK_PYTH, ORACLE_FALLBACK_SWITCHBOARD};
 
-/// Supported stablecoins for price oracle conversion
-pub const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+/// Supported stablecoins for price oracle conversion (cluster-selected where a devnet mint exists)
+pub const USDC_MINT: &str = crate::constants::USDC_MINT;
 pub const USDT_MINT: &str = "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB";
-pub const PYUSD_MINT: &str = "2b1kV6DkPAnxd5ixfnxCpjxmKwqjjaYmCZfHsFu24GXo";
+pub const PYUSD_MINT: &str = crate::constants::PYUSD_MINT;
+pub const EURC_MINT: &str = crate::constants::EURC_MINT;
 
-/// Pyth price feed IDs (these are the actual Pyth feed IDs for each token/USD pair)
+/// Switchboard v2 oracle program (owner of AggregatorAccountData feeds)
+pub const SWITCHBOARD_V2_PROGRAM_ID: &str = "SW1TCH7qEPTdLsDHRgPuMQjbQxKdH2aBStViMFnt64f";
+
+/// Pyth price feed IDs (hex, as read by get_feed_id_from_hex; the same on mainnet and devnet)
 /// Get latest from: https://pyth.network/developers/price-feed-ids
 pub mod pyth_feeds {
     // USDC/USD - reference price (should always be ~1.00)
-    pub const USDC_USD: &str = "Dpw1EAVrSB1ibxiDQyTAW6Zip3J4Btk2x4SgApQCeFbX";
+    pub const USDC_USD: &str = "eaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a";
 
     // USDT/USD
-    pub const USDT_USD: &str = "HT2PLQBcG5EiCcNSaMHAjSgd9F98ecpATbk4Sk5oYuM";
+    pub const USDT_USD: &str = "2b89b9dc8fdf9f34709a5b106b472f0f39bb6ca9ce04b0fd7f2e971688e2e53b";
 
     // PYUSD/USD
-    pub const PYUSD_USD: &str = "9zXQxpYH3kYhtoybmZfUNNCRVuud7fY9jswTg1hLyT8k";
+    pub const PYUSD_USD: &str = "c1da1b73d7f01e7ddd54b3766cf7fcd644395ad14f70aa706ec5384c59e76692";
+
+    // EURC/USD - tracks EUR/USD, not pegged to 1.00
+    pub const EURC_USD_PYTH_FEED: &str = "76fa85158bf14ede77087fe3ae472f66213f6ea2f5b411cb2de472794990fa5c";
 }
 
 /// Price oracle result with conversion rate
@@ -30,84 +38,211 @@ pub struct PriceConversion {
 
 /// Get price conversion from input token to USDC
 /// Uses Pyth oracle for real-time pricing with configurable slippage tolerance
+/// `price_update` must be a PriceUpdateV2 account owned by the Pyth receiver program
 pub fn get_price_conversion(
     input_token_mint: &Pubkey,
     input_amount: u64,
     price_update: &AccountInfo,
     slippage_bps: u16, // Slippage tolerance in basis points (e.g., 100 = 1%)
+    max_price_age_seconds: u64, // From config.oracle_max_age
+    current_time: i64,
 ) -> Result<PriceConversion> {
     let input_mint_str = input_token_mint.to_string();
 
-    // Get the Pyth feed ID for this token
-    let feed_id_hex = match input_mint_str.as_str() {
-        USDC_MINT => pyth_feeds::USDC_USD,
-        USDT_MINT => pyth_feeds::USDT_USD,
-        PYUSD_MINT => pyth_feeds::PYUSD_USD,
-        _ => return Err(crate::price_oracle::PriceErrorCode::UnsupportedToken.into()),
-    };
+    let (feed_id_hex, usd_pegged) = pyth_feed_for_mint(&input_mint_str)?;
 
     // Parse Pyth price feed
     let feed_id = get_feed_id_from_hex(feed_id_hex)
         .map_err(|_| crate::price_oracle::PriceErrorCode::InvalidPriceFeed)?;
 
-    let price_update_data = PriceUpdateV2::try_from_slice(&price_update.data.borrow())
+    // Owner and account discriminator checks: a look-alike account can't supply its own price
+    require!(
+        *price_update.owner == pyth_solana_receiver_sdk::ID,
+        crate::price_oracle::PriceErrorCode::InvalidPriceUpdate
+    );
+    let price_update_data = PriceUpdateV2::try_deserialize(&mut &price_update.data.borrow()[..])
         .map_err(|_| crate::price_oracle::PriceErrorCode::InvalidPriceUpdate)?;
 
+    // Partially verified updates carry fewer guardian signatures than the receiver requires
+    require!(
+        price_update_data.verification_level == VerificationLevel::Full,
+        crate::price_oracle::PriceErrorCode::PartiallyVerifiedPrice
+    );
+
     let price = price_update_data
-        .get_price_no_older_than(
-            &Clock::get()?,
-            60, // Max 60 seconds old
-            &feed_id,
-        )
-        .map_err(|_| crate::price_oracle::PriceErrorCode::PriceTooOld)?;
-
-    // Pyth prices have different exponents, normalize to 8 decimals
-    // Price format: price * 10^exponent
-    let normalized_price = if price.exponent >= 0 {
-        let multiplier = 10i64.checked_pow(price.exponent as u32)
-            .ok_or(crate::price_oracle::PriceErrorCode::PriceOutOfBounds)?;
-        price.price.checked_mul(multiplier)
-            .ok_or(crate::price_oracle::PriceErrorCode::PriceOutOfBounds)?
-    } else {
-        let divisor = 10i64.checked_pow((-price.exponent) as u32)
-            .ok_or(crate::price_oracle::PriceErrorCode::PriceOutOfBounds)?;
-        price.price.checked_div(divisor)
-            .ok_or(crate::price_oracle::PriceErrorCode::PriceOutOfBounds)?
-    };
+        .get_price_unchecked(&feed_id)
+        .map_err(|_| crate::price_oracle::PriceErrorCode::InvalidPriceFeed)?;
 
-    msg!("Pyth price for {}: ${} (confidence: ±${})",
+    // Staleness check: a price from minutes ago must not be trusted for swap validation
+    require!(
+        !crate::payment_helpers::is_price_stale(price.publish_time, current_time, max_price_age_seconds),
+        crate::errors::ErrorCode::StalePriceFeed
+    );
+
+    // Pyth prices have different exponents, normalize price and confidence to 8 decimals
+    let normalized_price = normalize_pyth_value(price.price as i128, price.exponent)
+        .ok_or(crate::price_oracle::PriceErrorCode::PriceOutOfBounds)?;
+    let normalized_conf = normalize_pyth_value(price.conf as i128, price.exponent)
+        .ok_or(crate::price_oracle::PriceErrorCode::PriceOutOfBounds)?;
+
+    msg!("Pyth price for {}: {} (confidence: ±{}, 8 decimals)",
         input_mint_str,
         normalized_price,
-        price.conf
+        normalized_conf
     );
 
-    // For stablecoins, price should be very close to 1.00
-    // Sanity check: price should be between $0.95 and $1.05
+    conversion_from_price(input_amount, normalized_price, normalized_conf as u64, usd_pegged, slippage_bps)
+}
+
+/// Convert a Pyth value (value * 10^exponent) to 8 decimals; None if it doesn't fit
+fn normalize_pyth_value(value: i128, exponent: i32) -> Option<i64> {
+    let shift = exponent.checked_add(8)?;
+    let scaled = if shift >= 0 {
+        value.checked_mul(10i128.checked_pow(shift as u32)?)?
+    } else {
+        value.checked_div(10i128.checked_pow(shift.unsigned_abs())?)?
+    };
+    i64::try_from(scaled).ok()
+}
+
+/// Pyth feed ID for a supported token, and whether it is pegged to USD
+fn pyth_feed_for_mint(input_mint_str: &str) -> Result<(&'static str, bool)> {
+    match input_mint_str {
+        USDC_MINT => Ok((pyth_feeds::USDC_USD, true)),
+        USDT_MINT => Ok((pyth_feeds::USDT_USD, true)),
+        PYUSD_MINT => Ok((pyth_feeds::PYUSD_USD, true)),
+        EURC_MINT => Ok((pyth_feeds::EURC_USD_PYTH_FEED, false)),
+        _ => Err(crate::price_oracle::PriceErrorCode::UnsupportedToken.into()),
+    }
+}
+
+/// Bounds-check an 8-decimal price and apply slippage protection to the converted amount
+fn conversion_from_price(
+    input_amount: u64,
+    normalized_price: i64,
+    confidence_interval: u64,
+    usd_pegged: bool,
+    slippage_bps: u16,
+) -> Result<PriceConversion> {
+    // For USD stablecoins, price should be very close to 1.00
+    // Sanity check: price should be between $0.95 and $1.05 (EURC: between $0.80 and $1.50)
+    let (min_price, max_price) = if usd_pegged {
+        (95_000_000, 105_000_000)
+    } else {
+        (80_000_000, 150_000_000)
+    };
     require!(
-        normalized_price > 95_000_000 && normalized_price < 105_000_000,
+        normalized_price > min_price && normalized_price < max_price,
         crate::price_oracle::PriceErrorCode::PriceOutOfBounds
     );
 
     // Calculate output amount
-    // Both USDC and USDT have 6 decimals, so direct conversion
+    // USDC, USDT, PYUSD and EURC all have 6 decimals, so no decimal scaling
     // Apply configurable slippage protection
-    let output_amount_exact = input_amount; // Stablecoins are 1:1
+    let output_amount_exact = if usd_pegged {
+        input_amount // Stablecoins are 1:1
+    } else {
+        // EURC converts at the EUR/USD rate (8 decimals)
+        ((input_amount as u128 * normalized_price as u128) / 100_000_000) as u64
+    };
     let slippage_multiplier = 10000u64.checked_sub(slippage_bps as u64)
         .ok_or(crate::price_oracle::PriceErrorCode::PriceOutOfBounds)?;
-    let output_amount_min = (output_amount_exact * slippage_multiplier) / 10000;
+    let output_amount_min = ((output_amount_exact as u128 * slippage_multiplier as u128) / 10000) as u64;
 
     Ok(PriceConversion {
         input_amount,
         output_amount_min,
         exchange_rate: normalized_price,
-        confidence_interval: price.conf,
+        confidence_interval,
     })
 }
 
+/// Get price conversion, falling back when the Pyth feed is stale
+/// Pyth -> Switchboard aggregator (if supplied) -> worst-case 2x slippage haircut
+/// Returns the conversion together with the fallback level that produced it
+pub fn get_price_conversion_with_fallback(
+    input_token_mint: &Pubkey,
+    input_amount: u64,
+    price_update: &AccountInfo,
+    switchboard_feed: Option<&AccountInfo>,
+    slippage_bps: u16,
+    max_price_age_seconds: u64,
+    current_time: i64,
+) -> Result<(PriceConversion, u8)> {
+    match get_price_conversion(input_token_mint, input_amount, price_update, slippage_bps, max_price_age_seconds, current_time) {
+        Ok(conversion) => return Ok((conversion, ORACLE_FALLBACK_PYTH)),
+        Err(err) if is_stale_price_error(&err) => {
+            msg!("Pyth price is stale, trying fallback oracles");
+        }
+        Err(err) => return Err(err),
+    }
+
+    if let Some(feed) = switchboard_feed {
+        match get_switchboard_price_conversion(input_token_mint, input_amount, feed, slippage_bps, max_price_age_seconds, current_time) {
+            Ok(conversion) => return Ok((conversion, ORACLE_FALLBACK_SWITCHBOARD)),
+            Err(err) => msg!("Switchboard fallback unavailable: {:?}", err),
+        }
+    }
+
+    let output_amount_min = crate::payment_helpers::conservative_output_amount_min(input_amount, slippage_bps);
+    msg!("No usable oracle price, using conservative minimum output {}", output_amount_min);
+
+    Ok((
+        PriceConversion {
+            input_amount,
+            output_amount_min,
+            exchange_rate: 0,
+            confidence_interval: 0,
+        },
+        ORACLE_FALLBACK_CONSERVATIVE,
+    ))
+}
+
+/// Get price conversion from a Switchboard v2 aggregator account
+pub fn get_switchboard_price_conversion(
+    input_token_mint: &Pubkey,
+    input_amount: u64,
+    feed: &AccountInfo,
+    slippage_bps: u16,
+    max_price_age_seconds: u64,
+    current_time: i64,
+) -> Result<PriceConversion> {
+    let (_, usd_pegged) = pyth_feed_for_mint(&input_token_mint.to_string())?;
+
+    require!(
+        feed.owner.to_string() == SWITCHBOARD_V2_PROGRAM_ID,
+        crate::price_oracle::PriceErrorCode::InvalidPriceFeed
+    );
+
+    let round = crate::payment_helpers::parse_switchboard_aggregator(&feed.data.borrow())
+        .ok_or(crate::price_oracle::PriceErrorCode::InvalidPriceUpdate)?;
+
+    require!(
+        !crate::payment_helpers::is_price_stale(round.round_open_timestamp, current_time, max_price_age_seconds),
+        crate::errors::ErrorCode::StalePriceFeed
+    );
+
+    let normalized_price = crate::payment_helpers::normalize_switchboard_price(round.mantissa, round.scale)
+        .ok_or(crate::price_oracle::PriceErrorCode::PriceOutOfBounds)?;
+
+    msg!("Switchboard price for {}: ${}", input_token_mint, normalized_price);
+
+    // Aggregators report a median without a Pyth-style confidence band
+    conversion_from_price(input_amount, normalized_price, 0, usd_pegged, slippage_bps)
+}
+
+fn is_stale_price_error(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::AnchorError(anchor_error)
+            if anchor_error.error_code_number == u32::from(crate::errors::ErrorCode::StalePriceFeed)
+    )
+}
+
 /// Validate that the price is within acceptable confidence bounds
 pub fn validate_price_confidence(conversion: &PriceConversion) -> Result<()> {
     // Confidence should be less than 0.5% of price
-    let max_confidence = (conversion.exchange_rate.abs() as u64) / 200; // 0.5%
+    let max_confidence = conversion.exchange_rate.unsigned_abs() / 200; // 0.5%
 
     require!(
         conversion.confidence_interval <= max_confidence,
@@ -129,8 +264,8 @@ pub enum PriceErrorCode {
     #[msg("Invalid Pyth price update data")]
     InvalidPriceUpdate,
 
-    #[msg("Price data is too old (>60 seconds)")]
-    PriceTooOld,
+    #[msg("Pyth price update is not fully verified")]
+    PartiallyVerifiedPrice,
 
     #[msg("Price is out of acceptable bounds for stablecoin")]
     PriceOutOfBounds,
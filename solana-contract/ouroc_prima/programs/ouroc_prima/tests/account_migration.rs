@@ -0,0 +1,114 @@
+//! Accounts written by the original program must decode after migration, and new fields only ever append
+
+use anchor_lang::prelude::*;
+use ouroc_prima::{
+    migrate_legacy_config, migrate_legacy_subscription, AuthorizationMode, LegacyConfig, LegacyFeeConfig,
+    LegacySubscription, PausedBy, Subscription, SubscriptionStatus, DEFAULT_SLIPPAGE_BPS,
+};
+
+fn legacy_subscription() -> LegacySubscription {
+    LegacySubscription {
+        id: "sub_legacy".to_string(),
+        subscriber: Pubkey::new_unique(),
+        merchant: Pubkey::new_unique(),
+        merchant_name: "Legacy Merchant".to_string(),
+        amount: 10_000_000,
+        interval_seconds: 2_592_000,
+        next_payment_time: 1_700_000_000,
+        status: SubscriptionStatus::Active,
+        created_at: 1_690_000_000,
+        last_payment_time: Some(1_697_408_000),
+        payments_made: 3,
+        total_paid: 30_000_000,
+        icp_canister_signature: [7u8; 64],
+        reminder_days_before_payment: 3,
+        escrow_pda: Pubkey::new_unique(),
+        escrow_balance: 5_000_000,
+    }
+}
+
+#[test]
+fn legacy_subscription_fits_original_allocation() {
+    let mut serialized = Vec::new();
+    legacy_subscription().serialize(&mut serialized).unwrap();
+    assert!(serialized.len() <= LegacySubscription::LEN);
+}
+
+#[test]
+fn migrated_subscription_keeps_legacy_fields_and_defaults_the_rest() {
+    let legacy = legacy_subscription();
+    let mut legacy_bytes = Vec::new();
+    legacy.serialize(&mut legacy_bytes).unwrap();
+
+    let migrated = migrate_legacy_subscription(legacy.clone());
+    assert_eq!(migrated.id, legacy.id);
+    assert_eq!(migrated.payments_made, 3);
+    assert_eq!(migrated.total_paid, 30_000_000);
+    assert_eq!(migrated.escrow_balance, 5_000_000);
+    assert_eq!(migrated.version, 0);
+    assert_eq!(migrated.grace_period_seconds, 0);
+    assert_eq!(migrated.remaining_payments, None);
+    assert_eq!(migrated.paused_by, PausedBy::Subscriber);
+    assert_eq!(migrated.period_start, legacy.created_at);
+    assert_eq!(migrated.slippage_bps, DEFAULT_SLIPPAGE_BPS);
+    assert!(!migrated.disputed);
+    assert_ne!(migrated.integrity_hash, [0u8; 32]);
+
+    // Appended fields only: the migrated encoding starts with the original bytes
+    let mut migrated_bytes = Vec::new();
+    migrated.serialize(&mut migrated_bytes).unwrap();
+    assert_eq!(&migrated_bytes[..legacy_bytes.len()], &legacy_bytes[..]);
+    assert!(migrated_bytes.len() <= Subscription::LEN);
+}
+
+#[test]
+fn legacy_config_migrates_single_key_into_allowlist() {
+    let authority = Pubkey::new_unique();
+    let fee_address = Pubkey::new_unique();
+    let legacy = LegacyConfig {
+        authority,
+        total_subscriptions: 42,
+        paused: false,
+        authorization_mode: AuthorizationMode::Hybrid,
+        icp_public_key: Some([9u8; 32]),
+        manual_processing_enabled: true,
+        time_based_processing_enabled: true,
+        fee_config: LegacyFeeConfig { fee_percentage_basis_points: 200, min_fee_amount: 1_000 },
+        icp_fee_collection_address: Some(fee_address),
+    };
+    let mut legacy_bytes = Vec::new();
+    legacy.serialize(&mut legacy_bytes).unwrap();
+    assert_eq!(legacy_bytes.len(), LegacyConfig::LEN);
+
+    let config = migrate_legacy_config(legacy).unwrap();
+    assert_eq!(config.authority, authority);
+    assert_eq!(config.total_subscriptions, 42);
+    assert_eq!(config.icp_key_count, 1);
+    assert_eq!(config.icp_public_keys[0], [9u8; 32]);
+    assert_eq!(config.fee_config.fee_percentage_basis_points, 200);
+    assert_eq!(config.fee_config.min_fee_amount, 1_000);
+    assert_eq!(config.fee_config.max_fee_amount, u64::MAX);
+    assert_eq!(config.fee_config.tier_count, 1);
+    assert_eq!(config.fee_config.recipient_count, 0);
+    assert_eq!(config.icp_fee_collection_address, Some(fee_address));
+    assert!(!config.multisig_mode);
+}
+
+#[test]
+fn legacy_config_without_key_migrates_to_empty_allowlist() {
+    let legacy = LegacyConfig {
+        authority: Pubkey::new_unique(),
+        total_subscriptions: 0,
+        paused: true,
+        authorization_mode: AuthorizationMode::ManualOnly,
+        icp_public_key: None,
+        manual_processing_enabled: true,
+        time_based_processing_enabled: false,
+        fee_config: LegacyFeeConfig { fee_percentage_basis_points: 100, min_fee_amount: 1_000 },
+        icp_fee_collection_address: None,
+    };
+
+    let config = migrate_legacy_config(legacy).unwrap();
+    assert_eq!(config.icp_key_count, 0);
+    assert!(config.paused);
+}
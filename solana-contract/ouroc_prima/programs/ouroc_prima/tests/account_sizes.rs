@@ -0,0 +1,138 @@
+//! Account LEN constants must cover the largest Borsh encoding, or writes fail with AccountDidNotSerialize
+
+use anchor_lang::prelude::*;
+use ouroc_prima::{
+    AuthorizationMode, DisputeRecord, MerchantDashboard, MerchantIndex, PausedBy, SubscriberReputation, Subscription, SubscriberIndex, SubscriptionStatus, MAX_INDEXED_SUBSCRIPTIONS,
+};
+
+// create_subscription limit for both id and merchant_name
+const MAX_STRING_LEN: usize = 32;
+
+fn max_subscription() -> Subscription {
+    Subscription {
+        id: "i".repeat(MAX_STRING_LEN),
+        subscriber: Pubkey::new_unique(),
+        merchant: Pubkey::new_unique(),
+        merchant_name: "m".repeat(MAX_STRING_LEN),
+        amount: u64::MAX,
+        interval_seconds: i64::MAX,
+        next_payment_time: i64::MAX,
+        status: SubscriptionStatus::Expired,
+        created_at: i64::MAX,
+        last_payment_time: Some(i64::MAX),
+        payments_made: u64::MAX,
+        total_paid: u64::MAX,
+        icp_canister_signature: [u8::MAX; 64],
+        reminder_days_before_payment: u32::MAX,
+        escrow_pda: Pubkey::new_unique(),
+        escrow_balance: u64::MAX,
+        grace_period_seconds: i64::MAX,
+        trial_end_time: Some(i64::MAX),
+        max_payments: Some(u64::MAX),
+        remaining_payments: Some(u64::MAX),
+        use_token_2022: true,
+        last_notification_time: Some(i64::MAX),
+        paused_at: Some(i64::MAX),
+        expires_at: Some(i64::MAX),
+        paused_by: PausedBy::Merchant,
+        authorization_mode_override: Some(AuthorizationMode::Hybrid),
+        spending_cap_per_period: u64::MAX,
+        period_start: i64::MAX,
+        spent_in_period: u64::MAX,
+        slippage_bps: u16::MAX,
+        slippage_updated_at: Some(i64::MAX),
+        integrity_hash: [u8::MAX; 32],
+        disputed: true,
+        dispute_payment_number: u64::MAX,
+        version: u64::MAX,
+        last_ruled_payment_number: u64::MAX,
+        vault_fees_paid: u64::MAX,
+    }
+}
+
+#[test]
+fn subscription_len_matches_borsh_serialized_size() {
+    let mut serialized = Vec::new();
+    max_subscription().serialize(&mut serialized).unwrap();
+
+    assert!(
+        serialized.len() <= Subscription::LEN,
+        "Subscription::LEN is {} but a fully populated Subscription serializes to {} bytes",
+        Subscription::LEN,
+        serialized.len()
+    );
+}
+
+#[test]
+fn index_len_matches_borsh_serialized_size() {
+    let subscription_ids = vec![[u8::MAX; 32]; MAX_INDEXED_SUBSCRIPTIONS];
+
+    let mut serialized = Vec::new();
+    SubscriberIndex { subscriber: Pubkey::new_unique(), subscription_ids: subscription_ids.clone(), count: u8::MAX }
+        .serialize(&mut serialized)
+        .unwrap();
+    assert!(
+        serialized.len() <= SubscriberIndex::LEN,
+        "SubscriberIndex::LEN is {} but a full index serializes to {} bytes",
+        SubscriberIndex::LEN,
+        serialized.len()
+    );
+
+    serialized.clear();
+    MerchantIndex { merchant: Pubkey::new_unique(), subscription_ids, count: u8::MAX }
+        .serialize(&mut serialized)
+        .unwrap();
+    assert!(
+        serialized.len() <= MerchantIndex::LEN,
+        "MerchantIndex::LEN is {} but a full index serializes to {} bytes",
+        MerchantIndex::LEN,
+        serialized.len()
+    );
+}
+
+#[test]
+fn merchant_dashboard_len_matches_borsh_serialized_size() {
+    let dashboard = MerchantDashboard {
+        merchant: Pubkey::new_unique(),
+        active_subscriptions: u32::MAX,
+        total_revenue_usdc: u64::MAX,
+        total_fees_paid: u64::MAX,
+        last_payment: Some(i64::MAX),
+        failed_payments: u32::MAX,
+    };
+
+    let mut serialized = Vec::new();
+    dashboard.serialize(&mut serialized).unwrap();
+    assert_eq!(serialized.len(), MerchantDashboard::LEN);
+}
+
+#[test]
+fn dispute_record_len_matches_borsh_serialized_size() {
+    let record = DisputeRecord {
+        subscription: Pubkey::new_unique(),
+        subscriber: Pubkey::new_unique(),
+        payment_number: u64::MAX,
+        reason: [u8::MAX; 128],
+        opened_at: i64::MAX,
+    };
+
+    let mut serialized = Vec::new();
+    record.serialize(&mut serialized).unwrap();
+    assert_eq!(serialized.len(), DisputeRecord::LEN);
+}
+
+#[test]
+fn subscriber_reputation_len_matches_borsh_serialized_size() {
+    let reputation = SubscriberReputation {
+        subscriber: Pubkey::new_unique(),
+        score: u16::MAX,
+        on_time_payments: u64::MAX,
+        late_payments: u64::MAX,
+        failed_payments: u64::MAX,
+        last_updated: i64::MAX,
+    };
+
+    let mut serialized = Vec::new();
+    reputation.serialize(&mut serialized).unwrap();
+    assert_eq!(serialized.len(), SubscriberReputation::LEN);
+}
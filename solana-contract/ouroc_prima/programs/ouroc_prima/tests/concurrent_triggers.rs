@@ -0,0 +1,46 @@
+//! Concurrent triggers: canister instances that read the same version race, only one may win
+
+use anchor_lang::prelude::*;
+use ouroc_prima::{claim_subscription_version, Subscription};
+
+fn subscription_at_version(version: u64) -> Subscription {
+    // All-zero bytes decode to a valid Subscription (empty strings, first enum variants, None)
+    let mut subscription = Subscription::deserialize(&mut &[0u8; Subscription::LEN][..]).unwrap();
+    subscription.version = version;
+    subscription
+}
+
+#[test]
+fn only_one_trigger_per_observed_version_succeeds() {
+    const INSTANCES: usize = 16;
+    const ROUNDS: u64 = 250;
+
+    let mut subscription = subscription_at_version(0);
+    for round in 0..ROUNDS {
+        // Every instance read the account before any of their transactions landed
+        let observed = subscription.version;
+        let successes = (0..INSTANCES)
+            .filter(|_| claim_subscription_version(&mut subscription, observed).is_ok())
+            .count();
+
+        assert_eq!(successes, 1, "round {}: {} triggers succeeded", round, successes);
+        assert_eq!(subscription.version, observed + 1);
+    }
+    assert_eq!(subscription.version, ROUNDS);
+}
+
+#[test]
+fn stale_and_future_versions_are_rejected() {
+    let mut subscription = subscription_at_version(7);
+
+    assert!(claim_subscription_version(&mut subscription, 6).is_err());
+    assert!(claim_subscription_version(&mut subscription, 8).is_err());
+    assert_eq!(subscription.version, 7);
+
+    assert!(claim_subscription_version(&mut subscription, 7).is_ok());
+    assert!(claim_subscription_version(&mut subscription, 7).is_err()); // Retry with the old read
+    assert!(claim_subscription_version(&mut subscription, 8).is_ok()); // Retry after re-reading
+
+    let mut exhausted = subscription_at_version(u64::MAX);
+    assert!(claim_subscription_version(&mut exhausted, u64::MAX).is_err());
+}
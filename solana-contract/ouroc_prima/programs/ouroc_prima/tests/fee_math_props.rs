@@ -0,0 +1,44 @@
+//! Property tests for the fee split shared by every charge path
+
+use ouroc_prima::{split_payment_amount, FeeConfig, FeeRecipient, FeeTier, MAX_FEE_BPS, MAX_FEE_RECIPIENTS, MAX_FEE_TIERS};
+use proptest::prelude::*;
+
+fn fee_config(min_fee_amount: u64, max_fee_amount: u64) -> FeeConfig {
+    FeeConfig {
+        fee_percentage_basis_points: 200,
+        min_fee_amount,
+        max_fee_amount,
+        fee_recipients: [FeeRecipient::default(); MAX_FEE_RECIPIENTS],
+        recipient_count: 0,
+        fee_tiers: [FeeTier::default(); MAX_FEE_TIERS],
+        tier_count: 0,
+    }
+}
+
+proptest! {
+    #[test]
+    fn split_conserves_amount(
+        amount in 1..=u64::MAX / 10_000,
+        fee_bps in 0..=MAX_FEE_BPS,
+        max_fee in 1_000..=u64::MAX, // update_fee_destination rejects caps below the floor
+    ) {
+        let (platform_fee, merchant_amount) = split_payment_amount(amount, fee_bps, &fee_config(1_000, max_fee)).unwrap();
+
+        prop_assert_eq!(merchant_amount + platform_fee, amount);
+        prop_assert!(platform_fee <= amount);
+        prop_assert!(platform_fee <= max_fee);
+        prop_assert!(platform_fee >= 1_000.min(amount - 1));
+    }
+
+    #[test]
+    fn merchant_always_paid_above_min_fee(
+        amount in 1..=u64::MAX / 10_000,
+        fee_bps in 0..=MAX_FEE_BPS,
+        min_fee in 0..1_000_000u64,
+    ) {
+        prop_assume!(amount > min_fee);
+        let (_, merchant_amount) = split_payment_amount(amount, fee_bps, &fee_config(min_fee, 100_000_000)).unwrap();
+
+        prop_assert!(merchant_amount >= 1);
+    }
+}
@@ -0,0 +1,121 @@
+//! Jupiter swap encoding and output accounting: the payment uses what the swap delivered, never the quote
+
+use anchor_lang::prelude::*;
+use ouroc_prima::jupiter_swap::{
+    build_jupiter_swap_instruction, calculate_min_output_with_slippage, swap_output_amount, RoutePlanStep, SwapType,
+    SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR,
+};
+
+/// `Side` from the Jupiter V6 IDL
+#[derive(AnchorDeserialize, Debug, PartialEq)]
+enum IdlSide {
+    Bid,
+    Ask,
+}
+
+/// Leading variants of `Swap` from the Jupiter V6 IDL, in declaration order so Borsh
+/// assigns the same variant indexes the on-chain program decodes
+#[derive(AnchorDeserialize, Debug, PartialEq)]
+enum IdlSwap {
+    Saber,
+    SaberAddDecimalsDeposit,
+    SaberAddDecimalsWithdraw,
+    TokenSwap,
+    Sencha,
+    Step,
+    Cropper,
+    Raydium,
+    Crema { a_to_b: bool },
+    Lifinity,
+    Mercurial,
+    Cykura,
+    Serum { side: IdlSide },
+    MarinadeDeposit,
+    MarinadeUnstake,
+    Aldrin { side: IdlSide },
+    AldrinV2 { side: IdlSide },
+    Whirlpool { a_to_b: bool },
+    Invariant { x_to_y: bool },
+    Meteora,
+}
+
+#[derive(AnchorDeserialize, Debug, PartialEq)]
+struct IdlRoutePlanStep {
+    swap: IdlSwap,
+    percent: u8,
+    input_index: u8,
+    output_index: u8,
+}
+
+/// `shared_accounts_route` arguments as declared in the Jupiter V6 IDL
+#[derive(AnchorDeserialize, Debug, PartialEq)]
+struct IdlSharedAccountsRouteArgs {
+    id: u8,
+    route_plan: Vec<IdlRoutePlanStep>,
+    in_amount: u64,
+    quoted_out_amount: u64,
+    slippage_bps: u16,
+    platform_fee_bps: u8,
+}
+
+/// Round-trips the encoder through IDL-shaped Borsh types. This is a decode against the IDL
+/// layout, not a recording of a devnet transaction; the route mixes fixed and field-carrying legs
+#[test]
+fn shared_accounts_route_round_trips_through_idl_layout() {
+    assert_eq!(
+        SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR,
+        anchor_lang::solana_program::hash::hash(b"global:shared_accounts_route").to_bytes()[..8]
+    );
+
+    let route_plan = [
+        RoutePlanStep { swap: SwapType::Whirlpool { a_to_b: true }, percent: 60, input_index: 0, output_index: 1 },
+        RoutePlanStep { swap: SwapType::Raydium, percent: 40, input_index: 0, output_index: 2 },
+        RoutePlanStep { swap: SwapType::Meteora, percent: 100, input_index: 2, output_index: 1 },
+        RoutePlanStep {
+            swap: SwapType::Other { index: 12, data: vec![1] }, // Serum { side: Ask }
+            percent: 100,
+            input_index: 1,
+            output_index: 3,
+        },
+    ];
+    let data = build_jupiter_swap_instruction(3, &route_plan, 10_000_000, 9_990_000, 50, 0);
+
+    assert_eq!(data[..8], SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR);
+    let args = IdlSharedAccountsRouteArgs::try_from_slice(&data[8..]).unwrap();
+    assert_eq!(
+        args,
+        IdlSharedAccountsRouteArgs {
+            id: 3,
+            route_plan: vec![
+                IdlRoutePlanStep { swap: IdlSwap::Whirlpool { a_to_b: true }, percent: 60, input_index: 0, output_index: 1 },
+                IdlRoutePlanStep { swap: IdlSwap::Raydium, percent: 40, input_index: 0, output_index: 2 },
+                IdlRoutePlanStep { swap: IdlSwap::Meteora, percent: 100, input_index: 2, output_index: 1 },
+                IdlRoutePlanStep { swap: IdlSwap::Serum { side: IdlSide::Ask }, percent: 100, input_index: 1, output_index: 3 },
+            ],
+            in_amount: 10_000_000,
+            quoted_out_amount: 9_990_000,
+            slippage_bps: 50,
+            platform_fee_bps: 0,
+        }
+    );
+}
+
+#[test]
+fn swap_returning_99_percent_pays_the_delivered_amount() {
+    let amount_in = 10_000_000;
+    let minimum_out = calculate_min_output_with_slippage(amount_in, 100_000_000, 100);
+    assert_eq!(minimum_out, 9_900_000);
+
+    // Mock swap delivers 99% into a destination that already held funds
+    let pre_swap_balance = 2_500_000;
+    let post_swap_balance = pre_swap_balance + amount_in * 99 / 100;
+    assert_eq!(swap_output_amount(pre_swap_balance, post_swap_balance, minimum_out).unwrap(), 9_900_000);
+}
+
+#[test]
+fn swap_below_minimum_output_is_rejected() {
+    let minimum_out = calculate_min_output_with_slippage(10_000_000, 100_000_000, 100);
+    assert!(swap_output_amount(0, 9_899_999, minimum_out).is_err());
+    // A balance that shrank (e.g. a concurrent withdrawal) is never mistaken for output
+    assert!(swap_output_amount(5_000_000, 4_000_000, 0).is_err());
+}
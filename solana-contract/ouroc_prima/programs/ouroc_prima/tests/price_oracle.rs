@@ -0,0 +1,138 @@
+//! Pyth price reads: only fresh prices from genuine PriceUpdateV2 accounts convert
+
+use anchor_lang::prelude::*;
+use ouroc_prima::price_oracle::{
+    get_price_conversion, get_price_conversion_with_fallback, pyth_feeds, EURC_MINT, PYUSD_MINT, SWITCHBOARD_V2_PROGRAM_ID, USDC_MINT,
+};
+use ouroc_prima::{
+    ORACLE_FALLBACK_CONSERVATIVE, ORACLE_FALLBACK_PYTH, ORACLE_FALLBACK_SWITCHBOARD, SWITCHBOARD_AGGREGATOR_DISCRIMINATOR,
+    SWITCHBOARD_LATEST_ROUND_OFFSET, SWITCHBOARD_MIN_ORACLE_RESULTS_OFFSET,
+};
+use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, PriceFeedMessage, PriceUpdateV2, VerificationLevel};
+
+const NOW: i64 = 1_700_000_000;
+const MAX_AGE: u64 = 60;
+
+/// Serialized PriceUpdateV2 account (discriminator included) for a $1.0001 price
+fn price_update_data(feed_hex: &str, publish_time: i64) -> Vec<u8> {
+    price_update_data_at(feed_hex, 100_010_000, publish_time)
+}
+
+fn price_update_data_at(feed_hex: &str, price: i64, publish_time: i64) -> Vec<u8> {
+    let update = PriceUpdateV2 {
+        write_authority: Pubkey::new_unique(),
+        verification_level: VerificationLevel::Full,
+        price_message: PriceFeedMessage {
+            feed_id: get_feed_id_from_hex(feed_hex).unwrap(),
+            price,
+            conf: 20_000,
+            exponent: -8,
+            publish_time,
+            prev_publish_time: publish_time - 1,
+            ema_price: 100_000_000,
+            ema_conf: 20_000,
+        },
+        posted_slot: 1,
+    };
+    let mut data = Vec::new();
+    update.try_serialize(&mut data).unwrap();
+    data
+}
+
+/// Switchboard v2 aggregator whose latest round confirmed $0.9995 at `round_open_timestamp`
+fn switchboard_data(round_open_timestamp: i64) -> Vec<u8> {
+    let round = SWITCHBOARD_LATEST_ROUND_OFFSET;
+    let mut data = vec![0u8; round + 64];
+    data[..8].copy_from_slice(&SWITCHBOARD_AGGREGATOR_DISCRIMINATOR);
+    data[SWITCHBOARD_MIN_ORACLE_RESULTS_OFFSET..SWITCHBOARD_MIN_ORACLE_RESULTS_OFFSET + 4].copy_from_slice(&1u32.to_le_bytes());
+    data[round..round + 4].copy_from_slice(&1u32.to_le_bytes());
+    data[round + 17..round + 25].copy_from_slice(&round_open_timestamp.to_le_bytes());
+    data[round + 25..round + 41].copy_from_slice(&9_995i128.to_le_bytes());
+    data[round + 41..round + 45].copy_from_slice(&4u32.to_le_bytes());
+    data
+}
+
+/// Output and fallback level for 10 USDC at 1% slippage through the Pyth -> Switchboard -> haircut chain
+fn convert_with_fallback(pyth_data: &mut [u8], switchboard: Option<&mut [u8]>) -> (u64, u8) {
+    let pyth_key = Pubkey::new_unique();
+    let mut pyth_lamports = 1_000_000;
+    let price_update = AccountInfo::new(
+        &pyth_key, false, false, &mut pyth_lamports, pyth_data, &pyth_solana_receiver_sdk::ID, false, 0,
+    );
+    let switchboard_key = Pubkey::new_unique();
+    let switchboard_owner: Pubkey = SWITCHBOARD_V2_PROGRAM_ID.parse().unwrap();
+    let mut switchboard_lamports = 1_000_000;
+    let feed = switchboard.map(|data| {
+        AccountInfo::new(&switchboard_key, false, false, &mut switchboard_lamports, data, &switchboard_owner, false, 0)
+    });
+
+    let (conversion, level) = get_price_conversion_with_fallback(
+        &USDC_MINT.parse().unwrap(),
+        10_000_000,
+        &price_update,
+        feed.as_ref(),
+        100,
+        MAX_AGE,
+        NOW,
+    )
+    .unwrap();
+    (conversion.output_amount_min, level)
+}
+
+fn convert(mint: &str, data: &mut [u8], owner: &Pubkey) -> Result<u64> {
+    let key = Pubkey::new_unique();
+    let mut lamports = 1_000_000;
+    let account = AccountInfo::new(&key, false, false, &mut lamports, data, owner, false, 0);
+    get_price_conversion(&mint.parse().unwrap(), 10_000_000, &account, 100, MAX_AGE, NOW)
+        .map(|conversion| conversion.output_amount_min)
+}
+
+#[test]
+fn fresh_price_converts_with_slippage() {
+    for (mint, feed) in [(USDC_MINT, pyth_feeds::USDC_USD), (PYUSD_MINT, pyth_feeds::PYUSD_USD)] {
+        let mut data = price_update_data(feed, NOW - MAX_AGE as i64);
+        assert_eq!(convert(mint, &mut data, &pyth_solana_receiver_sdk::ID).unwrap(), 9_900_000);
+    }
+}
+
+#[test]
+fn eurc_converts_at_the_eur_usd_rate() {
+    // EUR/USD 1.08: 10 EURC -> 10.8 USDC, less 1% slippage
+    let mut data = price_update_data_at(pyth_feeds::EURC_USD_PYTH_FEED, 108_000_000, NOW);
+    assert_eq!(convert(EURC_MINT, &mut data, &pyth_solana_receiver_sdk::ID).unwrap(), 10_692_000);
+
+    // A USDC feed can't stand in for the EURC one
+    let mut usdc_feed = price_update_data_at(pyth_feeds::USDC_USD, 108_000_000, NOW);
+    assert!(convert(EURC_MINT, &mut usdc_feed, &pyth_solana_receiver_sdk::ID).is_err());
+}
+
+#[test]
+fn stale_price_is_rejected() {
+    let mut data = price_update_data(pyth_feeds::USDC_USD, NOW - MAX_AGE as i64 - 1);
+    let err = convert(USDC_MINT, &mut data, &pyth_solana_receiver_sdk::ID).unwrap_err();
+    assert!(matches!(err, Error::AnchorError(e) if e.error_name == "StalePriceFeed"));
+}
+
+#[test]
+fn stale_pyth_falls_back_to_switchboard_then_conservative_haircut() {
+    let fresh = NOW - 10;
+    let stale = NOW - MAX_AGE as i64 - 1;
+
+    let mut pyth = price_update_data(pyth_feeds::USDC_USD, fresh);
+    let mut switchboard = switchboard_data(fresh);
+    assert_eq!(convert_with_fallback(&mut pyth, Some(&mut switchboard)), (9_900_000, ORACLE_FALLBACK_PYTH));
+
+    let mut pyth = price_update_data(pyth_feeds::USDC_USD, stale);
+    assert_eq!(convert_with_fallback(&mut pyth, Some(&mut switchboard)), (9_900_000, ORACLE_FALLBACK_SWITCHBOARD));
+
+    // Switchboard stale too (or absent): 2x slippage haircut
+    let mut stale_switchboard = switchboard_data(stale);
+    assert_eq!(convert_with_fallback(&mut pyth, Some(&mut stale_switchboard)), (9_800_000, ORACLE_FALLBACK_CONSERVATIVE));
+    assert_eq!(convert_with_fallback(&mut pyth, None), (9_800_000, ORACLE_FALLBACK_CONSERVATIVE));
+}
+
+#[test]
+fn price_update_from_another_program_is_rejected() {
+    let mut data = price_update_data(pyth_feeds::USDC_USD, NOW);
+    assert!(convert(USDC_MINT, &mut data, &Pubkey::new_unique()).is_err());
+}